@@ -0,0 +1,298 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{Decoder, DecoderOptions};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::{FormatOptions, FormatReader, SeekMode, SeekTo};
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::{MetadataOptions, StandardTagKey, Tag};
+use symphonia::core::probe::{Hint, ProbeResult};
+use symphonia::core::units::{Time, TimeBase};
+
+use super::AudioError;
+use super::mixer::CHANNELS;
+
+/// File extensions the decoder is able to open
+pub const SUPPORTED_EXTENSIONS: [&str; 6] = ["wav", "mp3", "flac", "ogg", "oga", "mka"];
+
+/// A fully decoded sound, stored as interleaved stereo samples
+#[derive(Debug)]
+pub struct AudioClip {
+    pub samples: Vec<f32>,
+    pub sample_rate: u32,
+}
+
+impl AudioClip {
+    pub fn frames(&self) -> usize {
+        self.samples.len() / CHANNELS
+    }
+
+    pub fn info(&self) -> ClipInfo {
+        ClipInfo {
+            frames: self.frames(),
+            sample_rate: self.sample_rate,
+        }
+    }
+}
+
+/// Length and sample rate of a sound, which for a long file is known without decoding it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClipInfo {
+    pub frames: usize,
+    pub sample_rate: u32,
+}
+
+impl ClipInfo {
+    pub fn duration_ms(&self) -> u32 {
+        (self.frames as u64 * 1000 / self.sample_rate.max(1) as u64) as u32
+    }
+
+    /// Source frame `ms` milliseconds in, which may be past the end
+    pub fn frame_at_ms(&self, ms: u32) -> usize {
+        (ms as u64 * self.sample_rate as u64 / 1000) as usize
+    }
+
+    /// Bytes the sound takes up once decoded
+    pub fn decoded_bytes(&self) -> usize {
+        self.frames * CHANNELS * size_of::<f32>()
+    }
+}
+
+/// Gain and peak from a file's ReplayGain tags
+#[derive(Debug, Clone, Copy)]
+pub struct ReplayGain {
+    pub track_gain_db: f32,
+    /// Highest sample, where 1.0 is full scale
+    pub track_peak: f32,
+}
+
+/// Open an audio file and work out its container format
+fn probe(path: &Path) -> Result<ProbeResult, AudioError> {
+    let file = File::open(path)
+        .map_err(|e| AudioError::SoundNotFound(format!("{}: {}", path.display(), e)))?;
+    let source = MediaSourceStream::new(Box::new(file), Default::default());
+
+    let mut hint = Hint::new();
+    if let Some(extension) = path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(extension);
+    }
+
+    symphonia::default::get_probe()
+        .format(
+            &hint,
+            source,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .map_err(|e| AudioError::PlaybackError(format!("{}: {}", path.display(), e)))
+}
+
+/// Read the track gain and peak from a file's ReplayGain tags, if it has both
+pub fn read_replaygain(path: &Path) -> Result<Option<ReplayGain>, AudioError> {
+    let mut probed = probe(path)?;
+    let mut gain = None;
+    let mut peak = None;
+
+    let mut read_tags = |tags: &[Tag]| {
+        for tag in tags {
+            let value = tag.value.to_string();
+            if tag.std_key == Some(StandardTagKey::ReplayGainTrackGain)
+                || tag.key.eq_ignore_ascii_case("REPLAYGAIN_TRACK_GAIN")
+            {
+                gain = gain.or_else(|| parse_replaygain_value(&value));
+            } else if tag.std_key == Some(StandardTagKey::ReplayGainTrackPeak)
+                || tag.key.eq_ignore_ascii_case("REPLAYGAIN_TRACK_PEAK")
+            {
+                peak = peak.or_else(|| parse_replaygain_value(&value));
+            }
+        }
+    };
+
+    // Tags in front of the container, like ID3v2, are read by the probe
+    if let Some(metadata) = probed.metadata.get()
+        && let Some(revision) = metadata.current()
+    {
+        read_tags(revision.tags());
+    }
+    if let Some(revision) = probed.format.metadata().current() {
+        read_tags(revision.tags());
+    }
+
+    Ok(gain
+        .zip(peak)
+        .map(|(track_gain_db, track_peak)| ReplayGain {
+            track_gain_db,
+            track_peak,
+        }))
+}
+
+/// Parse a tag value like "-6.48 dB" or "0.988525"
+fn parse_replaygain_value(value: &str) -> Option<f32> {
+    let value = value.trim();
+    let value = value
+        .strip_suffix("dB")
+        .or_else(|| value.strip_suffix("db"))
+        .unwrap_or(value);
+    value.trim().parse().ok().filter(|v: &f32| v.is_finite())
+}
+
+/// Decode an audio file into memory, down- or up-mixing it to stereo
+pub fn decode_file(path: &Path) -> Result<AudioClip, AudioError> {
+    let mut decoder = StreamDecoder::open(path)?;
+    let mut samples = Vec::new();
+    while decoder.decode_next(&mut samples)?.is_some() {}
+
+    Ok(AudioClip {
+        samples,
+        sample_rate: decoder.sample_rate,
+    })
+}
+
+/// Decodes a file a packet at a time, for reading long files while they play
+pub struct StreamDecoder {
+    path: PathBuf,
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    track_id: u32,
+    time_base: Option<TimeBase>,
+    pub sample_rate: u32,
+    /// Length stated by the file, which not every format has
+    frames: Option<u64>,
+    buffer: Option<SampleBuffer<f32>>,
+}
+
+impl StreamDecoder {
+    pub fn open(path: &Path) -> Result<Self, AudioError> {
+        let format = probe(path)?.format;
+
+        let track = format.default_track().ok_or_else(|| {
+            AudioError::NotSupported(format!("{}: no audio track", path.display()))
+        })?;
+        let sample_rate = track.codec_params.sample_rate.ok_or_else(|| {
+            AudioError::NotSupported(format!("{}: unknown sample rate", path.display()))
+        })?;
+        let decoder = symphonia::default::get_codecs()
+            .make(&track.codec_params, &DecoderOptions::default())
+            .map_err(|e| AudioError::PlaybackError(format!("{}: {}", path.display(), e)))?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            track_id: track.id,
+            time_base: track.codec_params.time_base,
+            frames: track.codec_params.n_frames,
+            sample_rate,
+            decoder,
+            format,
+            buffer: None,
+        })
+    }
+
+    /// Length and sample rate, if the file states its length
+    pub fn info(&self) -> Option<ClipInfo> {
+        self.frames.map(|frames| ClipInfo {
+            frames: frames as usize,
+            sample_rate: self.sample_rate,
+        })
+    }
+
+    /// Go to the packet holding `frame`. Decoding then starts at or before it,
+    /// at the frame [`StreamDecoder::decode_next`] returns.
+    pub fn seek(&mut self, frame: usize) -> Result<(), AudioError> {
+        let ts = match self.time_base {
+            Some(time_base) => {
+                time_base.calc_timestamp(Time::from(frame as f64 / self.sample_rate as f64))
+            }
+            None => frame as u64,
+        };
+        self.format
+            .seek(
+                SeekMode::Accurate,
+                SeekTo::TimeStamp {
+                    ts,
+                    track_id: self.track_id,
+                },
+            )
+            .map_err(|e| self.error(e))?;
+        self.decoder.reset();
+        Ok(())
+    }
+
+    /// Decode the next packet onto the end of `out` as stereo, returning the
+    /// frame it starts at, or `None` at the end of the file
+    pub fn decode_next(&mut self, out: &mut Vec<f32>) -> Result<Option<usize>, AudioError> {
+        loop {
+            let packet = match self.format.next_packet() {
+                Ok(packet) => packet,
+                // Symphonia reports the end of the stream as an unexpected EOF
+                Err(SymphoniaError::IoError(e))
+                    if e.kind() == std::io::ErrorKind::UnexpectedEof =>
+                {
+                    return Ok(None);
+                }
+                Err(e) => return Err(self.error(e)),
+            };
+
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+
+            let decoded = match self.decoder.decode(&packet) {
+                Ok(decoded) => decoded,
+                // A corrupt packet is skipped rather than failing the whole file
+                Err(SymphoniaError::DecodeError(_)) => continue,
+                Err(e) => return Err(self.error(e)),
+            };
+
+            let spec = *decoded.spec();
+            let buffer = match &mut self.buffer {
+                Some(buffer) if buffer.capacity() >= decoded.capacity() * spec.channels.count() => {
+                    buffer
+                }
+                buffer => buffer.insert(SampleBuffer::new(decoded.capacity() as u64, spec)),
+            };
+            buffer.copy_interleaved_ref(decoded);
+            push_stereo(out, buffer.samples(), spec.channels.count());
+
+            let frame = match self.time_base {
+                Some(time_base) => {
+                    let time = time_base.calc_time(packet.ts());
+                    ((time.seconds as f64 + time.frac) * self.sample_rate as f64).round() as usize
+                }
+                None => packet.ts() as usize,
+            };
+            return Ok(Some(frame));
+        }
+    }
+
+    fn error(&self, e: SymphoniaError) -> AudioError {
+        AudioError::PlaybackError(format!("{}: {}", self.path.display(), e))
+    }
+}
+
+/// Append interleaved samples with `channels` channels as stereo
+fn push_stereo(out: &mut Vec<f32>, samples: &[f32], channels: usize) {
+    match channels {
+        0 => {}
+        1 => out.extend(samples.iter().flat_map(|&s| [s, s])),
+        _ => out.extend(
+            samples
+                .chunks_exact(channels)
+                .flat_map(|frame| [frame[0], frame[1]]),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_replaygain_values() {
+        assert_eq!(parse_replaygain_value("-6.48 dB"), Some(-6.48));
+        assert_eq!(parse_replaygain_value(" +2.5db "), Some(2.5));
+        assert_eq!(parse_replaygain_value("0.988525"), Some(0.988525));
+        assert_eq!(parse_replaygain_value("loud"), None);
+        assert_eq!(parse_replaygain_value("NaN dB"), None);
+    }
+}
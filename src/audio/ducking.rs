@@ -0,0 +1,73 @@
+use super::mixer::SAMPLE_RATE;
+use crate::settings::ducking::DuckingSettings;
+
+/// How quickly the sidechain level falls between peaks of the triggering sounds
+const DETECTOR_RELEASE_MS: f32 = 10.0;
+
+/// Sidechain ducker: follows the level of the triggering sounds and works out
+/// the gain for the sounds and mic that are ducked by them
+pub struct Ducker {
+    /// Gain while ducked
+    depth: f32,
+    threshold: f32,
+    attack: f32,
+    release: f32,
+    detector_release: f32,
+    /// Level of the triggering sounds
+    level: f32,
+    gain: f32,
+    pub duck_mic: bool,
+}
+
+impl Ducker {
+    pub fn new(settings: &DuckingSettings) -> Self {
+        Self {
+            depth: db_to_gain(-settings.depth_db.abs()),
+            threshold: db_to_gain(settings.threshold_db),
+            attack: smoothing(settings.attack_ms as f32),
+            release: smoothing(settings.release_ms as f32),
+            detector_release: smoothing(DETECTOR_RELEASE_MS),
+            level: 0.0,
+            gain: 1.0,
+            duck_mic: settings.duck_mic,
+        }
+    }
+
+    /// Gain for the ducked sounds over the next frame, given the triggering sounds' peak in it
+    pub fn next(&mut self, peak: f32) -> f32 {
+        self.level = peak.max(self.level * self.detector_release);
+
+        let target = if self.level > self.threshold {
+            self.depth
+        } else {
+            1.0
+        };
+        let coefficient = if target < self.gain {
+            self.attack
+        } else {
+            self.release
+        };
+        self.gain = target + (self.gain - target) * coefficient;
+        self.gain
+    }
+}
+
+impl Default for Ducker {
+    fn default() -> Self {
+        Self::new(&DuckingSettings::default())
+    }
+}
+
+/// Per-frame coefficient of a one-pole smoother that gets most of the way there in `ms`
+pub(super) fn smoothing(ms: f32) -> f32 {
+    let frames = ms * SAMPLE_RATE as f32 / 1000.0;
+    if frames < 1.0 {
+        0.0
+    } else {
+        (-1.0 / frames).exp()
+    }
+}
+
+fn db_to_gain(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
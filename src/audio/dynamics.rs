@@ -0,0 +1,273 @@
+//! Noise gate, compressor and brickwall limiter for the passthrough mic.
+//!
+//! Like the rest of the mixer they run in the audio callback, so they are set
+//! up with all their buffers in [`MicDynamics::new`] and never allocate after.
+
+use serde::{Deserialize, Serialize};
+
+use super::db_to_gain;
+use super::ducking::smoothing;
+use super::mixer::{CHANNELS, SAMPLE_RATE};
+use crate::settings::mic::{CompressorSettings, GateSettings, LimiterSettings, MicSettings};
+
+/// How far ahead the limiter looks for peaks, about 1.5 ms. The mic is delayed by as much.
+const LOOKAHEAD_FRAMES: usize = SAMPLE_RATE as usize * 3 / 2000;
+/// How quickly the gate's level detector falls between peaks
+const GATE_DETECTOR_RELEASE_MS: f32 = 10.0;
+/// Quietest level the detectors work with, to keep the logarithms finite
+const SILENCE_DB: f32 = -120.0;
+
+/// How far each stage is turning the mic down, in dB
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct GainReduction {
+    pub gate_db: f32,
+    pub compressor_db: f32,
+    pub limiter_db: f32,
+}
+
+/// The gate and compressor clean the mic up before the voice changer, and
+/// the limiter catches whatever comes out of it
+pub struct MicDynamics {
+    gate: Option<Gate>,
+    compressor: Option<Compressor>,
+    limiter: Option<Limiter>,
+    /// Most reduction of each stage since it was last taken
+    reduction: GainReduction,
+}
+
+impl MicDynamics {
+    pub fn new(settings: &MicSettings) -> Self {
+        Self {
+            gate: settings.gate.enabled.then(|| Gate::new(&settings.gate)),
+            compressor: settings
+                .compressor
+                .enabled
+                .then(|| Compressor::new(&settings.compressor)),
+            limiter: settings
+                .limiter
+                .enabled
+                .then(|| Limiter::new(&settings.limiter)),
+            reduction: GainReduction::default(),
+        }
+    }
+
+    /// Gate and compress interleaved frames in place
+    pub fn clean(&mut self, frames: &mut [f32]) {
+        for frame in frames.chunks_exact_mut(CHANNELS) {
+            if let Some(gate) = &mut self.gate {
+                let reduction = gate.process(frame);
+                self.reduction.gate_db = self.reduction.gate_db.max(reduction);
+            }
+            if let Some(compressor) = &mut self.compressor {
+                let reduction = compressor.process(frame);
+                self.reduction.compressor_db = self.reduction.compressor_db.max(reduction);
+            }
+        }
+    }
+
+    /// Limit interleaved frames in place, delaying them by [`MicDynamics::latency_frames`]
+    pub fn limit(&mut self, frames: &mut [f32]) {
+        let Some(limiter) = &mut self.limiter else {
+            return;
+        };
+        for frame in frames.chunks_exact_mut(CHANNELS) {
+            let reduction = limiter.process(frame);
+            self.reduction.limiter_db = self.reduction.limiter_db.max(reduction);
+        }
+    }
+
+    pub fn latency_frames(&self) -> usize {
+        self.limiter.as_ref().map_or(0, |_| LOOKAHEAD_FRAMES)
+    }
+
+    /// Most reduction of each stage since the last call
+    pub fn take_reduction(&mut self) -> GainReduction {
+        std::mem::take(&mut self.reduction)
+    }
+}
+
+/// Noise gate that opens above a threshold and only closes again once the mic
+/// has been below the threshold minus the hysteresis for the hold time
+struct Gate {
+    open_level: f32,
+    close_level: f32,
+    hold_frames: u32,
+    attack: f32,
+    release: f32,
+    detector_release: f32,
+    closed_gain: f32,
+    level: f32,
+    open: bool,
+    /// Frames spent below the closing level while open
+    quiet_frames: u32,
+    gain: f32,
+}
+
+impl Gate {
+    fn new(settings: &GateSettings) -> Self {
+        Self {
+            open_level: db_to_gain(settings.threshold_db),
+            close_level: db_to_gain(settings.threshold_db - settings.hysteresis_db.abs()),
+            hold_frames: settings.hold_ms * SAMPLE_RATE / 1000,
+            attack: smoothing(settings.attack_ms as f32),
+            release: smoothing(settings.release_ms as f32),
+            detector_release: smoothing(GATE_DETECTOR_RELEASE_MS),
+            closed_gain: db_to_gain(-settings.range_db.abs()),
+            level: 0.0,
+            open: false,
+            quiet_frames: 0,
+            gain: db_to_gain(-settings.range_db.abs()),
+        }
+    }
+
+    /// Gate one frame, returning the reduction in dB
+    fn process(&mut self, frame: &mut [f32]) -> f32 {
+        self.level = peak(frame).max(self.level * self.detector_release);
+
+        if self.level >= self.open_level {
+            self.open = true;
+            self.quiet_frames = 0;
+        } else if self.level >= self.close_level {
+            self.quiet_frames = 0;
+        } else if self.open {
+            self.quiet_frames += 1;
+            self.open = self.quiet_frames < self.hold_frames;
+        }
+
+        let target = if self.open { 1.0 } else { self.closed_gain };
+        let coefficient = if target > self.gain {
+            self.attack
+        } else {
+            self.release
+        };
+        self.gain = target + (self.gain - target) * coefficient;
+
+        for sample in frame {
+            *sample *= self.gain;
+        }
+        -gain_to_db(self.gain)
+    }
+}
+
+/// Downward compressor with a soft knee, working on the peak level in dB
+struct Compressor {
+    threshold_db: f32,
+    slope: f32,
+    knee_db: f32,
+    attack: f32,
+    release: f32,
+    makeup_db: f32,
+    reduction_db: f32,
+}
+
+impl Compressor {
+    fn new(settings: &CompressorSettings) -> Self {
+        Self {
+            threshold_db: settings.threshold_db,
+            slope: 1.0 / settings.ratio.max(1.0) - 1.0,
+            knee_db: settings.knee_db.max(0.0),
+            attack: smoothing(settings.attack_ms as f32),
+            release: smoothing(settings.release_ms as f32),
+            makeup_db: settings.makeup_db,
+            reduction_db: 0.0,
+        }
+    }
+
+    /// Reduction in dB for a level, before smoothing
+    fn static_reduction(&self, level_db: f32) -> f32 {
+        let over = level_db - self.threshold_db;
+        if 2.0 * over <= -self.knee_db {
+            0.0
+        } else if 2.0 * over.abs() < self.knee_db {
+            let into_knee = over + self.knee_db / 2.0;
+            -self.slope * into_knee * into_knee / (2.0 * self.knee_db)
+        } else {
+            -self.slope * over
+        }
+    }
+
+    /// Compress one frame, returning the reduction in dB
+    fn process(&mut self, frame: &mut [f32]) -> f32 {
+        let target = self.static_reduction(gain_to_db(peak(frame)));
+        let coefficient = if target > self.reduction_db {
+            self.attack
+        } else {
+            self.release
+        };
+        self.reduction_db = target + (self.reduction_db - target) * coefficient;
+
+        let gain = db_to_gain(self.makeup_db - self.reduction_db);
+        for sample in frame {
+            *sample *= gain;
+        }
+        self.reduction_db
+    }
+}
+
+/// Look-ahead limiter: the mic is delayed so the gain is already down when a
+/// peak comes out, and anything still over the ceiling is clipped
+struct Limiter {
+    ceiling: f32,
+    attack: f32,
+    release: f32,
+    /// Interleaved frames waiting to come out
+    delay: Vec<f32>,
+    /// Gain each waiting frame needs to stay under the ceiling
+    needed: Vec<f32>,
+    position: usize,
+    gain: f32,
+}
+
+impl Limiter {
+    fn new(settings: &LimiterSettings) -> Self {
+        Self {
+            ceiling: db_to_gain(-settings.ceiling_db.abs()),
+            // Most of the way down within the look-ahead
+            attack: (-4.0 / LOOKAHEAD_FRAMES as f32).exp(),
+            release: smoothing(settings.release_ms as f32),
+            delay: vec![0.0; LOOKAHEAD_FRAMES * CHANNELS],
+            needed: vec![1.0; LOOKAHEAD_FRAMES],
+            position: 0,
+            gain: 1.0,
+        }
+    }
+
+    /// Limit one frame, returning the reduction in dB
+    fn process(&mut self, frame: &mut [f32]) -> f32 {
+        let peak = peak(frame);
+        self.needed[self.position] = if peak > self.ceiling {
+            self.ceiling / peak
+        } else {
+            1.0
+        };
+        let target = self
+            .needed
+            .iter()
+            .fold(1.0f32, |lowest, &gain| lowest.min(gain));
+
+        let coefficient = if target < self.gain {
+            self.attack
+        } else {
+            self.release
+        };
+        self.gain = target + (self.gain - target) * coefficient;
+
+        let delayed = &mut self.delay[self.position * CHANNELS..][..CHANNELS];
+        for (sample, waiting) in frame.iter_mut().zip(delayed) {
+            let output = (*waiting * self.gain).clamp(-self.ceiling, self.ceiling);
+            *waiting = *sample;
+            *sample = output;
+        }
+        self.position = (self.position + 1) % LOOKAHEAD_FRAMES;
+
+        -gain_to_db(self.gain)
+    }
+}
+
+fn peak(frame: &[f32]) -> f32 {
+    frame.iter().fold(0.0f32, |peak, s| peak.max(s.abs()))
+}
+
+fn gain_to_db(gain: f32) -> f32 {
+    (20.0 * gain.log10()).max(SILENCE_DB)
+}
@@ -0,0 +1,51 @@
+use super::{Effect, mix};
+use crate::audio::mixer::{CHANNELS, SAMPLE_RATE};
+use crate::settings::effects::DelaySettings;
+
+/// Echoes fade out once they are 60 dB down
+const SILENCE: f32 = 0.001;
+const MAX_TIME_MS: f32 = 2000.0;
+
+/// Echo, with each repeat fed back into the next
+pub struct Delay {
+    /// Interleaved frames, used as a ring
+    buffer: Vec<f32>,
+    position: usize,
+    feedback: f32,
+    mix: f32,
+}
+
+impl Delay {
+    pub fn new(settings: &DelaySettings) -> Self {
+        let frames = (settings.time_ms.clamp(1.0, MAX_TIME_MS) * SAMPLE_RATE as f32 / 1000.0)
+            .round()
+            .max(1.0) as usize;
+
+        Self {
+            buffer: vec![0.0; frames * CHANNELS],
+            position: 0,
+            feedback: settings.feedback.clamp(0.0, 0.95),
+            mix: settings.mix.clamp(0.0, 1.0),
+        }
+    }
+}
+
+impl Effect for Delay {
+    fn process(&mut self, frames: &mut [f32]) {
+        for sample in frames.iter_mut() {
+            let delayed = self.buffer[self.position];
+            self.buffer[self.position] = *sample + delayed * self.feedback;
+            self.position = (self.position + 1) % self.buffer.len();
+            *sample = mix(*sample, *sample + delayed, self.mix);
+        }
+    }
+
+    fn tail_frames(&self) -> usize {
+        let delay_frames = self.buffer.len() / CHANNELS;
+        if self.feedback <= 0.0 {
+            return delay_frames;
+        }
+        let repeats = (SILENCE.ln() / self.feedback.ln()).ceil() as usize;
+        delay_frames * (repeats + 1)
+    }
+}
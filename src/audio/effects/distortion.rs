@@ -0,0 +1,70 @@
+use super::{Effect, mix};
+use crate::audio::mixer::CHANNELS;
+use crate::settings::effects::{BitcrusherSettings, DistortionSettings};
+
+/// Soft clipping overdrive
+pub struct Distortion {
+    drive: f32,
+    /// Brings a full-scale input back to about full scale after clipping
+    makeup: f32,
+    mix: f32,
+}
+
+impl Distortion {
+    pub fn new(settings: &DistortionSettings) -> Self {
+        let drive = 10f32.powf(settings.drive_db.max(0.0) / 20.0);
+        Self {
+            drive,
+            makeup: 1.0 / drive.tanh(),
+            mix: settings.mix.clamp(0.0, 1.0),
+        }
+    }
+}
+
+impl Effect for Distortion {
+    fn process(&mut self, frames: &mut [f32]) {
+        for sample in frames.iter_mut() {
+            let wet = (*sample * self.drive).tanh() * self.makeup;
+            *sample = mix(*sample, wet, self.mix);
+        }
+    }
+}
+
+/// Lowers the bit depth and sample rate for a lo-fi sound
+pub struct Bitcrusher {
+    levels: f32,
+    downsample: u32,
+    held: [f32; CHANNELS],
+    counter: u32,
+    mix: f32,
+}
+
+impl Bitcrusher {
+    pub fn new(settings: &BitcrusherSettings) -> Self {
+        let bits = settings.bits.round().clamp(1.0, 16.0);
+        Self {
+            levels: 2f32.powf(bits - 1.0),
+            downsample: settings.downsample.round().max(1.0) as u32,
+            held: [0.0; CHANNELS],
+            counter: 0,
+            mix: settings.mix.clamp(0.0, 1.0),
+        }
+    }
+}
+
+impl Effect for Bitcrusher {
+    fn process(&mut self, frames: &mut [f32]) {
+        for frame in frames.chunks_exact_mut(CHANNELS) {
+            if self.counter == 0 {
+                for (held, sample) in self.held.iter_mut().zip(frame.iter()) {
+                    *held = (sample * self.levels).round() / self.levels;
+                }
+            }
+            self.counter = (self.counter + 1) % self.downsample;
+
+            for (sample, held) in frame.iter_mut().zip(self.held) {
+                *sample = mix(*sample, held, self.mix);
+            }
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use std::f32::consts::PI;
+
+use super::Effect;
+use crate::audio::mixer::{CHANNELS, SAMPLE_RATE};
+use crate::settings::effects::{EqSettings, HighPassSettings, LowPassSettings};
+
+/// Second-order filter section, with coefficients from the RBJ audio EQ cookbook
+#[derive(Clone, Copy)]
+struct Biquad {
+    b: [f32; 3],
+    a: [f32; 2],
+    state: [[f32; 2]; CHANNELS],
+}
+
+impl Biquad {
+    fn from_coefficients(b: [f32; 3], a: [f32; 3]) -> Self {
+        Self {
+            b: [b[0] / a[0], b[1] / a[0], b[2] / a[0]],
+            a: [a[1] / a[0], a[2] / a[0]],
+            state: [[0.0; 2]; CHANNELS],
+        }
+    }
+
+    /// Angular frequency and the cookbook's alpha for a cutoff and Q
+    fn omega(frequency: f32, q: f32) -> (f32, f32) {
+        let nyquist = SAMPLE_RATE as f32 / 2.0;
+        let omega = 2.0 * PI * frequency.clamp(10.0, nyquist * 0.95) / SAMPLE_RATE as f32;
+        (omega, omega.sin() / (2.0 * q.max(0.01)))
+    }
+
+    fn low_pass(frequency: f32, q: f32) -> Self {
+        let (omega, alpha) = Self::omega(frequency, q);
+        let cos = omega.cos();
+        Self::from_coefficients(
+            [(1.0 - cos) / 2.0, 1.0 - cos, (1.0 - cos) / 2.0],
+            [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+        )
+    }
+
+    fn high_pass(frequency: f32, q: f32) -> Self {
+        let (omega, alpha) = Self::omega(frequency, q);
+        let cos = omega.cos();
+        Self::from_coefficients(
+            [(1.0 + cos) / 2.0, -(1.0 + cos), (1.0 + cos) / 2.0],
+            [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+        )
+    }
+
+    fn peak(frequency: f32, q: f32, gain_db: f32) -> Self {
+        let (omega, alpha) = Self::omega(frequency, q);
+        let a = 10f32.powf(gain_db / 40.0);
+        let cos = omega.cos();
+        Self::from_coefficients(
+            [1.0 + alpha * a, -2.0 * cos, 1.0 - alpha * a],
+            [1.0 + alpha / a, -2.0 * cos, 1.0 - alpha / a],
+        )
+    }
+
+    /// A shelf boosting or cutting below `frequency`, or above it when `high`
+    fn shelf(frequency: f32, gain_db: f32, high: bool) -> Self {
+        let (omega, alpha) = Self::omega(frequency, std::f32::consts::FRAC_1_SQRT_2);
+        let a = 10f32.powf(gain_db / 40.0);
+        let cos = omega.cos();
+        let root = 2.0 * a.sqrt() * alpha;
+        let sign = if high { -1.0 } else { 1.0 };
+
+        Self::from_coefficients(
+            [
+                a * ((a + 1.0) - sign * (a - 1.0) * cos + root),
+                sign * 2.0 * a * ((a - 1.0) - sign * (a + 1.0) * cos),
+                a * ((a + 1.0) - sign * (a - 1.0) * cos - root),
+            ],
+            [
+                (a + 1.0) + sign * (a - 1.0) * cos + root,
+                -sign * 2.0 * ((a - 1.0) + sign * (a + 1.0) * cos),
+                (a + 1.0) + sign * (a - 1.0) * cos - root,
+            ],
+        )
+    }
+
+    fn process(&mut self, frames: &mut [f32]) {
+        for frame in frames.chunks_exact_mut(CHANNELS) {
+            for (sample, state) in frame.iter_mut().zip(&mut self.state) {
+                let x = *sample;
+                let y = self.b[0] * x + state[0];
+                state[0] = self.b[1] * x - self.a[0] * y + state[1];
+                state[1] = self.b[2] * x - self.a[1] * y;
+                *sample = y;
+            }
+        }
+    }
+}
+
+/// High- or low-pass filter
+pub struct Filter(Biquad);
+
+impl Filter {
+    pub fn high_pass(settings: &HighPassSettings) -> Self {
+        Self(Biquad::high_pass(settings.cutoff_hz, settings.q))
+    }
+
+    pub fn low_pass(settings: &LowPassSettings) -> Self {
+        Self(Biquad::low_pass(settings.cutoff_hz, settings.q))
+    }
+}
+
+impl Effect for Filter {
+    fn process(&mut self, frames: &mut [f32]) {
+        self.0.process(frames);
+    }
+}
+
+pub struct Eq {
+    bands: [Biquad; 3],
+}
+
+impl Eq {
+    pub fn new(settings: &EqSettings) -> Self {
+        Self {
+            bands: [
+                Biquad::shelf(settings.low_hz, settings.low_db, false),
+                Biquad::peak(settings.mid_hz, settings.mid_q, settings.mid_db),
+                Biquad::shelf(settings.high_hz, settings.high_db, true),
+            ],
+        }
+    }
+}
+
+impl Effect for Eq {
+    fn process(&mut self, frames: &mut [f32]) {
+        for band in &mut self.bands {
+            band.process(frames);
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//! Effects that sounds and the master output are run through.
+//!
+//! Effects are built on the control side, where they may allocate, and then
+//! handed to the mixer. From then on [`Effect::process`] runs in the audio
+//! callback and must neither allocate nor block.
+
+mod delay;
+mod distortion;
+mod filter;
+mod pitch;
+mod reverb;
+
+pub use pitch::{PitchShift, PitchShifter};
+
+use crate::settings::effects::EffectSettings;
+
+pub trait Effect: Send {
+    /// Process interleaved stereo frames in place
+    fn process(&mut self, frames: &mut [f32]);
+
+    /// Frames the effect keeps sounding after its input goes silent
+    fn tail_frames(&self) -> usize {
+        0
+    }
+
+    /// Frames by which the effect delays its input
+    fn latency_frames(&self) -> usize {
+        0
+    }
+}
+
+/// Build the effect described by `settings`
+pub fn build(settings: &EffectSettings) -> Box<dyn Effect> {
+    match settings {
+        EffectSettings::Eq(eq) => Box::new(filter::Eq::new(eq)),
+        EffectSettings::HighPass(high_pass) => Box::new(filter::Filter::high_pass(high_pass)),
+        EffectSettings::LowPass(low_pass) => Box::new(filter::Filter::low_pass(low_pass)),
+        EffectSettings::Reverb(reverb) => Box::new(reverb::Reverb::new(reverb)),
+        EffectSettings::Delay(delay) => Box::new(delay::Delay::new(delay)),
+        EffectSettings::Distortion(distortion) => Box::new(distortion::Distortion::new(distortion)),
+        EffectSettings::Bitcrusher(crusher) => Box::new(distortion::Bitcrusher::new(crusher)),
+    }
+}
+
+/// Effects applied one after the other
+#[derive(Default)]
+pub struct EffectChain {
+    effects: Vec<Box<dyn Effect>>,
+}
+
+impl EffectChain {
+    pub fn new(settings: &[EffectSettings]) -> Self {
+        Self {
+            effects: settings.iter().map(build).collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.effects.is_empty()
+    }
+
+    pub fn process(&mut self, frames: &mut [f32]) {
+        for effect in &mut self.effects {
+            effect.process(frames);
+        }
+    }
+
+    /// Frames until the chain falls silent once its input does
+    pub fn tail_frames(&self) -> usize {
+        self.effects.iter().map(|effect| effect.tail_frames()).sum()
+    }
+
+    pub fn latency_frames(&self) -> usize {
+        self.effects
+            .iter()
+            .map(|effect| effect.latency_frames())
+            .sum()
+    }
+}
+
+impl From<Vec<Box<dyn Effect>>> for EffectChain {
+    fn from(effects: Vec<Box<dyn Effect>>) -> Self {
+        Self { effects }
+    }
+}
+
+/// Blend a processed sample with the original, from 0.0 for all dry to 1.0 for all wet
+fn mix(dry: f32, wet: f32, mix: f32) -> f32 {
+    dry + (wet - dry) * mix
+}
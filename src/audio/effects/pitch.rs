@@ -0,0 +1,88 @@
+use std::f32::consts::PI;
+
+use super::Effect;
+use crate::audio::mixer::CHANNELS;
+
+/// Changes pitch without changing duration, on each channel separately
+pub struct PitchShift {
+    shifters: [PitchShifter; CHANNELS],
+    grain_frames: usize,
+}
+
+impl PitchShift {
+    pub fn new(semitones: f32, grain_frames: usize) -> Self {
+        let ratio = 2f32.powf(semitones / 12.0);
+        Self {
+            shifters: std::array::from_fn(|_| PitchShifter::new(ratio, grain_frames)),
+            grain_frames,
+        }
+    }
+}
+
+impl Effect for PitchShift {
+    fn process(&mut self, frames: &mut [f32]) {
+        for frame in frames.chunks_exact_mut(CHANNELS) {
+            for (sample, shifter) in frame.iter_mut().zip(&mut self.shifters) {
+                *sample = shifter.process(*sample);
+            }
+        }
+    }
+
+    fn tail_frames(&self) -> usize {
+        self.grain_frames
+    }
+
+    fn latency_frames(&self) -> usize {
+        self.grain_frames / 2
+    }
+}
+
+/// Shifts the pitch of one channel by reading a delay line through two taps
+/// whose delay drifts by `1 - ratio` per sample, each faded out as it wraps
+/// around. The signal is delayed by half of `grain_frames` on average.
+pub struct PitchShifter {
+    buffer: Vec<f32>,
+    write: usize,
+    grain_frames: f32,
+    /// Delay of the first tap, as a share of the grain
+    phase: f32,
+    step: f32,
+}
+
+impl PitchShifter {
+    pub fn new(ratio: f32, grain_frames: usize) -> Self {
+        Self {
+            buffer: vec![0.0; grain_frames + 2],
+            write: 0,
+            grain_frames: grain_frames as f32,
+            phase: 0.0,
+            step: (1.0 - ratio) / grain_frames as f32,
+        }
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        self.buffer[self.write] = input;
+
+        let mut output = 0.0;
+        for offset in [0.0, 0.5] {
+            let phase = (self.phase + offset).fract();
+            // The two taps' gains always add up to one
+            let gain = (PI * phase).sin().powi(2);
+            output += gain * self.read(phase * self.grain_frames);
+        }
+
+        self.phase = (self.phase + self.step).rem_euclid(1.0);
+        self.write = (self.write + 1) % self.buffer.len();
+        output
+    }
+
+    /// The input from `delay` samples ago, interpolated between samples
+    fn read(&self, delay: f32) -> f32 {
+        let length = self.buffer.len() as f32;
+        let position = (self.write as f32 - delay).rem_euclid(length);
+        let index = position as usize % self.buffer.len();
+        let fraction = position.fract();
+        let next = (index + 1) % self.buffer.len();
+        self.buffer[index] * (1.0 - fraction) + self.buffer[next] * fraction
+    }
+}
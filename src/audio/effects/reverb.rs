@@ -0,0 +1,107 @@
+use super::{Effect, mix};
+use crate::audio::mixer::{CHANNELS, SAMPLE_RATE};
+use crate::settings::effects::ReverbSettings;
+
+/// Delay lengths of the Freeverb design, in frames at 44.1 kHz
+const COMB_TUNING: [usize; 8] = [1116, 1188, 1277, 1356, 1422, 1491, 1557, 1617];
+const ALLPASS_TUNING: [usize; 4] = [556, 441, 341, 225];
+/// The right channel's delays are this much longer, to spread it across the stereo field
+const STEREO_SPREAD: usize = 23;
+const FIXED_GAIN: f32 = 0.015;
+const TAIL_SECONDS: f32 = 3.0;
+
+/// Feedback comb filter with a low-pass in the loop
+struct Comb {
+    buffer: Vec<f32>,
+    position: usize,
+    filter_state: f32,
+}
+
+impl Comb {
+    fn process(&mut self, input: f32, feedback: f32, damping: f32) -> f32 {
+        let output = self.buffer[self.position];
+        self.filter_state = output * (1.0 - damping) + self.filter_state * damping;
+        self.buffer[self.position] = input + self.filter_state * feedback;
+        self.position = (self.position + 1) % self.buffer.len();
+        output
+    }
+}
+
+struct Allpass {
+    buffer: Vec<f32>,
+    position: usize,
+}
+
+impl Allpass {
+    fn process(&mut self, input: f32) -> f32 {
+        let delayed = self.buffer[self.position];
+        self.buffer[self.position] = input + delayed * 0.5;
+        self.position = (self.position + 1) % self.buffer.len();
+        delayed - input
+    }
+}
+
+/// Freeverb-style reverb: parallel combs followed by allpasses, per channel
+pub struct Reverb {
+    combs: [Vec<Comb>; CHANNELS],
+    allpasses: [Vec<Allpass>; CHANNELS],
+    feedback: f32,
+    damping: f32,
+    mix: f32,
+}
+
+impl Reverb {
+    pub fn new(settings: &ReverbSettings) -> Self {
+        let scale = |frames: usize| (frames * SAMPLE_RATE as usize / 44_100).max(1);
+        let combs = |spread: usize| {
+            COMB_TUNING
+                .iter()
+                .map(|&frames| Comb {
+                    buffer: vec![0.0; scale(frames + spread)],
+                    position: 0,
+                    filter_state: 0.0,
+                })
+                .collect()
+        };
+        let allpasses = |spread: usize| {
+            ALLPASS_TUNING
+                .iter()
+                .map(|&frames| Allpass {
+                    buffer: vec![0.0; scale(frames + spread)],
+                    position: 0,
+                })
+                .collect()
+        };
+
+        Self {
+            combs: [combs(0), combs(STEREO_SPREAD)],
+            allpasses: [allpasses(0), allpasses(STEREO_SPREAD)],
+            feedback: 0.7 + settings.room_size.clamp(0.0, 1.0) * 0.28,
+            damping: settings.damping.clamp(0.0, 1.0) * 0.4,
+            mix: settings.mix.clamp(0.0, 1.0),
+        }
+    }
+}
+
+impl Effect for Reverb {
+    fn process(&mut self, frames: &mut [f32]) {
+        for frame in frames.chunks_exact_mut(CHANNELS) {
+            let input = frame.iter().sum::<f32>() * FIXED_GAIN;
+
+            for (channel, sample) in frame.iter_mut().enumerate() {
+                let mut wet: f32 = self.combs[channel]
+                    .iter_mut()
+                    .map(|comb| comb.process(input, self.feedback, self.damping))
+                    .sum();
+                for allpass in &mut self.allpasses[channel] {
+                    wet = allpass.process(wet);
+                }
+                *sample = mix(*sample, wet, self.mix);
+            }
+        }
+    }
+
+    fn tail_frames(&self) -> usize {
+        (TAIL_SECONDS * SAMPLE_RATE as f32) as usize
+    }
+}
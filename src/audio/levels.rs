@@ -0,0 +1,43 @@
+use serde::{Deserialize, Serialize};
+
+use super::mixer::CHANNELS;
+
+/// Peak and RMS of a signal over one measurement, as linear amplitudes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Level {
+    pub peak: f32,
+    pub rms: f32,
+}
+
+/// Accumulates the peak and RMS of the frames added to it until they are taken
+#[derive(Default)]
+pub struct LevelMeter {
+    peak: f32,
+    sum_of_squares: f32,
+    samples: usize,
+}
+
+impl LevelMeter {
+    pub fn add_frame(&mut self, frame: &[f32]) {
+        for &sample in frame {
+            self.peak = self.peak.max(sample.abs());
+            self.sum_of_squares += sample * sample;
+        }
+        self.samples += frame.len();
+    }
+
+    /// Frames added since the level was last taken
+    pub fn frames(&self) -> usize {
+        self.samples / CHANNELS
+    }
+
+    /// The level of the frames added since the last call, starting over
+    pub fn take(&mut self) -> Level {
+        let level = Level {
+            peak: self.peak,
+            rms: (self.sum_of_squares / self.samples.max(1) as f32).sqrt(),
+        };
+        *self = Self::default();
+        level
+    }
+}
@@ -0,0 +1,473 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use super::decoder::{self, AudioClip, ClipInfo, SUPPORTED_EXTENSIONS, StreamDecoder};
+use super::{AudioError, Sound};
+use crate::settings::library::LibrarySettings;
+
+/// A sound ready to play
+pub enum LoadedSound {
+    /// Decoded in memory
+    Decoded(Arc<AudioClip>),
+    /// Too long to decode up front, so read from disk as it plays
+    Streamed { path: PathBuf, info: ClipInfo },
+}
+
+impl LoadedSound {
+    pub fn info(&self) -> ClipInfo {
+        match self {
+            LoadedSound::Decoded(clip) => clip.info(),
+            LoadedSound::Streamed { info, .. } => *info,
+        }
+    }
+}
+
+/// The sounds found in the sound files directory. Short sounds are decoded in
+/// the background and kept in memory, within a budget; long ones are streamed.
+pub struct SoundLibrary {
+    directory: PathBuf,
+    sounds: Vec<Sound>,
+    settings: LibrarySettings,
+    cache: Arc<Mutex<ClipCache>>,
+    /// Sounds waiting to be decoded ahead of their first play
+    preload: Sender<(String, PathBuf)>,
+}
+
+impl SoundLibrary {
+    pub fn new(directory: &Path, settings: &LibrarySettings) -> Self {
+        let cache = Arc::new(Mutex::new(ClipCache::new(
+            settings.cache_mb as usize * 1024 * 1024,
+        )));
+        let (preload, jobs) = mpsc::channel();
+
+        let thread_cache = Arc::clone(&cache);
+        let thread_settings = settings.clone();
+        thread::spawn(move || preload_queued(jobs, &thread_cache, &thread_settings));
+
+        let mut library = Self {
+            directory: directory.to_path_buf(),
+            sounds: Vec::new(),
+            settings: settings.clone(),
+            cache,
+            preload,
+        };
+
+        if let Err(e) = library.rescan() {
+            eprintln!("Warning: Could not scan sound files directory ({}).", e);
+        }
+
+        library
+    }
+
+    pub fn sounds(&self) -> &[Sound] {
+        &self.sounds
+    }
+
+    pub fn get(&self, sound_id: &str) -> Result<&Sound, AudioError> {
+        self.sounds
+            .iter()
+            .find(|sound| sound.id == sound_id)
+            .ok_or_else(|| AudioError::SoundNotFound(sound_id.to_string()))
+    }
+
+    /// Get a sound ready to play, from the cache if it has been decoded already.
+    /// A short sound that hasn't is decoded now, making room for it if need be.
+    pub fn load(&mut self, sound_id: &str) -> Result<LoadedSound, AudioError> {
+        let path = self.get(sound_id)?.path.clone();
+        if let Some(clip) = self.lock_cache().get(sound_id) {
+            return Ok(LoadedSound::Decoded(clip));
+        }
+
+        let info = match self.lock_cache().infos.get(sound_id) {
+            Some(&info) => Some(info),
+            None => StreamDecoder::open(&path)?.info(),
+        };
+        if let Some(info) = info {
+            self.lock_cache().infos.insert(sound_id.to_string(), info);
+            if streams(info, &self.settings) {
+                return Ok(LoadedSound::Streamed { path, info });
+            }
+        }
+
+        let clip = Arc::new(decoder::decode_file(&path)?);
+        self.lock_cache()
+            .insert(sound_id, Arc::clone(&clip), Eviction::LeastRecentlyUsed);
+        Ok(LoadedSound::Decoded(clip))
+    }
+
+    /// Look for supported audio files in the sound files directory
+    pub fn rescan(&mut self) -> Result<(), AudioError> {
+        self.sounds.clear();
+
+        if self.directory.as_os_str().is_empty() {
+            self.lock_cache().retain(&[]);
+            return Ok(());
+        }
+
+        let entries = fs::read_dir(&self.directory).map_err(|e| {
+            AudioError::InitializationFailed(format!("{}: {}", self.directory.display(), e))
+        })?;
+
+        for path in entries.filter_map(|entry| entry.ok().map(|e| e.path())) {
+            let supported = path
+                .extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| SUPPORTED_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()));
+
+            let Some(name) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+
+            if supported && path.is_file() {
+                self.sounds.push(Sound {
+                    id: sound_id_from_name(name),
+                    name: name.to_string(),
+                    path,
+                });
+            }
+        }
+
+        self.sounds.sort_by(|a, b| a.name.cmp(&b.name));
+        self.sounds.dedup_by(|a, b| a.id == b.id);
+
+        // Drop cached audio for files that are gone
+        self.lock_cache().retain(&self.sounds);
+
+        Ok(())
+    }
+
+    /// Decode the short sounds in the background, before they are first played
+    pub fn preload(&self) {
+        for sound in &self.sounds {
+            let _ = self.preload.send((sound.id.clone(), sound.path.clone()));
+        }
+    }
+
+    fn lock_cache(&self) -> std::sync::MutexGuard<'_, ClipCache> {
+        self.cache.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Whether a sound is too long, or too big for the cache, to decode up front
+fn streams(info: ClipInfo, settings: &LibrarySettings) -> bool {
+    info.duration_ms() > settings.stream_after_secs.saturating_mul(1000)
+        || info.decoded_bytes() > settings.cache_mb as usize * 1024 * 1024
+}
+
+/// What the cache does when a clip doesn't fit
+#[derive(Clone, Copy, PartialEq)]
+enum Eviction {
+    /// Leave the clip out
+    None,
+    /// Drop the clips played longest ago until it fits
+    LeastRecentlyUsed,
+}
+
+struct CachedClip {
+    clip: Arc<AudioClip>,
+    /// [`ClipCache::clock`] when the clip was last played
+    last_used: u64,
+}
+
+/// Decoded sounds, kept within a memory budget
+struct ClipCache {
+    clips: HashMap<String, CachedClip>,
+    /// Lengths of the sounds whose files give them, whether decoded or not
+    infos: HashMap<String, ClipInfo>,
+    /// Taken up by the clips, and by those being preloaded
+    bytes: usize,
+    budget: usize,
+    /// Counts the clips played, to tell which was played longest ago
+    clock: u64,
+}
+
+impl ClipCache {
+    fn new(budget: usize) -> Self {
+        Self {
+            clips: HashMap::new(),
+            infos: HashMap::new(),
+            bytes: 0,
+            budget,
+            clock: 0,
+        }
+    }
+
+    fn get(&mut self, sound_id: &str) -> Option<Arc<AudioClip>> {
+        let cached = self.clips.get_mut(sound_id)?;
+        self.clock += 1;
+        cached.last_used = self.clock;
+        Some(Arc::clone(&cached.clip))
+    }
+
+    /// Keep a clip if there is room for it, returning whether it was kept
+    fn insert(&mut self, sound_id: &str, clip: Arc<AudioClip>, eviction: Eviction) -> bool {
+        if self.clips.contains_key(sound_id) {
+            return true;
+        }
+        let size = clip.info().decoded_bytes();
+        if size > self.budget {
+            return false;
+        }
+
+        while self.bytes + size > self.budget {
+            if eviction == Eviction::None {
+                return false;
+            }
+            // Clips that are playing stay, as dropping them here would free nothing
+            let Some(oldest) = self
+                .clips
+                .iter()
+                .filter(|(_, cached)| Arc::strong_count(&cached.clip) == 1)
+                .min_by_key(|(_, cached)| cached.last_used)
+                .map(|(id, _)| id.clone())
+            else {
+                return false;
+            };
+            self.remove(&oldest);
+        }
+
+        self.clock += 1;
+        self.bytes += size;
+        self.clips.insert(
+            sound_id.to_string(),
+            CachedClip {
+                clip,
+                last_used: self.clock,
+            },
+        );
+        true
+    }
+
+    /// Set aside room for a clip about to be decoded, returning false if there isn't any
+    fn reserve(&mut self, size: usize) -> bool {
+        if self.bytes + size > self.budget {
+            return false;
+        }
+        self.bytes += size;
+        true
+    }
+
+    /// Give back room set aside by [`ClipCache::reserve`]
+    fn release(&mut self, size: usize) {
+        self.bytes -= size;
+    }
+
+    fn remove(&mut self, sound_id: &str) {
+        if let Some(cached) = self.clips.remove(sound_id) {
+            self.bytes -= cached.clip.info().decoded_bytes();
+        }
+    }
+
+    /// Forget every sound but `sounds`
+    fn retain(&mut self, sounds: &[Sound]) {
+        let gone: Vec<String> = self
+            .clips
+            .keys()
+            .filter(|id| !sounds.iter().any(|sound| &sound.id == *id))
+            .cloned()
+            .collect();
+        for id in gone {
+            self.remove(&id);
+        }
+        self.infos
+            .retain(|id, _| sounds.iter().any(|sound| &sound.id == id));
+    }
+}
+
+/// Decode the short sounds queued up, for as long as they fit in the cache.
+/// Sounds that have been played take priority, so nothing is evicted here, and
+/// room is set aside before decoding so a clip is never decoded only to be left out.
+fn preload_queued(
+    jobs: Receiver<(String, PathBuf)>,
+    cache: &Mutex<ClipCache>,
+    settings: &LibrarySettings,
+) {
+    let lock = || cache.lock().unwrap_or_else(|e| e.into_inner());
+    while let Ok((sound_id, path)) = jobs.recv() {
+        if lock().clips.contains_key(&sound_id) {
+            continue;
+        }
+        // Unreadable files are reported when they are played, and files that
+        // don't give their length are decoded then too
+        let Ok(Some(info)) = StreamDecoder::open(&path).map(|decoder| decoder.info()) else {
+            continue;
+        };
+        let size = info.decoded_bytes();
+        {
+            let mut cache = lock();
+            cache.infos.insert(sound_id.clone(), info);
+            if streams(info, settings) || !cache.reserve(size) {
+                continue;
+            }
+        }
+
+        let clip = decoder::decode_file(&path);
+        let mut cache = lock();
+        cache.release(size);
+        if let Ok(clip) = clip {
+            cache.insert(&sound_id, Arc::new(clip), Eviction::None);
+        }
+    }
+}
+
+/// Turn a file name like "Air Horn (loud)" into an id like "air-horn-loud"
+fn sound_id_from_name(name: &str) -> String {
+    name.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A silent clip that takes up `frames` * 8 bytes
+    fn clip(frames: usize) -> Arc<AudioClip> {
+        Arc::new(AudioClip {
+            samples: vec![0.0; frames * 2],
+            sample_rate: 48000,
+        })
+    }
+
+    fn cached(cache: &ClipCache) -> Vec<&str> {
+        let mut ids: Vec<&str> = cache.clips.keys().map(String::as_str).collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn clips_are_kept_within_the_budget() {
+        let mut cache = ClipCache::new(800);
+        assert!(cache.insert("a", clip(50), Eviction::LeastRecentlyUsed));
+        assert!(cache.insert("b", clip(50), Eviction::LeastRecentlyUsed));
+        assert_eq!(cache.bytes, 800);
+
+        // Too big to ever fit
+        assert!(!cache.insert("c", clip(101), Eviction::LeastRecentlyUsed));
+        assert_eq!(cached(&cache), ["a", "b"]);
+
+        // Already there
+        assert!(cache.insert("a", clip(100), Eviction::None));
+        assert_eq!(cache.bytes, 800);
+
+        cache.remove("a");
+        assert_eq!(cache.bytes, 400);
+        assert!(cache.get("a").is_none());
+    }
+
+    #[test]
+    fn the_clip_played_longest_ago_makes_way() {
+        let mut cache = ClipCache::new(1200);
+        cache.insert("a", clip(50), Eviction::LeastRecentlyUsed);
+        cache.insert("b", clip(50), Eviction::LeastRecentlyUsed);
+        cache.insert("c", clip(50), Eviction::LeastRecentlyUsed);
+        cache.get("a");
+
+        assert!(cache.insert("d", clip(50), Eviction::LeastRecentlyUsed));
+        assert_eq!(cached(&cache), ["a", "c", "d"]);
+        assert!(cache.insert("e", clip(100), Eviction::LeastRecentlyUsed));
+        assert_eq!(cached(&cache), ["d", "e"]);
+        assert_eq!(cache.bytes, 1200);
+    }
+
+    #[test]
+    fn playing_clips_are_not_evicted() {
+        let mut cache = ClipCache::new(800);
+        cache.insert("a", clip(50), Eviction::LeastRecentlyUsed);
+        cache.insert("b", clip(50), Eviction::LeastRecentlyUsed);
+        let _playing = cache.get("a");
+        cache.get("b");
+
+        assert!(cache.insert("c", clip(50), Eviction::LeastRecentlyUsed));
+        assert_eq!(cached(&cache), ["a", "c"]);
+        let _also_playing = cache.get("c");
+        assert!(!cache.insert("d", clip(50), Eviction::LeastRecentlyUsed));
+        assert_eq!(cache.bytes, 800);
+    }
+
+    #[test]
+    fn preloading_never_evicts() {
+        let mut cache = ClipCache::new(800);
+        cache.insert("a", clip(50), Eviction::LeastRecentlyUsed);
+        assert!(cache.insert("b", clip(50), Eviction::None));
+        assert!(!cache.insert("c", clip(50), Eviction::None));
+        assert_eq!(cached(&cache), ["a", "b"]);
+    }
+
+    #[test]
+    fn reserved_room_is_kept_for_the_preload() {
+        let mut cache = ClipCache::new(800);
+        assert!(cache.reserve(400));
+        assert!(!cache.reserve(401));
+
+        // A sound played meanwhile can't take the room set aside
+        assert!(cache.insert("a", clip(50), Eviction::LeastRecentlyUsed));
+        let _playing = cache.get("a");
+        assert!(!cache.insert("b", clip(50), Eviction::LeastRecentlyUsed));
+        assert_eq!(cached(&cache), ["a"]);
+
+        cache.release(400);
+        assert!(cache.insert("c", clip(50), Eviction::None));
+        assert_eq!(cache.bytes, 800);
+    }
+
+    #[test]
+    fn sounds_that_are_gone_are_forgotten() {
+        let mut cache = ClipCache::new(800);
+        cache.insert("a", clip(50), Eviction::LeastRecentlyUsed);
+        cache.insert("b", clip(50), Eviction::LeastRecentlyUsed);
+        let info = ClipInfo {
+            frames: 50,
+            sample_rate: 48000,
+        };
+        cache.infos.insert("a".to_string(), info);
+        cache.infos.insert("b".to_string(), info);
+
+        let sounds = [Sound {
+            id: "b".to_string(),
+            name: "B".to_string(),
+            path: PathBuf::from("b.wav"),
+        }];
+        cache.retain(&sounds);
+        assert_eq!(cached(&cache), ["b"]);
+        assert_eq!(cache.bytes, 400);
+        assert!(!cache.infos.contains_key("a"));
+    }
+
+    #[test]
+    fn long_or_big_sounds_stream() {
+        let settings = LibrarySettings {
+            stream_after_secs: 10,
+            cache_mb: 1,
+        };
+        let short = ClipInfo {
+            frames: 48000,
+            sample_rate: 48000,
+        };
+        let long = ClipInfo {
+            frames: 48000 * 11,
+            sample_rate: 48000,
+        };
+        // Short, but more than a megabyte decoded
+        let big = ClipInfo {
+            frames: 200_000,
+            sample_rate: 48000,
+        };
+        assert!(!streams(short, &settings));
+        assert!(streams(long, &settings));
+        assert!(streams(big, &settings));
+    }
+
+    #[test]
+    fn sound_ids_come_from_file_names() {
+        assert_eq!(sound_id_from_name("Air Horn (loud)"), "air-horn-loud");
+        assert_eq!(sound_id_from_name("  Ba--dum_TSS! "), "ba-dum-tss");
+        assert_eq!(sound_id_from_name("Café 2"), "café-2");
+    }
+}
@@ -0,0 +1,504 @@
+use std::collections::BTreeMap;
+use std::f64::consts::PI;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::UNIX_EPOCH;
+
+use serde::{Deserialize, Serialize};
+
+use super::AudioError;
+use super::decoder::{self, StreamDecoder};
+use super::mixer::CHANNELS;
+
+/// ReplayGain 2.0 brings tracks to -18 LUFS
+const REPLAYGAIN_REFERENCE_LUFS: f32 = -18.0;
+
+/// Blocks quieter than this don't count towards the integrated loudness
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+
+/// Blocks this far below the ungated loudness don't count either
+const RELATIVE_GATE_LU: f64 = -10.0;
+
+/// Measurements are made over 400 ms blocks, overlapping by 300 ms
+const BLOCK_STEPS: usize = 4;
+const STEP_SECONDS: f64 = 0.1;
+
+/// True peak is found by upsampling four times
+const OVERSAMPLING: usize = 4;
+const INTERPOLATION_TAPS: usize = 48;
+
+/// How loud a sound is, as defined by ITU-R BS.1770
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Loudness {
+    pub integrated_lufs: f32,
+    pub true_peak_db: f32,
+}
+
+impl Loudness {
+    /// Gain in dB that brings the sound to `target_lufs` without turning it up
+    /// so far that its true peak goes over `max_true_peak_db`
+    pub fn gain_db(&self, target_lufs: f32, max_true_peak_db: f32) -> f32 {
+        let headroom = (max_true_peak_db - self.true_peak_db).max(0.0);
+        (target_lufs - self.integrated_lufs).min(headroom)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum LoudnessSource {
+    Analysis,
+    ReplayGain,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    /// Modification time and size of the file when it was measured
+    modified_ns: u64,
+    len: u64,
+    source: LoudnessSource,
+    /// Unset for silent files, which have no loudness to speak of
+    loudness: Option<Loudness>,
+}
+
+/// Measurements of every file analyzed so far, kept in the cache directory between runs
+/// rather than with the sound's metadata in the settings file, which only holds what
+/// the user chose
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct LoudnessCache {
+    entries: BTreeMap<PathBuf, CacheEntry>,
+}
+
+impl LoudnessCache {
+    fn path() -> Option<PathBuf> {
+        dirs::cache_dir().map(|dir| dir.join("boomcrab").join("loudness.json"))
+    }
+
+    fn load() -> Self {
+        Self::path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self) -> std::io::Result<()> {
+        let Some(path) = Self::path() else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        // Write to the side first so a run cut short can't leave half a file behind
+        let partial = path.with_extension("json.partial");
+        fs::write(&partial, serde_json::to_vec(self)?)?;
+        fs::rename(partial, path)
+    }
+}
+
+/// Measures the loudness of sound files on a background thread
+pub struct LoudnessAnalyzer {
+    cache: Arc<Mutex<LoudnessCache>>,
+    queue: Sender<PathBuf>,
+}
+
+impl LoudnessAnalyzer {
+    /// Start the analysis thread. With `replaygain`, files that have
+    /// ReplayGain tags are measured by their tags instead of decoding them.
+    pub fn start(replaygain: bool) -> Self {
+        let cache = Arc::new(Mutex::new(LoudnessCache::load()));
+        let (queue, jobs) = mpsc::channel();
+
+        let thread_cache = Arc::clone(&cache);
+        thread::spawn(move || analyze_queued(jobs, &thread_cache, replaygain));
+
+        Self { cache, queue }
+    }
+
+    /// Measure a file in the background, unless it is cached and hasn't changed since
+    pub fn queue(&self, path: &Path) {
+        let _ = self.queue.send(path.to_path_buf());
+    }
+
+    /// The loudness of a file, if it has been measured
+    pub fn get(&self, path: &Path) -> Option<Loudness> {
+        let cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        cache.entries.get(path).and_then(|entry| entry.loudness)
+    }
+
+    /// Save what has been measured so far, e.g. before the daemon stops
+    pub fn save(&self) {
+        let cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = cache.save() {
+            eprintln!("Warning: Could not save the loudness cache ({}).", e);
+        }
+    }
+}
+
+fn analyze_queued(jobs: Receiver<PathBuf>, cache: &Mutex<LoudnessCache>, replaygain: bool) {
+    while let Ok(path) = jobs.recv() {
+        let mut changed = analyze_if_stale(&path, cache, replaygain);
+
+        // Save once the queue has drained, rather than after every file
+        while let Ok(path) = jobs.try_recv() {
+            changed |= analyze_if_stale(&path, cache, replaygain);
+        }
+
+        if changed {
+            let cache = cache.lock().unwrap_or_else(|e| e.into_inner());
+            if let Err(e) = cache.save() {
+                eprintln!("Warning: Could not save the loudness cache ({}).", e);
+            }
+        }
+    }
+}
+
+/// Measure a file unless its cache entry is still good, returning whether the cache changed
+fn analyze_if_stale(path: &Path, cache: &Mutex<LoudnessCache>, replaygain: bool) -> bool {
+    let Ok(metadata) = fs::metadata(path) else {
+        return false;
+    };
+    let modified_ns = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |since| since.as_nanos() as u64);
+    let len = metadata.len();
+
+    {
+        let cache = cache.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(entry) = cache.entries.get(path)
+            && entry.modified_ns == modified_ns
+            && entry.len == len
+            && (replaygain || entry.source == LoudnessSource::Analysis)
+        {
+            return false;
+        }
+    }
+
+    let tags = if replaygain {
+        // A zero peak can't be right, so such tags are ignored
+        decoder::read_replaygain(path)
+            .ok()
+            .flatten()
+            .filter(|tags| tags.track_peak > 0.0)
+    } else {
+        None
+    };
+
+    let (source, loudness) = match tags {
+        Some(tags) => (
+            LoudnessSource::ReplayGain,
+            Some(Loudness {
+                integrated_lufs: REPLAYGAIN_REFERENCE_LUFS - tags.track_gain_db,
+                true_peak_db: amplitude_to_db(tags.track_peak as f64) as f32,
+            }),
+        ),
+        None => match measure_file(path) {
+            Ok(loudness) => (LoudnessSource::Analysis, loudness),
+            Err(e) => {
+                eprintln!("Warning: Could not analyze loudness ({}).", e);
+                return false;
+            }
+        },
+    };
+
+    let mut cache = cache.lock().unwrap_or_else(|e| e.into_inner());
+    cache.entries.insert(
+        path.to_path_buf(),
+        CacheEntry {
+            modified_ns,
+            len,
+            source,
+            loudness,
+        },
+    );
+    true
+}
+
+/// Measure a file a packet at a time, so a long one is never all in memory at once
+fn measure_file(path: &Path) -> Result<Option<Loudness>, AudioError> {
+    let mut decoder = StreamDecoder::open(path)?;
+    let mut meter = LoudnessMeter::new(decoder.sample_rate);
+    let mut samples = Vec::new();
+    while decoder.decode_next(&mut samples)?.is_some() {
+        meter.add(&samples);
+        samples.clear();
+    }
+    Ok(meter.finish())
+}
+
+/// Works out the integrated loudness and true peak of a sound fed to it in pieces
+struct LoudnessMeter {
+    filters: [[Biquad; 2]; CHANNELS],
+    step_frames: usize,
+    /// Sum of the weighted channels' squares over each whole 100 ms step so far
+    steps: Vec<f64>,
+    /// And over the step under way
+    sum: f64,
+    frames: usize,
+    true_peak: TruePeak,
+}
+
+impl LoudnessMeter {
+    /// The filters are worked out for the sound's own sample rate
+    fn new(sample_rate: u32) -> Self {
+        Self {
+            filters: [k_weighting(sample_rate); CHANNELS],
+            step_frames: ((sample_rate as f64 * STEP_SECONDS).round() as usize).max(1),
+            steps: Vec::new(),
+            sum: 0.0,
+            frames: 0,
+            true_peak: TruePeak::new(),
+        }
+    }
+
+    /// Measure the next interleaved frames of the sound
+    fn add(&mut self, samples: &[f32]) {
+        for frame in samples.chunks_exact(CHANNELS) {
+            for (filter, &sample) in self.filters.iter_mut().zip(frame) {
+                let weighted = filter
+                    .iter_mut()
+                    .fold(sample as f64, |x, stage| stage.process(x));
+                self.sum += weighted * weighted;
+            }
+            self.frames += 1;
+            if self.frames.is_multiple_of(self.step_frames) {
+                self.steps.push(self.sum);
+                self.sum = 0.0;
+            }
+            self.true_peak.add(frame);
+        }
+    }
+
+    /// The loudness of the whole sound, or nothing if it is silent
+    fn finish(self) -> Option<Loudness> {
+        let integrated = self.integrated_loudness()?;
+        Some(Loudness {
+            integrated_lufs: integrated as f32,
+            true_peak_db: amplitude_to_db(self.true_peak.peak) as f32,
+        })
+    }
+
+    /// Gated integrated loudness in LUFS, per ITU-R BS.1770-4
+    fn integrated_loudness(&self) -> Option<f64> {
+        let blocks: Vec<f64> = if self.steps.len() < BLOCK_STEPS {
+            // Sounds shorter than a block are measured as a whole rather than not at all
+            let total = self.steps.iter().sum::<f64>() + self.sum;
+            vec![total / self.frames.max(1) as f64]
+        } else {
+            self.steps
+                .windows(BLOCK_STEPS)
+                .map(|block| block.iter().sum::<f64>() / (BLOCK_STEPS * self.step_frames) as f64)
+                .collect()
+        };
+
+        let gated_mean = |threshold: f64| {
+            let gated: Vec<f64> = blocks
+                .iter()
+                .copied()
+                .filter(|&energy| energy_to_lufs(energy) > threshold)
+                .collect();
+            (!gated.is_empty()).then(|| gated.iter().sum::<f64>() / gated.len() as f64)
+        };
+
+        let ungated = gated_mean(ABSOLUTE_GATE_LUFS)?;
+        let relative_gate = energy_to_lufs(ungated) + RELATIVE_GATE_LU;
+        gated_mean(relative_gate.max(ABSOLUTE_GATE_LUFS)).map(energy_to_lufs)
+    }
+}
+
+fn amplitude_to_db(amplitude: f64) -> f64 {
+    20.0 * amplitude.log10()
+}
+
+fn energy_to_lufs(energy: f64) -> f64 {
+    -0.691 + 10.0 * energy.log10()
+}
+
+/// A second-order IIR filter section
+#[derive(Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    state: [f64; 2],
+}
+
+impl Biquad {
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.state[0];
+        self.state[0] = self.b[1] * x - self.a[0] * y + self.state[1];
+        self.state[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
+
+/// The K-weighting filter: a high shelf for the effect of the head, then a high pass.
+/// The coefficients are worked out for the clip's own sample rate.
+fn k_weighting(sample_rate: u32) -> [Biquad; 2] {
+    let rate = sample_rate as f64;
+
+    let k = (PI * 1681.974450955533 / rate).tan();
+    let q = 0.7071752369554196;
+    let vh = 10f64.powf(3.999843853973347 / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad {
+        b: [
+            (vh + vb * k / q + k * k) / a0,
+            2.0 * (k * k - vh) / a0,
+            (vh - vb * k / q + k * k) / a0,
+        ],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        state: [0.0; 2],
+    };
+
+    let k = (PI * 38.13547087602444 / rate).tan();
+    let q = 0.5003270373238773;
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Biquad {
+        b: [1.0, -2.0, 1.0],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        state: [0.0; 2],
+    };
+
+    [shelf, high_pass]
+}
+
+/// Taps of each of the interpolating filter's phases
+const PHASE_TAPS: usize = INTERPOLATION_TAPS / OVERSAMPLING;
+
+/// Highest absolute value of the signal between samples as well as on them
+struct TruePeak {
+    /// Windowed sinc, centred so that every fourth tap lands on an original sample
+    taps: [f64; INTERPOLATION_TAPS],
+    /// The last samples of each channel, latest first
+    history: [[f64; PHASE_TAPS]; CHANNELS],
+    peak: f64,
+}
+
+impl TruePeak {
+    fn new() -> Self {
+        let centre = (INTERPOLATION_TAPS / 2) as f64;
+        let taps = std::array::from_fn(|n| {
+            let x = n as f64 - centre;
+            let t = x / OVERSAMPLING as f64;
+            let sinc = if x == 0.0 {
+                1.0
+            } else {
+                (PI * t).sin() / (PI * t)
+            };
+            let window = 0.5 * (1.0 + (PI * x / centre).cos());
+            sinc * window
+        });
+        Self {
+            taps,
+            history: [[0.0; PHASE_TAPS]; CHANNELS],
+            peak: 0.0,
+        }
+    }
+
+    fn add(&mut self, frame: &[f32]) {
+        for (history, &sample) in self.history.iter_mut().zip(frame) {
+            history.rotate_right(1);
+            history[0] = sample as f64;
+            self.peak = self.peak.max(history[0].abs());
+            for phase in 1..OVERSAMPLING {
+                let interpolated: f64 = history
+                    .iter()
+                    .enumerate()
+                    .map(|(k, x)| self.taps[k * OVERSAMPLING + phase] * x)
+                    .sum();
+                self.peak = self.peak.max(interpolated.abs());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::decoder::AudioClip;
+
+    /// Measure a clip in uneven pieces, as packets come from a file
+    fn measure(clip: &AudioClip) -> Option<Loudness> {
+        let mut meter = LoudnessMeter::new(clip.sample_rate);
+        for packet in clip.samples.chunks(1151 * CHANNELS) {
+            meter.add(packet);
+        }
+        meter.finish()
+    }
+
+    /// A stereo sine with the same signal on both channels
+    fn sine(frequency: f64, amplitude: f64, phase: f64, seconds: f64) -> AudioClip {
+        let sample_rate = 48_000;
+        let frames = (sample_rate as f64 * seconds) as usize;
+        let samples = (0..frames)
+            .flat_map(|i| {
+                let t = i as f64 / sample_rate as f64;
+                let sample = (amplitude * (2.0 * PI * frequency * t + phase).sin()) as f32;
+                [sample; CHANNELS]
+            })
+            .collect();
+        AudioClip {
+            samples,
+            sample_rate,
+        }
+    }
+
+    fn loudness(integrated_lufs: f32, true_peak_db: f32) -> Loudness {
+        Loudness {
+            integrated_lufs,
+            true_peak_db,
+        }
+    }
+
+    #[test]
+    fn gain_reaches_the_target() {
+        assert_eq!(loudness(-20.0, -10.0).gain_db(-16.0, -1.0), 4.0);
+        assert_eq!(loudness(-10.0, -0.5).gain_db(-16.0, -1.0), -6.0);
+    }
+
+    #[test]
+    fn gain_stops_at_the_peak_limit() {
+        assert_eq!(loudness(-30.0, -3.0).gain_db(-16.0, -1.0), 2.0);
+        // A sound that already peaks over the limit is not turned up at all
+        assert_eq!(loudness(-20.0, 0.5).gain_db(-16.0, -1.0), 0.0);
+    }
+
+    #[test]
+    fn measures_a_sine_at_its_level() {
+        // Per BS.1770, a 1 kHz sine in both channels reads at its peak level in dBFS
+        let measured = measure(&sine(997.0, 0.1, 0.0, 3.0)).unwrap();
+        assert!(
+            (measured.integrated_lufs + 20.0).abs() < 0.2,
+            "{:?}",
+            measured
+        );
+        assert!((measured.true_peak_db + 20.0).abs() < 0.1, "{:?}", measured);
+    }
+
+    #[test]
+    fn silence_has_no_loudness() {
+        assert_eq!(measure(&sine(997.0, 0.0, 0.0, 1.0)), None);
+    }
+
+    #[test]
+    fn finds_peaks_between_samples() {
+        // At a quarter of the sample rate and 45 degrees off, every sample
+        // lands at 71% of the real peak
+        let clip = sine(12_000.0, 0.5, PI / 4.0, 0.1);
+        let sample_peak = clip
+            .samples
+            .iter()
+            .fold(0.0f32, |peak, s| peak.max(s.abs()));
+        assert!((sample_peak - 0.354).abs() < 0.01);
+        let mut true_peak = TruePeak::new();
+        for frame in clip.samples.chunks_exact(CHANNELS) {
+            true_peak.add(frame);
+        }
+        assert!((true_peak.peak - 0.5).abs() < 0.03);
+    }
+}
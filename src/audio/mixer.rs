@@ -0,0 +1,1628 @@
+use std::f32::consts::FRAC_PI_2;
+use std::mem;
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use rtrb::{Consumer, Producer, RingBuffer};
+use serde::{Deserialize, Serialize};
+
+use super::AudioError;
+use super::decoder::{AudioClip, ClipInfo};
+use super::ducking::Ducker;
+use super::dynamics::{GainReduction, MicDynamics};
+use super::effects::EffectChain;
+use super::levels::{Level, LevelMeter};
+use super::spectrum::{self, SpectrumAnalyzer, SpectrumSource};
+use super::stream::AudioStream;
+use crate::settings::ducking::DuckRole;
+use crate::settings::fade::FadeCurve;
+use crate::settings::sounds::{ChokeMode, StealPolicy, TriggerMode};
+
+/// Sample rate of the mixer output
+pub const SAMPLE_RATE: u32 = 48_000;
+/// Number of interleaved channels in the mixer output
+pub const CHANNELS: usize = 2;
+
+/// Maximum number of sounds that can play at the same time
+const MAX_VOICES: usize = 32;
+/// Queued events beyond which progress and level updates are dropped
+const EVENT_QUEUE_SIZE: usize = 256;
+/// Room kept in the event queue for voices starting and stopping, which
+/// clients need all of to know what is playing
+const LIFECYCLE_QUEUE_SIZE: usize = 1024;
+/// Effect chains, and voices' sources, waiting to be freed on the control side
+const RETIRED_QUEUE_SIZE: usize = MAX_VOICES * 2;
+/// Output frames per level measurement, for about 30 updates a second
+const LEVEL_INTERVAL_FRAMES: usize = SAMPLE_RATE as usize / 30;
+/// Frames mixed at a time, which sizes the mixer's buses
+const BLOCK_FRAMES: usize = 512;
+/// Room in each of the mic's ring buffers
+const MIC_BUFFER_FRAMES: usize = SAMPLE_RATE as usize / 4;
+/// Changes in mic latency smaller than this, about a millisecond, aren't reported
+const LATENCY_REPORT_FRAMES: usize = SAMPLE_RATE as usize / 1000;
+/// Slowest and fastest a voice plays, as a multiple of its normal speed
+const MIN_SPEED: f64 = 0.25;
+const MAX_SPEED: f64 = 4.0;
+/// Pausing, resuming and seeking ramp the voice's gain over this many frames,
+/// about 5 ms, to avoid clicks
+const PAUSE_RAMP_FRAMES: f32 = SAMPLE_RATE as f32 / 200.0;
+/// How long one output frame plays for
+const FRAME_DURATION: Duration = Duration::from_nanos(1_000_000_000 / SAMPLE_RATE as u64);
+/// Mic audio queued beyond this is thrown away, so a clock running ahead can't build up latency
+pub const MIC_MAX_BACKLOG_FRAMES: usize = 2048;
+
+/// Identifies one playback of a sound
+pub type VoiceId = u64;
+
+/// Commands sent from the control side to the mixer running in the audio callback
+pub enum MixerCommand {
+    Play {
+        voice: VoiceId,
+        sound_id: Arc<str>,
+        source: VoiceSource,
+        /// When the sound was asked for, to measure how long it takes to be heard
+        triggered: Instant,
+        volume: f32,
+        options: PlayOptions,
+        effects: EffectChain,
+    },
+    /// Stop every voice of a sound, fading out if the sound has a fade-out
+    Stop {
+        sound_id: Arc<str>,
+    },
+    /// The key or pad that played a sound was released, which stops it in [`TriggerMode::Hold`]
+    Release {
+        sound_id: Arc<str>,
+    },
+    StopAll,
+    FadeOutAll(Fade),
+    SetSoundVolume {
+        sound_id: Arc<str>,
+        volume: f32,
+    },
+    /// Stop one voice, fading out if its sound has a fade-out
+    StopVoice(VoiceId),
+    SetVoiceVolume {
+        voice: VoiceId,
+        volume: f32,
+    },
+    /// Pause or resume one voice where it is
+    PauseVoice {
+        voice: VoiceId,
+        paused: bool,
+    },
+    /// Pause or resume every voice of a sound
+    PauseSound {
+        sound_id: Arc<str>,
+        paused: bool,
+    },
+    /// Pause or resume every voice
+    PauseAll(bool),
+    /// Move one voice to `position_ms` into its file, kept between its trim points
+    SeekVoice {
+        voice: VoiceId,
+        position_ms: u32,
+    },
+    /// Move every voice of a sound to `position_ms` into its file
+    SeekSound {
+        sound_id: Arc<str>,
+        position_ms: u32,
+    },
+    SetMasterVolume(f32),
+    /// Multiply the speed of the voices playing now. Voices started later play
+    /// at their normal speed.
+    NudgeSpeed(f32),
+    /// Play every voice, including ones started later, this many times faster
+    /// until it is set back to 1, e.g. while a hotkey is held
+    HoldSpeed(f32),
+    SetMasterEffects(EffectChain),
+    /// Effects for the mic, such as the voice changer, before it is mixed with the sounds
+    SetMicEffects(EffectChain),
+}
+
+/// The audio a voice plays
+pub enum VoiceSource {
+    /// Decoded in memory, and shared with the library's cache
+    Decoded(Arc<AudioClip>),
+    /// Read from disk as it plays
+    Streamed(Box<AudioStream>),
+}
+
+impl VoiceSource {
+    fn info(&self) -> ClipInfo {
+        match self {
+            VoiceSource::Decoded(clip) => clip.info(),
+            VoiceSource::Streamed(stream) => stream.info(),
+        }
+    }
+
+    /// Source frames `index` and `next`, or `None` while a stream catches up
+    fn frames(&mut self, index: usize, next: usize) -> Option<([f32; CHANNELS], [f32; CHANNELS])> {
+        match self {
+            VoiceSource::Decoded(clip) => {
+                let frame = |i: usize| {
+                    let mut frame = [0.0; CHANNELS];
+                    frame.copy_from_slice(&clip.samples[i * CHANNELS..(i + 1) * CHANNELS]);
+                    frame
+                };
+                Some((frame(index), frame(next)))
+            }
+            VoiceSource::Streamed(stream) => stream.frames(index, next),
+        }
+    }
+
+    /// Get a stream reading from `frame`; decoded clips can already go anywhere
+    fn seek(&mut self, frame: usize) {
+        if let VoiceSource::Streamed(stream) = self {
+            stream.seek(frame);
+        }
+    }
+
+    fn has_failed(&self) -> bool {
+        match self {
+            VoiceSource::Decoded(_) => false,
+            VoiceSource::Streamed(stream) => stream.has_failed(),
+        }
+    }
+}
+
+/// How a voice plays, from its sound's settings
+#[derive(Debug, Clone)]
+pub struct PlayOptions {
+    pub mode: TriggerMode,
+    /// Times a [`TriggerMode::Loop`] sound plays; `None` loops until stopped
+    pub loop_count: Option<u32>,
+    /// Playback speed, changing pitch along with it
+    pub rate: f32,
+    /// Source frames between the sound's trim points, which loops repeat
+    pub region: Range<usize>,
+    /// Source frame the first play starts from, such as a cue
+    pub start_frame: usize,
+    pub fade_in: Fade,
+    /// Used whenever the voice is stopped before its end
+    pub fade_out: Fade,
+    /// Starting the voice fades out the other voices in this group
+    pub exclusive_group: Option<Arc<str>>,
+    pub choke_group: Option<ChokeGroup>,
+    pub ducking: DuckRole,
+}
+
+/// A choke group and its rules, as resolved for one voice
+#[derive(Debug, Clone)]
+pub struct ChokeGroup {
+    pub name: Arc<str>,
+    pub choke: ChokeMode,
+    /// Fade for choked and stolen voices when `choke` is [`ChokeMode::Fade`]
+    pub fade: Fade,
+    pub max_voices: Option<usize>,
+    pub steal: StealPolicy,
+}
+
+/// The shape and length of a fade; a length of zero cuts instantly
+#[derive(Debug, Clone, Copy)]
+pub struct Fade {
+    pub curve: FadeCurve,
+    pub frames: u32,
+}
+
+impl FadeCurve {
+    /// Gain at `progress`, from 0.0 for silence to 1.0 for full volume
+    fn gain(self, progress: f32) -> f32 {
+        match self {
+            FadeCurve::Linear => progress,
+            FadeCurve::Exponential => progress * progress,
+            FadeCurve::EqualPower => (progress * FRAC_PI_2).sin(),
+        }
+    }
+}
+
+/// Where a voice is in its fades
+#[derive(Clone, Copy)]
+enum Envelope {
+    FadingIn {
+        fade: Fade,
+        elapsed: u32,
+    },
+    Steady,
+    /// Fading out from `from`, after which the voice stops
+    FadingOut {
+        fade: Fade,
+        elapsed: u32,
+        from: f32,
+    },
+}
+
+impl Envelope {
+    fn new(fade_in: Fade) -> Self {
+        if fade_in.frames == 0 {
+            Envelope::Steady
+        } else {
+            Envelope::FadingIn {
+                fade: fade_in,
+                elapsed: 0,
+            }
+        }
+    }
+
+    /// Current gain, from 0.0 to 1.0
+    fn gain(&self) -> f32 {
+        match *self {
+            Envelope::FadingIn { fade, elapsed } => {
+                fade.curve.gain(elapsed as f32 / fade.frames as f32)
+            }
+            Envelope::Steady => 1.0,
+            Envelope::FadingOut {
+                fade,
+                elapsed,
+                from,
+            } => from * fade.curve.gain(1.0 - elapsed as f32 / fade.frames as f32),
+        }
+    }
+
+    /// Move one frame ahead, returning false once a fade-out has finished
+    fn advance(&mut self) -> bool {
+        match self {
+            Envelope::FadingIn { fade, elapsed } => {
+                *elapsed += 1;
+                if *elapsed >= fade.frames {
+                    *self = Envelope::Steady;
+                }
+                true
+            }
+            Envelope::Steady => true,
+            Envelope::FadingOut { fade, elapsed, .. } => {
+                *elapsed += 1;
+                *elapsed < fade.frames
+            }
+        }
+    }
+}
+
+/// Events published by the mixer as voices start and stop, plus regular output levels
+#[derive(Debug, Clone)]
+pub enum MixerEvent {
+    VoiceStarted {
+        voice: VoiceId,
+        sound_id: Arc<str>,
+    },
+    VoiceStopped {
+        voice: VoiceId,
+        sound_id: Arc<str>,
+    },
+    /// Where a voice is and how it is playing, sent with every level measurement
+    VoiceProgress {
+        voice: VoiceId,
+        sound_id: Arc<str>,
+        progress: VoiceProgress,
+    },
+    /// Levels of the output, the mic and the virtual source over the last
+    /// measurement interval. The mic's are unset without the passthrough.
+    Levels {
+        master: Level,
+        mic: Option<Level>,
+        virtual_mic: Option<Level>,
+    },
+    /// Frames from the microphone to the virtual source, sent when it changes
+    MicLatency {
+        frames: usize,
+    },
+    /// Most gain reduction of the mic's gate, compressor and limiter over the last
+    /// measurement interval
+    MicGainReduction(GainReduction),
+}
+
+/// Where a voice is and how it is playing. Times are in milliseconds from the
+/// start of the sound's file.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct VoiceProgress {
+    pub position_ms: u32,
+    /// The sound's trim points, which loops repeat between
+    pub start_ms: u32,
+    pub end_ms: u32,
+    /// Which play of a loop the voice is on, counting from one
+    pub play: u32,
+    /// Times the voice plays in all, or `None` to loop until stopped
+    pub plays: Option<u32>,
+    pub volume: f32,
+    pub paused: bool,
+    /// Whether the sound is read from disk as it plays rather than decoded up front
+    pub streamed: bool,
+    /// Time from the sound being asked for to its first sample being mixed,
+    /// once it has been
+    pub latency_ms: Option<f32>,
+}
+
+struct Voice {
+    id: VoiceId,
+    sound_id: Arc<str>,
+    source: VoiceSource,
+    /// When the sound was asked for
+    triggered: Instant,
+    /// How long the first sample took to be mixed after the trigger
+    latency: Option<Duration>,
+    /// Read position in source frames
+    position: f64,
+    /// Source frames the voice plays, from its trim points
+    region: Range<usize>,
+    /// Source frames to advance per output frame at normal speed
+    step: f64,
+    /// Speed the voice has been nudged to, from [`MixerCommand::NudgeSpeed`]
+    speed: f64,
+    volume: f32,
+    mode: TriggerMode,
+    /// Plays left including the current one, or `None` to loop until stopped
+    plays_left: Option<u32>,
+    /// Plays in all, or `None` to loop until stopped
+    plays: Option<u32>,
+    /// Loops played so far, for voices that loop until stopped
+    loops: u32,
+    paused: bool,
+    /// Gain of the ramp into and out of a pause or seek, from 0.0 to 1.0
+    pause_gain: f32,
+    /// Source frame to jump to once the voice has ramped down
+    pending_seek: Option<usize>,
+    envelope: Envelope,
+    fade_out: Fade,
+    exclusive_group: Option<Arc<str>>,
+    choke_group: Option<Arc<str>>,
+    ducking: DuckRole,
+    effects: EffectChain,
+    /// Frames left of the effects' tail once the clip has finished
+    tail: Option<usize>,
+}
+
+impl Voice {
+    /// Current volume, including fades
+    fn gain(&self) -> f32 {
+        self.volume * self.envelope.gain()
+    }
+
+    /// Whether the voice is fading out, or ringing out through its effects, on its way to stopping
+    fn is_stopping(&self) -> bool {
+        matches!(self.envelope, Envelope::FadingOut { .. }) || self.tail.is_some()
+    }
+
+    /// Start fading out from the current gain, unless already fading out
+    fn fade_out(&mut self, fade: Fade) {
+        if !self.is_stopping() {
+            self.envelope = Envelope::FadingOut {
+                fade,
+                elapsed: 0,
+                from: self.envelope.gain(),
+            };
+        }
+    }
+
+    /// Whether the voice is paused and has finished ramping down
+    fn is_held(&self) -> bool {
+        self.paused && self.pause_gain <= 0.0
+    }
+
+    /// Mix this voice into `out`, which starts playing at `started`, returning
+    /// false once the clip or its fade-out has finished. `held_speed` comes on
+    /// top of the voice's own speed.
+    fn render(
+        &mut self,
+        out: &mut [f32],
+        master_volume: f32,
+        held_speed: f64,
+        started: Instant,
+    ) -> bool {
+        let end = self.region.end;
+        let gain = self.volume * master_volume;
+        let step = self.step * (self.speed * held_speed).clamp(MIN_SPEED, MAX_SPEED);
+
+        for (i, frame) in out.chunks_exact_mut(CHANNELS).enumerate() {
+            // A seek waits for the ramp down, unless the clip runs out first
+            if self.pause_gain <= 0.0 || self.position as usize >= end {
+                self.land_seek();
+                if self.is_held() {
+                    return true;
+                }
+            }
+            if self.position as usize >= end && !self.rewind() {
+                return false;
+            }
+            let index = self.position as usize;
+
+            // Linear interpolation between neighbouring source frames
+            let next = (index + 1).min(end - 1);
+            let Some((a, b)) = self.source.frames(index, next) else {
+                // A stream that has fallen behind holds the voice where it is,
+                // and one that has run dry on the way to a seek goes there now
+                if self.source.has_failed() {
+                    return false;
+                }
+                if self.land_seek() {
+                    self.pause_gain = 0.0;
+                }
+                continue;
+            };
+            if self.latency.is_none() {
+                let mixed = started + FRAME_DURATION * i as u32;
+                self.latency = Some(mixed.saturating_duration_since(self.triggered));
+            }
+
+            let fraction = (self.position - index as f64) as f32;
+            let frame_gain = gain * self.envelope.gain() * self.pause_gain;
+            for ((sample, a), b) in frame.iter_mut().zip(a).zip(b) {
+                *sample += (a + (b - a) * fraction) * frame_gain;
+            }
+
+            self.position += step;
+            let audible = !self.paused && self.pending_seek.is_none();
+            let pause_step = if audible { 1.0 } else { -1.0 } / PAUSE_RAMP_FRAMES;
+            self.pause_gain = (self.pause_gain + pause_step).clamp(0.0, 1.0);
+            if !self.envelope.advance() {
+                return false;
+            }
+        }
+
+        (self.position as usize) < end || self.plays_left != Some(1) || self.pending_seek.is_some()
+    }
+
+    /// Like [`Voice::render`], but through the voice's effects by way of `scratch`.
+    /// The voice keeps going after the clip ends until the effects' tail has died away.
+    fn render_through_effects(
+        &mut self,
+        out: &mut [f32],
+        scratch: &mut [f32],
+        master_volume: f32,
+        held_speed: f64,
+        started: Instant,
+    ) -> bool {
+        scratch.fill(0.0);
+        if self.tail.is_none() && !self.render(scratch, master_volume, held_speed, started) {
+            self.tail = Some(self.effects.tail_frames());
+        }
+
+        self.effects.process(scratch);
+        for (sample, processed) in out.iter_mut().zip(scratch.iter()) {
+            *sample += processed;
+        }
+
+        match &mut self.tail {
+            Some(left) => {
+                *left = left.saturating_sub(scratch.len() / CHANNELS);
+                *left > 0
+            }
+            None => true,
+        }
+    }
+
+    /// Milliseconds into the clip's file a frame of it is
+    fn ms_of(&self, frame: f64) -> u32 {
+        (frame * 1000.0 / self.source.info().sample_rate.max(1) as f64) as u32
+    }
+
+    fn progress(&self) -> VoiceProgress {
+        VoiceProgress {
+            // A seek shows where it is going while the voice ramps down
+            position_ms: self.ms_of(self.pending_seek.map_or(self.position, |p| p as f64)),
+            start_ms: self.ms_of(self.region.start as f64),
+            end_ms: self.ms_of(self.region.end as f64),
+            play: match (self.plays, self.plays_left) {
+                (Some(plays), Some(left)) => plays.saturating_sub(left) + 1,
+                _ => self.loops + 1,
+            },
+            plays: self.plays,
+            volume: self.volume,
+            paused: self.paused,
+            streamed: matches!(self.source, VoiceSource::Streamed(_)),
+            latency_ms: self.latency.map(|latency| latency.as_secs_f32() * 1000.0),
+        }
+    }
+
+    /// Move to `position_ms` into the clip's file, kept between the trim points.
+    /// A voice that can be heard ramps down first and lands on the exact frame.
+    fn seek(&mut self, position_ms: u32) {
+        let last = self.region.end.saturating_sub(1).max(self.region.start);
+        let position = self
+            .source
+            .info()
+            .frame_at_ms(position_ms)
+            .clamp(self.region.start, last);
+        self.pending_seek = Some(position);
+        if self.is_held() {
+            self.land_seek();
+        }
+    }
+
+    /// Go to the pending seek, if any, and have a stream read from there.
+    /// A stream only hears of the seek now, so the frames the voice ramps
+    /// down on are still there to be read.
+    fn land_seek(&mut self) -> bool {
+        let Some(position) = self.pending_seek.take() else {
+            return false;
+        };
+        self.position = position as f64;
+        self.source.seek(position);
+        true
+    }
+
+    /// Go back to the start for the next play of a loop, returning false if there is none
+    fn rewind(&mut self) -> bool {
+        match &mut self.plays_left {
+            Some(1) => return false,
+            Some(plays) => *plays -= 1,
+            None => self.loops = self.loops.saturating_add(1),
+        }
+        // An empty region would loop forever without producing anything
+        if self.region.is_empty() {
+            return false;
+        }
+        let start = self.region.start as f64;
+        self.position = start + (self.position - start) % self.region.len() as f64;
+        true
+    }
+}
+
+/// The backend's ends of the mic passthrough, from [`Mixer::connect_mic`]
+pub struct MicLink {
+    /// Where the backend writes what the microphone picks up
+    pub input: Producer<f32>,
+    /// Where the backend reads the sounds and mic for the virtual source
+    pub virtual_mic: Consumer<f32>,
+}
+
+/// The mixer's ends of the mic passthrough
+struct MicBus {
+    input: Consumer<f32>,
+    virtual_mic: Producer<f32>,
+    volume: f32,
+    dynamics: MicDynamics,
+    effects: EffectChain,
+    /// A block of mic audio is read in here to go through the effects
+    scratch: Vec<f32>,
+    /// Latency last reported
+    latency: usize,
+    /// The mic as it comes in, after its volume
+    input_level: LevelMeter,
+    /// What goes out to the virtual source
+    output_level: LevelMeter,
+}
+
+impl MicBus {
+    /// The next frame from the microphone, or silence if it hasn't arrived
+    fn next_frame(&mut self) -> [f32; CHANNELS] {
+        let mut frame = [0.0; CHANNELS];
+        if let Ok(chunk) = self.input.read_chunk(CHANNELS) {
+            for (sample, value) in frame.iter_mut().zip(chunk) {
+                *sample = value * self.volume;
+            }
+        }
+        frame
+    }
+
+    /// Frames for the virtual source are dropped if the backend falls behind
+    fn send(&mut self, frame: [f32; CHANNELS]) {
+        if let Ok(chunk) = self.virtual_mic.write_chunk_uninit(CHANNELS) {
+            chunk.fill_from_iter(frame);
+        }
+    }
+
+    /// Frames waiting in both ring buffers plus the delay of the effects
+    fn latency_frames(&self) -> usize {
+        let queued_output = self.virtual_mic.buffer().capacity() - self.virtual_mic.slots();
+        (self.input.slots() + queued_output) / CHANNELS
+            + self.dynamics.latency_frames()
+            + self.effects.latency_frames()
+    }
+}
+
+fn mono(frame: &[f32]) -> f32 {
+    frame.iter().sum::<f32>() / CHANNELS as f32
+}
+
+/// Throw away all but the newest `max_frames` frames queued in `consumer`
+pub fn trim_backlog(consumer: &mut Consumer<f32>, max_frames: usize) {
+    let queued = consumer.slots() / CHANNELS * CHANNELS;
+    let excess = queued.saturating_sub(max_frames * CHANNELS);
+    if let Ok(chunk) = consumer.read_chunk(excess) {
+        chunk.commit_all();
+    }
+}
+
+/// Mixes all playing voices into the output buffer.
+///
+/// The mixer is owned by the backend's audio callback and must not block or
+/// allocate while processing; it is controlled through a [`MixerHandle`].
+pub struct Mixer {
+    commands: Receiver<MixerCommand>,
+    events: SyncSender<MixerEvent>,
+    /// Events sent and not yet received, shared with [`MixerEvents`]
+    queued_events: Arc<AtomicUsize>,
+    /// Effect chains and sources go back to the control side to be freed there
+    retired: SyncSender<EffectChain>,
+    retired_sources: SyncSender<VoiceSource>,
+    voices: Vec<Voice>,
+    master_volume: f32,
+    /// Speed every voice plays at on top of its own, from [`MixerCommand::HoldSpeed`]
+    held_speed: f64,
+    master_effects: EffectChain,
+    level: LevelMeter,
+    /// The output and the mic as they come in, in mono, for the spectrum analyzers
+    master_tap: Producer<f32>,
+    mic_tap: Producer<f32>,
+    ducker: Ducker,
+    /// Voices that duck others are mixed here first, to drive the ducker
+    sidechain: Vec<f32>,
+    /// Voices that get ducked are mixed here before the ducker's gain is applied
+    ducked: Vec<f32>,
+    /// A voice with effects is rendered here before being processed
+    voice_scratch: Vec<f32>,
+    mic: Option<MicBus>,
+}
+
+impl Mixer {
+    pub fn new() -> (Mixer, MixerHandle, MixerEvents) {
+        let (command_sender, command_receiver) = mpsc::channel();
+        let (event_sender, event_receiver) =
+            mpsc::sync_channel(EVENT_QUEUE_SIZE + LIFECYCLE_QUEUE_SIZE);
+        let queued_events = Arc::new(AtomicUsize::new(0));
+        let (retired_sender, retired_receiver) = mpsc::sync_channel(RETIRED_QUEUE_SIZE);
+        let (source_sender, source_receiver) = mpsc::sync_channel(RETIRED_QUEUE_SIZE);
+        let (master_tap, master_spectrum) = spectrum::tap();
+        let (mic_tap, mic_spectrum) = spectrum::tap();
+
+        let mixer = Mixer {
+            commands: command_receiver,
+            events: event_sender,
+            queued_events: Arc::clone(&queued_events),
+            retired: retired_sender,
+            retired_sources: source_sender,
+            voices: Vec::with_capacity(MAX_VOICES),
+            master_volume: 1.0,
+            held_speed: 1.0,
+            master_effects: EffectChain::default(),
+            level: LevelMeter::default(),
+            master_tap,
+            mic_tap,
+            ducker: Ducker::default(),
+            sidechain: vec![0.0; BLOCK_FRAMES * CHANNELS],
+            ducked: vec![0.0; BLOCK_FRAMES * CHANNELS],
+            voice_scratch: vec![0.0; BLOCK_FRAMES * CHANNELS],
+            mic: None,
+        };
+
+        let retired = Arc::new(Retired {
+            effects: Mutex::new(retired_receiver),
+            sources: Mutex::new(source_receiver),
+        });
+        let handle = MixerHandle {
+            commands: command_sender,
+            retired: Arc::clone(&retired),
+            next_voice: 0,
+            master_spectrum,
+            mic_spectrum,
+        };
+
+        let events = MixerEvents {
+            receiver: event_receiver,
+            queued: queued_events,
+            retired,
+        };
+        (mixer, handle, events)
+    }
+
+    pub fn set_ducker(&mut self, ducker: Ducker) {
+        self.ducker = ducker;
+    }
+
+    /// Mix the microphone into a second output for a virtual source, returning
+    /// the ends the backend feeds the microphone into and reads that output from
+    pub fn connect_mic(&mut self, volume: f32, dynamics: MicDynamics) -> MicLink {
+        let (input, mic_input) = RingBuffer::new(MIC_BUFFER_FRAMES * CHANNELS);
+        let (mic_output, virtual_mic) = RingBuffer::new(MIC_BUFFER_FRAMES * CHANNELS);
+        self.mic = Some(MicBus {
+            input: mic_input,
+            virtual_mic: mic_output,
+            volume,
+            dynamics,
+            effects: EffectChain::default(),
+            scratch: vec![0.0; BLOCK_FRAMES * CHANNELS],
+            latency: 0,
+            input_level: LevelMeter::default(),
+            output_level: LevelMeter::default(),
+        });
+        MicLink { input, virtual_mic }
+    }
+
+    /// Fill `out` with interleaved stereo samples at [`SAMPLE_RATE`]
+    pub fn process(&mut self, out: &mut [f32]) {
+        while let Ok(command) = self.commands.try_recv() {
+            self.handle_command(command);
+        }
+
+        if let Some(mic) = &mut self.mic {
+            trim_backlog(&mut mic.input, MIC_MAX_BACKLOG_FRAMES);
+        }
+
+        // Trigger latency is measured up to when a voice's first frame is mixed
+        let mut started = Instant::now();
+        for block in out.chunks_mut(BLOCK_FRAMES * CHANNELS) {
+            self.mix_block(block, started);
+            started += FRAME_DURATION * (block.len() / CHANNELS) as u32;
+        }
+
+        for frame in out.chunks_exact(CHANNELS) {
+            // Samples are dropped while the analyzer isn't reading
+            let _ = self.master_tap.push(mono(frame));
+            self.level.add_frame(frame);
+            if self.level.frames() >= LEVEL_INTERVAL_FRAMES {
+                self.report_levels();
+                self.report_mic();
+                self.report_progress();
+            }
+        }
+    }
+
+    fn report_levels(&mut self) {
+        let master = self.level.take();
+        let (mic, virtual_mic) = match &mut self.mic {
+            Some(mic) => (Some(mic.input_level.take()), Some(mic.output_level.take())),
+            None => (None, None),
+        };
+        self.emit(MixerEvent::Levels {
+            master,
+            mic,
+            virtual_mic,
+        });
+    }
+
+    /// Report the mic's gain reduction, and its latency if that has changed
+    fn report_mic(&mut self) {
+        let Some(mic) = &mut self.mic else {
+            return;
+        };
+        let reduction = mic.dynamics.take_reduction();
+        let latency = mic.latency_frames();
+        let latency_changed = latency.abs_diff(mic.latency) >= LATENCY_REPORT_FRAMES;
+        if latency_changed {
+            mic.latency = latency;
+        }
+
+        self.emit(MixerEvent::MicGainReduction(reduction));
+        if latency_changed {
+            self.emit(MixerEvent::MicLatency { frames: latency });
+        }
+    }
+
+    fn report_progress(&self) {
+        for voice in &self.voices {
+            self.emit(MixerEvent::VoiceProgress {
+                voice: voice.id,
+                sound_id: Arc::clone(&voice.sound_id),
+                progress: voice.progress(),
+            });
+        }
+    }
+
+    /// Mix up to [`BLOCK_FRAMES`] frames into `out`, and the same plus the mic
+    /// into the virtual source. `started` is when the block's first frame is mixed.
+    fn mix_block(&mut self, out: &mut [f32], started: Instant) {
+        // The buses are taken out of `self` while voices are rendered, since
+        // finished voices are removed as we go
+        let mut sidechain = mem::take(&mut self.sidechain);
+        let mut ducked = mem::take(&mut self.ducked);
+        let mut voice_scratch = mem::take(&mut self.voice_scratch);
+        let (sidechain_block, ducked_block) =
+            (&mut sidechain[..out.len()], &mut ducked[..out.len()]);
+        let voice_block = &mut voice_scratch[..out.len()];
+
+        out.fill(0.0);
+        sidechain_block.fill(0.0);
+        ducked_block.fill(0.0);
+
+        let mut i = 0;
+        while i < self.voices.len() {
+            let voice = &mut self.voices[i];
+            let bus = match voice.ducking {
+                DuckRole::None => &mut *out,
+                DuckRole::Trigger => &mut *sidechain_block,
+                DuckRole::Ducked => &mut *ducked_block,
+            };
+            let playing = if voice.effects.is_empty() {
+                voice.render(bus, self.master_volume, self.held_speed, started)
+            } else {
+                voice.render_through_effects(
+                    bus,
+                    voice_block,
+                    self.master_volume,
+                    self.held_speed,
+                    started,
+                )
+            };
+            if playing {
+                i += 1;
+            } else {
+                self.remove_voice(i);
+            }
+        }
+
+        // The mic is ducked along with the sounds, so the gains are kept for it
+        let mut duck_gains = [1.0; BLOCK_FRAMES];
+        for (((frame, trigger), ducked), duck_gain) in out
+            .chunks_exact_mut(CHANNELS)
+            .zip(sidechain_block.chunks_exact(CHANNELS))
+            .zip(ducked_block.chunks_exact(CHANNELS))
+            .zip(duck_gains.iter_mut())
+        {
+            let peak = trigger.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+            let gain = self.ducker.next(peak);
+            for ((sample, trigger), ducked) in frame.iter_mut().zip(trigger).zip(ducked) {
+                *sample += trigger + ducked * gain;
+            }
+            *duck_gain = gain;
+        }
+
+        self.master_effects.process(out);
+
+        if let Some(mic) = &mut self.mic {
+            let mut scratch = mem::take(&mut mic.scratch);
+            let mic_block = &mut scratch[..out.len()];
+            for frame in mic_block.chunks_exact_mut(CHANNELS) {
+                frame.copy_from_slice(&mic.next_frame());
+                mic.input_level.add_frame(frame);
+                let _ = self.mic_tap.push(mono(frame));
+            }
+            mic.dynamics.clean(mic_block);
+            mic.effects.process(mic_block);
+            mic.dynamics.limit(mic_block);
+
+            for ((frame, mic_frame), duck_gain) in out
+                .chunks_exact(CHANNELS)
+                .zip(mic_block.chunks_exact(CHANNELS))
+                .zip(duck_gains)
+            {
+                let mic_gain = if self.ducker.duck_mic { duck_gain } else { 1.0 };
+                let mut virtual_frame = [0.0; CHANNELS];
+                for ((sample, mic), mixed) in virtual_frame.iter_mut().zip(mic_frame).zip(frame) {
+                    *sample = mic * mic_gain + mixed;
+                }
+                mic.output_level.add_frame(&virtual_frame);
+                mic.send(virtual_frame);
+            }
+            mic.scratch = scratch;
+        }
+
+        self.sidechain = sidechain;
+        self.ducked = ducked;
+        self.voice_scratch = voice_scratch;
+    }
+
+    fn handle_command(&mut self, command: MixerCommand) {
+        match command {
+            MixerCommand::Play {
+                voice,
+                sound_id,
+                source,
+                triggered,
+                volume,
+                options,
+                effects,
+            } => {
+                // Voices on their way out don't count as playing
+                let playing = self
+                    .voices
+                    .iter()
+                    .any(|v| v.sound_id == sound_id && !v.is_stopping());
+                match options.mode {
+                    TriggerMode::OneShot | TriggerMode::Hold | TriggerMode::Loop if playing => {
+                        self.retire(effects);
+                        self.retire_source(source);
+                        return;
+                    }
+                    TriggerMode::Toggle if playing => {
+                        self.stop_voices(|v| v.sound_id == sound_id, |v| v.fade_out);
+                        self.retire(effects);
+                        self.retire_source(source);
+                        return;
+                    }
+                    TriggerMode::Restart => {
+                        self.stop_voices(|v| v.sound_id == sound_id, |v| v.fade_out)
+                    }
+                    _ => {}
+                }
+
+                // Crossfade with the rest of the group, falling back to this
+                // voice's fade-in for voices that would otherwise cut out
+                if let Some(group) = &options.exclusive_group {
+                    self.stop_voices(
+                        |v| v.exclusive_group.as_ref() == Some(group),
+                        |v| match v.fade_out.frames {
+                            0 => options.fade_in,
+                            _ => v.fade_out,
+                        },
+                    );
+                }
+
+                if let Some(group) = &options.choke_group
+                    && !self.make_room_in_group(&sound_id, group)
+                {
+                    self.retire(effects);
+                    self.retire_source(source);
+                    return;
+                }
+
+                // Steal the oldest voice when all voices are in use
+                if self.voices.len() == MAX_VOICES {
+                    self.remove_voice(0);
+                }
+
+                let info = source.info();
+                let end = options.region.end.min(info.frames);
+                let region = options.region.start.min(end)..end;
+                let position = options.start_frame.clamp(region.start, end) as f64;
+
+                self.emit(MixerEvent::VoiceStarted {
+                    voice,
+                    sound_id: Arc::clone(&sound_id),
+                });
+                let plays_left = match options.mode {
+                    TriggerMode::Loop => options.loop_count.map(|count| count.max(1)),
+                    _ => Some(1),
+                };
+                self.voices.push(Voice {
+                    id: voice,
+                    sound_id,
+                    step: info.sample_rate as f64 / SAMPLE_RATE as f64
+                        * options.rate.clamp(MIN_SPEED as f32, MAX_SPEED as f32) as f64,
+                    speed: 1.0,
+                    source,
+                    triggered,
+                    latency: None,
+                    position,
+                    region,
+                    volume,
+                    mode: options.mode,
+                    plays_left,
+                    plays: plays_left,
+                    loops: 0,
+                    paused: false,
+                    pause_gain: 1.0,
+                    pending_seek: None,
+                    envelope: Envelope::new(options.fade_in),
+                    fade_out: options.fade_out,
+                    exclusive_group: options.exclusive_group,
+                    choke_group: options.choke_group.map(|group| group.name),
+                    ducking: options.ducking,
+                    effects,
+                    tail: None,
+                });
+            }
+            MixerCommand::Stop { sound_id } => {
+                self.stop_voices(|v| v.sound_id == sound_id, |v| v.fade_out)
+            }
+            MixerCommand::Release { sound_id } => self.stop_voices(
+                |v| v.sound_id == sound_id && v.mode == TriggerMode::Hold,
+                |v| v.fade_out,
+            ),
+            MixerCommand::StopAll => {
+                while !self.voices.is_empty() {
+                    self.remove_voice(self.voices.len() - 1);
+                }
+            }
+            MixerCommand::FadeOutAll(fade) => self.stop_voices(|_| true, |_| fade),
+            MixerCommand::SetSoundVolume { sound_id, volume } => {
+                for voice in self.voices.iter_mut().filter(|v| v.sound_id == sound_id) {
+                    voice.volume = volume;
+                }
+            }
+            MixerCommand::StopVoice(voice) => self.stop_voices(|v| v.id == voice, |v| v.fade_out),
+            MixerCommand::SetVoiceVolume { voice, volume } => {
+                if let Some(voice) = self.voices.iter_mut().find(|v| v.id == voice) {
+                    voice.volume = volume;
+                }
+            }
+            MixerCommand::PauseVoice { voice, paused } => {
+                if let Some(voice) = self.voices.iter_mut().find(|v| v.id == voice) {
+                    voice.paused = paused;
+                }
+            }
+            MixerCommand::PauseSound { sound_id, paused } => {
+                for voice in self.voices.iter_mut().filter(|v| v.sound_id == sound_id) {
+                    voice.paused = paused;
+                }
+            }
+            MixerCommand::PauseAll(paused) => {
+                for voice in &mut self.voices {
+                    voice.paused = paused;
+                }
+            }
+            MixerCommand::SeekVoice { voice, position_ms } => {
+                if let Some(voice) = self.voices.iter_mut().find(|v| v.id == voice) {
+                    voice.seek(position_ms);
+                }
+            }
+            MixerCommand::SeekSound {
+                sound_id,
+                position_ms,
+            } => {
+                for voice in self.voices.iter_mut().filter(|v| v.sound_id == sound_id) {
+                    voice.seek(position_ms);
+                }
+            }
+            MixerCommand::SetMasterVolume(volume) => self.master_volume = volume,
+            MixerCommand::NudgeSpeed(factor) => {
+                for voice in self.voices.iter_mut().filter(|v| !v.is_stopping()) {
+                    voice.speed = (voice.speed * factor as f64).clamp(MIN_SPEED, MAX_SPEED);
+                }
+            }
+            MixerCommand::HoldSpeed(factor) => {
+                self.held_speed = (factor as f64).clamp(MIN_SPEED, MAX_SPEED)
+            }
+            MixerCommand::SetMasterEffects(effects) => {
+                let previous = mem::replace(&mut self.master_effects, effects);
+                self.retire(previous);
+            }
+            MixerCommand::SetMicEffects(effects) => match &mut self.mic {
+                Some(mic) => {
+                    let previous = mem::replace(&mut mic.effects, effects);
+                    self.retire(previous);
+                }
+                None => self.retire(effects),
+            },
+        }
+    }
+
+    /// Choke the other sounds in a voice's group and steal a voice if the group
+    /// is full, returning false if the new voice should be dropped instead
+    fn make_room_in_group(&mut self, sound_id: &Arc<str>, group: &ChokeGroup) -> bool {
+        let in_group = |v: &Voice| v.choke_group.as_ref() == Some(&group.name) && !v.is_stopping();
+        let fade = Fade {
+            curve: group.fade.curve,
+            frames: match group.choke {
+                ChokeMode::Fade => group.fade.frames,
+                ChokeMode::Cut | ChokeMode::None => 0,
+            },
+        };
+
+        if group.choke != ChokeMode::None {
+            self.stop_voices(|v| in_group(v) && v.sound_id != *sound_id, |_| fade);
+        }
+
+        let Some(max_voices) = group.max_voices else {
+            return true;
+        };
+        while self.voices.iter().filter(|v| in_group(v)).count() >= max_voices {
+            let victim = match group.steal {
+                StealPolicy::Oldest => self.voices.iter().find(|v| in_group(v)),
+                StealPolicy::Quietest => self
+                    .voices
+                    .iter()
+                    .filter(|v| in_group(v))
+                    .min_by(|a, b| a.gain().total_cmp(&b.gain())),
+                StealPolicy::None => return false,
+            };
+            let Some(victim) = victim.map(|v| v.id) else {
+                break;
+            };
+            self.stop_voices(|v| v.id == victim, |_| fade);
+        }
+        true
+    }
+
+    /// Stop the voices `select` picks, with the fade `fade` gives for each of them
+    fn stop_voices<S, F>(&mut self, select: S, fade: F)
+    where
+        S: Fn(&Voice) -> bool,
+        F: Fn(&Voice) -> Fade,
+    {
+        let mut i = 0;
+        while i < self.voices.len() {
+            let voice = &mut self.voices[i];
+            if !select(voice) {
+                i += 1;
+                continue;
+            }
+
+            // A paused voice would never get through its fade-out
+            let fade = fade(voice);
+            if fade.frames == 0 || voice.is_held() {
+                self.remove_voice(i);
+            } else {
+                voice.fade_out(fade);
+                i += 1;
+            }
+        }
+    }
+
+    /// Remove a voice, keeping the remaining voices in start order
+    fn remove_voice(&mut self, index: usize) {
+        let voice = self.voices.remove(index);
+        self.emit(MixerEvent::VoiceStopped {
+            voice: voice.id,
+            sound_id: voice.sound_id,
+        });
+        self.retire(voice.effects);
+        self.retire_source(voice.source);
+    }
+
+    /// Hand an effect chain back to be freed off the audio thread. If the
+    /// queue is full it is freed here, which is better than holding it forever.
+    fn retire(&self, effects: EffectChain) {
+        if !effects.is_empty() {
+            let _ = self.retired.try_send(effects);
+        }
+    }
+
+    /// Hand a voice's audio back like [`Mixer::retire`], since the clip may be
+    /// its last reference and a stream's buffer is freed with it
+    fn retire_source(&self, source: VoiceSource) {
+        let _ = self.retired_sources.try_send(source);
+    }
+
+    /// Events are dropped rather than blocking the audio thread when nobody reads
+    /// them. Progress and levels come again shortly, so they give way first and
+    /// leave the rest of the queue to voices starting and stopping.
+    fn emit(&self, event: MixerEvent) {
+        let periodic = match event {
+            MixerEvent::VoiceStarted { .. } | MixerEvent::VoiceStopped { .. } => false,
+            MixerEvent::VoiceProgress { .. }
+            | MixerEvent::Levels { .. }
+            | MixerEvent::MicLatency { .. }
+            | MixerEvent::MicGainReduction(_) => true,
+        };
+        if periodic && self.queued_events.load(Ordering::Relaxed) >= EVENT_QUEUE_SIZE {
+            return;
+        }
+        // Counted before it is sent, so the receiver can't count it off first
+        self.queued_events.fetch_add(1, Ordering::Relaxed);
+        if self.events.try_send(event).is_err() {
+            self.queued_events.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Control side of the [`Mixer`]
+/// Effect chains and sources the mixer is done with, freed by whichever of
+/// the control side's ends gets to them first
+struct Retired {
+    effects: Mutex<Receiver<EffectChain>>,
+    sources: Mutex<Receiver<VoiceSource>>,
+}
+
+impl Retired {
+    fn free(&self) {
+        let effects = self.effects.lock().unwrap_or_else(|e| e.into_inner());
+        while effects.try_recv().is_ok() {}
+        let sources = self.sources.lock().unwrap_or_else(|e| e.into_inner());
+        while sources.try_recv().is_ok() {}
+    }
+}
+
+/// The receiving end of the mixer's events, in the order they happened. Reading
+/// them also frees what the mixer is done with, as levels come about 30 times a
+/// second even when no commands are sent.
+pub struct MixerEvents {
+    receiver: Receiver<MixerEvent>,
+    queued: Arc<AtomicUsize>,
+    retired: Arc<Retired>,
+}
+
+impl Iterator for MixerEvents {
+    type Item = MixerEvent;
+
+    /// Block until the next event, or return `None` once the mixer is gone
+    fn next(&mut self) -> Option<MixerEvent> {
+        let event = self.receiver.recv().ok()?;
+        self.queued.fetch_sub(1, Ordering::Relaxed);
+        self.retired.free();
+        Some(event)
+    }
+}
+
+pub struct MixerHandle {
+    commands: Sender<MixerCommand>,
+    retired: Arc<Retired>,
+    next_voice: VoiceId,
+    master_spectrum: SpectrumAnalyzer,
+    mic_spectrum: SpectrumAnalyzer,
+}
+
+impl MixerHandle {
+    pub fn send(&self, command: MixerCommand) -> Result<(), AudioError> {
+        self.retired.free();
+
+        self.commands
+            .send(command)
+            .map_err(|_| AudioError::PlaybackError("The mixer is not running".to_string()))
+    }
+
+    pub fn next_voice_id(&mut self) -> VoiceId {
+        self.next_voice += 1;
+        self.next_voice
+    }
+
+    pub fn spectrum(&mut self, source: SpectrumSource) -> &mut SpectrumAnalyzer {
+        match source {
+            SpectrumSource::Master => &mut self.master_spectrum,
+            SpectrumSource::Mic => &mut self.mic_spectrum,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::stream::StreamReaders;
+    use crate::audio::stream::tests::write_wav;
+
+    /// A clip at the mixer's own rate that holds `value` throughout
+    fn clip(frames: usize, value: f32) -> Arc<AudioClip> {
+        Arc::new(AudioClip {
+            samples: vec![value; frames * CHANNELS],
+            sample_rate: SAMPLE_RATE,
+        })
+    }
+
+    fn options(frames: usize) -> PlayOptions {
+        let no_fade = Fade {
+            curve: FadeCurve::Linear,
+            frames: 0,
+        };
+        PlayOptions {
+            mode: TriggerMode::Overlap,
+            loop_count: None,
+            rate: 1.0,
+            region: 0..frames,
+            start_frame: 0,
+            fade_in: no_fade,
+            fade_out: no_fade,
+            exclusive_group: None,
+            choke_group: None,
+            ducking: DuckRole::None,
+        }
+    }
+
+    fn play(mixer: &mut Mixer, handle: &mut MixerHandle, source: VoiceSource) -> VoiceId {
+        let voice = handle.next_voice_id();
+        let frames = source.info().frames;
+        handle
+            .send(MixerCommand::Play {
+                voice,
+                sound_id: Arc::from("sound"),
+                source,
+                triggered: Instant::now(),
+                volume: 1.0,
+                options: options(frames),
+                effects: EffectChain::default(),
+            })
+            .unwrap();
+        process(mixer, 0);
+        voice
+    }
+
+    /// Mix `frames` frames and return them
+    fn process(mixer: &mut Mixer, frames: usize) -> Vec<f32> {
+        let mut out = vec![0.0; frames * CHANNELS];
+        mixer.process(&mut out);
+        out
+    }
+
+    fn position(mixer: &Mixer, voice: VoiceId) -> f64 {
+        mixer
+            .voices
+            .iter()
+            .find(|v| v.id == voice)
+            .unwrap()
+            .position
+    }
+
+    #[test]
+    fn held_speed_reaches_voices_started_later() {
+        let (mut mixer, mut handle, _events) = Mixer::new();
+        handle.send(MixerCommand::HoldSpeed(2.0)).unwrap();
+        let voice = play(
+            &mut mixer,
+            &mut handle,
+            VoiceSource::Decoded(clip(10_000, 0.5)),
+        );
+
+        process(&mut mixer, 100);
+        assert_eq!(position(&mixer, voice), 200.0);
+
+        handle.send(MixerCommand::HoldSpeed(1.0)).unwrap();
+        process(&mut mixer, 100);
+        assert_eq!(position(&mixer, voice), 300.0);
+    }
+
+    #[test]
+    fn nudged_speed_stays_with_the_voices_playing_then() {
+        let (mut mixer, mut handle, _events) = Mixer::new();
+        let first = play(
+            &mut mixer,
+            &mut handle,
+            VoiceSource::Decoded(clip(10_000, 0.5)),
+        );
+        handle.send(MixerCommand::NudgeSpeed(2.0)).unwrap();
+        let second = play(
+            &mut mixer,
+            &mut handle,
+            VoiceSource::Decoded(clip(10_000, 0.5)),
+        );
+
+        process(&mut mixer, 100);
+        assert_eq!(position(&mixer, first), 200.0);
+        assert_eq!(position(&mixer, second), 100.0);
+    }
+
+    #[test]
+    fn held_and_nudged_speed_are_limited_together() {
+        let (mut mixer, mut handle, _events) = Mixer::new();
+        let voice = play(
+            &mut mixer,
+            &mut handle,
+            VoiceSource::Decoded(clip(10_000, 0.5)),
+        );
+        handle.send(MixerCommand::NudgeSpeed(3.0)).unwrap();
+        handle.send(MixerCommand::HoldSpeed(3.0)).unwrap();
+
+        process(&mut mixer, 100);
+        assert_eq!(position(&mixer, voice), 100.0 * MAX_SPEED);
+    }
+
+    fn play_with(mixer: &mut Mixer, handle: &mut MixerHandle, options: PlayOptions) -> VoiceId {
+        let voice = handle.next_voice_id();
+        handle
+            .send(MixerCommand::Play {
+                voice,
+                sound_id: Arc::from("sound"),
+                source: VoiceSource::Decoded(clip(1000, 0.5)),
+                triggered: Instant::now(),
+                volume: 1.0,
+                options,
+                effects: EffectChain::default(),
+            })
+            .unwrap();
+        process(mixer, 0);
+        voice
+    }
+
+    fn voice(mixer: &Mixer, voice: VoiceId) -> &Voice {
+        mixer.voices.iter().find(|v| v.id == voice).unwrap()
+    }
+
+    #[test]
+    fn trim_points_are_kept_within_the_clip() {
+        let (mut mixer, mut handle, _events) = Mixer::new();
+        let first = play_with(
+            &mut mixer,
+            &mut handle,
+            PlayOptions {
+                region: 200..5000,
+                start_frame: 100,
+                ..options(1000)
+            },
+        );
+        assert_eq!(voice(&mixer, first).region, 200..1000);
+        assert_eq!(position(&mixer, first), 200.0);
+
+        let second = play_with(
+            &mut mixer,
+            &mut handle,
+            PlayOptions {
+                region: 3000..5000,
+                start_frame: 4000,
+                ..options(1000)
+            },
+        );
+        assert_eq!(voice(&mixer, second).region, 1000..1000);
+        // With nothing left to play the voice ends straight away
+        process(&mut mixer, 1);
+        assert!(mixer.voices.iter().all(|v| v.id != second));
+    }
+
+    #[test]
+    fn loops_repeat_between_the_trim_points() {
+        let (mut mixer, mut handle, _events) = Mixer::new();
+        let voice = play_with(
+            &mut mixer,
+            &mut handle,
+            PlayOptions {
+                mode: TriggerMode::Loop,
+                region: 200..300,
+                start_frame: 250,
+                ..options(1000)
+            },
+        );
+        process(&mut mixer, 100);
+        assert_eq!(position(&mixer, voice), 250.0);
+    }
+
+    #[test]
+    fn seeks_stay_between_the_trim_points() {
+        let (mut mixer, mut handle, _events) = Mixer::new();
+        let voice = play_with(
+            &mut mixer,
+            &mut handle,
+            PlayOptions {
+                region: 480..960,
+                start_frame: 480,
+                ..options(1000)
+            },
+        );
+        let seek = |mixer: &mut Mixer, position_ms| {
+            mixer.voices[0].seek(position_ms);
+            mixer.voices[0].pending_seek.unwrap()
+        };
+        // 48 frames to a millisecond
+        assert_eq!(seek(&mut mixer, 0), 480);
+        assert_eq!(seek(&mut mixer, 15), 720);
+        assert_eq!(seek(&mut mixer, 1000), 959);
+        assert_eq!(mixer.voices[0].id, voice);
+    }
+
+    #[test]
+    fn progress_gives_way_to_voices_stopping() {
+        let (mut mixer, mut handle, events) = Mixer::new();
+        let voice = play(
+            &mut mixer,
+            &mut handle,
+            VoiceSource::Decoded(clip(SAMPLE_RATE as usize * 10, 0.5)),
+        );
+
+        // Nobody reads while progress and levels pile up
+        for _ in 0..EVENT_QUEUE_SIZE {
+            process(&mut mixer, LEVEL_INTERVAL_FRAMES);
+        }
+        handle.send(MixerCommand::StopVoice(voice)).unwrap();
+        process(&mut mixer, 1);
+        drop(mixer);
+
+        let events: Vec<_> = events.collect();
+        assert_eq!(events.len(), EVENT_QUEUE_SIZE + 1);
+        assert!(matches!(events[0], MixerEvent::VoiceStarted { .. }));
+        assert!(
+            matches!(events.last(), Some(MixerEvent::VoiceStopped { voice: v, .. }) if *v == voice)
+        );
+    }
+
+    /// Left channel of each frame mixed
+    fn left(out: &[f32]) -> Vec<f32> {
+        out.iter().step_by(CHANNELS).copied().collect()
+    }
+
+    #[test]
+    fn pausing_ramps_down_and_holds_the_voice() {
+        let (mut mixer, mut handle, _events) = Mixer::new();
+        let voice = play(
+            &mut mixer,
+            &mut handle,
+            VoiceSource::Decoded(clip(10_000, 0.5)),
+        );
+
+        handle
+            .send(MixerCommand::PauseVoice {
+                voice,
+                paused: true,
+            })
+            .unwrap();
+        let out = left(&process(&mut mixer, 300));
+        assert!((out[0] - 0.5).abs() < 0.01);
+        assert!(out.windows(2).all(|pair| pair[1] <= pair[0]));
+        assert!(
+            out[PAUSE_RAMP_FRAMES as usize + 2..]
+                .iter()
+                .all(|s| *s == 0.0)
+        );
+
+        // Held where the ramp ended
+        let held = position(&mixer, voice);
+        assert!((held - PAUSE_RAMP_FRAMES as f64).abs() <= 2.0);
+        process(&mut mixer, 1000);
+        assert_eq!(position(&mixer, voice), held);
+
+        handle
+            .send(MixerCommand::PauseVoice {
+                voice,
+                paused: false,
+            })
+            .unwrap();
+        let out = left(&process(&mut mixer, 300));
+        assert_eq!(out[0], 0.0);
+        assert!(out.windows(2).all(|pair| pair[1] >= pair[0]));
+        assert!((out[299] - 0.5).abs() < 0.01);
+        assert_eq!(position(&mixer, voice), held + 300.0);
+    }
+
+    #[test]
+    fn seeking_lands_after_the_ramp_down() {
+        let (mut mixer, mut handle, _events) = Mixer::new();
+        let voice = play(
+            &mut mixer,
+            &mut handle,
+            VoiceSource::Decoded(clip(48_000, 0.5)),
+        );
+        process(&mut mixer, 100);
+
+        handle
+            .send(MixerCommand::SeekVoice {
+                voice,
+                position_ms: 500,
+            })
+            .unwrap();
+        process(&mut mixer, 100);
+        // Still ramping down where it was, but already reporting where it is going
+        assert_eq!(position(&mixer, voice), 200.0);
+        assert_eq!(mixer.voices[0].progress().position_ms, 500);
+
+        let out = left(&process(&mut mixer, 400));
+        let landed = PAUSE_RAMP_FRAMES as usize - 100;
+        assert!(out[landed - 1] < 0.01);
+        assert!((out[399] - 0.5).abs() < 0.01);
+        assert!(mixer.voices[0].pending_seek.is_none());
+        let expected = 24_000.0 + 400.0 - landed as f64;
+        assert!((position(&mixer, voice) - expected).abs() <= 1.0);
+    }
+
+    /// Mix in small blocks, giving the stream's reader time to read, until
+    /// `done` or a few seconds have passed
+    fn process_until(mixer: &mut Mixer, mut done: impl FnMut(&Mixer) -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !done(mixer) && Instant::now() < deadline {
+            process(mixer, 240);
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn streamed_voices_land_their_seeks() {
+        let frames = SAMPLE_RATE as usize * 5;
+        let path = write_wav("seek", frames);
+        let info = ClipInfo {
+            frames,
+            sample_rate: SAMPLE_RATE,
+        };
+        let stream = StreamReaders::start().open(&path, info, 0..frames, 0);
+
+        let (mut mixer, mut handle, _events) = Mixer::new();
+        let voice = play(
+            &mut mixer,
+            &mut handle,
+            VoiceSource::Streamed(Box::new(stream)),
+        );
+        // Straight away, before the stream has read far enough to ramp down on.
+        // The seek lands within the ramp's worth of mixing, not once the
+        // stream has come back round to where the voice was.
+        handle
+            .send(MixerCommand::SeekVoice {
+                voice,
+                position_ms: 3000,
+            })
+            .unwrap();
+        process(&mut mixer, 2 * PAUSE_RAMP_FRAMES as usize);
+        assert!(position(&mixer, voice) >= 144_000.0);
+
+        process_until(&mut mixer, |mixer| position(mixer, voice) >= 145_000.0);
+        std::fs::remove_file(&path).ok();
+        let position = position(&mixer, voice);
+        assert!(
+            (145_000.0..150_000.0).contains(&position),
+            "stuck at {}",
+            position
+        );
+    }
+
+    #[test]
+    fn reading_events_frees_voices_that_ended_by_themselves() {
+        let (mut mixer, mut handle, mut events) = Mixer::new();
+        let source = clip(100, 0.5);
+        play(
+            &mut mixer,
+            &mut handle,
+            VoiceSource::Decoded(Arc::clone(&source)),
+        );
+        process(&mut mixer, 200);
+        assert!(mixer.voices.is_empty());
+        // Held in the queue, not dropped on the audio thread
+        assert_eq!(Arc::strong_count(&source), 2);
+
+        events.next();
+        assert_eq!(Arc::strong_count(&source), 1);
+    }
+}
@@ -1,7 +1,52 @@
+pub mod decoder;
+pub mod ducking;
+pub mod dynamics;
+pub mod effects;
+pub mod levels;
+pub mod library;
+pub mod loudness;
+pub mod mixer;
+pub mod overview;
 pub mod pipewire;
-use std::path::PathBuf;
+pub mod spectrum;
+pub mod stream;
+pub mod voice_changer;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::hash::{BuildHasher, Hasher, RandomState};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::mpsc::Receiver;
+use std::time::Instant;
 
-#[derive(Debug)]
+use serde::{Deserialize, Serialize};
+
+use decoder::ClipInfo;
+use ducking::Ducker;
+use dynamics::MicDynamics;
+use effects::{Effect, EffectChain, PitchShift};
+use library::{LoadedSound, SoundLibrary};
+use loudness::LoudnessAnalyzer;
+use mixer::{
+    ChokeGroup, Fade, MicLink, Mixer, MixerCommand, MixerEvents, MixerHandle, PlayOptions,
+    SAMPLE_RATE, VoiceId, VoiceSource,
+};
+use overview::{Overview, OverviewExtractor};
+use spectrum::SpectrumSource;
+use stream::StreamReaders;
+
+use crate::settings::BoomCrabSettings;
+use crate::settings::effects::EffectSettings;
+use crate::settings::fade::FadeSettings;
+use crate::settings::loudness::LoudnessSettings;
+use crate::settings::sounds::{ChokeGroupSettings, SoundMetadata};
+use crate::settings::voice_changer::VoiceChangerSettings;
+
+/// Grain of the pitch shift that keeps a sound's length. Longer than the voice
+/// changer's, since sounds don't need to keep up with a live mic.
+const PITCH_GRAIN_FRAMES: usize = 2048;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum AudioError {
     DeviceNotFound(String),
     InitializationFailed(String),
@@ -10,21 +55,45 @@ pub enum AudioError {
     NotSupported(String),
 }
 
-#[derive(Debug, Clone)]
+impl fmt::Display for AudioError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AudioError::DeviceNotFound(e) => write!(f, "Device not found: {}", e),
+            AudioError::InitializationFailed(e) => write!(f, "Initialization failed: {}", e),
+            AudioError::SoundNotFound(e) => write!(f, "Sound not found: {}", e),
+            AudioError::PlaybackError(e) => write!(f, "Playback error: {}", e),
+            AudioError::NotSupported(e) => write!(f, "Not supported: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for AudioError {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AudioDevice {
     pub name: String,
     pub id: String,
     pub device_type: DeviceType,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum DeviceType {
     Input,
     Output,
     Virtual,
 }
 
-#[derive(Debug)]
+/// Where in a sound a play starts or a seek lands
+#[derive(Debug, Clone, Copy)]
+pub enum StartPoint<'a> {
+    /// The sound's start trim point, or the start of the file
+    Start,
+    Cue(&'a str),
+    /// Milliseconds from the start of the file, kept within the trim points
+    Position(u32),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Sound {
     pub id: String,
     pub name: String,
@@ -32,10 +101,21 @@ pub struct Sound {
 }
 
 /// Trait that abstracts audio backend behavior across different operating systems.
-pub trait AudioBackend {
+pub trait AudioBackend: Send {
     fn list_audio_outputs(&self) -> Result<Vec<AudioDevice>, AudioError>;
     fn list_audio_inputs(&self) -> Result<Vec<AudioDevice>, AudioError>;
 
+    /// Start the output stream. The backend drives `mixer` from its audio callback.
+    fn start_output(&mut self, mixer: Mixer) -> Result<(), AudioError>;
+
+    /// Feed the microphone (`device`, or the default source) into `link`'s input, and
+    /// publish its virtual mic output as a source that other applications can record from
+    fn start_mic_passthrough(
+        &mut self,
+        device: Option<&str>,
+        link: MicLink,
+    ) -> Result<(), AudioError>;
+
     // fn set_input_device(&mut self, device_id: &str) -> Result<(), AudioError>;
     // fn set_output_device(&mut self, device_id: &str) -> Result<(), AudioError>;
 
@@ -44,36 +124,106 @@ pub trait AudioBackend {
 
     // fn load_sound(&mut self, path: PathBuf, name: String) -> Result<String, AudioError>;
     // fn unload_sound(&mut self, sound_id: &str) -> Result<(), AudioError>;
-    // fn list_sounds(&self) -> Vec<Sound>;
 
-    // fn play_sound(&mut self, sound_id: &str) -> Result<(), AudioError>;
-    // fn pause_sound(&mut self, sound_id: &str) -> Result<(), AudioError>;
-    // fn stop_sound(&mut self, sound_id: &str) -> Result<(), AudioError>;
-    // fn stop_all(&mut self) -> Result<(), AudioError>;
-
-    // fn set_sound_volume(&mut self, sound_id: &str, volume: f32) -> Result<(), AudioError>;
-    // fn set_master_volume(&mut self, volume: f32) -> Result<(), AudioError>;
-
-    // fn enable_mic_passthrough(&mut self, enabled: bool) -> Result<(), AudioError>;
     // fn set_mic_volume(&mut self, volume: f32) -> Result<(), AudioError>;
 }
 
 pub struct BoomCrabAudioInterface {
     backend: Box<dyn AudioBackend>,
+    library: SoundLibrary,
+    mixer: MixerHandle,
+    mixer_events: Option<MixerEvents>,
+    sound_volumes: HashMap<String, f32>,
+    sound_metadata: BTreeMap<String, SoundMetadata>,
+    choke_groups: BTreeMap<String, ChokeGroupSettings>,
+    fade_settings: FadeSettings,
+    loudness_settings: LoudnessSettings,
+    /// Unset when loudness normalization is turned off
+    loudness: Option<LoudnessAnalyzer>,
+    overviews: OverviewExtractor,
+    stream_readers: StreamReaders,
+    master_effects: Vec<EffectSettings>,
+    voice_changer: VoiceChangerSettings,
+    /// State of the generator behind random pitch variation
+    random_state: u64,
+}
+
+/// Create the audio backend for the current operating system
+pub fn default_backend() -> Result<Box<dyn AudioBackend>, AudioError> {
+    #[cfg(target_os = "linux")]
+    let backend = Box::new(pipewire::PipeWireBackend::new()?) as Box<dyn AudioBackend>;
+
+    // #[cfg(target_os = "windows")]
+    // let backend = Box::new(wasapi::WasapiBackend::new()?) as Box<dyn AudioBackend>;
+
+    // #[cfg(target_os = "macos")]
+    // let backend = Box::new(coreaudio::CoreAudioBackend::new()?) as Box<dyn AudioBackend>;
+
+    Ok(backend)
 }
 
 impl BoomCrabAudioInterface {
-    pub fn new() -> Result<Self, AudioError> {
-        #[cfg(target_os = "linux")]
-        let backend = Box::new(pipewire::PipeWireBackend::new()?) as Box<dyn AudioBackend>;
+    pub fn new(settings: &BoomCrabSettings) -> Result<Self, AudioError> {
+        let mut backend = default_backend()?;
+        let (mut mixer, handle, events) = Mixer::new();
+        mixer.set_ducker(Ducker::new(&settings.ducking));
+        let mic = settings.mic.passthrough.then(|| {
+            mixer.connect_mic(
+                settings.mic.volume.max(0.0),
+                MicDynamics::new(&settings.mic),
+            )
+        });
+        backend.start_output(mixer)?;
 
-        // #[cfg(target_os = "windows")]
-        // let backend = Box::new(wasapi::WasapiBackend::new()?) as Box<dyn AudioBackend>;
+        // Sounds still play without the mic, so a missing mic isn't fatal
+        if let Some(link) = mic
+            && let Err(e) = backend.start_mic_passthrough(settings.mic.device.as_deref(), link)
+        {
+            eprintln!("Warning: Could not start the mic passthrough ({}).", e);
+        }
 
-        // #[cfg(target_os = "macos")]
-        // let backend = Box::new(coreaudio::CoreAudioBackend::new()?) as Box<dyn AudioBackend>;
+        let library = SoundLibrary::new(
+            Path::new(&settings.sound_files_directory),
+            &settings.library,
+        );
+        let loudness = settings
+            .loudness
+            .enabled
+            .then(|| LoudnessAnalyzer::start(settings.loudness.replaygain));
+        if let Some(loudness) = &loudness {
+            for sound in library.sounds() {
+                loudness.queue(&sound.path);
+            }
+        }
+        library.preload();
+        let overviews = OverviewExtractor::start();
+        for sound in library.sounds() {
+            overviews.queue(&sound.id, &sound.path);
+        }
 
-        Ok(Self { backend })
+        let mut interface = Self {
+            backend,
+            library,
+            mixer: handle,
+            mixer_events: Some(events),
+            sound_volumes: HashMap::new(),
+            sound_metadata: settings.sounds.clone(),
+            choke_groups: settings.choke_groups.clone(),
+            fade_settings: settings.fade.clone(),
+            loudness_settings: settings.loudness.clone(),
+            loudness,
+            overviews,
+            stream_readers: StreamReaders::start(),
+            master_effects: Vec::new(),
+            voice_changer: settings.voice_changer.clone(),
+            // Never zero, which xorshift would be stuck on
+            random_state: RandomState::new().build_hasher().finish() | 1,
+        };
+        interface.set_master_effects(settings.master_effects.clone())?;
+        if let Err(e) = interface.set_voice_preset(settings.voice_changer.preset.as_deref()) {
+            eprintln!("Warning: Voice changer disabled ({}).", e);
+        }
+        Ok(interface)
     }
 
     pub fn list_audio_outputs(&self) -> Result<Vec<AudioDevice>, AudioError> {
@@ -83,6 +233,453 @@ impl BoomCrabAudioInterface {
     pub fn list_audio_inputs(&self) -> Result<Vec<AudioDevice>, AudioError> {
         self.backend.list_audio_inputs()
     }
+
+    /// Take the receiving end of the mixer's voice events. Only the first caller gets it.
+    pub fn take_mixer_events(&mut self) -> Option<MixerEvents> {
+        self.mixer_events.take()
+    }
+
+    pub fn list_sounds(&self) -> Vec<Sound> {
+        self.library.sounds().to_vec()
+    }
+
+    /// Rescan the sound files directory for new or removed files
+    pub fn rescan_sounds(&mut self) -> Result<(), AudioError> {
+        self.library.rescan()?;
+        self.library.preload();
+        if let Some(loudness) = &self.loudness {
+            for sound in self.library.sounds() {
+                loudness.queue(&sound.path);
+            }
+        }
+        for sound in self.library.sounds() {
+            self.overviews.queue(&sound.id, &sound.path);
+        }
+        Ok(())
+    }
+
+    pub fn play_sound(&mut self, sound_id: &str) -> Result<(), AudioError> {
+        self.play_sound_scaled(sound_id, 1.0)
+    }
+
+    /// Play a sound with its volume multiplied by `gain`, e.g. from a note's velocity
+    pub fn play_sound_scaled(&mut self, sound_id: &str, gain: f32) -> Result<(), AudioError> {
+        self.play(sound_id, gain, StartPoint::Start)
+    }
+
+    /// Play a sound from a cue or a position, e.g. to audition its trim points
+    pub fn play_sound_from(&mut self, sound_id: &str, start: StartPoint) -> Result<(), AudioError> {
+        self.play(sound_id, 1.0, start)
+    }
+
+    fn play(&mut self, sound_id: &str, gain: f32, start: StartPoint) -> Result<(), AudioError> {
+        // Latency is measured from here, so it includes loading the sound
+        let triggered = Instant::now();
+        let sound = self.library.load(sound_id)?;
+        let voice = self.mixer.next_voice_id();
+        let volume = self.sound_volumes.get(sound_id).copied().unwrap_or(1.0);
+        let normalization = db_to_gain(self.normalization_gain_db(sound_id)?);
+        let options = self.play_options(sound_id, sound.info(), start)?;
+        let source = match sound {
+            LoadedSound::Decoded(clip) => VoiceSource::Decoded(clip),
+            LoadedSound::Streamed { path, info } => VoiceSource::Streamed(Box::new(
+                self.stream_readers
+                    .open(&path, info, options.region.clone(), options.start_frame),
+            )),
+        };
+        self.mixer.send(MixerCommand::Play {
+            voice,
+            sound_id: sound_id.into(),
+            source,
+            triggered,
+            volume: volume * normalization * gain.max(0.0),
+            options,
+            effects: self.effect_chain(sound_id),
+        })
+    }
+
+    /// A sound's effects, after the pitch shift if it has one
+    fn effect_chain(&self, sound_id: &str) -> EffectChain {
+        let pitch_semitones = self
+            .sound_metadata
+            .get(sound_id)
+            .and_then(|metadata| metadata.pitch_semitones)
+            .filter(|semitones| semitones.abs() > 0.01);
+
+        let mut chain: Vec<Box<dyn Effect>> = Vec::new();
+        if let Some(semitones) = pitch_semitones {
+            chain.push(Box::new(PitchShift::new(semitones, PITCH_GRAIN_FRAMES)));
+        }
+        chain.extend(self.sound_effects(sound_id).iter().map(effects::build));
+        chain.into()
+    }
+
+    /// A sound's playback rate, varied at random within its `random_pitch_semitones`
+    fn playback_rate(&mut self, sound_id: &str) -> f32 {
+        let metadata = self.sound_metadata.get(sound_id);
+        let rate = metadata.and_then(|m| m.rate).unwrap_or(1.0);
+        let range = metadata
+            .and_then(|m| m.random_pitch_semitones)
+            .unwrap_or(0.0)
+            .abs();
+        if range == 0.0 {
+            return rate;
+        }
+        rate * 2f32.powf(self.next_random() * range / 12.0)
+    }
+
+    /// A random number from -1.0 to 1.0, from a xorshift generator
+    fn next_random(&mut self) -> f32 {
+        self.random_state ^= self.random_state << 13;
+        self.random_state ^= self.random_state >> 7;
+        self.random_state ^= self.random_state << 17;
+        (self.random_state >> 40) as f32 / (1u64 << 23) as f32 - 1.0
+    }
+
+    /// Gain that brings a sound to the target loudness, or its own `gain_db`.
+    /// Sounds that haven't been analyzed yet play as they are.
+    fn normalization_gain_db(&self, sound_id: &str) -> Result<f32, AudioError> {
+        if let Some(gain_db) = self.sound_metadata.get(sound_id).and_then(|m| m.gain_db) {
+            return Ok(gain_db);
+        }
+
+        let path = &self.library.get(sound_id)?.path;
+        Ok(self
+            .loudness
+            .as_ref()
+            .and_then(|loudness| loudness.get(path))
+            .map_or(0.0, |loudness| {
+                loudness.gain_db(
+                    self.loudness_settings.target_lufs,
+                    self.loudness_settings.max_true_peak_db,
+                )
+            }))
+    }
+
+    /// Milliseconds into a sound's file a start point is
+    fn start_ms(&self, sound_id: &str, start: StartPoint) -> Result<u32, AudioError> {
+        let metadata = self.sound_metadata.get(sound_id);
+        match start {
+            StartPoint::Start => Ok(metadata.and_then(|m| m.start_ms).unwrap_or(0)),
+            StartPoint::Cue(name) => {
+                metadata
+                    .and_then(|m| m.cues.get(name).copied())
+                    .ok_or_else(|| {
+                        AudioError::NotSupported(format!("'{}' has no cue '{}'", sound_id, name))
+                    })
+            }
+            StartPoint::Position(ms) => Ok(ms),
+        }
+    }
+
+    /// How a sound plays, from its own settings with the global fades filling the gaps
+    fn play_options(
+        &mut self,
+        sound_id: &str,
+        info: ClipInfo,
+        start: StartPoint,
+    ) -> Result<PlayOptions, AudioError> {
+        let rate = self.playback_rate(sound_id);
+        let metadata = self.sound_metadata(sound_id);
+        let curve = metadata.fade_curve.unwrap_or(self.fade_settings.curve);
+
+        let region_start = metadata.start_ms.map_or(0, |ms| info.frame_at_ms(ms));
+        let region_end = metadata
+            .end_ms
+            .map_or(info.frames, |ms| info.frame_at_ms(ms));
+        let start_frame = info.frame_at_ms(self.start_ms(sound_id, start)?);
+
+        Ok(PlayOptions {
+            mode: metadata.mode,
+            loop_count: metadata.loop_count,
+            rate,
+            region: region_start..region_end,
+            start_frame,
+            fade_in: Fade {
+                curve,
+                frames: ms_to_frames(metadata.fade_in_ms.unwrap_or(self.fade_settings.fade_in_ms)),
+            },
+            fade_out: Fade {
+                curve,
+                frames: ms_to_frames(
+                    metadata
+                        .fade_out_ms
+                        .unwrap_or(self.fade_settings.fade_out_ms),
+                ),
+            },
+            exclusive_group: metadata.exclusive_group.as_deref().map(Arc::from),
+            choke_group: metadata.choke_group.map(|name| {
+                // Groups that sounds name without a section of their own use the defaults
+                let group = self.choke_groups.get(&name).cloned().unwrap_or_default();
+                ChokeGroup {
+                    name: name.into(),
+                    choke: group.choke,
+                    fade: Fade {
+                        curve,
+                        frames: ms_to_frames(group.fade_ms),
+                    },
+                    max_voices: group.max_voices.map(|max| max.max(1) as usize),
+                    steal: group.steal,
+                }
+            }),
+            ducking: metadata.ducking,
+        })
+    }
+
+    /// Let go of a sound played by a key or pad that is no longer held down
+    pub fn release_sound(&mut self, sound_id: &str) -> Result<(), AudioError> {
+        self.library.get(sound_id)?;
+        self.mixer.send(MixerCommand::Release {
+            sound_id: sound_id.into(),
+        })
+    }
+
+    pub fn stop_sound(&mut self, sound_id: &str) -> Result<(), AudioError> {
+        self.library.get(sound_id)?;
+        self.mixer.send(MixerCommand::Stop {
+            sound_id: sound_id.into(),
+        })
+    }
+
+    pub fn stop_all(&mut self) -> Result<(), AudioError> {
+        self.mixer.send(MixerCommand::StopAll)
+    }
+
+    /// Stop one voice of a sound, fading out if the sound has a fade-out. A
+    /// voice that has already finished is left alone.
+    pub fn stop_voice(&mut self, voice: VoiceId) -> Result<(), AudioError> {
+        self.mixer.send(MixerCommand::StopVoice(voice))
+    }
+
+    /// Set the volume of one voice, leaving the sound's volume as it is
+    pub fn set_voice_volume(&mut self, voice: VoiceId, volume: f32) -> Result<(), AudioError> {
+        self.mixer.send(MixerCommand::SetVoiceVolume {
+            voice,
+            volume: volume.max(0.0),
+        })
+    }
+
+    pub fn pause_voice(&mut self, voice: VoiceId, paused: bool) -> Result<(), AudioError> {
+        self.mixer.send(MixerCommand::PauseVoice { voice, paused })
+    }
+
+    /// Pause every voice of a sound where it is, or resume them. Voices started
+    /// afterwards play as usual.
+    pub fn pause_sound(&mut self, sound_id: &str, paused: bool) -> Result<(), AudioError> {
+        self.library.get(sound_id)?;
+        self.mixer.send(MixerCommand::PauseSound {
+            sound_id: sound_id.into(),
+            paused,
+        })
+    }
+
+    /// Pause every voice playing now, or resume them
+    pub fn pause_all(&mut self, paused: bool) -> Result<(), AudioError> {
+        self.mixer.send(MixerCommand::PauseAll(paused))
+    }
+
+    /// Move every voice of a sound to its start point, a cue or a position
+    pub fn seek_sound(&mut self, sound_id: &str, to: StartPoint) -> Result<(), AudioError> {
+        self.library.get(sound_id)?;
+        let position_ms = self.start_ms(sound_id, to)?;
+        self.mixer.send(MixerCommand::SeekSound {
+            sound_id: sound_id.into(),
+            position_ms,
+        })
+    }
+
+    /// Move a voice to `position_ms` into its sound's file, kept between its trim points
+    pub fn seek_voice(&mut self, voice: VoiceId, position_ms: u32) -> Result<(), AudioError> {
+        self.mixer
+            .send(MixerCommand::SeekVoice { voice, position_ms })
+    }
+
+    /// Fade out every playing sound over the `[fade]` section's `fade_out_all_ms`
+    pub fn fade_out_all(&mut self) -> Result<(), AudioError> {
+        self.mixer.send(MixerCommand::FadeOutAll(Fade {
+            curve: self.fade_settings.curve,
+            frames: ms_to_frames(self.fade_settings.fade_out_all_ms),
+        }))
+    }
+
+    pub fn set_sound_volume(&mut self, sound_id: &str, volume: f32) -> Result<(), AudioError> {
+        self.library.get(sound_id)?;
+        self.sound_volumes
+            .insert(sound_id.to_string(), volume.max(0.0));
+        self.mixer.send(MixerCommand::SetSoundVolume {
+            sound_id: sound_id.into(),
+            volume: volume.max(0.0),
+        })
+    }
+
+    pub fn set_master_volume(&mut self, volume: f32) -> Result<(), AudioError> {
+        self.mixer
+            .send(MixerCommand::SetMasterVolume(volume.max(0.0)))
+    }
+
+    /// Multiply the speed of the sounds playing now by `factor`, changing their
+    /// pitch along with it. Sounds played afterwards play at their own rate.
+    pub fn nudge_speed(&mut self, factor: f32) -> Result<(), AudioError> {
+        check_speed_factor(factor)?;
+        self.mixer.send(MixerCommand::NudgeSpeed(factor))
+    }
+
+    /// Play every sound, including ones played afterwards, `factor` times faster
+    /// until this is called again with 1
+    pub fn hold_speed(&mut self, factor: f32) -> Result<(), AudioError> {
+        check_speed_factor(factor)?;
+        self.mixer.send(MixerCommand::HoldSpeed(factor))
+    }
+
+    /// A sound's settings, or the defaults for a sound without any
+    pub fn sound_metadata(&self, sound_id: &str) -> SoundMetadata {
+        self.sound_metadata
+            .get(sound_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// The shape of a sound, if the background extractor has got to it. If
+    /// not, it sees to the sound next and says so through [`Self::take_overviews_ready`].
+    pub fn overview(&self, sound_id: &str) -> Result<Option<Overview>, AudioError> {
+        let path = &self.library.get(sound_id)?.path;
+        let overview = self.overviews.get(path);
+        if overview.is_none() {
+            self.overviews.hurry(sound_id, path);
+        }
+        Ok(overview)
+    }
+
+    /// Save the loudness and waveform caches, with anything not saved yet
+    pub fn save_caches(&self) {
+        if let Some(loudness) = &self.loudness {
+            loudness.save();
+        }
+        self.overviews.save();
+    }
+
+    /// Take the ids of sounds as their shapes are worked out. Only the first caller gets them.
+    pub fn take_overviews_ready(&mut self) -> Option<Receiver<String>> {
+        self.overviews.take_ready()
+    }
+
+    /// Level in dB of `bands` bands of the output or the mic, from 20 Hz to 20 kHz
+    pub fn spectrum(&mut self, source: SpectrumSource, bands: usize) -> Vec<f32> {
+        self.mixer.spectrum(source).bands(bands)
+    }
+
+    /// Set where a sound starts and ends and its cues, which apply from its next play on
+    pub fn set_sound_trim(
+        &mut self,
+        sound_id: &str,
+        start_ms: Option<u32>,
+        end_ms: Option<u32>,
+        cues: BTreeMap<String, u32>,
+    ) -> Result<(), AudioError> {
+        self.library.get(sound_id)?;
+        if let (Some(start), Some(end)) = (start_ms, end_ms)
+            && start >= end
+        {
+            return Err(AudioError::NotSupported(format!(
+                "The start of '{}' must be before its end",
+                sound_id
+            )));
+        }
+
+        let metadata = self.sound_metadata.entry(sound_id.to_string()).or_default();
+        metadata.start_ms = start_ms;
+        metadata.end_ms = end_ms;
+        metadata.cues = cues;
+        Ok(())
+    }
+
+    pub fn sound_effects(&self, sound_id: &str) -> &[EffectSettings] {
+        self.sound_metadata
+            .get(sound_id)
+            .map_or(&[], |metadata| &metadata.effects)
+    }
+
+    /// Replace a sound's effects, which apply from its next play on
+    pub fn set_sound_effects(
+        &mut self,
+        sound_id: &str,
+        effects: Vec<EffectSettings>,
+    ) -> Result<(), AudioError> {
+        self.library.get(sound_id)?;
+        self.sound_metadata
+            .entry(sound_id.to_string())
+            .or_default()
+            .effects = effects;
+        Ok(())
+    }
+
+    pub fn master_effects(&self) -> &[EffectSettings] {
+        &self.master_effects
+    }
+
+    pub fn set_master_effects(&mut self, effects: Vec<EffectSettings>) -> Result<(), AudioError> {
+        self.mixer
+            .send(MixerCommand::SetMasterEffects(EffectChain::new(&effects)))?;
+        self.master_effects = effects;
+        Ok(())
+    }
+
+    /// Names of the voice changer presets, built-in ones included
+    pub fn voice_presets(&self) -> Vec<String> {
+        self.voice_changer.all_presets().into_keys().collect()
+    }
+
+    pub fn voice_preset(&self) -> Option<&str> {
+        self.voice_changer.preset.as_deref()
+    }
+
+    /// Preset after the current one, going from no preset through all of them and back
+    pub fn next_voice_preset(&self) -> Option<String> {
+        let presets = self.voice_presets();
+        match self.voice_preset() {
+            Some(current) => presets
+                .iter()
+                .skip_while(|name| name.as_str() != current)
+                .nth(1)
+                .cloned(),
+            None => presets.first().cloned(),
+        }
+    }
+
+    /// Transform the passthrough mic with a voice changer preset, or pass it through unchanged
+    pub fn set_voice_preset(&mut self, preset: Option<&str>) -> Result<(), AudioError> {
+        let chain = match preset {
+            Some(name) => {
+                let presets = self.voice_changer.all_presets();
+                let settings = presets.get(name).ok_or_else(|| {
+                    AudioError::NotSupported(format!("Unknown voice preset '{}'", name))
+                })?;
+                voice_changer::build(settings)
+            }
+            None => EffectChain::default(),
+        };
+        self.mixer.send(MixerCommand::SetMicEffects(chain))?;
+        self.voice_changer.preset = preset.map(str::to_string);
+        Ok(())
+    }
+}
+
+fn check_speed_factor(factor: f32) -> Result<(), AudioError> {
+    if !factor.is_finite() || factor <= 0.0 {
+        return Err(AudioError::NotSupported(format!(
+            "Speed factor must be above zero, not {}",
+            factor
+        )));
+    }
+    Ok(())
+}
+
+fn db_to_gain(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+fn ms_to_frames(ms: u32) -> u32 {
+    (ms as u64 * SAMPLE_RATE as u64 / 1000) as u32
 }
 
 //     pub fn create_virtual_mic(&mut self, name: &str) -> Result<AudioDevice, AudioError> {
@@ -0,0 +1,411 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::UNIX_EPOCH;
+
+use serde::{Deserialize, Serialize};
+
+use super::AudioError;
+use super::decoder::{ClipInfo, StreamDecoder};
+use super::mixer::CHANNELS;
+
+/// Slices each sound is reduced to, enough for a wide terminal drawn in braille
+pub const OVERVIEW_POINTS: usize = 1024;
+
+/// The shape of a sound: its lowest and highest sample in each of
+/// [`OVERVIEW_POINTS`] equal slices, or fewer for very short sounds
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Overview {
+    pub duration_ms: u32,
+    pub min: Vec<f32>,
+    pub max: Vec<f32>,
+}
+
+impl Overview {
+    /// Reduce a file a packet at a time, so a long one is never all in memory at once
+    fn of_file(path: &Path) -> Result<Self, AudioError> {
+        let mut decoder = StreamDecoder::open(path)?;
+        let mut builder = OverviewBuilder::new(decoder.sample_rate);
+        let mut samples = Vec::new();
+        while decoder.decode_next(&mut samples)?.is_some() {
+            builder.add(&samples);
+            samples.clear();
+        }
+        Ok(builder.finish())
+    }
+}
+
+/// Reduces a sound fed to it in pieces, without knowing its length up front.
+/// Frames go into buckets of a few at a time, and when there are twice as
+/// many buckets as points, neighbouring ones are merged and buckets hold twice
+/// as many frames from then on.
+struct OverviewBuilder {
+    sample_rate: u32,
+    min: Vec<f32>,
+    max: Vec<f32>,
+    bucket_frames: usize,
+    /// Frames in the last bucket so far
+    filling: usize,
+    frames: usize,
+}
+
+impl OverviewBuilder {
+    fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            min: Vec::with_capacity(2 * OVERVIEW_POINTS),
+            max: Vec::with_capacity(2 * OVERVIEW_POINTS),
+            bucket_frames: 1,
+            filling: 0,
+            frames: 0,
+        }
+    }
+
+    /// Add the next interleaved frames of the sound
+    fn add(&mut self, samples: &[f32]) {
+        for frame in samples.chunks_exact(CHANNELS) {
+            let (low, high) = frame.iter().fold((0.0f32, 0.0f32), |(low, high), &s| {
+                (low.min(s), high.max(s))
+            });
+            if self.filling == 0 {
+                self.min.push(low);
+                self.max.push(high);
+            } else if let (Some(min), Some(max)) = (self.min.last_mut(), self.max.last_mut()) {
+                *min = min.min(low);
+                *max = max.max(high);
+            }
+            self.frames += 1;
+            self.filling += 1;
+
+            if self.filling == self.bucket_frames {
+                self.filling = 0;
+                if self.min.len() == 2 * OVERVIEW_POINTS {
+                    merge_pairs(&mut self.min, f32::min);
+                    merge_pairs(&mut self.max, f32::max);
+                    self.bucket_frames *= 2;
+                }
+            }
+        }
+    }
+
+    /// Share the buckets out between [`OVERVIEW_POINTS`] equal slices, or one
+    /// per frame for very short sounds
+    fn finish(self) -> Overview {
+        let buckets = self.min.len();
+        let points = OVERVIEW_POINTS.min(buckets);
+        // Three decimals are plenty to draw with, and keep the cache small
+        let round = |sample: f32| (sample * 1000.0).round() / 1000.0;
+
+        let (min, max) = (0..points)
+            .map(|point| {
+                let start = point * buckets / points;
+                let end = ((point + 1) * buckets / points).max(start + 1);
+                let low = self.min[start..end].iter().fold(0.0f32, |a, &b| a.min(b));
+                let high = self.max[start..end].iter().fold(0.0f32, |a, &b| a.max(b));
+                (round(low), round(high))
+            })
+            .unzip();
+
+        let info = ClipInfo {
+            frames: self.frames,
+            sample_rate: self.sample_rate,
+        };
+        Overview {
+            duration_ms: info.duration_ms(),
+            min,
+            max,
+        }
+    }
+}
+
+/// Halve `values` by combining each pair of neighbours
+fn merge_pairs(values: &mut Vec<f32>, combine: fn(f32, f32) -> f32) {
+    let merged = values.len() / 2;
+    for i in 0..merged {
+        values[i] = combine(values[2 * i], values[2 * i + 1]);
+    }
+    values.truncate(merged);
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    /// Modification time and size of the file when it was reduced
+    modified_ns: u64,
+    len: u64,
+    overview: Overview,
+}
+
+/// Overviews of every file seen so far, kept in the cache directory between runs
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct OverviewCache {
+    entries: BTreeMap<PathBuf, CacheEntry>,
+}
+
+impl OverviewCache {
+    fn path() -> Option<PathBuf> {
+        dirs::cache_dir().map(|dir| dir.join("boomcrab").join("overviews.json"))
+    }
+
+    fn load() -> Self {
+        Self::path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self) -> std::io::Result<()> {
+        let Some(path) = Self::path() else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let partial = path.with_extension("json.partial");
+        fs::write(&partial, serde_json::to_vec(self)?)?;
+        fs::rename(partial, path)
+    }
+}
+
+/// Sounds waiting to be reduced, by id and file
+#[derive(Default)]
+struct Queue {
+    jobs: Mutex<VecDeque<(String, PathBuf)>>,
+    added: Condvar,
+}
+
+impl Queue {
+    fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<(String, PathBuf)>> {
+        self.jobs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Add a sound at the back, or at the front for one wanted now. A sound
+    /// already waiting isn't added twice.
+    fn push(&self, sound_id: &str, path: &Path, first: bool) {
+        let mut jobs = self.lock();
+        let queued = jobs.iter().position(|(id, p)| id == sound_id && p == path);
+        match (queued, first) {
+            (Some(_), false) => return,
+            (Some(index), true) => {
+                jobs.remove(index);
+            }
+            (None, _) => {}
+        }
+        let job = (sound_id.to_string(), path.to_path_buf());
+        if first {
+            jobs.push_front(job);
+        } else {
+            jobs.push_back(job);
+        }
+        self.added.notify_one();
+    }
+
+    /// Wait for the next sound, also returning whether it is the last one waiting
+    fn pop(&self) -> ((String, PathBuf), bool) {
+        let mut jobs = self.lock();
+        loop {
+            if let Some(job) = jobs.pop_front() {
+                return (job, jobs.is_empty());
+            }
+            jobs = self.added.wait(jobs).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+}
+
+/// Works out the overviews of sound files on a background thread
+pub struct OverviewExtractor {
+    cache: Arc<Mutex<OverviewCache>>,
+    queue: Arc<Queue>,
+    ready: Option<Receiver<String>>,
+}
+
+impl OverviewExtractor {
+    pub fn start() -> Self {
+        let cache = Arc::new(Mutex::new(OverviewCache::load()));
+        let queue = Arc::new(Queue::default());
+        let (sender, ready) = mpsc::channel();
+
+        let thread_cache = Arc::clone(&cache);
+        let thread_queue = Arc::clone(&queue);
+        thread::spawn(move || extract_queued(&thread_queue, &thread_cache, &sender));
+
+        Self {
+            cache,
+            queue,
+            ready: Some(ready),
+        }
+    }
+
+    /// Reduce a sound's file in the background, unless it is cached and hasn't changed since
+    pub fn queue(&self, sound_id: &str, path: &Path) {
+        self.queue.push(sound_id, path, false);
+    }
+
+    /// Reduce a sound's file before any others waiting, as it is wanted now
+    pub fn hurry(&self, sound_id: &str, path: &Path) {
+        self.queue.push(sound_id, path, true);
+    }
+
+    /// Take the ids of sounds as their overviews are worked out. Only the first caller gets them.
+    pub fn take_ready(&mut self) -> Option<Receiver<String>> {
+        self.ready.take()
+    }
+
+    /// The overview of a file, if it has been worked out and the file hasn't changed since
+    pub fn get(&self, path: &Path) -> Option<Overview> {
+        let stamp = file_stamp(path)?;
+        let cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        cache
+            .entries
+            .get(path)
+            .filter(|entry| (entry.modified_ns, entry.len) == stamp)
+            .map(|entry| entry.overview.clone())
+    }
+
+    /// Save what has been worked out so far, e.g. before the daemon stops
+    pub fn save(&self) {
+        let cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = cache.save() {
+            eprintln!("Warning: Could not save the waveform cache ({}).", e);
+        }
+    }
+}
+
+fn extract_queued(queue: &Queue, cache: &Mutex<OverviewCache>, ready: &Sender<String>) {
+    let mut changed = false;
+    loop {
+        let ((sound_id, path), last) = queue.pop();
+        if extract_if_stale(&path, cache) {
+            changed = true;
+            let _ = ready.send(sound_id);
+        }
+
+        // Save once the queue has drained, rather than after every file
+        if changed && last {
+            changed = false;
+            let cache = cache.lock().unwrap_or_else(|e| e.into_inner());
+            if let Err(e) = cache.save() {
+                eprintln!("Warning: Could not save the waveform cache ({}).", e);
+            }
+        }
+    }
+}
+
+/// Reduce a file unless its cache entry is still good, returning whether the cache changed
+fn extract_if_stale(path: &Path, cache: &Mutex<OverviewCache>) -> bool {
+    let Some((modified_ns, len)) = file_stamp(path) else {
+        return false;
+    };
+
+    {
+        let cache = cache.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(entry) = cache.entries.get(path)
+            && entry.modified_ns == modified_ns
+            && entry.len == len
+        {
+            return false;
+        }
+    }
+
+    let overview = match Overview::of_file(path) {
+        Ok(overview) => overview,
+        Err(e) => {
+            eprintln!("Warning: Could not draw the waveform ({}).", e);
+            return false;
+        }
+    };
+
+    let mut cache = cache.lock().unwrap_or_else(|e| e.into_inner());
+    cache.entries.insert(
+        path.to_path_buf(),
+        CacheEntry {
+            modified_ns,
+            len,
+            overview,
+        },
+    );
+    true
+}
+
+/// Modification time in nanoseconds and size of a file
+fn file_stamp(path: &Path) -> Option<(u64, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    let modified_ns = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |since| since.as_nanos() as u64);
+    Some((modified_ns, metadata.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overview(samples: &[f32]) -> Overview {
+        let mut builder = OverviewBuilder::new(1000);
+        for packet in samples.chunks(7 * CHANNELS) {
+            builder.add(packet);
+        }
+        builder.finish()
+    }
+
+    #[test]
+    fn short_sounds_get_a_point_per_frame() {
+        let overview = overview(&[0.5, -0.25, 0.1, 0.2, -0.3, -0.4]);
+        assert_eq!(overview.min, [-0.25, 0.0, -0.4]);
+        assert_eq!(overview.max, [0.5, 0.2, 0.0]);
+        assert_eq!(overview.duration_ms, 3);
+    }
+
+    #[test]
+    fn long_sounds_are_shared_out_between_the_points() {
+        // A spike at the start of every slice a tenth of the way in, and silence
+        let frames = OVERVIEW_POINTS * 100;
+        let samples: Vec<f32> = (0..frames)
+            .flat_map(|i| {
+                let sample = if i % (frames / 10) == 0 { 0.9 } else { 0.0 };
+                [sample, -sample]
+            })
+            .collect();
+        let overview = overview(&samples);
+
+        assert_eq!(overview.min.len(), OVERVIEW_POINTS);
+        assert_eq!(overview.duration_ms, 102_400);
+        let spikes: Vec<usize> = (0..OVERVIEW_POINTS)
+            .filter(|&point| overview.max[point] > 0.0)
+            .collect();
+        let expected: Vec<usize> = (0..10).map(|n| n * OVERVIEW_POINTS / 10).collect();
+        assert_eq!(spikes.len(), 10);
+        for (spike, expected) in spikes.iter().zip(expected) {
+            assert!(spike.abs_diff(expected) <= 1, "{} vs {}", spike, expected);
+        }
+        assert!(spikes.iter().all(|&point| overview.min[point] == -0.9));
+    }
+
+    fn ids(queue: &Queue) -> Vec<String> {
+        queue.lock().iter().map(|(id, _)| id.clone()).collect()
+    }
+
+    #[test]
+    fn wanted_sounds_go_first() {
+        let queue = Queue::default();
+        for id in ["a", "b", "c"] {
+            queue.push(id, Path::new(id), false);
+        }
+        queue.push("b", Path::new("b"), false);
+        assert_eq!(ids(&queue), ["a", "b", "c"]);
+
+        queue.push("c", Path::new("c"), true);
+        queue.push("d", Path::new("d"), true);
+        assert_eq!(ids(&queue), ["d", "c", "a", "b"]);
+
+        assert_eq!(queue.pop(), (("d".to_string(), PathBuf::from("d")), false));
+        queue.pop();
+        queue.pop();
+        assert_eq!(queue.pop(), (("b".to_string(), PathBuf::from("b")), true));
+    }
+}
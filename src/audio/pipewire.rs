@@ -1,13 +1,21 @@
 use pipewire as pw;
 use pw::context::ContextRc;
 use pw::main_loop::MainLoopRc;
-use std::sync::{Arc, Mutex};
+use pw::{properties::properties, spa};
+use std::sync::{Arc, Mutex, mpsc};
 use std::thread;
 
+use super::mixer::{self, CHANNELS, MIC_MAX_BACKLOG_FRAMES, MicLink, Mixer, SAMPLE_RATE};
 use super::{AudioBackend, AudioDevice, AudioError, DeviceType};
 
+/// Largest number of frames rendered by the mixer in one go
+const MAX_FRAMES_PER_CHUNK: usize = 4096;
+
 /// PipeWire backend implementation for Linux audio
-pub struct PipeWireBackend {}
+pub struct PipeWireBackend {
+    output: Option<OutputStream>,
+    mic: Option<MicPassthrough>,
+}
 
 impl PipeWireBackend {
     /// Creates a new PipeWire backend instance
@@ -15,7 +23,10 @@ impl PipeWireBackend {
         // Initialize PipeWire library
         pw::init();
 
-        Ok(PipeWireBackend {})
+        Ok(PipeWireBackend {
+            output: None,
+            mic: None,
+        })
     }
 
     /// Internal helper to list devices by media class
@@ -106,6 +117,390 @@ impl AudioBackend for PipeWireBackend {
     fn list_audio_inputs(&self) -> Result<Vec<AudioDevice>, AudioError> {
         self.list_devices_by_class("Audio/Source")
     }
+
+    /// Starts a playback stream on the default sink, fed by the mixer
+    fn start_output(&mut self, mixer: Mixer) -> Result<(), AudioError> {
+        if self.output.is_some() {
+            return Err(AudioError::InitializationFailed(
+                "Output stream already started".to_string(),
+            ));
+        }
+
+        self.output = Some(OutputStream::new("boomcrab", mixer)?);
+        Ok(())
+    }
+
+    /// Captures the microphone and creates the "BoomCrab Mic" virtual source
+    fn start_mic_passthrough(
+        &mut self,
+        device: Option<&str>,
+        link: MicLink,
+    ) -> Result<(), AudioError> {
+        if self.mic.is_some() {
+            return Err(AudioError::InitializationFailed(
+                "Mic passthrough already started".to_string(),
+            ));
+        }
+
+        self.mic = Some(MicPassthrough::new(device, link)?);
+        Ok(())
+    }
+}
+
+/// Serialized `EnumFormat` parameter for interleaved stereo F32LE at the mixer's rate
+fn audio_format() -> Result<Vec<u8>, AudioError> {
+    let mut audio_info = spa::param::audio::AudioInfoRaw::new();
+    audio_info.set_format(spa::param::audio::AudioFormat::F32LE);
+    audio_info.set_rate(SAMPLE_RATE);
+    audio_info.set_channels(CHANNELS as u32);
+    let mut position = [0; spa::param::audio::MAX_CHANNELS];
+    position[0] = spa::sys::SPA_AUDIO_CHANNEL_FL;
+    position[1] = spa::sys::SPA_AUDIO_CHANNEL_FR;
+    audio_info.set_position(position);
+
+    Ok(spa::pod::serialize::PodSerializer::serialize(
+        std::io::Cursor::new(Vec::new()),
+        &spa::pod::Value::Object(spa::pod::Object {
+            type_: spa::utils::SpaTypes::ObjectParamFormat.as_raw(),
+            id: spa::param::ParamType::EnumFormat.as_raw(),
+            properties: audio_info.into(),
+        }),
+    )
+    .map_err(|e| {
+        AudioError::InitializationFailed(format!("Failed to build stream format: {:?}", e))
+    })?
+    .0
+    .into_inner())
+}
+
+/// Playback stream running on its own PipeWire main loop thread
+struct OutputStream {
+    main_loop_thread: Option<thread::JoinHandle<()>>,
+    terminate_sender: Option<pw::channel::Sender<()>>,
+}
+
+/// State owned by the stream's process callback
+struct OutputData {
+    mixer: Mixer,
+    scratch: Vec<f32>,
+}
+
+impl OutputStream {
+    fn new(name: &str, mixer: Mixer) -> Result<Self, AudioError> {
+        let (terminate_sender, terminate_receiver) = pw::channel::channel::<()>();
+        let (ready_sender, ready_receiver) = mpsc::channel();
+        let name = name.to_string();
+
+        let main_loop_thread = thread::spawn(move || {
+            let result = Self::run(&name, mixer, terminate_receiver, &ready_sender);
+            if let Err(e) = result {
+                ready_sender.send(Err(e)).ok();
+            }
+        });
+
+        // Wait until the stream is connected so setup errors reach the caller
+        match ready_receiver.recv() {
+            Ok(Ok(())) => Ok(OutputStream {
+                main_loop_thread: Some(main_loop_thread),
+                terminate_sender: Some(terminate_sender),
+            }),
+            Ok(Err(e)) => Err(e),
+            Err(_) => Err(AudioError::InitializationFailed(
+                "Output stream thread exited".to_string(),
+            )),
+        }
+    }
+
+    fn run(
+        name: &str,
+        mixer: Mixer,
+        terminate_receiver: pw::channel::Receiver<()>,
+        ready_sender: &mpsc::Sender<Result<(), AudioError>>,
+    ) -> Result<(), AudioError> {
+        let failed = |what: &str, e: pw::Error| {
+            AudioError::InitializationFailed(format!("Failed to {}: {}", what, e))
+        };
+
+        let main_loop = MainLoopRc::new(None).map_err(|e| failed("create main loop", e))?;
+        let context = ContextRc::new(&main_loop, None).map_err(|e| failed("create context", e))?;
+        let core = context
+            .connect_rc(None)
+            .map_err(|e| failed("connect to PipeWire", e))?;
+
+        let stream = pw::stream::StreamBox::new(
+            &core,
+            name,
+            properties! {
+                *pw::keys::MEDIA_TYPE => "Audio",
+                *pw::keys::MEDIA_ROLE => "Game",
+                *pw::keys::MEDIA_CATEGORY => "Playback",
+                *pw::keys::AUDIO_CHANNELS => CHANNELS.to_string().as_str(),
+            },
+        )
+        .map_err(|e| failed("create stream", e))?;
+
+        let data = OutputData {
+            mixer,
+            scratch: vec![0.0; MAX_FRAMES_PER_CHUNK * CHANNELS],
+        };
+
+        let _listener = stream
+            .add_local_listener_with_user_data(data)
+            .process(|stream, data| {
+                if let Some(mut buffer) = stream.dequeue_buffer() {
+                    let datas = buffer.datas_mut();
+                    if datas.is_empty() {
+                        return;
+                    }
+                    let stride = std::mem::size_of::<f32>() * CHANNELS;
+                    let out = &mut datas[0];
+
+                    let n_frames = match out.data() {
+                        Some(bytes) => {
+                            let n_frames = bytes.len() / stride;
+                            for chunk in
+                                bytes[..n_frames * stride].chunks_mut(MAX_FRAMES_PER_CHUNK * stride)
+                            {
+                                let samples = &mut data.scratch[..chunk.len() / 4];
+                                data.mixer.process(samples);
+                                for (bytes, sample) in chunk.chunks_exact_mut(4).zip(samples) {
+                                    bytes.copy_from_slice(&sample.to_le_bytes());
+                                }
+                            }
+                            n_frames
+                        }
+                        None => 0,
+                    };
+
+                    let chunk = out.chunk_mut();
+                    *chunk.offset_mut() = 0;
+                    *chunk.stride_mut() = stride as _;
+                    *chunk.size_mut() = (stride * n_frames) as _;
+                }
+            })
+            .register()
+            .map_err(|e| failed("register stream listener", e))?;
+
+        let values = audio_format()?;
+        let mut params = [spa::pod::Pod::from_bytes(&values).ok_or_else(|| {
+            AudioError::InitializationFailed("Invalid stream format".to_string())
+        })?];
+
+        stream
+            .connect(
+                spa::utils::Direction::Output,
+                None,
+                pw::stream::StreamFlags::AUTOCONNECT
+                    | pw::stream::StreamFlags::MAP_BUFFERS
+                    | pw::stream::StreamFlags::RT_PROCESS,
+                &mut params,
+            )
+            .map_err(|e| failed("connect stream", e))?;
+
+        let _receiver = terminate_receiver.attach(main_loop.loop_(), {
+            let main_loop = main_loop.clone();
+            move |_| main_loop.quit()
+        });
+
+        ready_sender.send(Ok(())).ok();
+
+        // Run the main loop (blocks until terminate signal)
+        main_loop.run();
+
+        Ok(())
+    }
+}
+
+impl Drop for OutputStream {
+    fn drop(&mut self) {
+        if let Some(sender) = self.terminate_sender.take() {
+            sender.send(()).ok();
+        }
+        if let Some(thread) = self.main_loop_thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+/// Capture and virtual source streams for the mic passthrough, running on their own
+/// PipeWire main loop thread
+struct MicPassthrough {
+    main_loop_thread: Option<thread::JoinHandle<()>>,
+    terminate_sender: Option<pw::channel::Sender<()>>,
+}
+
+impl MicPassthrough {
+    fn new(device: Option<&str>, link: MicLink) -> Result<Self, AudioError> {
+        let (terminate_sender, terminate_receiver) = pw::channel::channel::<()>();
+        let (ready_sender, ready_receiver) = mpsc::channel();
+        let device = device.map(str::to_string);
+
+        let main_loop_thread = thread::spawn(move || {
+            let result = Self::run(device.as_deref(), link, terminate_receiver, &ready_sender);
+            if let Err(e) = result {
+                ready_sender.send(Err(e)).ok();
+            }
+        });
+
+        match ready_receiver.recv() {
+            Ok(Ok(())) => Ok(MicPassthrough {
+                main_loop_thread: Some(main_loop_thread),
+                terminate_sender: Some(terminate_sender),
+            }),
+            Ok(Err(e)) => Err(e),
+            Err(_) => Err(AudioError::InitializationFailed(
+                "Mic passthrough thread exited".to_string(),
+            )),
+        }
+    }
+
+    fn run(
+        device: Option<&str>,
+        link: MicLink,
+        terminate_receiver: pw::channel::Receiver<()>,
+        ready_sender: &mpsc::Sender<Result<(), AudioError>>,
+    ) -> Result<(), AudioError> {
+        let failed = |what: &str, e: pw::Error| {
+            AudioError::InitializationFailed(format!("Failed to {}: {}", what, e))
+        };
+
+        let main_loop = MainLoopRc::new(None).map_err(|e| failed("create main loop", e))?;
+        let context = ContextRc::new(&main_loop, None).map_err(|e| failed("create context", e))?;
+        let core = context
+            .connect_rc(None)
+            .map_err(|e| failed("connect to PipeWire", e))?;
+
+        let mut capture_properties = properties! {
+            *pw::keys::MEDIA_TYPE => "Audio",
+            *pw::keys::MEDIA_ROLE => "Communication",
+            *pw::keys::MEDIA_CATEGORY => "Capture",
+            *pw::keys::AUDIO_CHANNELS => CHANNELS.to_string().as_str(),
+        };
+        if let Some(device) = device {
+            capture_properties.insert(*pw::keys::TARGET_OBJECT, device);
+        }
+        let capture = pw::stream::StreamBox::new(&core, "boomcrab-mic", capture_properties)
+            .map_err(|e| failed("create mic stream", e))?;
+
+        let _capture_listener = capture
+            .add_local_listener_with_user_data(link.input)
+            .process(|stream, input| {
+                let Some(mut buffer) = stream.dequeue_buffer() else {
+                    return;
+                };
+                let datas = buffer.datas_mut();
+                if datas.is_empty() {
+                    return;
+                }
+                let data = &mut datas[0];
+                let offset = data.chunk().offset() as usize;
+                let size = data.chunk().size() as usize;
+
+                if let Some(bytes) = data.data() {
+                    let end = (offset + size).min(bytes.len());
+                    let frame_bytes = std::mem::size_of::<f32>() * CHANNELS;
+                    for frame in bytes[offset.min(end)..end].chunks_exact(frame_bytes) {
+                        // The mixer has fallen behind when there's no room; drop the frame
+                        if let Ok(chunk) = input.write_chunk_uninit(CHANNELS) {
+                            chunk.fill_from_iter(
+                                frame
+                                    .chunks_exact(4)
+                                    .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+                            );
+                        }
+                    }
+                }
+            })
+            .register()
+            .map_err(|e| failed("register mic stream listener", e))?;
+
+        let source = pw::stream::StreamBox::new(
+            &core,
+            "boomcrab-virtual-mic",
+            properties! {
+                *pw::keys::MEDIA_TYPE => "Audio",
+                *pw::keys::MEDIA_CLASS => "Audio/Source",
+                *pw::keys::NODE_NAME => "boomcrab_mic",
+                *pw::keys::NODE_DESCRIPTION => "BoomCrab Mic",
+                *pw::keys::AUDIO_CHANNELS => CHANNELS.to_string().as_str(),
+            },
+        )
+        .map_err(|e| failed("create virtual mic stream", e))?;
+
+        let _source_listener = source
+            .add_local_listener_with_user_data(link.virtual_mic)
+            .process(|stream, virtual_mic| {
+                let Some(mut buffer) = stream.dequeue_buffer() else {
+                    return;
+                };
+                let datas = buffer.datas_mut();
+                if datas.is_empty() {
+                    return;
+                }
+                let stride = std::mem::size_of::<f32>() * CHANNELS;
+                let out = &mut datas[0];
+
+                mixer::trim_backlog(virtual_mic, MIC_MAX_BACKLOG_FRAMES);
+                let n_frames = match out.data() {
+                    Some(bytes) => {
+                        let n_frames = bytes.len() / stride;
+                        // Silence fills in for audio the mixer hasn't produced yet
+                        for bytes in bytes[..n_frames * stride].chunks_exact_mut(4) {
+                            let sample = virtual_mic.pop().unwrap_or(0.0);
+                            bytes.copy_from_slice(&sample.to_le_bytes());
+                        }
+                        n_frames
+                    }
+                    None => 0,
+                };
+
+                let chunk = out.chunk_mut();
+                *chunk.offset_mut() = 0;
+                *chunk.stride_mut() = stride as _;
+                *chunk.size_mut() = (stride * n_frames) as _;
+            })
+            .register()
+            .map_err(|e| failed("register virtual mic stream listener", e))?;
+
+        let values = audio_format()?;
+        let format = || {
+            spa::pod::Pod::from_bytes(&values).ok_or_else(|| {
+                AudioError::InitializationFailed("Invalid stream format".to_string())
+            })
+        };
+
+        let flags = pw::stream::StreamFlags::AUTOCONNECT
+            | pw::stream::StreamFlags::MAP_BUFFERS
+            | pw::stream::StreamFlags::RT_PROCESS;
+        capture
+            .connect(spa::utils::Direction::Input, None, flags, &mut [format()?])
+            .map_err(|e| failed("connect mic stream", e))?;
+        source
+            .connect(spa::utils::Direction::Output, None, flags, &mut [format()?])
+            .map_err(|e| failed("connect virtual mic stream", e))?;
+
+        let _receiver = terminate_receiver.attach(main_loop.loop_(), {
+            let main_loop = main_loop.clone();
+            move |_| main_loop.quit()
+        });
+
+        ready_sender.send(Ok(())).ok();
+
+        main_loop.run();
+
+        Ok(())
+    }
+}
+
+impl Drop for MicPassthrough {
+    fn drop(&mut self) {
+        if let Some(sender) = self.terminate_sender.take() {
+            sender.send(()).ok();
+        }
+        if let Some(thread) = self.main_loop_thread.take() {
+            thread.join().ok();
+        }
+    }
 }
 
 // Keep the VirtualMicrophone struct for future use
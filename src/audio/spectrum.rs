@@ -0,0 +1,155 @@
+//! Spectrum analysis of the master output and the mic.
+//!
+//! The mixer copies both signals, mixed down to mono, into ring buffers; the
+//! FFT runs on the control side whenever a spectrum is asked for, so the
+//! audio callback does no more than a copy.
+
+use std::f32::consts::PI;
+
+use rtrb::{Consumer, Producer, RingBuffer};
+use serde::{Deserialize, Serialize};
+
+use super::mixer::SAMPLE_RATE;
+
+/// Samples per transform, about 85 ms, which gives bins about 12 Hz wide
+pub const FFT_SIZE: usize = 4096;
+/// Most bands a spectrum can be split into
+pub const MAX_BANDS: usize = 256;
+/// The bands spread evenly in pitch between these frequencies
+pub const LOWEST_HZ: f32 = 20.0;
+pub const HIGHEST_HZ: f32 = 20_000.0;
+/// Quietest level reported, in dB below full scale
+pub const FLOOR_DB: f32 = -90.0;
+
+/// Which signal a spectrum is taken from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpectrumSource {
+    #[default]
+    Master,
+    Mic,
+}
+
+/// Create the mixer's end of a tap and the analyzer reading from it
+pub fn tap() -> (Producer<f32>, SpectrumAnalyzer) {
+    let (producer, consumer) = RingBuffer::new(FFT_SIZE * 2);
+    (producer, SpectrumAnalyzer::new(consumer))
+}
+
+/// Keeps the latest [`FFT_SIZE`] samples of a tap and works out their spectrum
+pub struct SpectrumAnalyzer {
+    tap: Consumer<f32>,
+    /// The latest samples, oldest first
+    history: Vec<f32>,
+    window: Vec<f32>,
+}
+
+impl SpectrumAnalyzer {
+    fn new(tap: Consumer<f32>) -> Self {
+        Self {
+            tap,
+            history: vec![0.0; FFT_SIZE],
+            // Hann window, which keeps a strong hum from smearing over its neighbours
+            window: (0..FFT_SIZE)
+                .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / FFT_SIZE as f32).cos())
+                .collect(),
+        }
+    }
+
+    /// Level in dB of each of `bands` bands from 20 Hz to 20 kHz, spaced evenly
+    /// in pitch. Each band is as loud as its loudest bin.
+    pub fn bands(&mut self, bands: usize) -> Vec<f32> {
+        self.read_tap();
+
+        let mut real: Vec<f32> = self
+            .history
+            .iter()
+            .zip(&self.window)
+            .map(|(sample, weight)| sample * weight)
+            .collect();
+        let mut imaginary = vec![0.0; FFT_SIZE];
+        fft(&mut real, &mut imaginary);
+
+        // A full-scale sine comes out at 0 dB
+        let scale = 2.0 / self.window.iter().sum::<f32>();
+        let magnitudes: Vec<f32> = real[..FFT_SIZE / 2]
+            .iter()
+            .zip(&imaginary)
+            .map(|(re, im)| (re * re + im * im).sqrt() * scale)
+            .collect();
+
+        let bin_hz = SAMPLE_RATE as f32 / FFT_SIZE as f32;
+        let edge =
+            |band: usize| LOWEST_HZ * (HIGHEST_HZ / LOWEST_HZ).powf(band as f32 / bands as f32);
+        (0..bands)
+            .map(|band| {
+                let low = (edge(band) / bin_hz).round() as usize;
+                // Low bands are narrower than a bin, and get the bin they fall in
+                let high = ((edge(band + 1) / bin_hz).round() as usize).max(low + 1);
+                let peak = magnitudes[low.min(magnitudes.len() - 1)..high.min(magnitudes.len())]
+                    .iter()
+                    .fold(0.0f32, |peak, &m| peak.max(m));
+                (20.0 * peak.max(f32::MIN_POSITIVE).log10()).max(FLOOR_DB)
+            })
+            .collect()
+    }
+
+    /// Move everything the mixer has written since the last read into the history
+    fn read_tap(&mut self) {
+        let waiting = self.tap.slots();
+        // A full buffer has been waiting since nobody last looked, and is too old to show
+        let stale = waiting == self.tap.buffer().capacity();
+        let Ok(chunk) = self.tap.read_chunk(waiting) else {
+            return;
+        };
+        if stale {
+            chunk.commit_all();
+            return;
+        }
+
+        let fresh: Vec<f32> = chunk.into_iter().collect();
+        let keep = fresh.len().min(FFT_SIZE);
+        self.history.drain(..keep);
+        self.history.extend_from_slice(&fresh[fresh.len() - keep..]);
+    }
+}
+
+/// In-place radix-2 FFT; the length must be a power of two
+fn fft(real: &mut [f32], imaginary: &mut [f32]) {
+    let n = real.len();
+
+    // Put the samples in bit-reversed order
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            real.swap(i, j);
+            imaginary.swap(i, j);
+        }
+    }
+
+    let mut length = 2;
+    while length <= n {
+        let angle = -2.0 * PI / length as f32;
+        for start in (0..n).step_by(length) {
+            for k in 0..length / 2 {
+                let (sin, cos) = (angle * k as f32).sin_cos();
+                let (a, b) = (start + k, start + k + length / 2);
+                let (re, im) = (
+                    real[b] * cos - imaginary[b] * sin,
+                    real[b] * sin + imaginary[b] * cos,
+                );
+                real[b] = real[a] - re;
+                imaginary[b] = imaginary[a] - im;
+                real[a] += re;
+                imaginary[a] += im;
+            }
+        }
+        length <<= 1;
+    }
+}
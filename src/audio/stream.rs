@@ -0,0 +1,427 @@
+//! Playing long files straight from disk.
+//!
+//! A few reader threads, shared by every streamed voice, decode ahead of the
+//! playback position into a ring buffer for each voice. Frames carry their
+//! place in the file, so the mixer can tell which ones it wants after a seek
+//! without waiting for the reader to catch up. A reader keeps its end of the
+//! ring until the voice lets go of the other, so the ring is never freed on
+//! the audio thread.
+
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::Duration;
+
+use rtrb::{Consumer, Producer, RingBuffer};
+
+use super::decoder::{ClipInfo, StreamDecoder};
+use super::mixer::{CHANNELS, SAMPLE_RATE};
+
+/// Frames decoded ahead of the playback position, about two seconds
+const READ_AHEAD_FRAMES: usize = SAMPLE_RATE as usize * 2;
+/// Threads reading for all the streamed voices between them
+const READER_THREADS: usize = 2;
+/// How long a reader waits for room in the rings before trying again
+const WAIT: Duration = Duration::from_millis(10);
+/// No seek waiting in [`StreamControl::seek`]
+const NO_SEEK: u64 = u64::MAX;
+
+#[derive(Debug, Clone, Copy)]
+struct StreamFrame {
+    /// Place of the frame in the file
+    index: u32,
+    samples: [f32; CHANNELS],
+}
+
+/// Shared between a stream and its reader
+struct StreamControl {
+    /// Frame the reader should read from next, or [`NO_SEEK`]
+    seek: AtomicU64,
+    /// Set when the file can't be read any further
+    failed: AtomicBool,
+}
+
+/// The mixer's end of a streamed file
+pub struct AudioStream {
+    frames: Consumer<StreamFrame>,
+    control: Arc<StreamControl>,
+    info: ClipInfo,
+    /// The frame last read, which the voice may ask for again
+    current: Option<StreamFrame>,
+}
+
+/// The threads that read streamed files, which hand each new stream to the
+/// next thread in turn
+pub struct StreamReaders {
+    threads: Vec<Sender<ReadAhead>>,
+    next: usize,
+}
+
+impl StreamReaders {
+    pub fn start() -> Self {
+        let threads = (0..READER_THREADS)
+            .map(|_| {
+                let (sender, readers) = mpsc::channel();
+                thread::spawn(move || read_streams(readers));
+                sender
+            })
+            .collect();
+        Self { threads, next: 0 }
+    }
+
+    /// Start reading `path` from `start`, wrapping round from the end of
+    /// `region` to its start for loops
+    pub fn open(
+        &mut self,
+        path: &Path,
+        info: ClipInfo,
+        region: Range<usize>,
+        start: usize,
+    ) -> AudioStream {
+        let end = region.end.min(info.frames);
+        let region = region.start.min(end)..end;
+        let start = start.clamp(region.start, end);
+
+        let (producer, frames) = RingBuffer::new(READ_AHEAD_FRAMES);
+        let control = Arc::new(StreamControl {
+            seek: AtomicU64::new(start as u64),
+            failed: AtomicBool::new(false),
+        });
+
+        let reader = ReadAhead {
+            path: path.to_path_buf(),
+            wanted: region.start,
+            region,
+            frames: producer,
+            control: Arc::clone(&control),
+            decoder: None,
+            samples: Vec::new(),
+            decoded: None,
+            at: None,
+        };
+        self.next = (self.next + 1) % self.threads.len();
+        if self.threads[self.next].send(reader).is_err() {
+            control.failed.store(true, Ordering::Release);
+        }
+
+        AudioStream {
+            frames,
+            control,
+            info,
+            current: None,
+        }
+    }
+}
+
+impl AudioStream {
+    pub fn info(&self) -> ClipInfo {
+        self.info
+    }
+
+    /// Frames `index` and `next` of the file, or `None` if the reader hasn't
+    /// decoded them yet. Frames before `index` are dropped.
+    pub fn frames(
+        &mut self,
+        index: usize,
+        next: usize,
+    ) -> Option<([f32; CHANNELS], [f32; CHANNELS])> {
+        while self
+            .current
+            .is_none_or(|frame| frame.index as usize != index)
+        {
+            self.current = Some(self.frames.pop().ok()?);
+        }
+        let current = self.current?.samples;
+
+        // The next frame is only for interpolating, so a frame that hasn't
+        // arrived yet, or that belongs to an earlier read, is stood in for
+        let following = match self.frames.peek() {
+            Ok(frame) if frame.index as usize == next => frame.samples,
+            _ => current,
+        };
+        Some((current, following))
+    }
+
+    /// Have the reader read from `frame` next. Frames already read from
+    /// elsewhere are dropped as they come.
+    pub fn seek(&mut self, frame: usize) {
+        self.control.seek.store(frame as u64, Ordering::Release);
+    }
+
+    /// Whether the file could not be read and nothing more will come
+    pub fn has_failed(&self) -> bool {
+        self.control.failed.load(Ordering::Acquire) && self.frames.is_empty()
+    }
+}
+
+/// Read a packet at a time for each of the streams handed over, until every
+/// voice is done with its stream and no more can come
+fn read_streams(new: Receiver<ReadAhead>) {
+    let mut readers: Vec<ReadAhead> = Vec::new();
+    loop {
+        if readers.is_empty() {
+            match new.recv() {
+                Ok(reader) => readers.push(reader),
+                Err(_) => return,
+            }
+        }
+
+        let mut reading = false;
+        readers.retain_mut(|reader| match reader.step() {
+            Step::Read => {
+                reading = true;
+                true
+            }
+            Step::Wait => true,
+            Step::Done => false,
+        });
+
+        // With every ring full, wait for room, or for a new stream
+        let wait = if reading { Duration::ZERO } else { WAIT };
+        match new.recv_timeout(wait) {
+            Ok(reader) => readers.push(reader),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => thread::sleep(wait),
+        }
+    }
+}
+
+/// What became of a stream after a turn at reading for it
+enum Step {
+    Read,
+    /// The ring is full, or the file can't be read
+    Wait,
+    /// The voice has let go of the stream
+    Done,
+}
+
+/// The reader's end of a stream
+struct ReadAhead {
+    path: PathBuf,
+    region: Range<usize>,
+    frames: Producer<StreamFrame>,
+    control: Arc<StreamControl>,
+    /// Opened on the reader's first turn, so opening never holds up the voice
+    decoder: Option<StreamDecoder>,
+    /// The last packet decoded, starting at frame `decoded`, some of which
+    /// may not have fit in the ring yet
+    samples: Vec<f32>,
+    decoded: Option<usize>,
+    /// Frame the voice wants next
+    wanted: usize,
+    /// Where the decoder is, if it can carry on from there
+    at: Option<usize>,
+}
+
+impl ReadAhead {
+    fn step(&mut self) -> Step {
+        if self.frames.is_abandoned() {
+            return Step::Done;
+        }
+        // Hold on to the ring until the voice is done with it
+        if self.control.failed.load(Ordering::Acquire) {
+            return Step::Wait;
+        }
+        match self.read() {
+            Ok(true) => Step::Read,
+            Ok(false) => Step::Wait,
+            Err(e) => {
+                eprintln!("Warning: Could not stream {} ({}).", self.path.display(), e);
+                self.control.failed.store(true, Ordering::Release);
+                Step::Wait
+            }
+        }
+    }
+
+    /// Decode a packet, unless the last one is still waiting for room, and add
+    /// it to the ring, returning false if the ring filled up first
+    fn read(&mut self) -> Result<bool, super::AudioError> {
+        let seek = self.control.seek.swap(NO_SEEK, Ordering::Acquire);
+        if seek != NO_SEEK {
+            self.wanted = seek as usize;
+            self.decoded = None;
+            self.at = None;
+        }
+        if self.wanted >= self.region.end {
+            self.wanted = self.region.start;
+            self.decoded = None;
+            self.at = None;
+        }
+
+        if self.decoded.is_none() {
+            let decoder = match &mut self.decoder {
+                Some(decoder) => decoder,
+                None => self.decoder.insert(StreamDecoder::open(&self.path)?),
+            };
+            if self.at.is_none_or(|at| at > self.wanted) {
+                decoder.seek(self.wanted)?;
+            }
+            self.samples.clear();
+            // The file ran out before the length it gave, which is made up
+            // with silence to the end of the region
+            let first = decoder.decode_next(&mut self.samples)?;
+            self.decoded = Some(first.unwrap_or(self.region.end));
+        }
+        Ok(self.push_decoded())
+    }
+
+    /// Add what is left of the last packet to the ring, returning false if it
+    /// didn't all fit
+    fn push_decoded(&mut self) -> bool {
+        let Some(first) = self.decoded else {
+            return true;
+        };
+        let end = self.region.end;
+
+        // A seek that lands past the frame asked for leaves a gap, also filled with
+        // silence, so the voice never waits for a frame that won't come
+        while self.wanted < first.min(end) {
+            if !push(&mut self.frames, self.wanted, &[0.0; CHANNELS]) {
+                return false;
+            }
+            self.wanted += 1;
+        }
+        for (index, frame) in (first..).zip(self.samples.chunks_exact(CHANNELS)) {
+            if index < self.wanted {
+                continue;
+            }
+            if index >= end {
+                break;
+            }
+            if !push(&mut self.frames, index, frame) {
+                return false;
+            }
+            self.wanted = index + 1;
+        }
+
+        self.decoded = None;
+        self.at = Some(first + self.samples.len() / CHANNELS);
+        true
+    }
+}
+
+/// Add a frame if there is room for it
+fn push(frames: &mut Producer<StreamFrame>, index: usize, frame: &[f32]) -> bool {
+    let mut samples = [0.0; CHANNELS];
+    samples.copy_from_slice(frame);
+    frames
+        .push(StreamFrame {
+            index: index as u32,
+            samples,
+        })
+        .is_ok()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::time::Instant;
+
+    use super::*;
+
+    /// Sample of both channels of frame `index` in [`write_wav`]'s files
+    fn sample_of(index: usize) -> f32 {
+        (index % 1024) as f32 / 2048.0
+    }
+
+    /// Write a stereo 16 bit WAV file of `frames` frames at the mixer's rate,
+    /// each frame telling where it is
+    pub(crate) fn write_wav(name: &str, frames: usize) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("boomcrab-{}-{}.wav", std::process::id(), name));
+        let data_len = (frames * CHANNELS * 2) as u32;
+        let mut wav = Vec::new();
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes());
+        wav.extend_from_slice(&(CHANNELS as u16).to_le_bytes());
+        wav.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+        wav.extend_from_slice(&(SAMPLE_RATE * CHANNELS as u32 * 2).to_le_bytes());
+        wav.extend_from_slice(&(CHANNELS as u16 * 2).to_le_bytes());
+        wav.extend_from_slice(&16u16.to_le_bytes());
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&data_len.to_le_bytes());
+        for index in 0..frames {
+            let sample = ((index % 1024) as i16 * 16).to_le_bytes();
+            for _ in 0..CHANNELS {
+                wav.extend_from_slice(&sample);
+            }
+        }
+        std::fs::write(&path, wav).unwrap();
+        path
+    }
+
+    /// Wait for the reader to get to frame `index`
+    fn frame(stream: &mut AudioStream, index: usize) -> [f32; CHANNELS] {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            if let Some((frame, _)) = stream.frames(index, index + 1) {
+                return frame;
+            }
+            assert!(Instant::now() < deadline, "frame {} never came", index);
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn readers_share_streams_and_wrap_round_the_region() {
+        let frames = 10_000;
+        let path = write_wav("wrap", frames);
+        let info = ClipInfo {
+            frames,
+            sample_rate: SAMPLE_RATE,
+        };
+
+        let mut readers = StreamReaders::start();
+        let mut streams: Vec<AudioStream> = (0..READER_THREADS * 3)
+            .map(|_| readers.open(&path, info, 1000..3000, 2500))
+            .collect();
+        for stream in &mut streams {
+            for index in (2500..3000).chain(1000..1500) {
+                assert_eq!(frame(stream, index), [sample_of(index); CHANNELS]);
+            }
+        }
+
+        // Frames already read from before the seek are passed over
+        streams[0].seek(2200);
+        assert_eq!(frame(&mut streams[0], 2200), [sample_of(2200); CHANNELS]);
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn files_that_run_short_are_made_up_with_silence() {
+        let path = write_wav("short", 1000);
+        // Longer than the file really is
+        let info = ClipInfo {
+            frames: 3000,
+            sample_rate: SAMPLE_RATE,
+        };
+
+        let mut stream = StreamReaders::start().open(&path, info, 0..3000, 900);
+        assert_eq!(frame(&mut stream, 999), [sample_of(999); CHANNELS]);
+        assert_eq!(frame(&mut stream, 2999), [0.0; CHANNELS]);
+        assert_eq!(frame(&mut stream, 0), [0.0; CHANNELS]);
+        assert!(!stream.has_failed());
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn unreadable_files_fail() {
+        let info = ClipInfo {
+            frames: 1000,
+            sample_rate: SAMPLE_RATE,
+        };
+        let mut stream =
+            StreamReaders::start().open(Path::new("/nonexistent.wav"), info, 0..1000, 0);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !stream.has_failed() {
+            assert!(Instant::now() < deadline);
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert!(stream.frames(0, 1).is_none());
+    }
+}
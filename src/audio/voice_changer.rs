@@ -0,0 +1,315 @@
+//! Live voice changer for the passthrough mic.
+//!
+//! Pitch is shifted by reading a delay line through two crossfaded taps that
+//! drift faster or slower than it is written. To keep the formants, linear
+//! prediction first splits the voice into its spectral envelope and a residual;
+//! only the residual is shifted before the envelope is put back. The robot voice
+//! replaces the residual with a sawtooth on a single pitch.
+//!
+//! The chain is built on the control side and then only processes in the audio
+//! callback, so nothing here allocates after [`build`].
+
+use std::f32::consts::TAU;
+
+use super::effects::{self, Effect, EffectChain, PitchShifter};
+use super::mixer::{CHANNELS, SAMPLE_RATE};
+use crate::settings::effects::{
+    DistortionSettings, EffectSettings, HighPassSettings, LowPassSettings,
+};
+use crate::settings::voice_changer::VoicePreset;
+
+/// Prediction order, enough for the formants of a voice at 48 kHz
+const LPC_ORDER: usize = 24;
+/// Samples the envelope is estimated from, about 21 ms
+const LPC_WINDOW: usize = 1024;
+/// Samples between envelope updates
+const LPC_HOP: usize = 256;
+/// Longest delay of the pitch shifter's taps. The voice is delayed by half of
+/// this on average, so it is kept short enough for voice chat.
+const GRAIN_FRAMES: usize = 1024;
+/// Smallest bandwidth of the estimated formants
+const FORMANT_BANDWIDTH_HZ: f32 = 100.0;
+/// How quickly the level match lets go after a peak
+const LEVEL_RELEASE_MS: f32 = 50.0;
+/// How quickly the robot follows the loudness of the voice
+const ROBOT_LEVEL_SMOOTHING: f32 = 0.999;
+
+/// Build the mic chain for a preset
+pub fn build(preset: &VoicePreset) -> EffectChain {
+    let mut chain: Vec<Box<dyn Effect>> = Vec::new();
+
+    let shifting = preset.pitch_semitones.abs() > 0.01;
+    if shifting || preset.robot_hz.is_some() {
+        chain.push(Box::new(VoiceChanger::new(preset)));
+    }
+    if let Some(frequency) = preset.ring_mod_hz {
+        chain.push(Box::new(RingModulator::new(frequency)));
+    }
+    if preset.radio {
+        for settings in [
+            EffectSettings::HighPass(HighPassSettings {
+                cutoff_hz: 400.0,
+                ..HighPassSettings::default()
+            }),
+            EffectSettings::LowPass(LowPassSettings {
+                cutoff_hz: 3000.0,
+                ..LowPassSettings::default()
+            }),
+            EffectSettings::Distortion(DistortionSettings {
+                drive_db: 6.0,
+                mix: 0.5,
+            }),
+        ] {
+            chain.push(effects::build(&settings));
+        }
+    }
+
+    chain.into()
+}
+
+/// Pitch shift and robot voice, on the mic mixed down to mono
+struct VoiceChanger {
+    /// Unset when the whole voice is shifted, formants and all
+    lpc: Option<Lpc>,
+    /// The envelope lags behind onsets, so the result is kept as loud as the input
+    level_match: LevelMatch,
+    shifter: Option<PitchShifter>,
+    robot: Option<Robot>,
+}
+
+impl VoiceChanger {
+    fn new(preset: &VoicePreset) -> Self {
+        let ratio = 2f32.powf(preset.pitch_semitones / 12.0);
+        let robot = preset.robot_hz.map(Robot::new);
+        Self {
+            lpc: (preset.preserve_formants || robot.is_some()).then(Lpc::new),
+            level_match: LevelMatch::new(),
+            shifter: robot
+                .is_none()
+                .then(|| PitchShifter::new(ratio, GRAIN_FRAMES)),
+            robot,
+        }
+    }
+}
+
+impl Effect for VoiceChanger {
+    fn process(&mut self, frames: &mut [f32]) {
+        for frame in frames.chunks_exact_mut(CHANNELS) {
+            let input = frame.iter().sum::<f32>() / CHANNELS as f32;
+
+            let output = match &mut self.lpc {
+                Some(lpc) => {
+                    let residual = lpc.whiten(input);
+                    let excitation = match (&mut self.robot, &mut self.shifter) {
+                        (Some(robot), _) => robot.next(residual),
+                        (None, Some(shifter)) => shifter.process(residual),
+                        (None, None) => residual,
+                    };
+                    self.level_match.apply(input, lpc.color(excitation))
+                }
+                None => match &mut self.shifter {
+                    Some(shifter) => shifter.process(input),
+                    None => input,
+                },
+            };
+
+            frame.fill(output);
+        }
+    }
+
+    fn latency_frames(&self) -> usize {
+        self.shifter.as_ref().map_or(0, |_| GRAIN_FRAMES / 2)
+    }
+}
+
+/// Linear prediction: estimates the spectral envelope of the recent input and
+/// filters with it, using lattice filters so the envelope can change between
+/// samples without the filters blowing up
+struct Lpc {
+    history: Vec<f32>,
+    position: usize,
+    window: Vec<f32>,
+    windowed: Vec<f64>,
+    since_update: usize,
+    reflection: [f32; LPC_ORDER],
+    whiten_state: [f32; LPC_ORDER],
+    color_state: [f32; LPC_ORDER],
+}
+
+impl Lpc {
+    fn new() -> Self {
+        Self {
+            history: vec![0.0; LPC_WINDOW],
+            position: 0,
+            window: (0..LPC_WINDOW)
+                .map(|i| 0.5 - 0.5 * (TAU * i as f32 / LPC_WINDOW as f32).cos())
+                .collect(),
+            windowed: vec![0.0; LPC_WINDOW],
+            since_update: 0,
+            reflection: [0.0; LPC_ORDER],
+            whiten_state: [0.0; LPC_ORDER],
+            color_state: [0.0; LPC_ORDER],
+        }
+    }
+
+    /// Take the envelope out of the next input sample, leaving the residual
+    fn whiten(&mut self, input: f32) -> f32 {
+        self.history[self.position] = input;
+        self.position = (self.position + 1) % LPC_WINDOW;
+        self.since_update += 1;
+        if self.since_update == LPC_HOP {
+            self.since_update = 0;
+            self.update();
+        }
+
+        let mut forward = input;
+        let mut backward = input;
+        for (k, state) in self.reflection.iter().zip(&mut self.whiten_state) {
+            let previous = *state;
+            *state = backward;
+            backward = previous + k * forward;
+            forward += k * previous;
+        }
+        forward
+    }
+
+    /// Put the envelope back onto a residual
+    fn color(&mut self, residual: f32) -> f32 {
+        let mut forward = residual;
+        for i in (0..LPC_ORDER).rev() {
+            forward -= self.reflection[i] * self.color_state[i];
+            if i + 1 < LPC_ORDER {
+                self.color_state[i + 1] = self.color_state[i] + self.reflection[i] * forward;
+            }
+        }
+        self.color_state[0] = forward;
+        forward
+    }
+
+    /// Estimate the envelope of the last [`LPC_WINDOW`] samples with the
+    /// autocorrelation method and Levinson-Durbin recursion
+    fn update(&mut self) {
+        for (i, sample) in self.windowed.iter_mut().enumerate() {
+            let index = (self.position + i) % LPC_WINDOW;
+            *sample = (self.history[index] * self.window[i]) as f64;
+        }
+
+        let mut correlation = [0.0f64; LPC_ORDER + 1];
+        for (lag, value) in correlation.iter_mut().enumerate() {
+            *value = self.windowed[lag..]
+                .iter()
+                .zip(&self.windowed)
+                .map(|(a, b)| a * b)
+                .sum();
+        }
+        // Widening the formants a little and adding a touch of white noise keeps
+        // the envelope from ringing on very tonal input
+        for (lag, value) in correlation.iter_mut().enumerate() {
+            let spread = TAU * FORMANT_BANDWIDTH_HZ * lag as f32 / SAMPLE_RATE as f32;
+            *value *= (-0.5 * spread * spread).exp() as f64;
+        }
+        correlation[0] *= 1.0 + 1e-3;
+
+        let mut error = correlation[0];
+        if error < 1e-10 {
+            self.reflection = [0.0; LPC_ORDER];
+            return;
+        }
+
+        let mut coefficients = [0.0f64; LPC_ORDER + 1];
+        coefficients[0] = 1.0;
+        for i in 1..=LPC_ORDER {
+            let acc: f64 = (0..i).map(|j| coefficients[j] * correlation[i - j]).sum();
+            let k = (-acc / error).clamp(-0.999, 0.999);
+
+            let previous = coefficients;
+            for j in 1..i {
+                coefficients[j] = previous[j] + k * previous[i - j];
+            }
+            coefficients[i] = k;
+            error *= 1.0 - k * k;
+            self.reflection[i - 1] = k as f32;
+        }
+    }
+}
+
+/// Scales a signal so its peaks follow those of another
+struct LevelMatch {
+    release: f32,
+    reference: f32,
+    level: f32,
+}
+
+impl LevelMatch {
+    fn new() -> Self {
+        Self {
+            release: (-1000.0 / (LEVEL_RELEASE_MS * SAMPLE_RATE as f32)).exp(),
+            reference: 0.0,
+            level: 0.0,
+        }
+    }
+
+    /// `output` scaled to the level of `reference`
+    fn apply(&mut self, reference: f32, output: f32) -> f32 {
+        self.reference = reference.abs().max(self.reference * self.release);
+        self.level = output.abs().max(self.level * self.release);
+        if self.level <= f32::EPSILON {
+            return output;
+        }
+        output * (self.reference / self.level).min(1.0)
+    }
+}
+
+/// A sawtooth on one pitch, as loud as the residual it replaces
+struct Robot {
+    phase: f32,
+    step: f32,
+    /// Smoothed power of the residual
+    power: f32,
+}
+
+impl Robot {
+    fn new(frequency: f32) -> Self {
+        Self {
+            phase: 0.0,
+            step: frequency.clamp(20.0, 2000.0) / SAMPLE_RATE as f32,
+            power: 0.0,
+        }
+    }
+
+    fn next(&mut self, residual: f32) -> f32 {
+        self.power = self.power * ROBOT_LEVEL_SMOOTHING
+            + residual * residual * (1.0 - ROBOT_LEVEL_SMOOTHING);
+
+        self.phase = (self.phase + self.step).fract();
+        // A sawtooth from -1 to 1 has a power of a third
+        (2.0 * self.phase - 1.0) * (3.0 * self.power).sqrt()
+    }
+}
+
+/// Multiplies the signal by a sine, for a metallic, alien sound
+struct RingModulator {
+    phase: f32,
+    step: f32,
+}
+
+impl RingModulator {
+    fn new(frequency: f32) -> Self {
+        Self {
+            phase: 0.0,
+            step: frequency.max(0.0) / SAMPLE_RATE as f32,
+        }
+    }
+}
+
+impl Effect for RingModulator {
+    fn process(&mut self, frames: &mut [f32]) {
+        for frame in frames.chunks_exact_mut(CHANNELS) {
+            let gain = (TAU * self.phase).sin();
+            for sample in frame {
+                *sample *= gain;
+            }
+            self.phase = (self.phase + self.step).fract();
+        }
+    }
+}
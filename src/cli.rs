@@ -0,0 +1,199 @@
+use std::path::Path;
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand};
+
+use crate::audio::library::SoundLibrary;
+use crate::audio::mixer::MixerEvent;
+use crate::audio::{self, AudioDevice, AudioError, BoomCrabAudioInterface, Sound};
+use crate::daemon::{self, DaemonClient, DaemonError};
+use crate::settings::BoomCrabSettings;
+use crate::settings::sounds::TriggerMode;
+
+/// Terminal based Linux soundboard. Starts the TUI when no command is given.
+#[derive(Parser)]
+#[command(name = "boomcrab", version)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Run the audio engine in the foreground without the TUI
+    Daemon,
+    /// Play a sound by id
+    Play { id: String },
+    /// Stop a playing sound, or every sound with --all
+    Stop {
+        #[arg(required_unless_present = "all")]
+        id: Option<String>,
+        #[arg(long, conflicts_with = "id")]
+        all: bool,
+        /// Fade everything out instead of cutting it off
+        #[arg(long, requires = "all")]
+        fade: bool,
+    },
+    /// List the sounds in the sound files directory
+    List {
+        #[arg(long)]
+        json: bool,
+    },
+    /// List audio output and input devices
+    Devices {
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+/// Exit codes reported for errors, one per `AudioError` variant
+pub mod exit_code {
+    pub const FAILURE: u8 = 1;
+    pub const DEVICE_NOT_FOUND: u8 = 3;
+    pub const INITIALIZATION_FAILED: u8 = 4;
+    pub const SOUND_NOT_FOUND: u8 = 5;
+    pub const PLAYBACK_ERROR: u8 = 6;
+    pub const NOT_SUPPORTED: u8 = 7;
+}
+
+/// Run a command, talking to the daemon when one is running and to the
+/// audio engine directly otherwise
+pub fn run(command: Command, settings: &BoomCrabSettings) -> ExitCode {
+    let result = match command {
+        Command::Daemon => daemon::run(settings),
+        Command::Play { id } => play(settings, &id),
+        Command::Stop { id, fade, .. } => stop(id.as_deref(), fade),
+        Command::List { json } => list(settings, json),
+        Command::Devices { json } => devices(json),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::from(error_exit_code(&e))
+        }
+    }
+}
+
+fn error_exit_code(error: &DaemonError) -> u8 {
+    match error {
+        DaemonError::Audio(AudioError::DeviceNotFound(_)) => exit_code::DEVICE_NOT_FOUND,
+        DaemonError::Audio(AudioError::InitializationFailed(_)) => exit_code::INITIALIZATION_FAILED,
+        DaemonError::Audio(AudioError::SoundNotFound(_)) => exit_code::SOUND_NOT_FOUND,
+        DaemonError::Audio(AudioError::PlaybackError(_)) => exit_code::PLAYBACK_ERROR,
+        DaemonError::Audio(AudioError::NotSupported(_)) => exit_code::NOT_SUPPORTED,
+        _ => exit_code::FAILURE,
+    }
+}
+
+/// Connect to the daemon, or return `None` if no daemon is running
+fn connect() -> Result<Option<DaemonClient>, DaemonError> {
+    match DaemonClient::connect(&daemon::socket_path()) {
+        Ok(client) => Ok(Some(client)),
+        Err(DaemonError::Io(_)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn play(settings: &BoomCrabSettings, sound_id: &str) -> Result<(), DaemonError> {
+    if let Some(mut client) = connect()? {
+        return client.play(sound_id);
+    }
+
+    let mut audio = BoomCrabAudioInterface::new(settings)?;
+    let events = audio.take_mixer_events();
+
+    // A sound that loops until stopped would keep this process waiting for ever,
+    // so it is stopped once it comes round the first time
+    let metadata = audio.sound_metadata(sound_id);
+    let endless = metadata.mode == TriggerMode::Loop && metadata.loop_count.is_none();
+    if endless {
+        eprintln!(
+            "'{}' loops until stopped, so it plays once; run `boomcrab daemon` to keep it looping",
+            sound_id
+        );
+    }
+    audio.play_sound(sound_id)?;
+
+    // Without a daemon the sound only plays as long as this process lives
+    let mut stopping = false;
+    for event in events.into_iter().flatten() {
+        match event {
+            MixerEvent::VoiceStopped { .. } => break,
+            MixerEvent::VoiceProgress {
+                voice, progress, ..
+            } if endless && !stopping && progress.play > 1 => {
+                audio.stop_voice(voice)?;
+                stopping = true;
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn stop(sound_id: Option<&str>, fade: bool) -> Result<(), DaemonError> {
+    // Nothing can be playing without a daemon
+    let Some(mut client) = connect()? else {
+        return Ok(());
+    };
+
+    match sound_id {
+        Some(sound_id) => client.stop(sound_id),
+        None if fade => client.fade_out_all(),
+        None => client.stop_all(),
+    }
+}
+
+fn list(settings: &BoomCrabSettings, json: bool) -> Result<(), DaemonError> {
+    let sounds = match connect()? {
+        Some(mut client) => client.list_sounds()?,
+        None => SoundLibrary::new(
+            Path::new(&settings.sound_files_directory),
+            &settings.library,
+        )
+        .sounds()
+        .to_vec(),
+    };
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&sounds)?);
+    } else {
+        print_sounds(&sounds);
+    }
+    Ok(())
+}
+
+fn devices(json: bool) -> Result<(), DaemonError> {
+    let (outputs, inputs) = match connect()? {
+        Some(mut client) => client.list_devices()?,
+        None => {
+            let backend = audio::default_backend()?;
+            (backend.list_audio_outputs()?, backend.list_audio_inputs()?)
+        }
+    };
+
+    if json {
+        let devices = serde_json::json!({ "outputs": outputs, "inputs": inputs });
+        println!("{}", serde_json::to_string_pretty(&devices)?);
+    } else {
+        print_devices("Outputs", &outputs);
+        print_devices("Inputs", &inputs);
+    }
+    Ok(())
+}
+
+fn print_sounds(sounds: &[Sound]) {
+    let width = sounds.iter().map(|s| s.id.len()).max().unwrap_or(0);
+    for sound in sounds {
+        println!("{:width$}  {}", sound.id, sound.name, width = width);
+    }
+}
+
+fn print_devices(heading: &str, devices: &[AudioDevice]) {
+    println!("{}:", heading);
+    for device in devices {
+        println!("  {:>6}  {}", device.id, device.name);
+    }
+}
@@ -0,0 +1,273 @@
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use super::DaemonError;
+use super::protocol::{Event, PROTOCOL_VERSION, Request, Response, ServerMessage, Trim};
+use crate::audio::overview::Overview;
+use crate::audio::spectrum::SpectrumSource;
+use crate::audio::{AudioDevice, Sound};
+use crate::settings::effects::EffectSettings;
+use crate::settings::midi::MidiBinding;
+
+/// Connection to a running daemon
+pub struct DaemonClient {
+    writer: UnixStream,
+    responses: Receiver<Response>,
+    events: Receiver<Event>,
+}
+
+impl DaemonClient {
+    /// Connect to the daemon listening on `path` and check it speaks our protocol version
+    pub fn connect(path: &Path) -> Result<Self, DaemonError> {
+        let writer = UnixStream::connect(path)?;
+        let reader = BufReader::new(writer.try_clone()?);
+
+        let (response_sender, responses) = mpsc::channel();
+        let (event_sender, events) = mpsc::channel();
+
+        // Split incoming lines into responses and events, so events can be
+        // polled without getting in the way of request/response pairs
+        thread::spawn(move || {
+            for line in reader.lines() {
+                let Ok(line) = line else { break };
+                let sent = match serde_json::from_str(&line) {
+                    Ok(ServerMessage::Response(response)) => response_sender.send(response).is_ok(),
+                    Ok(ServerMessage::Event(event)) => event_sender.send(event).is_ok(),
+                    Err(e) => {
+                        eprintln!("Warning: Ignoring invalid daemon message ({}).", e);
+                        true
+                    }
+                };
+                if !sent {
+                    break;
+                }
+            }
+        });
+
+        let mut client = Self {
+            writer,
+            responses,
+            events,
+        };
+
+        match client.request(&Request::Hello {
+            version: PROTOCOL_VERSION,
+        })? {
+            Response::Hello { version } if version == PROTOCOL_VERSION => Ok(client),
+            Response::Hello { version } => Err(DaemonError::VersionMismatch {
+                daemon: version,
+                client: PROTOCOL_VERSION,
+            }),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    fn request(&mut self, request: &Request) -> Result<Response, DaemonError> {
+        let mut line = serde_json::to_string(request)?;
+        line.push('\n');
+        self.writer.write_all(line.as_bytes())?;
+
+        match self.responses.recv() {
+            Ok(Response::Error { error }) => Err(DaemonError::Audio(error)),
+            Ok(response) => Ok(response),
+            Err(_) => Err(DaemonError::Protocol(
+                "The daemon closed the connection".to_string(),
+            )),
+        }
+    }
+
+    /// Send a request that is answered with a plain acknowledgement
+    pub fn send(&mut self, request: &Request) -> Result<(), DaemonError> {
+        match self.request(request)? {
+            Response::Ok => Ok(()),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    pub fn list_sounds(&mut self) -> Result<Vec<Sound>, DaemonError> {
+        match self.request(&Request::ListSounds)? {
+            Response::Sounds { sounds } => Ok(sounds),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    pub fn rescan_sounds(&mut self) -> Result<Vec<Sound>, DaemonError> {
+        match self.request(&Request::RescanSounds)? {
+            Response::Sounds { sounds } => Ok(sounds),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Returns the output and input devices, in that order
+    pub fn list_devices(&mut self) -> Result<(Vec<AudioDevice>, Vec<AudioDevice>), DaemonError> {
+        match self.request(&Request::ListDevices)? {
+            Response::Devices { outputs, inputs } => Ok((outputs, inputs)),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    pub fn play(&mut self, sound_id: &str) -> Result<(), DaemonError> {
+        self.send(&Request::Play {
+            id: sound_id.to_string(),
+        })
+    }
+
+    pub fn stop(&mut self, sound_id: &str) -> Result<(), DaemonError> {
+        self.send(&Request::Stop {
+            id: sound_id.to_string(),
+        })
+    }
+
+    pub fn stop_all(&mut self) -> Result<(), DaemonError> {
+        self.send(&Request::StopAll)
+    }
+
+    /// Ask the daemon to save what it hasn't and stop. It exits without
+    /// answering, so the connection closing is the sign it has.
+    pub fn shutdown(&mut self) -> Result<(), DaemonError> {
+        match self.request(&Request::Shutdown) {
+            Err(DaemonError::Protocol(_)) => Ok(()),
+            Ok(other) => Err(unexpected(other)),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn fade_out_all(&mut self) -> Result<(), DaemonError> {
+        self.send(&Request::FadeOutAll)
+    }
+
+    /// Effects of one sound, or the master effects when `sound_id` is `None`
+    pub fn effects(&mut self, sound_id: Option<&str>) -> Result<Vec<EffectSettings>, DaemonError> {
+        match self.request(&Request::GetEffects {
+            id: sound_id.map(str::to_string),
+        })? {
+            Response::Effects { effects } => Ok(effects),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    pub fn trim(&mut self, sound_id: &str) -> Result<Trim, DaemonError> {
+        match self.request(&Request::GetTrim {
+            id: sound_id.to_string(),
+        })? {
+            Response::Trim { trim } => Ok(trim),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// The shape and length of a sound, for drawing its waveform, or `None`
+    /// until a [`Event::WaveformReady`] for it if it hasn't been worked out yet
+    pub fn waveform(&mut self, sound_id: &str) -> Result<Option<Overview>, DaemonError> {
+        match self.request(&Request::GetWaveform {
+            id: sound_id.to_string(),
+        })? {
+            Response::Waveform { overview } => Ok(Some(overview)),
+            Response::WaveformPending => Ok(None),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Level in dB of `bands` bands of the output or the mic, from 20 Hz to 20 kHz
+    pub fn spectrum(
+        &mut self,
+        source: SpectrumSource,
+        bands: usize,
+    ) -> Result<Vec<f32>, DaemonError> {
+        match self.request(&Request::GetSpectrum { source, bands })? {
+            Response::Spectrum { levels } => Ok(levels),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Voice changer presets, and the one in use
+    pub fn voice_presets(&mut self) -> Result<(Vec<String>, Option<String>), DaemonError> {
+        match self.request(&Request::ListVoicePresets)? {
+            Response::VoicePresets { presets, current } => Ok((presets, current)),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    pub fn list_midi_bindings(&mut self) -> Result<Vec<MidiBinding>, DaemonError> {
+        match self.request(&Request::ListMidiBindings)? {
+            Response::MidiBindings { bindings } => Ok(bindings),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Bind the next MIDI note or controller the daemon receives to a sound
+    pub fn midi_learn(&mut self, sound_id: &str) -> Result<(), DaemonError> {
+        self.send(&Request::MidiLearn {
+            id: sound_id.to_string(),
+        })
+    }
+
+    /// Ask the daemon to send events on this connection
+    pub fn subscribe(&mut self) -> Result<(), DaemonError> {
+        self.send(&Request::Subscribe)
+    }
+
+    /// Next event received from the daemon, if any, without blocking
+    pub fn try_event(&self) -> Option<Event> {
+        self.events.try_recv().ok()
+    }
+}
+
+fn unexpected(response: Response) -> DaemonError {
+    DaemonError::Protocol(format!("Unexpected response: {:?}", response))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::net::UnixListener;
+
+    use super::*;
+
+    /// A daemon that greets one client, then reads requests, stopping at a
+    /// shutdown. Returns the requests' types.
+    fn fake_daemon(name: &str) -> (std::path::PathBuf, thread::JoinHandle<Vec<String>>) {
+        let path =
+            std::env::temp_dir().join(format!("boomcrab-{}-{}.sock", std::process::id(), name));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+
+        let daemon = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut writer = stream.try_clone().unwrap();
+            let mut types = Vec::new();
+            for line in BufReader::new(stream).lines() {
+                let request: serde_json::Value = serde_json::from_str(&line.unwrap()).unwrap();
+                let kind = request["type"].as_str().unwrap().to_string();
+                let response = match kind.as_str() {
+                    "hello" => Response::Hello {
+                        version: PROTOCOL_VERSION,
+                    },
+                    "shutdown" => {
+                        types.push(kind);
+                        break;
+                    }
+                    _ => Response::Ok,
+                };
+                types.push(kind);
+                let mut line = serde_json::to_string(&ServerMessage::Response(response)).unwrap();
+                line.push('\n');
+                writer.write_all(line.as_bytes()).unwrap();
+            }
+            types
+        });
+        (path, daemon)
+    }
+
+    #[test]
+    fn shutting_down_waits_for_the_daemon_to_go() {
+        let (path, daemon) = fake_daemon("shutdown");
+        let mut client = DaemonClient::connect(&path).unwrap();
+        client.send(&Request::StopAll).unwrap();
+        client.shutdown().unwrap();
+
+        assert_eq!(daemon.join().unwrap(), ["hello", "stop_all", "shutdown"]);
+        std::fs::remove_file(&path).ok();
+    }
+}
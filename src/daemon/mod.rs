@@ -0,0 +1,123 @@
+pub mod client;
+pub mod protocol;
+mod server;
+
+use std::fs::{self, File};
+use std::os::unix::net::UnixStream;
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+use std::{env, fmt, io, path::PathBuf, thread};
+
+use crate::audio::AudioError;
+
+pub use client::DaemonClient;
+pub use server::run;
+
+/// File name of the control socket inside `$XDG_RUNTIME_DIR`
+const SOCKET_NAME: &str = "boomcrab.sock";
+/// How long a daemon started in the background gets to open its socket
+const SPAWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug)]
+pub enum DaemonError {
+    Io(io::Error),
+    AlreadyRunning(PathBuf),
+    Protocol(String),
+    VersionMismatch {
+        daemon: u32,
+        client: u32,
+    },
+    Audio(AudioError),
+    /// A daemon started in the background exited or never opened its socket;
+    /// the reason is in its log
+    SpawnFailed(PathBuf),
+}
+
+impl fmt::Display for DaemonError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DaemonError::Io(e) => write!(f, "Daemon connection error: {}", e),
+            DaemonError::AlreadyRunning(path) => {
+                write!(f, "A daemon is already listening on {}", path.display())
+            }
+            DaemonError::Protocol(e) => write!(f, "Daemon protocol error: {}", e),
+            DaemonError::VersionMismatch { daemon, client } => write!(
+                f,
+                "Daemon speaks protocol version {}, but this client speaks version {}",
+                daemon, client
+            ),
+            DaemonError::Audio(e) => write!(f, "{}", e),
+            DaemonError::SpawnFailed(log) => {
+                write!(f, "The daemon did not start, see {}", log.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for DaemonError {}
+
+impl From<io::Error> for DaemonError {
+    fn from(err: io::Error) -> Self {
+        DaemonError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for DaemonError {
+    fn from(err: serde_json::Error) -> Self {
+        DaemonError::Protocol(err.to_string())
+    }
+}
+
+impl From<AudioError> for DaemonError {
+    fn from(err: AudioError) -> Self {
+        DaemonError::Audio(err)
+    }
+}
+
+/// Path of the control socket, under `$XDG_RUNTIME_DIR` when it is set
+pub fn socket_path() -> PathBuf {
+    dirs::runtime_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join(SOCKET_NAME)
+}
+
+/// Where a daemon started in the background writes its messages
+pub fn log_path() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(env::temp_dir)
+        .join("boomcrab")
+        .join("daemon.log")
+}
+
+/// Start `boomcrab daemon` as a child process, with its output going to
+/// [`log_path`] rather than over the caller's terminal.
+///
+/// Returns once the socket takes connections, so clients can connect right away.
+pub fn spawn() -> Result<Child, DaemonError> {
+    let log_path = log_path();
+    if let Some(dir) = log_path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let log = File::create(&log_path)?;
+    let mut child = Command::new(env::current_exe()?)
+        .arg("daemon")
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log)
+        .spawn()?;
+
+    let path = socket_path();
+    let started = Instant::now();
+    while UnixStream::connect(&path).is_err() {
+        if child.try_wait()?.is_some() {
+            return Err(DaemonError::SpawnFailed(log_path));
+        }
+        if started.elapsed() > SPAWN_TIMEOUT {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(DaemonError::SpawnFailed(log_path));
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    Ok(child)
+}
@@ -0,0 +1,301 @@
+//! JSON protocol spoken over the daemon's Unix socket.
+//!
+//! Every message is a single line of JSON. A client starts by sending
+//! [`Request::Hello`] with its [`PROTOCOL_VERSION`]; the daemon answers with
+//! its own version and refuses to serve clients speaking a different one.
+//! After that, each request gets exactly one [`Response`], in order. Clients
+//! that send [`Request::Subscribe`] also receive [`Event`]s, interleaved with
+//! responses, until they disconnect.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::audio::levels::Level;
+use crate::audio::mixer::VoiceProgress;
+use crate::audio::overview::Overview;
+use crate::audio::spectrum::SpectrumSource;
+use crate::audio::{AudioDevice, AudioError, Sound};
+use crate::settings::effects::EffectSettings;
+use crate::settings::midi::MidiBinding;
+
+/// Bumped whenever a message changes in an incompatible way
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Where a sound starts and ends and its cues, in milliseconds from the start of its file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Trim {
+    pub start_ms: Option<u32>,
+    pub end_ms: Option<u32>,
+    #[serde(default)]
+    pub cues: BTreeMap<String, u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Request {
+    Hello {
+        version: u32,
+    },
+    ListSounds,
+    /// Look for sound files added to or removed from the sound files
+    /// directory, answered with the new list like [`Request::ListSounds`]
+    RescanSounds,
+    Play {
+        id: String,
+    },
+    /// Play a sound from one of its cues, or from `position_ms` into its file.
+    /// Without either it plays like [`Request::Play`].
+    PlayFrom {
+        id: String,
+        #[serde(default)]
+        cue: Option<String>,
+        #[serde(default)]
+        position_ms: Option<u32>,
+    },
+    Stop {
+        id: String,
+    },
+    /// The key or pad that played `id` was released, stopping it if it plays in hold mode
+    Release {
+        id: String,
+    },
+    StopAll,
+    /// Stop every sound with the global fade-out-all fade
+    FadeOutAll,
+    /// Pause every voice of one sound where it is, or every voice when `id` is
+    /// `None`; `paused: false` resumes them
+    Pause {
+        id: Option<String>,
+        paused: bool,
+    },
+    /// Move every voice of a sound to one of its cues, or to `position_ms` into
+    /// its file. Without either it goes back to the sound's start point.
+    Seek {
+        id: String,
+        #[serde(default)]
+        cue: Option<String>,
+        #[serde(default)]
+        position_ms: Option<u32>,
+    },
+    /// Stop one voice, from [`Event::SoundStarted`], leaving other voices of its sound playing
+    StopVoice {
+        voice: u64,
+    },
+    /// Set the volume of one voice, leaving the sound's volume as it is
+    SetVoiceVolume {
+        voice: u64,
+        volume: f32,
+    },
+    PauseVoice {
+        voice: u64,
+        paused: bool,
+    },
+    /// Move a voice to `position_ms` into its sound's file, kept between its trim points
+    SeekVoice {
+        voice: u64,
+        position_ms: u32,
+    },
+    /// Set the volume of one sound, or the master volume when `id` is `None`
+    SetVolume {
+        id: Option<String>,
+        volume: f32,
+    },
+    /// Multiply the speed of the sounds playing now, changing their pitch with it
+    NudgeSpeed {
+        factor: f32,
+    },
+    /// Play every sound, including ones played afterwards, `factor` times faster
+    /// until this is sent again with 1, e.g. while a key is held
+    HoldSpeed {
+        factor: f32,
+    },
+    /// Effects of one sound, or the master effects when `id` is `None`
+    GetEffects {
+        id: Option<String>,
+    },
+    /// Replace the effects of one sound, or the master effects when `id` is `None`
+    SetEffects {
+        id: Option<String>,
+        effects: Vec<EffectSettings>,
+    },
+    GetTrim {
+        id: String,
+    },
+    /// Replace a sound's trim points and cues
+    SetTrim {
+        id: String,
+        trim: Trim,
+    },
+    /// The lowest and highest samples along a sound, for drawing its waveform
+    GetWaveform {
+        id: String,
+    },
+    /// Level in dB of `bands` bands of the output or the mic, spaced evenly in
+    /// pitch from 20 Hz to 20 kHz
+    GetSpectrum {
+        source: SpectrumSource,
+        bands: usize,
+    },
+    ListVoicePresets,
+    /// Switch the voice changer to a preset, or turn it off when `preset` is `None`
+    SetVoicePreset {
+        preset: Option<String>,
+    },
+    /// Switch to the next voice changer preset, turning it off after the last one
+    NextVoicePreset,
+    ListDevices,
+    Subscribe,
+    /// Save anything unsaved and stop the daemon. Only taken over the socket.
+    Shutdown,
+    ListMidiBindings,
+    /// Bind the next note or controller received to the sound `id`
+    MidiLearn {
+        id: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Response {
+    Hello {
+        version: u32,
+    },
+    Ok,
+    Sounds {
+        sounds: Vec<Sound>,
+    },
+    Devices {
+        outputs: Vec<AudioDevice>,
+        inputs: Vec<AudioDevice>,
+    },
+    MidiBindings {
+        bindings: Vec<MidiBinding>,
+    },
+    Effects {
+        effects: Vec<EffectSettings>,
+    },
+    Trim {
+        trim: Trim,
+    },
+    Waveform {
+        overview: Overview,
+    },
+    /// The waveform is still being worked out. A `waveform_ready` event follows once it is.
+    WaveformPending,
+    Spectrum {
+        levels: Vec<f32>,
+    },
+    VoicePresets {
+        presets: Vec<String>,
+        current: Option<String>,
+    },
+    Error {
+        error: AudioError,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    SoundStarted {
+        voice: u64,
+        id: String,
+    },
+    SoundStopped {
+        voice: u64,
+        id: String,
+    },
+    MidiLearned {
+        binding: MidiBinding,
+    },
+    /// The volume of a sound, or the master volume when `id` is `None`, changed
+    VolumeChanged {
+        id: Option<String>,
+        volume: f32,
+    },
+    /// The effects of a sound, or the master effects when `id` is `None`, changed
+    EffectsChanged {
+        id: Option<String>,
+        effects: Vec<EffectSettings>,
+    },
+    TrimChanged {
+        id: String,
+        trim: Trim,
+    },
+    VoicePresetChanged {
+        preset: Option<String>,
+    },
+    /// A sound's waveform has been worked out, or worked out again as its file changed
+    WaveformReady {
+        id: String,
+    },
+    /// Delay from the microphone to the virtual source, sent when it changes
+    MicLatency {
+        latency_ms: f32,
+    },
+    /// How far the mic's gate, compressor and limiter turned it down, in dB,
+    /// sent about 30 times a second while the mic passes through
+    MicGainReduction {
+        gate_db: f32,
+        compressor_db: f32,
+        limiter_db: f32,
+    },
+    /// Where a voice is and how it is playing, sent about 30 times a second while it plays
+    SoundProgress {
+        voice: u64,
+        id: String,
+        progress: VoiceProgress,
+    },
+    /// Peak and RMS of the output, the mic and the virtual source, sent about
+    /// 30 times a second. The mic's are unset without the passthrough.
+    Levels {
+        master: Level,
+        mic: Option<Level>,
+        virtual_mic: Option<Level>,
+    },
+}
+
+/// Anything the daemon writes to a client
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ServerMessage {
+    Response(Response),
+    Event(Event),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_are_tagged_by_type() {
+        let request: Request = serde_json::from_str(r#"{"type": "rescan_sounds"}"#).unwrap();
+        assert!(matches!(request, Request::RescanSounds));
+
+        let request: Request =
+            serde_json::from_str(r#"{"type": "seek", "id": "music-bed"}"#).unwrap();
+        assert!(matches!(
+            request,
+            Request::Seek {
+                cue: None,
+                position_ms: None,
+                ..
+            }
+        ));
+
+        assert!(serde_json::from_str::<Request>(r#"{"type": "explode"}"#).is_err());
+    }
+
+    #[test]
+    fn events_keep_their_fields() {
+        let message = ServerMessage::Event(Event::SoundStopped {
+            voice: 7,
+            id: "airhorn".to_string(),
+        });
+        let json = serde_json::to_value(&message).unwrap();
+        assert_eq!(json["event"]["type"], "sound_stopped");
+        assert_eq!(json["event"]["voice"], 7);
+        assert_eq!(json["event"]["id"], "airhorn");
+    }
+}
@@ -0,0 +1,723 @@
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use super::protocol::{Event, PROTOCOL_VERSION, Request, Response, ServerMessage, Trim};
+use super::{DaemonError, socket_path};
+use crate::audio::mixer::{MixerEvent, MixerEvents, SAMPLE_RATE};
+use crate::audio::spectrum::MAX_BANDS;
+use crate::audio::{AudioError, BoomCrabAudioInterface, StartPoint};
+use crate::hotkeys;
+use crate::midi::feedback::MidiFeedback;
+use crate::midi::{MidiInputPort, MidiMessage};
+use crate::mpris;
+use crate::obs;
+use crate::osc::OscServer;
+use crate::settings::BoomCrabSettings;
+use crate::settings::effects::EffectSettings;
+use crate::settings::midi::{MidiAction, MidiBinding};
+use crate::web::WebServer;
+
+/// Messages queued for a subscriber that isn't reading, a few seconds of
+/// levels and progress with every voice playing, before it is dropped
+const SUBSCRIBER_QUEUE_SIZE: usize = 4096;
+
+/// Run the daemon in the foreground, serving clients until the process is killed
+pub fn run(settings: &BoomCrabSettings) -> Result<(), DaemonError> {
+    let path = socket_path();
+    let listener = bind(&path)?;
+    let daemon = Daemon::new(settings)?;
+
+    println!("BoomCrab daemon listening on {}", path.display());
+    daemon.serve(listener);
+    Ok(())
+}
+
+/// Bind the control socket, replacing a stale socket file left by a daemon that died
+fn bind(path: &Path) -> Result<UnixListener, DaemonError> {
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            return Err(DaemonError::AlreadyRunning(path.to_path_buf()));
+        }
+        std::fs::remove_file(path)?;
+    }
+
+    Ok(UnixListener::bind(path)?)
+}
+
+/// State shared by all client connections
+struct Daemon {
+    audio: Mutex<BoomCrabAudioInterface>,
+    settings: Mutex<BoomCrabSettings>,
+    subscribers: Mutex<Vec<SyncSender<ServerMessage>>>,
+    midi_input: Mutex<Option<MidiInputPort>>,
+    midi_feedback: Mutex<Option<MidiFeedback>>,
+    /// Sound to bind the next MIDI note or controller to
+    midi_learn: Mutex<Option<String>>,
+}
+
+impl Daemon {
+    fn new(settings: &BoomCrabSettings) -> Result<Arc<Self>, DaemonError> {
+        let mut audio = BoomCrabAudioInterface::new(settings)?;
+        let mixer_events = audio.take_mixer_events();
+        let overviews_ready = audio.take_overviews_ready();
+
+        let daemon = Arc::new(Self {
+            audio: Mutex::new(audio),
+            settings: Mutex::new(settings.clone()),
+            subscribers: Mutex::new(Vec::new()),
+            midi_input: Mutex::new(None),
+            midi_feedback: Mutex::new(None),
+            midi_learn: Mutex::new(None),
+        });
+
+        if let Some(mixer_events) = mixer_events {
+            let daemon = Arc::clone(&daemon);
+            thread::spawn(move || daemon.forward_events(mixer_events));
+        }
+        if let Some(overviews_ready) = overviews_ready {
+            let daemon = Arc::clone(&daemon);
+            thread::spawn(move || {
+                for id in overviews_ready {
+                    daemon.broadcast(Event::WaveformReady { id });
+                }
+            });
+        }
+
+        if settings.midi.enabled {
+            daemon.start_midi();
+        }
+        if settings.midi.feedback.enabled {
+            daemon.start_midi_feedback();
+        }
+        if settings.osc.enabled {
+            daemon.start_osc();
+        }
+        if settings.web.enabled {
+            daemon.start_web();
+        }
+        if settings.mpris.enabled {
+            daemon.start_mpris();
+        }
+        if settings.obs.enabled {
+            daemon.start_obs();
+        }
+        if settings.hotkeys.enabled {
+            daemon.start_hotkeys();
+        }
+
+        Ok(daemon)
+    }
+
+    fn start_midi(self: &Arc<Self>) {
+        let midi_settings = self.settings.lock().unwrap().midi.clone();
+
+        // A weak reference, since the daemon owns the input that owns this callback
+        let daemon = Arc::downgrade(self);
+        let input = MidiInputPort::open(&midi_settings, move |message| {
+            if let Some(daemon) = daemon.upgrade() {
+                daemon.handle_midi(message);
+            }
+        });
+
+        match input {
+            Ok(input) => {
+                println!("Listening for MIDI on '{}'", input.name);
+                *self.midi_input.lock().unwrap() = Some(input);
+            }
+            Err(e) => eprintln!("Warning: MIDI input disabled ({}).", e),
+        }
+    }
+
+    fn start_midi_feedback(&self) {
+        let midi_settings = self.settings.lock().unwrap().midi.clone();
+
+        match MidiFeedback::open(&midi_settings.feedback) {
+            Ok(mut feedback) => {
+                println!("Sending MIDI feedback to '{}'", feedback.name);
+                feedback.clear(&midi_settings.bindings);
+                *self.midi_feedback.lock().unwrap() = Some(feedback);
+            }
+            Err(e) => eprintln!("Warning: MIDI feedback disabled ({}).", e),
+        }
+    }
+
+    fn start_osc(self: &Arc<Self>) {
+        let osc_settings = self.settings.lock().unwrap().osc.clone();
+
+        let events = self.subscribe();
+
+        let daemon = Arc::clone(self);
+        let server = OscServer::start(
+            &osc_settings,
+            move |action| {
+                let Some(request) = action.to_request() else {
+                    return;
+                };
+                if let Response::Error { error } = daemon.handle_request(request) {
+                    eprintln!("Warning: OSC action failed ({}).", error);
+                }
+            },
+            events,
+        );
+
+        match server {
+            Ok(server) => println!("Listening for OSC on {}", server.local_addr),
+            Err(e) => eprintln!("Warning: OSC server disabled ({}).", e),
+        }
+    }
+
+    fn start_web(self: &Arc<Self>) {
+        let web_settings = self.settings.lock().unwrap().web.clone();
+
+        let handler = Arc::clone(self);
+        let subscriber = Arc::clone(self);
+        let server = WebServer::start(
+            &web_settings,
+            move |request| handler.handle_request(request),
+            move || subscriber.subscribe(),
+        );
+
+        match server {
+            Ok(server) => println!("Serving the web soundboard on http://{}", server.local_addr),
+            Err(e) => eprintln!("Warning: Web server disabled ({}).", e),
+        }
+    }
+
+    fn start_mpris(self: &Arc<Self>) {
+        let daemon = Arc::clone(self);
+        match mpris::start(
+            move |request| daemon.handle_request(request),
+            self.subscribe(),
+        ) {
+            Ok(()) => println!("Registered as an MPRIS player on the session bus"),
+            Err(e) => eprintln!("Warning: MPRIS disabled ({}).", e),
+        }
+    }
+
+    fn start_obs(self: &Arc<Self>) {
+        let obs_settings = self.settings.lock().unwrap().obs.clone();
+
+        let daemon = Arc::clone(self);
+        obs::start(
+            &obs_settings,
+            move |request| daemon.handle_request(request),
+            self.subscribe(),
+        );
+        println!("Connecting to OBS at {}", obs_settings.url);
+    }
+
+    fn start_hotkeys(self: &Arc<Self>) {
+        let hotkey_settings = self.settings.lock().unwrap().hotkeys.clone();
+
+        let daemon = Arc::clone(self);
+        match hotkeys::start(&hotkey_settings, move |request| {
+            daemon.handle_request(request)
+        }) {
+            Ok(devices) => println!("Reading hotkeys from '{}'", devices.join("', '")),
+            Err(e) => eprintln!("Warning: Hotkeys disabled ({}).", e),
+        }
+    }
+
+    /// Receive every event from now on, for in-process listeners
+    fn subscribe(&self) -> Receiver<ServerMessage> {
+        let (sender, receiver) = mpsc::sync_channel(SUBSCRIBER_QUEUE_SIZE);
+        self.subscribers.lock().unwrap().push(sender);
+        receiver
+    }
+
+    fn serve(self: Arc<Self>, listener: UnixListener) {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let daemon = Arc::clone(&self);
+                    thread::spawn(move || {
+                        if let Err(e) = daemon.handle_client(stream) {
+                            eprintln!("Warning: Client connection failed ({}).", e);
+                        }
+                    });
+                }
+                Err(e) => eprintln!("Warning: Could not accept client ({}).", e),
+            }
+        }
+    }
+
+    /// Publish mixer events to every subscribed client and the MIDI controller's pads
+    fn forward_events(&self, mixer_events: MixerEvents) {
+        for event in mixer_events {
+            if let Some(feedback) = self.midi_feedback.lock().unwrap().as_mut() {
+                let bindings = &self.settings.lock().unwrap().midi.bindings;
+                match &event {
+                    MixerEvent::VoiceStarted { sound_id, .. } => {
+                        feedback.sound_started(sound_id, bindings)
+                    }
+                    MixerEvent::VoiceStopped { sound_id, .. } => {
+                        feedback.sound_stopped(sound_id, bindings)
+                    }
+                    MixerEvent::VoiceProgress { .. }
+                    | MixerEvent::Levels { .. }
+                    | MixerEvent::MicLatency { .. }
+                    | MixerEvent::MicGainReduction(_) => {}
+                }
+            }
+
+            self.broadcast(match event {
+                MixerEvent::VoiceStarted { voice, sound_id } => Event::SoundStarted {
+                    voice,
+                    id: sound_id.to_string(),
+                },
+                MixerEvent::VoiceStopped { voice, sound_id } => Event::SoundStopped {
+                    voice,
+                    id: sound_id.to_string(),
+                },
+                MixerEvent::VoiceProgress {
+                    voice,
+                    sound_id,
+                    progress,
+                } => Event::SoundProgress {
+                    voice,
+                    id: sound_id.to_string(),
+                    progress,
+                },
+                MixerEvent::Levels {
+                    master,
+                    mic,
+                    virtual_mic,
+                } => Event::Levels {
+                    master,
+                    mic,
+                    virtual_mic,
+                },
+                MixerEvent::MicLatency { frames } => Event::MicLatency {
+                    latency_ms: frames as f32 * 1000.0 / SAMPLE_RATE as f32,
+                },
+                MixerEvent::MicGainReduction(reduction) => Event::MicGainReduction {
+                    gate_db: reduction.gate_db,
+                    compressor_db: reduction.compressor_db,
+                    limiter_db: reduction.limiter_db,
+                },
+            });
+        }
+    }
+
+    fn broadcast(&self, event: Event) {
+        // Subscribers that have disconnected, or fallen so far behind that their
+        // queue is full, are dropped from the list rather than queued for without end
+        self.subscribers.lock().unwrap().retain(|subscriber| {
+            match subscriber.try_send(ServerMessage::Event(event.clone())) {
+                Ok(()) => true,
+                Err(TrySendError::Full(_)) => {
+                    eprintln!("Warning: Dropped a subscriber that stopped reading events.");
+                    false
+                }
+                Err(TrySendError::Disconnected(_)) => false,
+            }
+        });
+    }
+
+    fn handle_midi(&self, message: MidiMessage) {
+        if message.trigger().is_none() {
+            self.release_midi_note(message);
+            return;
+        }
+
+        if let Some(sound) = self.midi_learn.lock().unwrap().take() {
+            if let Some(binding) = MidiBinding::learned(&message, sound) {
+                self.learn_midi_binding(binding);
+            }
+            return;
+        }
+
+        let (actions, velocity_volume) = {
+            let settings = self.settings.lock().unwrap();
+            let actions: Vec<MidiAction> = settings
+                .midi
+                .bindings
+                .iter()
+                .filter(|binding| binding.matches(&message))
+                .map(|binding| binding.action.clone())
+                .collect();
+            (actions, settings.midi.velocity_volume)
+        };
+
+        // Buttons sending controller messages send zero on release
+        let pressed = message.value() > 0.0;
+
+        let mut audio = self.audio.lock().unwrap();
+        for action in actions {
+            let result = match action {
+                MidiAction::Play { sound } if pressed => {
+                    let gain = match message {
+                        MidiMessage::NoteOn { .. } if velocity_volume => message.value(),
+                        _ => 1.0,
+                    };
+                    audio.play_sound_scaled(&sound, gain)
+                }
+                MidiAction::Play { sound } => audio.release_sound(&sound),
+                MidiAction::Stop { sound } if pressed => audio.stop_sound(&sound),
+                MidiAction::StopAll if pressed => audio.stop_all(),
+                MidiAction::FadeOutAll if pressed => audio.fade_out_all(),
+                MidiAction::Volume { sound } => audio.set_sound_volume(&sound, message.value()),
+                MidiAction::MasterVolume => audio.set_master_volume(message.value()),
+                _ => Ok(()),
+            };
+
+            if let Err(e) = result {
+                eprintln!("Warning: MIDI action failed ({}).", e);
+            }
+        }
+    }
+
+    /// Release the sounds played by the note that was let go of
+    fn release_midi_note(&self, message: MidiMessage) {
+        let sounds: Vec<String> = self
+            .settings
+            .lock()
+            .unwrap()
+            .midi
+            .bindings
+            .iter()
+            .filter(|binding| binding.released_by(&message))
+            .filter_map(|binding| match &binding.action {
+                MidiAction::Play { sound } => Some(sound.clone()),
+                _ => None,
+            })
+            .collect();
+
+        let mut audio = self.audio.lock().unwrap();
+        for sound in sounds {
+            if let Err(e) = audio.release_sound(&sound) {
+                eprintln!("Warning: MIDI action failed ({}).", e);
+            }
+        }
+    }
+
+    /// Add a learned binding, replacing any binding for the same trigger, and save it
+    fn learn_midi_binding(&self, binding: MidiBinding) {
+        {
+            let mut settings = self.settings.lock().unwrap();
+            settings.midi.bind(binding.clone());
+
+            if let Err(e) = settings.save_to_file() {
+                eprintln!("Warning: Could not save MIDI binding ({}).", e);
+            }
+        }
+
+        self.broadcast(Event::MidiLearned { binding });
+    }
+
+    /// Changes that are saved to the settings file are refused while it
+    /// couldn't be loaded, since saving would replace it with the defaults
+    fn check_settings_saveable(&self) -> Result<(), AudioError> {
+        self.settings
+            .lock()
+            .unwrap()
+            .check_saveable()
+            .map_err(|e| AudioError::NotSupported(e.to_string()))
+    }
+
+    /// Switch the voice changer and remember the preset in the settings file
+    fn set_voice_preset(
+        &self,
+        audio: &mut BoomCrabAudioInterface,
+        preset: Option<String>,
+    ) -> Result<(), AudioError> {
+        self.check_settings_saveable()?;
+        audio.set_voice_preset(preset.as_deref())?;
+
+        {
+            let mut settings = self.settings.lock().unwrap();
+            settings.voice_changer.preset = preset.clone();
+            if let Err(e) = settings.save_to_file() {
+                eprintln!("Warning: Could not save voice preset ({}).", e);
+            }
+        }
+
+        self.broadcast(Event::VoicePresetChanged { preset });
+        Ok(())
+    }
+
+    /// Keep edited effects in the settings file
+    fn save_effects(&self, id: Option<&str>, effects: &[EffectSettings]) {
+        let mut settings = self.settings.lock().unwrap();
+        match id {
+            Some(id) => {
+                settings.sounds.entry(id.to_string()).or_default().effects = effects.to_vec();
+            }
+            None => settings.master_effects = effects.to_vec(),
+        }
+
+        if let Err(e) = settings.save_to_file() {
+            eprintln!("Warning: Could not save effects ({}).", e);
+        }
+    }
+
+    /// Keep edited trim points and cues in the settings file
+    fn save_trim(&self, id: &str, trim: &Trim) {
+        let mut settings = self.settings.lock().unwrap();
+        let metadata = settings.sounds.entry(id.to_string()).or_default();
+        metadata.start_ms = trim.start_ms;
+        metadata.end_ms = trim.end_ms;
+        metadata.cues = trim.cues.clone();
+
+        if let Err(e) = settings.save_to_file() {
+            eprintln!("Warning: Could not save trim points ({}).", e);
+        }
+    }
+
+    fn handle_client(&self, stream: UnixStream) -> Result<(), DaemonError> {
+        // Responses and events share one writer thread so their lines never interleave
+        let (sender, receiver) = mpsc::sync_channel::<ServerMessage>(SUBSCRIBER_QUEUE_SIZE);
+        let mut writer = stream.try_clone()?;
+        thread::spawn(move || {
+            for message in receiver {
+                let Ok(mut line) = serde_json::to_string(&message) else {
+                    continue;
+                };
+                line.push('\n');
+                if writer.write_all(line.as_bytes()).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut greeted = false;
+        for line in BufReader::new(stream).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let response = match serde_json::from_str::<Request>(&line) {
+                Ok(Request::Hello { version }) => {
+                    let response = Response::Hello {
+                        version: PROTOCOL_VERSION,
+                    };
+                    // The client sees our version and reports the mismatch itself
+                    if version != PROTOCOL_VERSION {
+                        sender.send(ServerMessage::Response(response)).ok();
+                        return Ok(());
+                    }
+                    greeted = true;
+                    response
+                }
+                Ok(_) if !greeted => error_response(AudioError::NotSupported(
+                    "Send a hello request first".to_string(),
+                )),
+                Ok(Request::Subscribe) => {
+                    self.subscribers.lock().unwrap().push(sender.clone());
+                    Response::Ok
+                }
+                Ok(Request::Shutdown) => self.shutdown(),
+                Ok(request) => self.handle_request(request),
+                Err(e) => {
+                    error_response(AudioError::NotSupported(format!("Invalid request: {}", e)))
+                }
+            };
+
+            if sender.send(ServerMessage::Response(response)).is_err() {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Exit once no request is under way and nothing is being saved, after
+    /// saving the caches the background threads would have saved later
+    fn shutdown(&self) -> ! {
+        // In the order requests take them, which save settings holding both
+        let audio = self.audio.lock().unwrap_or_else(|e| e.into_inner());
+        let _settings = self.settings.lock().unwrap_or_else(|e| e.into_inner());
+        audio.save_caches();
+
+        let _ = std::fs::remove_file(socket_path());
+        println!("BoomCrab daemon stopped");
+        std::process::exit(0)
+    }
+
+    fn handle_request(&self, request: Request) -> Response {
+        let mut audio = self.audio.lock().unwrap();
+
+        let result = match request {
+            Request::ListSounds => {
+                return Response::Sounds {
+                    sounds: audio.list_sounds(),
+                };
+            }
+            Request::RescanSounds => {
+                return match audio.rescan_sounds() {
+                    Ok(()) => Response::Sounds {
+                        sounds: audio.list_sounds(),
+                    },
+                    Err(e) => error_response(e),
+                };
+            }
+            Request::ListDevices => {
+                let devices = audio
+                    .list_audio_outputs()
+                    .and_then(|outputs| Ok((outputs, audio.list_audio_inputs()?)));
+                return match devices {
+                    Ok((outputs, inputs)) => Response::Devices { outputs, inputs },
+                    Err(e) => error_response(e),
+                };
+            }
+            Request::GetEffects { id } => {
+                let effects = match &id {
+                    Some(id) if !audio.list_sounds().iter().any(|sound| &sound.id == id) => {
+                        return error_response(AudioError::SoundNotFound(id.clone()));
+                    }
+                    Some(id) => audio.sound_effects(id).to_vec(),
+                    None => audio.master_effects().to_vec(),
+                };
+                return Response::Effects { effects };
+            }
+            Request::GetTrim { id } => {
+                if !audio.list_sounds().iter().any(|sound| sound.id == id) {
+                    return error_response(AudioError::SoundNotFound(id));
+                }
+                let metadata = audio.sound_metadata(&id);
+                return Response::Trim {
+                    trim: Trim {
+                        start_ms: metadata.start_ms,
+                        end_ms: metadata.end_ms,
+                        cues: metadata.cues,
+                    },
+                };
+            }
+            Request::GetWaveform { id } => {
+                return match audio.overview(&id) {
+                    Ok(Some(overview)) => Response::Waveform { overview },
+                    Ok(None) => Response::WaveformPending,
+                    Err(e) => error_response(e),
+                };
+            }
+            Request::GetSpectrum { source, bands } => {
+                return Response::Spectrum {
+                    levels: audio.spectrum(source, bands.clamp(1, MAX_BANDS)),
+                };
+            }
+            Request::ListVoicePresets => {
+                return Response::VoicePresets {
+                    presets: audio.voice_presets(),
+                    current: audio.voice_preset().map(str::to_string),
+                };
+            }
+            Request::ListMidiBindings => {
+                return Response::MidiBindings {
+                    bindings: self.settings.lock().unwrap().midi.bindings.clone(),
+                };
+            }
+            Request::MidiLearn { id } => {
+                if self.midi_input.lock().unwrap().is_none() {
+                    Err(AudioError::NotSupported(
+                        "MIDI input is not enabled".to_string(),
+                    ))
+                } else if !audio.list_sounds().iter().any(|sound| sound.id == id) {
+                    Err(AudioError::SoundNotFound(id))
+                } else {
+                    self.check_settings_saveable().map(|()| {
+                        *self.midi_learn.lock().unwrap() = Some(id);
+                    })
+                }
+            }
+            Request::Play { id } => audio.play_sound(&id),
+            Request::PlayFrom {
+                id,
+                cue,
+                position_ms,
+            } => {
+                let start = match (&cue, position_ms) {
+                    (Some(cue), _) => StartPoint::Cue(cue),
+                    (None, Some(ms)) => StartPoint::Position(ms),
+                    (None, None) => StartPoint::Start,
+                };
+                audio.play_sound_from(&id, start)
+            }
+            Request::Stop { id } => audio.stop_sound(&id),
+            Request::Release { id } => audio.release_sound(&id),
+            Request::StopAll => audio.stop_all(),
+            Request::FadeOutAll => audio.fade_out_all(),
+            Request::Pause { id, paused } => match &id {
+                Some(id) => audio.pause_sound(id, paused),
+                None => audio.pause_all(paused),
+            },
+            Request::Seek {
+                id,
+                cue,
+                position_ms,
+            } => {
+                let to = match (&cue, position_ms) {
+                    (Some(cue), _) => StartPoint::Cue(cue),
+                    (None, Some(ms)) => StartPoint::Position(ms),
+                    (None, None) => StartPoint::Start,
+                };
+                audio.seek_sound(&id, to)
+            }
+            Request::StopVoice { voice } => audio.stop_voice(voice),
+            Request::SetVoiceVolume { voice, volume } => audio.set_voice_volume(voice, volume),
+            Request::PauseVoice { voice, paused } => audio.pause_voice(voice, paused),
+            Request::SeekVoice { voice, position_ms } => audio.seek_voice(voice, position_ms),
+            Request::NudgeSpeed { factor } => audio.nudge_speed(factor),
+            Request::HoldSpeed { factor } => audio.hold_speed(factor),
+            Request::SetVolume { id, volume } => {
+                let result = match &id {
+                    Some(id) => audio.set_sound_volume(id, volume),
+                    None => audio.set_master_volume(volume),
+                };
+                if result.is_ok() {
+                    self.broadcast(Event::VolumeChanged {
+                        id,
+                        volume: volume.max(0.0),
+                    });
+                }
+                result
+            }
+            Request::SetEffects { id, effects } => {
+                if let Err(e) = self.check_settings_saveable() {
+                    return error_response(e);
+                }
+                let result = match &id {
+                    Some(id) => audio.set_sound_effects(id, effects.clone()),
+                    None => audio.set_master_effects(effects.clone()),
+                };
+                if result.is_ok() {
+                    self.save_effects(id.as_deref(), &effects);
+                    self.broadcast(Event::EffectsChanged { id, effects });
+                }
+                result
+            }
+            Request::SetTrim { id, trim } => {
+                if let Err(e) = self.check_settings_saveable() {
+                    return error_response(e);
+                }
+                let result =
+                    audio.set_sound_trim(&id, trim.start_ms, trim.end_ms, trim.cues.clone());
+                if result.is_ok() {
+                    self.save_trim(&id, &trim);
+                    self.broadcast(Event::TrimChanged { id, trim });
+                }
+                result
+            }
+            Request::SetVoicePreset { preset } => self.set_voice_preset(&mut audio, preset),
+            Request::NextVoicePreset => {
+                let preset = audio.next_voice_preset();
+                self.set_voice_preset(&mut audio, preset)
+            }
+            // Connection-level requests are answered by `handle_client`
+            Request::Hello { .. } | Request::Subscribe | Request::Shutdown => Ok(()),
+        };
+
+        match result {
+            Ok(()) => Response::Ok,
+            Err(e) => error_response(e),
+        }
+    }
+}
+
+fn error_response(error: AudioError) -> Response {
+    Response::Error { error }
+}
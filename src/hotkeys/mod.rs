@@ -0,0 +1,155 @@
+//! Global hotkeys read straight from input devices with evdev, so they work
+//! whichever window has focus and report key releases for hold mode. Reading
+//! `/dev/input` needs the user to be in the `input` group.
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::thread;
+
+use evdev::{Device, EventSummary, KeyCode};
+
+use crate::daemon::protocol::{Request, Response};
+use crate::settings::hotkeys::{HotkeyAction, HotkeySettings};
+
+/// Key event values; holding a key down also sends 2 as it auto-repeats
+const KEY_RELEASED: i32 = 0;
+const KEY_PRESSED: i32 = 1;
+
+#[derive(Debug)]
+pub enum HotkeyError {
+    InvalidKey(String),
+    NoDevices,
+}
+
+impl fmt::Display for HotkeyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HotkeyError::InvalidKey(key) => write!(f, "Unknown key '{}'", key),
+            HotkeyError::NoDevices => write!(
+                f,
+                "No readable input device has any of the bound keys (is the user in the input group?)"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for HotkeyError {}
+
+/// Start reading every input device that has one of the bound keys, calling
+/// `handle_request` for the actions they trigger. Returns the devices' names.
+pub fn start<H>(settings: &HotkeySettings, handle_request: H) -> Result<Vec<String>, HotkeyError>
+where
+    H: Fn(Request) -> Response + Send + Sync + 'static,
+{
+    let bindings = settings
+        .bindings
+        .iter()
+        .map(|binding| Ok((parse_key(&binding.key)?, binding.action.clone())))
+        .collect::<Result<Vec<_>, HotkeyError>>()?;
+    let bindings: Arc<[(KeyCode, HotkeyAction)]> = bindings.into();
+    let handle_request = Arc::new(handle_request);
+
+    let mut names = Vec::new();
+    for (_, device) in evdev::enumerate() {
+        let name = device.name().unwrap_or("Unnamed device").to_string();
+        if settings
+            .device
+            .as_ref()
+            .is_some_and(|wanted| !name.contains(wanted.as_str()))
+        {
+            continue;
+        }
+
+        let has_bound_key = device
+            .supported_keys()
+            .is_some_and(|keys| bindings.iter().any(|(key, _)| keys.contains(*key)));
+        if !has_bound_key {
+            continue;
+        }
+
+        let bindings = Arc::clone(&bindings);
+        let handle_request = Arc::clone(&handle_request);
+        let thread_name = name.clone();
+        thread::spawn(move || read_device(device, &thread_name, &bindings, &*handle_request));
+        names.push(name);
+    }
+
+    if names.is_empty() {
+        return Err(HotkeyError::NoDevices);
+    }
+    Ok(names)
+}
+
+/// Accepts kernel names such as `KEY_F13`, with or without the `KEY_` prefix
+fn parse_key(name: &str) -> Result<KeyCode, HotkeyError> {
+    let name_upper = name.to_ascii_uppercase();
+    let full_name = if name_upper.starts_with("KEY_") || name_upper.starts_with("BTN_") {
+        name_upper
+    } else {
+        format!("KEY_{}", name_upper)
+    };
+    KeyCode::from_str(&full_name).map_err(|_| HotkeyError::InvalidKey(name.to_string()))
+}
+
+fn read_device<H>(
+    mut device: Device,
+    name: &str,
+    bindings: &[(KeyCode, HotkeyAction)],
+    handle_request: &H,
+) where
+    H: Fn(Request) -> Response,
+{
+    loop {
+        let events = match device.fetch_events() {
+            Ok(events) => events,
+            Err(e) => {
+                eprintln!("Warning: Stopped reading hotkeys from '{}' ({}).", name, e);
+                return;
+            }
+        };
+
+        for event in events {
+            let EventSummary::Key(_, key, value) = event.destructure() else {
+                continue;
+            };
+
+            for (_, action) in bindings.iter().filter(|(bound, _)| *bound == key) {
+                let request = match (action, value) {
+                    (HotkeyAction::Play { sound, cue: None }, KEY_PRESSED) => {
+                        Request::Play { id: sound.clone() }
+                    }
+                    (HotkeyAction::Play { sound, cue }, KEY_PRESSED) => Request::PlayFrom {
+                        id: sound.clone(),
+                        cue: cue.clone(),
+                        position_ms: None,
+                    },
+                    (HotkeyAction::Play { sound, .. }, KEY_RELEASED) => {
+                        Request::Release { id: sound.clone() }
+                    }
+                    (HotkeyAction::Stop { sound }, KEY_PRESSED) => {
+                        Request::Stop { id: sound.clone() }
+                    }
+                    (HotkeyAction::StopAll, KEY_PRESSED) => Request::StopAll,
+                    (HotkeyAction::FadeOutAll, KEY_PRESSED) => Request::FadeOutAll,
+                    (HotkeyAction::Speed { factor }, KEY_PRESSED) => {
+                        Request::HoldSpeed { factor: *factor }
+                    }
+                    (HotkeyAction::Speed { .. }, KEY_RELEASED) => {
+                        Request::HoldSpeed { factor: 1.0 }
+                    }
+                    (HotkeyAction::VoicePreset { preset }, KEY_PRESSED) => {
+                        Request::SetVoicePreset {
+                            preset: preset.clone(),
+                        }
+                    }
+                    (HotkeyAction::NextVoicePreset, KEY_PRESSED) => Request::NextVoicePreset,
+                    _ => continue,
+                };
+                if let Response::Error { error } = handle_request(request) {
+                    eprintln!("Warning: Hotkey action failed ({}).", error);
+                }
+            }
+        }
+    }
+}
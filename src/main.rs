@@ -1,31 +1,107 @@
 mod audio;
+mod cli;
+mod daemon;
+mod hotkeys;
+mod midi;
+mod mpris;
+mod obs;
+mod osc;
+mod settings;
 mod ui;
+mod web;
 
-use audio::BoomCrabAudioInterface;
-use ui::{UiAction, app::App, restore_terminal, setup_terminal};
+use std::process::{Child, ExitCode};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use audio::AudioError;
+use clap::Parser;
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let audio_interface = BoomCrabAudioInterface::new().unwrap();
+use cli::Cli;
+use daemon::protocol::Request;
+use daemon::{DaemonClient, DaemonError};
+use settings::BoomCrabSettings;
+use ui::app::{App, SPECTRUM_BANDS};
+use ui::{UiAction, reports_key_releases, restore_terminal, setup_terminal};
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let settings = BoomCrabSettings::new();
+
+    if let Some(command) = cli.command {
+        return cli::run(command, &settings);
+    }
+
+    match run_tui(settings) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run_tui(settings: BoomCrabSettings) -> Result<(), Box<dyn std::error::Error>> {
+    let (mut client, _daemon) = connect_or_spawn_daemon()?;
+    client.subscribe()?;
 
     let mut terminal = setup_terminal()?;
-    let mut ui_app = App::new();
-    ui_app.audio_outputs = audio_interface.list_audio_outputs().unwrap_or_default();
-    ui_app.audio_inputs = audio_interface.list_audio_inputs().unwrap_or_default();
+    let mut ui_app = App::new(settings.keymap);
+    ui_app.key_releases = reports_key_releases();
+    (ui_app.audio_outputs, ui_app.audio_inputs) = client.list_devices().unwrap_or_default();
+    ui_app.update_sounds(client.list_sounds().unwrap_or_default());
+    ui_app.midi_bindings = client.list_midi_bindings().unwrap_or_default();
+    ui_app.voice_preset = client.voice_presets().unwrap_or_default().1;
 
     loop {
-        terminal.draw(|frame| ui_app.render(frame))?;
+        while let Some(event) = client.try_event() {
+            ui_app.handle_daemon_event(event);
+        }
 
-        match ui_app.poll_events()? {
-            UiAction::Quit => break,
-            UiAction::RefreshAudioDevices => {
-                let outputs = audio_interface.list_audio_outputs().unwrap_or_default();
-                let inputs = audio_interface.list_audio_inputs().unwrap_or_default();
-                ui_app.update_audio_devices(outputs, inputs);
+        if let Some(sound_id) = ui_app.wanted_overview() {
+            let overview = client.waveform(&sound_id).unwrap_or_else(|e| {
+                ui_app.status_message = Some(e.to_string());
+                None
+            });
+            ui_app.update_overview(sound_id, overview);
+        }
+        if let Some(source) = ui_app.wanted_spectrum() {
+            match client.spectrum(source, SPECTRUM_BANDS) {
+                Ok(levels) => ui_app.update_spectrum(levels),
+                Err(e) => ui_app.status_message = Some(e.to_string()),
             }
-            UiAction::None => {}
         }
+
+        terminal.draw(|frame| ui_app.render(frame))?;
+
+        let result = match ui_app.poll_events()? {
+            UiAction::Quit => {
+                if ui_app.holding_speed() {
+                    let _ = client.send(&Request::HoldSpeed { factor: 1.0 });
+                }
+                break;
+            }
+            UiAction::RefreshAudioDevices => client.list_devices().and_then(|(outputs, inputs)| {
+                ui_app.update_audio_devices(outputs, inputs);
+                ui_app.update_sounds(client.rescan_sounds()?);
+                Ok(())
+            }),
+            UiAction::MidiLearn(sound_id) => client.midi_learn(&sound_id).inspect_err(|_| {
+                ui_app.midi_learning = None;
+            }),
+            UiAction::LoadEffects(sound_id) => client.effects(sound_id.as_deref()).map(|effects| {
+                ui_app.effects = effects;
+            }),
+            UiAction::LoadTrim(sound_id) => client.trim(&sound_id).map(|trim| {
+                ui_app.trim = trim;
+            }),
+            UiAction::None => continue,
+            action => match action.to_request() {
+                Some(request) => client.send(&request),
+                None => continue,
+            },
+        };
+
+        ui_app.status_message = result.err().map(|e| e.to_string());
     }
 
     // Restore terminal
@@ -33,3 +109,43 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+/// How long a daemon started for the TUI gets to save and stop before it is killed
+const DAEMON_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A daemon started for the TUI, which stops along with it
+struct OwnedDaemon(Child);
+
+impl Drop for OwnedDaemon {
+    fn drop(&mut self) {
+        // Asked to stop, so it can finish saving rather than leave files half written
+        let asked = DaemonClient::connect(&daemon::socket_path())
+            .and_then(|mut client| client.shutdown())
+            .is_ok();
+        if asked {
+            let started = Instant::now();
+            while started.elapsed() < DAEMON_SHUTDOWN_TIMEOUT {
+                if !matches!(self.0.try_wait(), Ok(None)) {
+                    return;
+                }
+                thread::sleep(Duration::from_millis(20));
+            }
+        }
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+/// Connect to a running daemon, or start one in the background if there is none
+fn connect_or_spawn_daemon() -> Result<(DaemonClient, Option<OwnedDaemon>), DaemonError> {
+    let path = daemon::socket_path();
+
+    match DaemonClient::connect(&path) {
+        Ok(client) => Ok((client, None)),
+        Err(DaemonError::Io(_)) => {
+            let daemon = OwnedDaemon(daemon::spawn()?);
+            Ok((DaemonClient::connect(&path)?, Some(daemon)))
+        }
+        Err(e) => Err(e),
+    }
+}
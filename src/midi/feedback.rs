@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+
+use midir::os::unix::VirtualOutput;
+use midir::{MidiOutput, MidiOutputConnection};
+
+use super::{CLIENT_NAME, MidiError};
+use crate::settings::midi::{MidiAction, MidiBinding, MidiFeedbackSettings, MidiTrigger};
+
+/// Name of the virtual output port created when no port is configured
+const VIRTUAL_PORT_NAME: &str = "BoomCrab MIDI Out";
+
+/// Lights the pads bound to a sound while any voice of that sound is playing
+pub struct MidiFeedback {
+    connection: MidiOutputConnection,
+    pub name: String,
+    settings: MidiFeedbackSettings,
+    /// Number of playing voices per sound id
+    playing: HashMap<String, usize>,
+}
+
+impl MidiFeedback {
+    pub fn open(settings: &MidiFeedbackSettings) -> Result<Self, MidiError> {
+        let output = MidiOutput::new(CLIENT_NAME)
+            .map_err(|e| MidiError::InitializationFailed(e.to_string()))?;
+
+        let (connection, name) = match &settings.port {
+            None => {
+                let connection = output
+                    .create_virtual(VIRTUAL_PORT_NAME)
+                    .map_err(|e| MidiError::ConnectionFailed(e.to_string()))?;
+                (connection, VIRTUAL_PORT_NAME.to_string())
+            }
+            Some(wanted) => {
+                let (port, name) = output
+                    .ports()
+                    .into_iter()
+                    .find_map(|port| {
+                        let name = output.port_name(&port).ok()?;
+                        name.contains(wanted.as_str()).then_some((port, name))
+                    })
+                    .ok_or_else(|| MidiError::PortNotFound(wanted.clone()))?;
+
+                let connection = output
+                    .connect(&port, "boomcrab-out")
+                    .map_err(|e| MidiError::ConnectionFailed(e.to_string()))?;
+                (connection, name)
+            }
+        };
+
+        Ok(Self {
+            connection,
+            name,
+            settings: settings.clone(),
+            playing: HashMap::new(),
+        })
+    }
+
+    /// Turn off every pad bound to a sound, e.g. after connecting
+    pub fn clear(&mut self, bindings: &[MidiBinding]) {
+        self.playing.clear();
+        for binding in bindings.iter().filter(|b| bound_sound(b).is_some()) {
+            self.send(binding, false);
+        }
+    }
+
+    pub fn sound_started(&mut self, sound_id: &str, bindings: &[MidiBinding]) {
+        let voices = self.playing.entry(sound_id.to_string()).or_default();
+        *voices += 1;
+        if *voices == 1 {
+            self.send_for_sound(sound_id, bindings, true);
+        }
+    }
+
+    pub fn sound_stopped(&mut self, sound_id: &str, bindings: &[MidiBinding]) {
+        let Some(voices) = self.playing.get_mut(sound_id) else {
+            return;
+        };
+
+        *voices -= 1;
+        if *voices == 0 {
+            self.playing.remove(sound_id);
+            self.send_for_sound(sound_id, bindings, false);
+        }
+    }
+
+    fn send_for_sound(&mut self, sound_id: &str, bindings: &[MidiBinding], on: bool) {
+        for binding in bindings.iter().filter(|b| bound_sound(b) == Some(sound_id)) {
+            self.send(binding, on);
+        }
+    }
+
+    fn send(&mut self, binding: &MidiBinding, on: bool) {
+        let channel = self.settings.channel.or(binding.channel).unwrap_or(1);
+        let status_channel = channel.clamp(1, 16) - 1;
+        let value = if on {
+            self.settings.on_value()
+        } else {
+            self.settings.off_value()
+        };
+
+        let message = match binding.trigger {
+            MidiTrigger::Note(note) => [0x90 | status_channel, note, value],
+            MidiTrigger::Cc(controller) => [0xb0 | status_channel, controller, value],
+        };
+
+        if let Err(e) = self.connection.send(&message) {
+            eprintln!("Warning: Could not send MIDI feedback ({}).", e);
+        }
+    }
+}
+
+/// The sound whose playback state a binding's pad shows
+fn bound_sound(binding: &MidiBinding) -> Option<&str> {
+    match &binding.action {
+        MidiAction::Play { sound } | MidiAction::Stop { sound } => Some(sound),
+        MidiAction::StopAll
+        | MidiAction::FadeOutAll
+        | MidiAction::Volume { .. }
+        | MidiAction::MasterVolume => None,
+    }
+}
@@ -0,0 +1,369 @@
+pub mod feedback;
+
+use std::fmt;
+
+use midir::os::unix::VirtualInput;
+use midir::{Ignore, MidiInput, MidiInputConnection};
+
+use crate::settings::midi::{MidiAction, MidiBinding, MidiSettings, MidiTrigger};
+
+/// Name of the ALSA sequencer client
+const CLIENT_NAME: &str = "BoomCrab";
+/// Name of the virtual input port created when no port is configured
+const VIRTUAL_PORT_NAME: &str = "BoomCrab MIDI In";
+
+#[derive(Debug)]
+pub enum MidiError {
+    InitializationFailed(String),
+    PortNotFound(String),
+    ConnectionFailed(String),
+}
+
+impl fmt::Display for MidiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MidiError::InitializationFailed(e) => write!(f, "Failed to initialize MIDI: {}", e),
+            MidiError::PortNotFound(name) => write!(f, "No MIDI port matches '{}'", name),
+            MidiError::ConnectionFailed(e) => write!(f, "Failed to connect MIDI port: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for MidiError {}
+
+/// The channel voice messages BoomCrab reacts to. Channels are numbered 1 to 16.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MidiMessage {
+    NoteOn {
+        channel: u8,
+        note: u8,
+        velocity: u8,
+    },
+    NoteOff {
+        channel: u8,
+        note: u8,
+    },
+    ControlChange {
+        channel: u8,
+        controller: u8,
+        value: u8,
+    },
+}
+
+impl MidiMessage {
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        let (&status, data) = bytes.split_first()?;
+        let channel = (status & 0x0f) + 1;
+
+        match (status & 0xf0, data) {
+            // A note-on with zero velocity is a note-off by convention
+            (0x90, &[note, 0, ..]) | (0x80, &[note, _, ..]) => {
+                Some(MidiMessage::NoteOff { channel, note })
+            }
+            (0x90, &[note, velocity, ..]) => Some(MidiMessage::NoteOn {
+                channel,
+                note,
+                velocity,
+            }),
+            (0xb0, &[controller, value, ..]) => Some(MidiMessage::ControlChange {
+                channel,
+                controller,
+                value,
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn channel(&self) -> u8 {
+        match *self {
+            MidiMessage::NoteOn { channel, .. }
+            | MidiMessage::NoteOff { channel, .. }
+            | MidiMessage::ControlChange { channel, .. } => channel,
+        }
+    }
+
+    /// The trigger a binding needs to react to this message. Note-offs trigger nothing.
+    pub fn trigger(&self) -> Option<MidiTrigger> {
+        match *self {
+            MidiMessage::NoteOn { note, .. } => Some(MidiTrigger::Note(note)),
+            MidiMessage::ControlChange { controller, .. } => Some(MidiTrigger::Cc(controller)),
+            MidiMessage::NoteOff { .. } => None,
+        }
+    }
+
+    /// Velocity or controller value scaled to 0.0..=1.0
+    pub fn value(&self) -> f32 {
+        match *self {
+            MidiMessage::NoteOn { velocity, .. } => velocity as f32 / 127.0,
+            MidiMessage::ControlChange { value, .. } => value as f32 / 127.0,
+            MidiMessage::NoteOff { .. } => 0.0,
+        }
+    }
+}
+
+impl MidiBinding {
+    /// The binding learned from `message` for `sound`, on the message's own
+    /// channel: notes play the sound and controllers set its volume. Note-offs
+    /// teach nothing.
+    pub fn learned(message: &MidiMessage, sound: String) -> Option<Self> {
+        let trigger = message.trigger()?;
+        let action = match trigger {
+            MidiTrigger::Note(_) => MidiAction::Play { sound },
+            MidiTrigger::Cc(_) => MidiAction::Volume { sound },
+        };
+        Some(Self {
+            channel: Some(message.channel()),
+            trigger,
+            action,
+        })
+    }
+
+    pub fn matches(&self, message: &MidiMessage) -> bool {
+        self.channel
+            .is_none_or(|channel| channel == message.channel())
+            && message.trigger() == Some(self.trigger)
+    }
+
+    /// Whether `message` lets go of the note this binding is triggered by
+    pub fn released_by(&self, message: &MidiMessage) -> bool {
+        match *message {
+            MidiMessage::NoteOff { channel, note } => {
+                self.channel.is_none_or(|c| c == channel) && self.trigger == MidiTrigger::Note(note)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl MidiSettings {
+    /// Add a binding, replacing any binding for the same channel and trigger
+    pub fn bind(&mut self, binding: MidiBinding) {
+        self.bindings
+            .retain(|b| b.channel != binding.channel || b.trigger != binding.trigger);
+        self.bindings.push(binding);
+    }
+}
+
+/// An open MIDI input; messages are delivered until it is dropped
+pub struct MidiInputPort {
+    _connection: MidiInputConnection<()>,
+    pub name: String,
+}
+
+impl MidiInputPort {
+    /// Open the input configured in `settings` and call `on_message` for every
+    /// message received, on the MIDI thread
+    pub fn open<F>(settings: &MidiSettings, mut on_message: F) -> Result<Self, MidiError>
+    where
+        F: FnMut(MidiMessage) + Send + 'static,
+    {
+        let mut input = MidiInput::new(CLIENT_NAME)
+            .map_err(|e| MidiError::InitializationFailed(e.to_string()))?;
+        input.ignore(Ignore::All);
+
+        let callback = move |_timestamp: u64, bytes: &[u8], _: &mut ()| {
+            if let Some(message) = MidiMessage::parse(bytes) {
+                on_message(message);
+            }
+        };
+
+        let Some(wanted) = &settings.port else {
+            let connection = input
+                .create_virtual(VIRTUAL_PORT_NAME, callback, ())
+                .map_err(|e| MidiError::ConnectionFailed(e.to_string()))?;
+            return Ok(Self {
+                _connection: connection,
+                name: VIRTUAL_PORT_NAME.to_string(),
+            });
+        };
+
+        let (port, name) = input
+            .ports()
+            .into_iter()
+            .find_map(|port| {
+                let name = input.port_name(&port).ok()?;
+                name.contains(wanted.as_str()).then_some((port, name))
+            })
+            .ok_or_else(|| MidiError::PortNotFound(wanted.clone()))?;
+
+        let connection = input
+            .connect(&port, "boomcrab-in", callback, ())
+            .map_err(|e| MidiError::ConnectionFailed(e.to_string()))?;
+
+        Ok(Self {
+            _connection: connection,
+            name,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    use midir::MidiOutput;
+
+    use super::*;
+
+    fn binding(channel: Option<u8>, trigger: MidiTrigger, sound: &str) -> MidiBinding {
+        MidiBinding {
+            channel,
+            trigger,
+            action: MidiAction::Play {
+                sound: sound.to_string(),
+            },
+        }
+    }
+
+    #[test]
+    fn parses_notes_and_controllers() {
+        assert_eq!(
+            MidiMessage::parse(&[0x90, 60, 100]),
+            Some(MidiMessage::NoteOn {
+                channel: 1,
+                note: 60,
+                velocity: 100
+            })
+        );
+        assert_eq!(
+            MidiMessage::parse(&[0x80, 60, 64]),
+            Some(MidiMessage::NoteOff {
+                channel: 1,
+                note: 60
+            })
+        );
+        assert_eq!(
+            MidiMessage::parse(&[0xb3, 7, 127]),
+            Some(MidiMessage::ControlChange {
+                channel: 4,
+                controller: 7,
+                value: 127
+            })
+        );
+    }
+
+    #[test]
+    fn note_on_without_velocity_is_a_note_off() {
+        let message = MidiMessage::parse(&[0x95, 36, 0]).unwrap();
+        assert_eq!(
+            message,
+            MidiMessage::NoteOff {
+                channel: 6,
+                note: 36
+            }
+        );
+        assert_eq!(message.trigger(), None);
+        assert_eq!(message.value(), 0.0);
+    }
+
+    #[test]
+    fn channel_comes_from_the_low_nibble() {
+        assert_eq!(MidiMessage::parse(&[0x9f, 1, 1]).unwrap().channel(), 16);
+        assert_eq!(MidiMessage::parse(&[0xb0, 1, 1]).unwrap().channel(), 1);
+    }
+
+    #[test]
+    fn ignores_other_and_short_messages() {
+        assert_eq!(MidiMessage::parse(&[]), None);
+        assert_eq!(MidiMessage::parse(&[0x90, 60]), None);
+        // Pitch bend and program change
+        assert_eq!(MidiMessage::parse(&[0xe0, 0, 64]), None);
+        assert_eq!(MidiMessage::parse(&[0xc0, 5]), None);
+    }
+
+    #[test]
+    fn bindings_match_channel_and_trigger() {
+        let on = MidiMessage::parse(&[0x91, 36, 90]).unwrap();
+        let off = MidiMessage::parse(&[0x81, 36, 0]).unwrap();
+
+        assert!(binding(None, MidiTrigger::Note(36), "a").matches(&on));
+        assert!(binding(Some(2), MidiTrigger::Note(36), "a").matches(&on));
+        assert!(!binding(Some(1), MidiTrigger::Note(36), "a").matches(&on));
+        assert!(!binding(None, MidiTrigger::Cc(36), "a").matches(&on));
+        assert!(!binding(None, MidiTrigger::Note(36), "a").matches(&off));
+
+        assert!(binding(None, MidiTrigger::Note(36), "a").released_by(&off));
+        assert!(!binding(Some(1), MidiTrigger::Note(36), "a").released_by(&off));
+        assert!(!binding(None, MidiTrigger::Note(36), "a").released_by(&on));
+    }
+
+    #[test]
+    fn learns_play_from_notes_and_volume_from_controllers() {
+        let note = MidiMessage::parse(&[0x92, 40, 100]).unwrap();
+        assert_eq!(
+            MidiBinding::learned(&note, "airhorn".to_string()),
+            Some(binding(Some(3), MidiTrigger::Note(40), "airhorn"))
+        );
+
+        let cc = MidiMessage::parse(&[0xb0, 21, 64]).unwrap();
+        assert_eq!(
+            MidiBinding::learned(&cc, "music".to_string()),
+            Some(MidiBinding {
+                channel: Some(1),
+                trigger: MidiTrigger::Cc(21),
+                action: MidiAction::Volume {
+                    sound: "music".to_string()
+                },
+            })
+        );
+
+        let off = MidiMessage::parse(&[0x80, 40, 0]).unwrap();
+        assert_eq!(MidiBinding::learned(&off, "airhorn".to_string()), None);
+    }
+
+    #[test]
+    fn learning_replaces_the_binding_for_the_same_trigger() {
+        let mut settings = MidiSettings::default();
+        settings.bind(binding(Some(1), MidiTrigger::Note(36), "old"));
+        settings.bind(binding(Some(2), MidiTrigger::Note(36), "other channel"));
+        settings.bind(binding(Some(1), MidiTrigger::Note(36), "new"));
+
+        assert_eq!(
+            settings.bindings,
+            vec![
+                binding(Some(2), MidiTrigger::Note(36), "other channel"),
+                binding(Some(1), MidiTrigger::Note(36), "new"),
+            ]
+        );
+    }
+
+    /// Needs an ALSA sequencer, e.g. with the `snd-seq` module loaded
+    #[test]
+    #[ignore]
+    fn receives_from_the_virtual_port() {
+        let (sender, received) = mpsc::channel();
+        let settings = MidiSettings {
+            enabled: true,
+            ..MidiSettings::default()
+        };
+        let _input = MidiInputPort::open(&settings, move |message| {
+            let _ = sender.send(message);
+        })
+        .unwrap();
+
+        let output = MidiOutput::new("BoomCrab test").unwrap();
+        let port = output
+            .ports()
+            .into_iter()
+            .find(|port| {
+                output
+                    .port_name(port)
+                    .is_ok_and(|name| name.contains(VIRTUAL_PORT_NAME))
+            })
+            .expect("virtual port not found");
+        let Ok(mut connection) = output.connect(&port, "boomcrab-test") else {
+            panic!("could not connect to the virtual port");
+        };
+        connection.send(&[0x90, 60, 100]).unwrap();
+
+        assert_eq!(
+            received.recv_timeout(Duration::from_secs(1)).unwrap(),
+            MidiMessage::NoteOn {
+                channel: 1,
+                note: 60,
+                velocity: 100
+            }
+        );
+    }
+}
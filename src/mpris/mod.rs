@@ -0,0 +1,707 @@
+//! Desktop integration over the session D-Bus: an `org.mpris.MediaPlayer2`
+//! player for media keys and `playerctl`, plus a `dev.boomcrab.Soundboard`
+//! interface for everything MPRIS has no words for.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use zbus::blocking::connection::Builder;
+use zbus::blocking::object_server::InterfaceRef;
+use zbus::object_server::SignalEmitter;
+use zbus::zvariant::{ObjectPath, OwnedValue, Value};
+use zbus::{block_on, fdo, interface};
+
+use crate::audio::AudioError;
+use crate::daemon::protocol::{Event, Request, Response, ServerMessage};
+
+const MPRIS_BUS_NAME: &str = "org.mpris.MediaPlayer2.boomcrab";
+const MPRIS_PATH: &str = "/org/mpris/MediaPlayer2";
+const SOUNDBOARD_BUS_NAME: &str = "dev.boomcrab.Soundboard";
+const SOUNDBOARD_PATH: &str = "/dev/boomcrab/Soundboard";
+/// Track id MPRIS reserves for "nothing is playing"
+const NO_TRACK: &str = "/org/mpris/MediaPlayer2/TrackList/NoTrack";
+
+type RequestHandler = Arc<dyn Fn(Request) -> Response + Send + Sync>;
+
+/// Register on the session bus. `handle_request` carries out method calls and
+/// `events` keeps the player's properties up to date.
+pub fn start<H>(handle_request: H, events: Receiver<ServerMessage>) -> zbus::Result<()>
+where
+    H: Fn(Request) -> Response + Send + Sync + 'static,
+{
+    serve(Builder::session()?, handle_request, events)
+}
+
+fn serve<H>(
+    builder: Builder,
+    handle_request: H,
+    events: Receiver<ServerMessage>,
+) -> zbus::Result<()>
+where
+    H: Fn(Request) -> Response + Send + Sync + 'static,
+{
+    let handler: RequestHandler = Arc::new(handle_request);
+    let state = Arc::new(Mutex::new(PlayerState {
+        playing: Vec::new(),
+        paused: HashSet::new(),
+        positions: HashMap::new(),
+        volume: 1.0,
+    }));
+
+    let connection = builder
+        .name(MPRIS_BUS_NAME)?
+        .name(SOUNDBOARD_BUS_NAME)?
+        .serve_at(MPRIS_PATH, MediaPlayer)?
+        .serve_at(
+            MPRIS_PATH,
+            Player {
+                handler: Arc::clone(&handler),
+                state: Arc::clone(&state),
+            },
+        )?
+        .serve_at(SOUNDBOARD_PATH, Soundboard { handler })?
+        .build()?;
+    let player = connection
+        .object_server()
+        .interface::<_, Player>(MPRIS_PATH)?;
+    let soundboard = connection
+        .object_server()
+        .interface::<_, Soundboard>(SOUNDBOARD_PATH)?;
+
+    // The thread keeps the connection, and with it the bus names, alive
+    thread::spawn(move || {
+        let _connection = connection;
+        publish_events(&player, &soundboard, &state, events);
+    });
+    Ok(())
+}
+
+/// What the player reports, updated from daemon events
+struct PlayerState {
+    /// Voice and sound id of every playing voice, oldest first
+    playing: Vec<(u64, String)>,
+    /// Playing voices that are paused
+    paused: HashSet<u64>,
+    /// Last reported position of each voice, in milliseconds into its file
+    positions: HashMap<u64, u32>,
+    volume: f64,
+}
+
+impl PlayerState {
+    fn playback_status(&self) -> &'static str {
+        if self.playing.is_empty() {
+            "Stopped"
+        } else if self
+            .playing
+            .iter()
+            .all(|(voice, _)| self.paused.contains(voice))
+        {
+            "Paused"
+        } else {
+            "Playing"
+        }
+    }
+
+    /// The most recently started voice, which is reported as the current track
+    fn current_voice(&self) -> Option<u64> {
+        self.playing.last().map(|(voice, _)| *voice)
+    }
+
+    /// Position of the current track in microseconds
+    fn position(&self) -> i64 {
+        self.current_voice()
+            .and_then(|voice| self.positions.get(&voice))
+            .map_or(0, |ms| *ms as i64 * 1000)
+    }
+
+    /// Reports the most recently started sound as the current track
+    fn metadata(&self) -> HashMap<String, OwnedValue> {
+        let mut metadata = HashMap::new();
+
+        let track_id = match self.playing.last() {
+            Some((voice, id)) => {
+                insert_value(&mut metadata, "xesam:title", Value::from(id.as_str()));
+                track_path(*voice)
+            }
+            None => NO_TRACK.to_string(),
+        };
+        if let Ok(path) = ObjectPath::try_from(track_id) {
+            insert_value(&mut metadata, "mpris:trackid", Value::from(path));
+        }
+
+        metadata
+    }
+}
+
+fn track_path(voice: u64) -> String {
+    format!("{}/Voice{}", SOUNDBOARD_PATH, voice)
+}
+
+fn insert_value(metadata: &mut HashMap<String, OwnedValue>, key: &str, value: Value) {
+    if let Ok(value) = OwnedValue::try_from(value) {
+        metadata.insert(key.to_string(), value);
+    }
+}
+
+/// Emit property changes and soundboard signals as the daemon reports them
+fn publish_events(
+    player: &InterfaceRef<Player>,
+    soundboard: &InterfaceRef<Soundboard>,
+    state: &Mutex<PlayerState>,
+    events: Receiver<ServerMessage>,
+) {
+    let player_emitter = player.signal_emitter();
+    let soundboard_emitter = soundboard.signal_emitter();
+
+    for message in events {
+        let ServerMessage::Event(event) = message else {
+            continue;
+        };
+
+        let result = match event {
+            Event::SoundStarted { voice, id } => {
+                state.lock().unwrap().playing.push((voice, id.clone()));
+                track_changed(player)
+                    .and_then(|()| block_on(Soundboard::sound_started(soundboard_emitter, &id)))
+            }
+            Event::SoundStopped { voice, id } => {
+                {
+                    let mut state = state.lock().unwrap();
+                    state.playing.retain(|(v, _)| *v != voice);
+                    state.paused.remove(&voice);
+                    state.positions.remove(&voice);
+                }
+                track_changed(player)
+                    .and_then(|()| block_on(Soundboard::sound_stopped(soundboard_emitter, &id)))
+            }
+            // Progress arrives many times a second, so only a change of status is published
+            Event::SoundProgress {
+                voice, progress, ..
+            } => {
+                let changed = {
+                    let mut state = state.lock().unwrap();
+                    let status = state.playback_status();
+                    state.positions.insert(voice, progress.position_ms);
+                    if progress.paused {
+                        state.paused.insert(voice);
+                    } else {
+                        state.paused.remove(&voice);
+                    }
+                    state.playback_status() != status
+                };
+                if changed {
+                    block_on(player.get().playback_status_changed(player_emitter))
+                } else {
+                    Ok(())
+                }
+            }
+            Event::VolumeChanged { id: None, volume } => {
+                state.lock().unwrap().volume = volume as f64;
+                block_on(player.get().volume_changed(player_emitter))
+            }
+            Event::VolumeChanged { id: Some(_), .. }
+            | Event::MidiLearned { .. }
+            | Event::EffectsChanged { .. }
+            | Event::TrimChanged { .. }
+            | Event::VoicePresetChanged { .. }
+            | Event::WaveformReady { .. }
+            | Event::MicLatency { .. }
+            | Event::MicGainReduction { .. }
+            | Event::Levels { .. } => Ok(()),
+        };
+
+        if let Err(e) = result {
+            eprintln!("Warning: Could not publish D-Bus update ({}).", e);
+        }
+    }
+}
+
+/// Publish a new current track, and the status that goes with it
+fn track_changed(player: &InterfaceRef<Player>) -> zbus::Result<()> {
+    let emitter = player.signal_emitter();
+    let player = player.get();
+    block_on(player.playback_status_changed(emitter))?;
+    block_on(player.metadata_changed(emitter))
+}
+
+/// Run a request, turning an error response into a D-Bus error
+fn call(handler: &RequestHandler, request: Request) -> fdo::Result<Response> {
+    match handler(request) {
+        Response::Error {
+            error: error @ AudioError::SoundNotFound(_),
+        } => Err(fdo::Error::InvalidArgs(error.to_string())),
+        Response::Error { error } => Err(fdo::Error::Failed(error.to_string())),
+        response => Ok(response),
+    }
+}
+
+/// The `org.mpris.MediaPlayer2` root interface
+struct MediaPlayer;
+
+#[interface(name = "org.mpris.MediaPlayer2")]
+impl MediaPlayer {
+    fn raise(&self) {}
+
+    fn quit(&self) {}
+
+    #[zbus(property)]
+    fn can_quit(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn can_raise(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn has_track_list(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn identity(&self) -> &str {
+        "BoomCrab"
+    }
+
+    #[zbus(property)]
+    fn supported_uri_schemes(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    #[zbus(property)]
+    fn supported_mime_types(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// The `org.mpris.MediaPlayer2.Player` interface. Pausing and playing pause
+/// and resume every sound; starting a sound needs its id and goes through
+/// the soundboard interface.
+struct Player {
+    handler: RequestHandler,
+    state: Arc<Mutex<PlayerState>>,
+}
+
+impl Player {
+    fn pause_all(&self, paused: bool) -> fdo::Result<()> {
+        call(&self.handler, Request::Pause { id: None, paused }).map(|_| ())
+    }
+
+    /// Move the current track to `position` microseconds into its file and
+    /// announce the jump
+    async fn seek_to(
+        &self,
+        voice: u64,
+        position: i64,
+        emitter: &SignalEmitter<'_>,
+    ) -> fdo::Result<()> {
+        let position_ms = u32::try_from(position.max(0) / 1000).unwrap_or(u32::MAX);
+        call(&self.handler, Request::SeekVoice { voice, position_ms })?;
+        self.state
+            .lock()
+            .unwrap()
+            .positions
+            .insert(voice, position_ms);
+        Self::seeked(emitter, position_ms as i64 * 1000).await?;
+        Ok(())
+    }
+}
+
+#[interface(name = "org.mpris.MediaPlayer2.Player")]
+impl Player {
+    fn next(&self) {}
+
+    fn previous(&self) {}
+
+    fn pause(&self) -> fdo::Result<()> {
+        self.pause_all(true)
+    }
+
+    fn play_pause(&self) -> fdo::Result<()> {
+        let playing = self.state.lock().unwrap().playback_status() == "Playing";
+        self.pause_all(playing)
+    }
+
+    fn stop(&self) -> fdo::Result<()> {
+        call(&self.handler, Request::StopAll).map(|_| ())
+    }
+
+    fn play(&self) -> fdo::Result<()> {
+        self.pause_all(false)
+    }
+
+    /// Move the current track by `offset` microseconds
+    async fn seek(
+        &self,
+        offset: i64,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> fdo::Result<()> {
+        let current = {
+            let state = self.state.lock().unwrap();
+            state.current_voice().map(|voice| (voice, state.position()))
+        };
+        match current {
+            Some((voice, position)) => {
+                self.seek_to(voice, position.saturating_add(offset), &emitter)
+                    .await
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Move the current track to `position` microseconds, unless it changed since
+    /// the caller looked
+    async fn set_position(
+        &self,
+        track_id: ObjectPath<'_>,
+        position: i64,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> fdo::Result<()> {
+        let voice = self.state.lock().unwrap().current_voice();
+        match voice {
+            Some(voice) if position >= 0 && track_id.as_str() == track_path(voice) => {
+                self.seek_to(voice, position, &emitter).await
+            }
+            _ => Ok(()),
+        }
+    }
+
+    #[zbus(signal)]
+    async fn seeked(emitter: &SignalEmitter<'_>, position: i64) -> zbus::Result<()>;
+
+    fn open_uri(&self, _uri: &str) -> fdo::Result<()> {
+        Err(fdo::Error::NotSupported(
+            "Play sounds by id through dev.boomcrab.Soundboard".to_string(),
+        ))
+    }
+
+    #[zbus(property)]
+    fn playback_status(&self) -> &str {
+        self.state.lock().unwrap().playback_status()
+    }
+
+    #[zbus(property)]
+    fn rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn minimum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn maximum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn metadata(&self) -> HashMap<String, OwnedValue> {
+        self.state.lock().unwrap().metadata()
+    }
+
+    #[zbus(property)]
+    fn volume(&self) -> f64 {
+        self.state.lock().unwrap().volume
+    }
+
+    #[zbus(property)]
+    fn set_volume(&mut self, volume: f64) -> fdo::Result<()> {
+        let request = Request::SetVolume {
+            id: None,
+            volume: volume as f32,
+        };
+        call(&self.handler, request)?;
+        // Updated right away, since zbus reports the new value as soon as this returns
+        self.state.lock().unwrap().volume = volume.max(0.0);
+        Ok(())
+    }
+
+    /// Changes continuously, so it is only announced through `Seeked`
+    #[zbus(property(emits_changed_signal = "false"))]
+    fn position(&self) -> i64 {
+        self.state.lock().unwrap().position()
+    }
+
+    #[zbus(property)]
+    fn can_go_next(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn can_go_previous(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn can_play(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_pause(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_seek(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_control(&self) -> bool {
+        true
+    }
+}
+
+/// The `dev.boomcrab.Soundboard` interface
+struct Soundboard {
+    handler: RequestHandler,
+}
+
+#[interface(name = "dev.boomcrab.Soundboard")]
+impl Soundboard {
+    fn play(&self, id: String) -> fdo::Result<()> {
+        call(&self.handler, Request::Play { id }).map(|_| ())
+    }
+
+    fn stop(&self, id: String) -> fdo::Result<()> {
+        call(&self.handler, Request::Stop { id }).map(|_| ())
+    }
+
+    fn stop_all(&self) -> fdo::Result<()> {
+        call(&self.handler, Request::StopAll).map(|_| ())
+    }
+
+    fn set_volume(&self, id: String, volume: f64) -> fdo::Result<()> {
+        let request = Request::SetVolume {
+            id: Some(id),
+            volume: volume as f32,
+        };
+        call(&self.handler, request).map(|_| ())
+    }
+
+    /// Id and name of every sound
+    fn list_sounds(&self) -> fdo::Result<Vec<(String, String)>> {
+        match call(&self.handler, Request::ListSounds)? {
+            Response::Sounds { sounds } => Ok(sounds
+                .into_iter()
+                .map(|sound| (sound.id, sound.name))
+                .collect()),
+            other => Err(fdo::Error::Failed(format!(
+                "Unexpected response: {:?}",
+                other
+            ))),
+        }
+    }
+
+    /// A sound started playing
+    #[zbus(signal)]
+    async fn sound_started(emitter: &SignalEmitter<'_>, id: &str) -> zbus::Result<()>;
+
+    /// A voice of a sound stopped
+    #[zbus(signal)]
+    async fn sound_stopped(emitter: &SignalEmitter<'_>, id: &str) -> zbus::Result<()>;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader};
+    use std::process::{Child, Command, Stdio};
+    use std::sync::mpsc::{self, Sender};
+    use std::time::{Duration, Instant};
+
+    use serde_json::json;
+    use zbus::blocking::{Connection, Proxy};
+    use zbus::proxy::CacheProperties;
+
+    use super::*;
+    use crate::audio::Sound;
+    use crate::audio::mixer::VoiceProgress;
+
+    /// A private session bus, stopped when dropped
+    struct Bus(Child);
+
+    impl Drop for Bus {
+        fn drop(&mut self) {
+            self.0.kill().ok();
+            self.0.wait().ok();
+        }
+    }
+
+    fn start_bus() -> Option<(Bus, String)> {
+        let mut child = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .ok()?;
+        let mut address = String::new();
+        BufReader::new(child.stdout.take()?)
+            .read_line(&mut address)
+            .ok()?;
+        Some((Bus(child), address.trim().to_string()))
+    }
+
+    fn proxy<'a>(connection: &Connection, path: &'a str, interface: &'a str) -> Proxy<'a> {
+        zbus::blocking::proxy::Builder::new(connection)
+            .destination(if path == MPRIS_PATH {
+                MPRIS_BUS_NAME
+            } else {
+                SOUNDBOARD_BUS_NAME
+            })
+            .unwrap()
+            .path(path)
+            .unwrap()
+            .interface(interface)
+            .unwrap()
+            .cache_properties(CacheProperties::No)
+            .build()
+            .unwrap()
+    }
+
+    /// Wait for the published state to catch up with the events sent
+    fn wait_for<T: PartialEq>(mut get: impl FnMut() -> T, expected: T) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while get() != expected {
+            assert!(Instant::now() < deadline, "timed out");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn progress(events: &Sender<ServerMessage>, voice: u64, position_ms: u32, paused: bool) {
+        let progress = VoiceProgress {
+            position_ms,
+            start_ms: 0,
+            end_ms: 10_000,
+            play: 1,
+            plays: Some(1),
+            volume: 1.0,
+            paused,
+            streamed: false,
+            latency_ms: None,
+        };
+        events
+            .send(ServerMessage::Event(Event::SoundProgress {
+                voice,
+                id: "airhorn".to_string(),
+                progress,
+            }))
+            .unwrap();
+    }
+
+    #[test]
+    fn serves_player_and_soundboard() {
+        let Some((_bus, address)) = start_bus() else {
+            eprintln!("dbus-daemon is not installed, skipping");
+            return;
+        };
+
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let recorder = Arc::clone(&received);
+        let (events, receiver) = mpsc::channel();
+        serve(
+            Builder::address(address.as_str()).unwrap(),
+            move |request| {
+                let response = match request {
+                    Request::ListSounds => Response::Sounds {
+                        sounds: vec![Sound {
+                            id: "airhorn".to_string(),
+                            name: "Air horn".to_string(),
+                            path: "airhorn.wav".into(),
+                        }],
+                    },
+                    _ => Response::Ok,
+                };
+                recorder
+                    .lock()
+                    .unwrap()
+                    .push(serde_json::to_value(request).unwrap());
+                response
+            },
+            receiver,
+        )
+        .unwrap();
+
+        let client = Builder::address(address.as_str()).unwrap().build().unwrap();
+        let player = proxy(&client, MPRIS_PATH, "org.mpris.MediaPlayer2.Player");
+        let soundboard = proxy(&client, SOUNDBOARD_PATH, "dev.boomcrab.Soundboard");
+        let status = || player.get_property::<String>("PlaybackStatus").unwrap();
+
+        assert_eq!(status(), "Stopped");
+        assert!(player.get_property::<bool>("CanSeek").unwrap());
+
+        let mut started = soundboard.receive_signal("SoundStarted").unwrap();
+        events
+            .send(ServerMessage::Event(Event::SoundStarted {
+                voice: 7,
+                id: "airhorn".to_string(),
+            }))
+            .unwrap();
+        let signal = started.next().unwrap();
+        assert_eq!(signal.body().deserialize::<String>().unwrap(), "airhorn");
+        wait_for(status, "Playing".to_string());
+
+        let metadata: HashMap<String, OwnedValue> = player.get_property("Metadata").unwrap();
+        assert_eq!(
+            String::try_from(metadata["xesam:title"].clone()).unwrap(),
+            "airhorn"
+        );
+        assert_eq!(
+            ObjectPath::try_from(metadata["mpris:trackid"].clone())
+                .unwrap()
+                .as_str(),
+            "/dev/boomcrab/Soundboard/Voice7"
+        );
+
+        progress(&events, 7, 1500, true);
+        wait_for(status, "Paused".to_string());
+        assert_eq!(player.get_property::<i64>("Position").unwrap(), 1_500_000);
+
+        let mut seeked = player.receive_signal("Seeked").unwrap();
+        player.call_method("Seek", &(2_000_000i64,)).unwrap();
+        let signal = seeked.next().unwrap();
+        assert_eq!(signal.body().deserialize::<i64>().unwrap(), 3_500_000);
+        assert_eq!(player.get_property::<i64>("Position").unwrap(), 3_500_000);
+
+        let other_track = ObjectPath::try_from("/dev/boomcrab/Soundboard/Voice1").unwrap();
+        player
+            .call_method("SetPosition", &(other_track, 0i64))
+            .unwrap();
+        let track = ObjectPath::try_from("/dev/boomcrab/Soundboard/Voice7").unwrap();
+        player
+            .call_method("SetPosition", &(track, 250_000i64))
+            .unwrap();
+
+        soundboard.call_method("Play", &("airhorn",)).unwrap();
+        let sounds: Vec<(String, String)> = soundboard.call("ListSounds", &()).unwrap();
+        assert_eq!(
+            sounds,
+            vec![("airhorn".to_string(), "Air horn".to_string())]
+        );
+
+        let mut stopped = soundboard.receive_signal("SoundStopped").unwrap();
+        events
+            .send(ServerMessage::Event(Event::SoundStopped {
+                voice: 7,
+                id: "airhorn".to_string(),
+            }))
+            .unwrap();
+        let signal = stopped.next().unwrap();
+        assert_eq!(signal.body().deserialize::<String>().unwrap(), "airhorn");
+        wait_for(status, "Stopped".to_string());
+
+        assert_eq!(
+            *received.lock().unwrap(),
+            vec![
+                json!({"type": "seek_voice", "voice": 7, "position_ms": 3500}),
+                json!({"type": "seek_voice", "voice": 7, "position_ms": 250}),
+                json!({"type": "play", "id": "airhorn"}),
+                json!({"type": "list_sounds"}),
+            ]
+        );
+    }
+}
@@ -0,0 +1,593 @@
+//! Client for obs-websocket 5, the remote control protocol built into OBS Studio 28 and later
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::net::TcpStream;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use tungstenite::http::Uri;
+use tungstenite::{Message, WebSocket};
+
+use crate::daemon::protocol::{Event, Request, Response, ServerMessage};
+use crate::settings::obs::{
+    ObsAction, ObsEvent, ObsEventBinding, ObsSettings, ObsSoundBinding, SoundEdge,
+};
+
+const RPC_VERSION: u32 = 1;
+/// Subscribe to the Scenes (1 << 2) and Outputs (1 << 6) event categories
+const EVENT_SUBSCRIPTIONS: u32 = (1 << 2) | (1 << 6);
+/// How often the connection checks for requests to send while waiting for events
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Delay before reconnecting after OBS closes or can't be reached
+#[cfg(not(test))]
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+#[cfg(test)]
+const RECONNECT_DELAY: Duration = Duration::from_millis(50);
+
+/// Message opcodes used by the protocol
+mod op {
+    pub const HELLO: u8 = 0;
+    pub const IDENTIFY: u8 = 1;
+    pub const IDENTIFIED: u8 = 2;
+    pub const EVENT: u8 = 5;
+    pub const REQUEST: u8 = 6;
+    pub const REQUEST_RESPONSE: u8 = 7;
+}
+
+#[derive(Debug)]
+pub enum ObsError {
+    ConnectionFailed(String),
+    AuthenticationFailed(String),
+    Protocol(String),
+}
+
+impl fmt::Display for ObsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObsError::ConnectionFailed(e) => write!(f, "Failed to connect to OBS: {}", e),
+            ObsError::AuthenticationFailed(e) => write!(f, "OBS authentication failed: {}", e),
+            ObsError::Protocol(e) => write!(f, "OBS protocol error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ObsError {}
+
+impl From<io::Error> for ObsError {
+    fn from(err: io::Error) -> Self {
+        ObsError::ConnectionFailed(err.to_string())
+    }
+}
+
+impl From<tungstenite::Error> for ObsError {
+    fn from(err: tungstenite::Error) -> Self {
+        ObsError::ConnectionFailed(err.to_string())
+    }
+}
+
+impl From<serde_json::Error> for ObsError {
+    fn from(err: serde_json::Error) -> Self {
+        ObsError::Protocol(err.to_string())
+    }
+}
+
+impl ObsEventBinding {
+    pub fn matches(&self, event: &ObsEvent) -> bool {
+        match (&self.event, event) {
+            (ObsEvent::SceneChanged { scene: wanted }, ObsEvent::SceneChanged { scene }) => {
+                wanted.is_none() || wanted == scene
+            }
+            (wanted, event) => wanted == event,
+        }
+    }
+}
+
+/// A request to send to OBS: its type and data
+type ObsRequest = (String, Option<Value>);
+
+/// Connect to OBS in the background, reconnecting whenever the connection drops.
+///
+/// `handle_request` carries out the actions bound to OBS events, and `events`
+/// triggers the requests bound to sounds.
+pub fn start<H>(settings: &ObsSettings, handle_request: H, events: Receiver<ServerMessage>)
+where
+    H: Fn(Request) -> Response + Send + 'static,
+{
+    let (request_sender, requests) = mpsc::channel();
+    let sound_bindings = settings.on_sound.clone();
+    thread::spawn(move || forward_sound_events(&sound_bindings, events, request_sender));
+
+    let settings = settings.clone();
+    thread::spawn(move || {
+        let mut last_error = None;
+        loop {
+            // Requests for sounds that played while OBS was away are stale by now
+            while requests.try_recv().is_ok() {}
+
+            let error = match run_session(&settings, &handle_request, &requests) {
+                Ok(()) => "OBS closed the connection".to_string(),
+                Err(e) => e.to_string(),
+            };
+            // Only report changes, rather than every retry while OBS isn't running
+            if last_error.as_ref() != Some(&error) {
+                eprintln!("Warning: {}. Reconnecting.", error);
+                last_error = Some(error);
+            }
+
+            thread::sleep(RECONNECT_DELAY);
+        }
+    });
+}
+
+/// Turn sound events into the requests bound to them
+fn forward_sound_events(
+    bindings: &[ObsSoundBinding],
+    events: Receiver<ServerMessage>,
+    requests: Sender<ObsRequest>,
+) {
+    // Number of playing voices per sound id
+    let mut playing: HashMap<String, usize> = HashMap::new();
+
+    for message in events {
+        let (sound_id, edge) = match message {
+            ServerMessage::Event(Event::SoundStarted { id, .. }) => {
+                *playing.entry(id.clone()).or_default() += 1;
+                (id, SoundEdge::Started)
+            }
+            ServerMessage::Event(Event::SoundStopped { id, .. }) => {
+                let Some(voices) = playing.get_mut(&id) else {
+                    continue;
+                };
+                *voices -= 1;
+                if *voices > 0 {
+                    continue;
+                }
+                playing.remove(&id);
+                (id, SoundEdge::Finished)
+            }
+            _ => continue,
+        };
+
+        for binding in bindings
+            .iter()
+            .filter(|b| b.sound == sound_id && b.when == edge)
+        {
+            if requests
+                .send((binding.request.clone(), binding.data.clone()))
+                .is_err()
+            {
+                return;
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ObsMessage {
+    op: u8,
+    #[serde(default)]
+    d: Value,
+}
+
+/// Connect, identify and handle events until the connection closes
+fn run_session<H>(
+    settings: &ObsSettings,
+    handle_request: &H,
+    requests: &Receiver<ObsRequest>,
+) -> Result<(), ObsError>
+where
+    H: Fn(Request) -> Response,
+{
+    let mut socket = connect(&settings.url)?;
+    identify(&mut socket, settings.password.as_deref())?;
+    println!("Connected to OBS at {}", settings.url);
+
+    socket.get_mut().set_read_timeout(Some(POLL_INTERVAL))?;
+    let mut next_request_id: u64 = 0;
+
+    loop {
+        match socket.read() {
+            Ok(Message::Text(text)) => {
+                let message: ObsMessage = serde_json::from_str(&text)?;
+                match message.op {
+                    op::EVENT => handle_event(&message.d, &settings.on_event, handle_request),
+                    op::REQUEST_RESPONSE => check_response(&message.d),
+                    _ => {}
+                }
+            }
+            Ok(Message::Close(_)) => return Ok(()),
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(e))
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        while let Ok((request_type, request_data)) = requests.try_recv() {
+            next_request_id += 1;
+            let mut data = json!({
+                "requestType": request_type,
+                "requestId": next_request_id.to_string(),
+            });
+            if let Some(request_data) = request_data {
+                data["requestData"] = request_data;
+            }
+            send(&mut socket, op::REQUEST, data)?;
+        }
+    }
+}
+
+fn connect(url: &str) -> Result<WebSocket<TcpStream>, ObsError> {
+    let uri: Uri = url
+        .parse()
+        .map_err(|_| ObsError::ConnectionFailed(format!("Invalid URL '{}'", url)))?;
+    if uri.scheme_str() != Some("ws") {
+        return Err(ObsError::ConnectionFailed(
+            "Only ws:// URLs are supported".to_string(),
+        ));
+    }
+    let host = uri.host().unwrap_or("127.0.0.1");
+    let port = uri.port_u16().unwrap_or(80);
+
+    let stream = TcpStream::connect((host, port))?;
+    let (socket, _) =
+        tungstenite::client(url, stream).map_err(|e| ObsError::ConnectionFailed(e.to_string()))?;
+    Ok(socket)
+}
+
+/// Answer the server's hello, authenticating if it asks to, and wait to be identified
+fn identify(socket: &mut WebSocket<TcpStream>, password: Option<&str>) -> Result<(), ObsError> {
+    let hello = read_message(socket)?;
+    if hello.op != op::HELLO {
+        return Err(ObsError::Protocol(format!(
+            "Expected hello, got opcode {}",
+            hello.op
+        )));
+    }
+
+    let mut identify = json!({
+        "rpcVersion": RPC_VERSION,
+        "eventSubscriptions": EVENT_SUBSCRIPTIONS,
+    });
+    if let Some(auth) = hello.d.get("authentication") {
+        let password = password
+            .ok_or_else(|| ObsError::AuthenticationFailed("OBS requires a password".to_string()))?;
+        let (Some(challenge), Some(salt)) = (auth["challenge"].as_str(), auth["salt"].as_str())
+        else {
+            return Err(ObsError::Protocol(
+                "Hello is missing the authentication challenge".to_string(),
+            ));
+        };
+        identify["authentication"] = json!(authentication_string(password, salt, challenge));
+    }
+    send(socket, op::IDENTIFY, identify)?;
+
+    // OBS closes the connection instead of answering when authentication fails
+    match read_message(socket) {
+        Ok(message) if message.op == op::IDENTIFIED => Ok(()),
+        Ok(message) => Err(ObsError::Protocol(format!(
+            "Expected identified, got opcode {}",
+            message.op
+        ))),
+        Err(_) if password.is_some() => Err(ObsError::AuthenticationFailed(
+            "OBS closed the connection; check the password".to_string(),
+        )),
+        Err(e) => Err(e),
+    }
+}
+
+/// `base64(sha256(base64(sha256(password + salt)) + challenge))`
+fn authentication_string(password: &str, salt: &str, challenge: &str) -> String {
+    let secret = BASE64.encode(Sha256::digest(format!("{}{}", password, salt)));
+    BASE64.encode(Sha256::digest(format!("{}{}", secret, challenge)))
+}
+
+/// Block until the next text message arrives
+fn read_message(socket: &mut WebSocket<TcpStream>) -> Result<ObsMessage, ObsError> {
+    loop {
+        match socket.read()? {
+            Message::Text(text) => return Ok(serde_json::from_str(&text)?),
+            Message::Close(_) => {
+                return Err(ObsError::ConnectionFailed(
+                    "OBS closed the connection".to_string(),
+                ));
+            }
+            _ => {}
+        }
+    }
+}
+
+fn send(socket: &mut WebSocket<TcpStream>, op: u8, data: Value) -> Result<(), ObsError> {
+    let message = json!({ "op": op, "d": data });
+    socket.send(Message::text(message.to_string()))?;
+    Ok(())
+}
+
+fn handle_event<H>(data: &Value, bindings: &[ObsEventBinding], handle_request: &H)
+where
+    H: Fn(Request) -> Response,
+{
+    let Some(event) = parse_event(data) else {
+        return;
+    };
+
+    for binding in bindings.iter().filter(|b| b.matches(&event)) {
+        let request = match &binding.action {
+            ObsAction::Play { sound } => Request::Play { id: sound.clone() },
+            ObsAction::Stop { sound } => Request::Stop { id: sound.clone() },
+            ObsAction::StopAll => Request::StopAll,
+            ObsAction::FadeOutAll => Request::FadeOutAll,
+        };
+        if let Response::Error { error } = handle_request(request) {
+            eprintln!("Warning: OBS action failed ({}).", error);
+        }
+    }
+}
+
+fn parse_event(data: &Value) -> Option<ObsEvent> {
+    let event_data = &data["eventData"];
+    let started = match event_data["outputState"].as_str() {
+        Some("OBS_WEBSOCKET_OUTPUT_STARTED") => Some(true),
+        Some("OBS_WEBSOCKET_OUTPUT_STOPPED") => Some(false),
+        _ => None,
+    };
+
+    match data["eventType"].as_str()? {
+        "CurrentProgramSceneChanged" => Some(ObsEvent::SceneChanged {
+            scene: event_data["sceneName"].as_str().map(str::to_string),
+        }),
+        "StreamStateChanged" => Some(match started? {
+            true => ObsEvent::StreamStarted,
+            false => ObsEvent::StreamStopped,
+        }),
+        "RecordStateChanged" => Some(match started? {
+            true => ObsEvent::RecordingStarted,
+            false => ObsEvent::RecordingStopped,
+        }),
+        _ => None,
+    }
+}
+
+/// Report requests OBS rejected, e.g. switching to a scene that doesn't exist
+fn check_response(data: &Value) {
+    let status = &data["requestStatus"];
+    if status["result"].as_bool() == Some(false) {
+        eprintln!(
+            "Warning: OBS rejected {} ({}).",
+            data["requestType"].as_str().unwrap_or("a request"),
+            status["comment"].as_str().unwrap_or("no reason given")
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    /// Accept the next connection as the OBS side
+    fn accept(listener: &TcpListener) -> WebSocket<TcpStream> {
+        let (stream, _) = listener.accept().unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        tungstenite::accept(stream).unwrap()
+    }
+
+    fn read_op(socket: &mut WebSocket<TcpStream>) -> (u8, Value) {
+        let message = read_message(socket).unwrap();
+        (message.op, message.d)
+    }
+
+    /// Say hello, optionally asking for authentication, and return the identify data
+    fn handshake(socket: &mut WebSocket<TcpStream>, authentication: Option<Value>) -> Value {
+        let mut hello = json!({"obsWebSocketVersion": "5.0.0", "rpcVersion": 1});
+        if let Some(authentication) = authentication {
+            hello["authentication"] = authentication;
+        }
+        send(socket, op::HELLO, hello).unwrap();
+
+        let (op, identify) = read_op(socket);
+        assert_eq!(op, op::IDENTIFY);
+        identify
+    }
+
+    /// Run `obs` on a mock server and identify against it as the client
+    fn identify_with<F>(password: Option<&str>, obs: F) -> Result<(), ObsError>
+    where
+        F: FnOnce(&mut WebSocket<TcpStream>) + Send + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let server = thread::spawn(move || obs(&mut accept(&listener)));
+
+        let result = connect(&url).and_then(|mut socket| identify(&mut socket, password));
+        server.join().unwrap();
+        result
+    }
+
+    #[test]
+    fn identifies_without_a_password() {
+        let result = identify_with(None, |socket| {
+            let identify = handshake(socket, None);
+            assert_eq!(identify["rpcVersion"], RPC_VERSION);
+            assert_eq!(identify["eventSubscriptions"], EVENT_SUBSCRIPTIONS);
+            assert!(identify.get("authentication").is_none());
+            send(socket, op::IDENTIFIED, json!({"negotiatedRpcVersion": 1})).unwrap();
+        });
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn answers_the_authentication_challenge() {
+        // The example from the obs-websocket protocol documentation
+        let result = identify_with(Some("supersecretpassword"), |socket| {
+            let identify = handshake(
+                socket,
+                Some(json!({
+                    "challenge": "+IxH4CnCiqpX1rM9scsNynZzbOe4KhDeYcTNS3PDaeY=",
+                    "salt": "lM1GncleQOaCu9lT1yeUZhFYnqhsLLP1G5lAGo3ixaI=",
+                })),
+            );
+            assert_eq!(
+                identify["authentication"],
+                "1Ct943GAT+6YQUUX47Ia/ncufilbe6+oD6lY+5kaCu4="
+            );
+            send(socket, op::IDENTIFIED, json!({"negotiatedRpcVersion": 1})).unwrap();
+        });
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn reports_a_rejected_password() {
+        let result = identify_with(Some("wrong"), |socket| {
+            handshake(socket, Some(json!({"challenge": "c", "salt": "s"})));
+            socket.close(None).unwrap();
+            socket.flush().ok();
+        });
+        assert!(matches!(result, Err(ObsError::AuthenticationFailed(_))));
+    }
+
+    #[test]
+    fn needs_a_password_when_obs_asks_for_one() {
+        let result = identify_with(None, |socket| {
+            let hello = json!({"rpcVersion": 1, "authentication": {"challenge": "c", "salt": "s"}});
+            send(socket, op::HELLO, hello).unwrap();
+        });
+        assert!(matches!(result, Err(ObsError::AuthenticationFailed(_))));
+    }
+
+    #[test]
+    fn sends_requests_runs_actions_and_reconnects() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let settings = ObsSettings {
+            enabled: true,
+            url: format!("ws://{}", listener.local_addr().unwrap()),
+            password: None,
+            on_event: vec![ObsEventBinding {
+                event: ObsEvent::SceneChanged {
+                    scene: Some("Intro".to_string()),
+                },
+                action: ObsAction::Play {
+                    sound: "airhorn".to_string(),
+                },
+            }],
+            on_sound: vec![ObsSoundBinding {
+                sound: "airhorn".to_string(),
+                when: SoundEdge::Started,
+                request: "SetCurrentProgramScene".to_string(),
+                data: Some(json!({"sceneName": "Main"})),
+            }],
+        };
+
+        let (actions, received_actions) = mpsc::channel();
+        let actions = Arc::new(Mutex::new(actions));
+        let (events, receiver) = mpsc::channel();
+        start(
+            &settings,
+            move |request| {
+                actions.lock().unwrap().send(request).unwrap();
+                Response::Ok
+            },
+            receiver,
+        );
+
+        let mut socket = accept(&listener);
+        handshake(&mut socket, None);
+        send(
+            &mut socket,
+            op::IDENTIFIED,
+            json!({"negotiatedRpcVersion": 1}),
+        )
+        .unwrap();
+
+        // OBS to daemon
+        let event = json!({
+            "eventType": "CurrentProgramSceneChanged",
+            "eventIntent": 4,
+            "eventData": {"sceneName": "Intro"},
+        });
+        send(&mut socket, op::EVENT, event).unwrap();
+        let action = received_actions
+            .recv_timeout(Duration::from_secs(5))
+            .unwrap();
+        assert!(matches!(action, Request::Play { id } if id == "airhorn"));
+
+        // Daemon to OBS, and the response back
+        events
+            .send(ServerMessage::Event(Event::SoundStarted {
+                voice: 1,
+                id: "airhorn".to_string(),
+            }))
+            .unwrap();
+        let (op, request) = read_op(&mut socket);
+        assert_eq!(op, op::REQUEST);
+        assert_eq!(request["requestType"], "SetCurrentProgramScene");
+        assert_eq!(request["requestData"], json!({"sceneName": "Main"}));
+        let response = json!({
+            "requestType": "SetCurrentProgramScene",
+            "requestId": request["requestId"],
+            "requestStatus": {"result": true, "code": 100},
+        });
+        send(&mut socket, op::REQUEST_RESPONSE, response).unwrap();
+
+        socket.close(None).unwrap();
+        socket.flush().ok();
+        drop(socket);
+
+        let mut socket = accept(&listener);
+        let identify = handshake(&mut socket, None);
+        assert_eq!(identify["rpcVersion"], RPC_VERSION);
+    }
+
+    #[test]
+    fn parses_events() {
+        let scene = json!({
+            "eventType": "CurrentProgramSceneChanged",
+            "eventData": {"sceneName": "Main"},
+        });
+        assert_eq!(
+            parse_event(&scene),
+            Some(ObsEvent::SceneChanged {
+                scene: Some("Main".to_string())
+            })
+        );
+
+        let recording = json!({
+            "eventType": "RecordStateChanged",
+            "eventData": {"outputActive": true, "outputState": "OBS_WEBSOCKET_OUTPUT_STARTED"},
+        });
+        assert_eq!(parse_event(&recording), Some(ObsEvent::RecordingStarted));
+
+        // Starting and stopping states are in between and ignored
+        let stream = json!({
+            "eventType": "StreamStateChanged",
+            "eventData": {"outputActive": false, "outputState": "OBS_WEBSOCKET_OUTPUT_STOPPING"},
+        });
+        assert_eq!(parse_event(&stream), None);
+    }
+
+    #[test]
+    fn scene_bindings_without_a_scene_match_any() {
+        let binding = ObsEventBinding {
+            event: ObsEvent::SceneChanged { scene: None },
+            action: ObsAction::StopAll,
+        };
+        assert!(binding.matches(&ObsEvent::SceneChanged {
+            scene: Some("Main".to_string())
+        }));
+        assert!(!binding.matches(&ObsEvent::StreamStarted));
+    }
+}
@@ -0,0 +1,225 @@
+//! Minimal OSC 1.0 encoding and decoding, covering the argument types sent by
+//! common control surfaces.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OscArg {
+    Int(i32),
+    Float(f32),
+    String(String),
+    Bool(bool),
+}
+
+impl OscArg {
+    /// Numeric and boolean arguments as a float
+    pub fn as_f32(&self) -> Option<f32> {
+        match *self {
+            OscArg::Int(i) => Some(i as f32),
+            OscArg::Float(f) => Some(f),
+            OscArg::Bool(b) => Some(if b { 1.0 } else { 0.0 }),
+            OscArg::String(_) => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OscMessage {
+    pub address: String,
+    pub args: Vec<OscArg>,
+}
+
+impl OscMessage {
+    pub fn new(address: impl Into<String>, args: Vec<OscArg>) -> Self {
+        Self {
+            address: address.into(),
+            args,
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut packet = Vec::new();
+        push_string(&mut packet, &self.address);
+
+        let mut type_tags = String::from(",");
+        for arg in &self.args {
+            type_tags.push(match arg {
+                OscArg::Int(_) => 'i',
+                OscArg::Float(_) => 'f',
+                OscArg::String(_) => 's',
+                OscArg::Bool(true) => 'T',
+                OscArg::Bool(false) => 'F',
+            });
+        }
+        push_string(&mut packet, &type_tags);
+
+        for arg in &self.args {
+            match arg {
+                OscArg::Int(i) => packet.extend_from_slice(&i.to_be_bytes()),
+                OscArg::Float(f) => packet.extend_from_slice(&f.to_be_bytes()),
+                OscArg::String(s) => push_string(&mut packet, s),
+                OscArg::Bool(_) => {}
+            }
+        }
+
+        packet
+    }
+}
+
+/// Decode a packet into its messages, flattening bundles. Timetags are ignored
+/// and everything is handled as soon as it arrives.
+pub fn decode_packet(packet: &[u8]) -> Option<Vec<OscMessage>> {
+    let mut messages = Vec::new();
+    decode_into(packet, &mut messages)?;
+    Some(messages)
+}
+
+fn decode_into(packet: &[u8], messages: &mut Vec<OscMessage>) -> Option<()> {
+    let mut reader = Reader { data: packet };
+
+    if packet.starts_with(b"#bundle\0") {
+        reader.take(16)?; // "#bundle\0" and the timetag
+        while !reader.data.is_empty() {
+            let size = reader.i32()?;
+            let element = reader.take(usize::try_from(size).ok()?)?;
+            decode_into(element, messages)?;
+        }
+        return Some(());
+    }
+
+    let address = reader.string()?;
+    // Some senders leave out the type tag string when there are no arguments
+    let type_tags = if reader.data.is_empty() {
+        String::from(",")
+    } else {
+        reader.string()?
+    };
+
+    let mut args = Vec::new();
+    for tag in type_tags.strip_prefix(',')?.chars() {
+        args.push(match tag {
+            'i' => OscArg::Int(reader.i32()?),
+            'f' => OscArg::Float(f32::from_bits(reader.i32()? as u32)),
+            's' | 'S' => OscArg::String(reader.string()?),
+            'h' => OscArg::Int(i64::from_be_bytes(reader.take(8)?.try_into().ok()?) as i32),
+            'd' => OscArg::Float(f64::from_be_bytes(reader.take(8)?.try_into().ok()?) as f32),
+            'T' => OscArg::Bool(true),
+            'F' => OscArg::Bool(false),
+            // Nil and impulse carry no data
+            'N' | 'I' => continue,
+            _ => return None,
+        });
+    }
+
+    messages.push(OscMessage { address, args });
+    Some(())
+}
+
+/// Append a null terminated string padded to a multiple of four bytes
+fn push_string(packet: &mut Vec<u8>, s: &str) {
+    packet.extend_from_slice(s.as_bytes());
+    let padding = 4 - s.len() % 4;
+    packet.extend(std::iter::repeat_n(0, padding));
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if n > self.data.len() {
+            return None;
+        }
+        let (taken, rest) = self.data.split_at(n);
+        self.data = rest;
+        Some(taken)
+    }
+
+    fn i32(&mut self) -> Option<i32> {
+        Some(i32::from_be_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    fn string(&mut self) -> Option<String> {
+        let end = self.data.iter().position(|&b| b == 0)?;
+        let s = std::str::from_utf8(&self.data[..end]).ok()?.to_string();
+        self.take((end / 4 + 1) * 4)?;
+        Some(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_every_argument_type() {
+        let message = OscMessage::new(
+            "/boomcrab/volume/a",
+            vec![
+                OscArg::Int(-3),
+                OscArg::Float(0.5),
+                OscArg::String("four".to_string()),
+                OscArg::Bool(true),
+                OscArg::Bool(false),
+            ],
+        );
+        let packet = message.encode();
+        assert_eq!(packet.len() % 4, 0);
+        assert_eq!(decode_packet(&packet), Some(vec![message]));
+    }
+
+    #[test]
+    fn pads_strings_to_four_bytes() {
+        let packet = OscMessage::new("/abc", vec![]).encode();
+        assert_eq!(packet, b"/abc\0\0\0\0,\0\0\0");
+    }
+
+    #[test]
+    fn decodes_messages_without_type_tags() {
+        assert_eq!(
+            decode_packet(b"/boomcrab/stop\0\0"),
+            Some(vec![OscMessage::new("/boomcrab/stop", vec![])])
+        );
+    }
+
+    #[test]
+    fn flattens_bundles() {
+        let first = OscMessage::new("/a", vec![OscArg::Int(1)]).encode();
+        let second = OscMessage::new("/b", vec![]).encode();
+
+        let mut bundle = b"#bundle\0".to_vec();
+        bundle.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1]);
+        for element in [&first, &second] {
+            bundle.extend_from_slice(&(element.len() as i32).to_be_bytes());
+            bundle.extend_from_slice(element);
+        }
+
+        assert_eq!(
+            decode_packet(&bundle),
+            Some(vec![
+                OscMessage::new("/a", vec![OscArg::Int(1)]),
+                OscMessage::new("/b", vec![]),
+            ])
+        );
+    }
+
+    #[test]
+    fn widens_and_narrows_64_bit_arguments() {
+        let mut packet = b"/x\0\0,hd\0".to_vec();
+        packet.extend_from_slice(&7i64.to_be_bytes());
+        packet.extend_from_slice(&0.25f64.to_be_bytes());
+        assert_eq!(
+            decode_packet(&packet),
+            Some(vec![OscMessage::new(
+                "/x",
+                vec![OscArg::Int(7), OscArg::Float(0.25)]
+            )])
+        );
+    }
+
+    #[test]
+    fn rejects_truncated_and_unknown_arguments() {
+        assert_eq!(decode_packet(b"/x\0\0,i\0\0\0\0"), None);
+        assert_eq!(decode_packet(b"/x\0\0,b\0\0"), None);
+        assert_eq!(decode_packet(b"/unterminated"), None);
+    }
+}
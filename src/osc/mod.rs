@@ -0,0 +1,316 @@
+pub mod message;
+
+use std::collections::HashMap;
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use message::{OscArg, OscMessage, decode_packet};
+
+use crate::daemon::protocol::{Event, ServerMessage};
+use crate::settings::osc::OscSettings;
+use crate::ui::UiAction;
+
+/// Prefix of every address BoomCrab listens to and sends
+const ADDRESS_PREFIX: &str = "/boomcrab/";
+/// Largest UDP datagram we accept
+const MAX_PACKET_SIZE: usize = 65_536;
+/// Clients that sent nothing for this long stop getting state updates
+const PEER_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+/// Most clients that get state updates besides the configured targets
+const MAX_PEERS: usize = 32;
+
+/// OSC listener on UDP.
+///
+/// Understands these addresses:
+/// - `/boomcrab/play/<id>` or `/boomcrab/play ,s <id>`
+/// - `/boomcrab/stop` to stop everything, `/boomcrab/stop/<id>` for one sound
+/// - `/boomcrab/fade` to fade out everything
+/// - `/boomcrab/volume ,f <volume>` for the master volume, `/boomcrab/volume/<id> ,f <volume>`
+///
+/// Stop ignores messages whose first argument is zero, which is what buttons
+/// send on release; for play they release sounds in hold mode. State changes are sent back as
+/// `/boomcrab/playing/<id> ,i 1|0` and the volume addresses above.
+pub struct OscServer {
+    pub local_addr: SocketAddr,
+}
+
+impl OscServer {
+    /// Start listening. `on_action` runs on the listener thread for every action
+    /// received, and `events` feeds the state updates sent back to clients.
+    pub fn start<F>(
+        settings: &OscSettings,
+        on_action: F,
+        events: Receiver<ServerMessage>,
+    ) -> io::Result<Self>
+    where
+        F: Fn(UiAction) + Send + 'static,
+    {
+        let socket = UdpSocket::bind((settings.bind_address.as_str(), settings.port))?;
+        let local_addr = socket.local_addr()?;
+
+        let mut targets = Vec::new();
+        for target in &settings.targets {
+            match target.to_socket_addrs() {
+                Ok(addrs) => targets.extend(addrs),
+                Err(e) => eprintln!("Warning: Ignoring OSC target '{}' ({}).", target, e),
+            }
+        }
+        let peers = Arc::new(Mutex::new(Peers::new(targets)));
+
+        let sender = StateSender {
+            socket: socket.try_clone()?,
+            peers: Arc::clone(&peers),
+            playing: HashMap::new(),
+        };
+        thread::spawn(move || sender.run(events));
+
+        let reply_port = settings.reply_port;
+        thread::spawn(move || {
+            let mut buffer = vec![0; MAX_PACKET_SIZE];
+            loop {
+                let (len, peer) = match socket.recv_from(&mut buffer) {
+                    Ok(received) => received,
+                    Err(e) => {
+                        eprintln!("Warning: OSC receive failed ({}).", e);
+                        continue;
+                    }
+                };
+
+                let Some(messages) = decode_packet(&buffer[..len]) else {
+                    continue;
+                };
+
+                // Anyone who talks to us gets state updates from then on
+                let reply_to = SocketAddr::new(peer.ip(), reply_port.unwrap_or(peer.port()));
+                peers.lock().unwrap().seen(reply_to, Instant::now());
+
+                for action in messages.iter().filter_map(action_for_message) {
+                    on_action(action);
+                }
+            }
+        });
+
+        Ok(Self { local_addr })
+    }
+}
+
+/// Where state updates go: the configured targets, plus every client that
+/// talked to us recently
+struct Peers {
+    targets: Vec<SocketAddr>,
+    /// Clients with the time they last sent something, oldest first
+    recent: Vec<(SocketAddr, Instant)>,
+}
+
+impl Peers {
+    fn new(targets: Vec<SocketAddr>) -> Self {
+        Self {
+            targets,
+            recent: Vec::new(),
+        }
+    }
+
+    fn seen(&mut self, peer: SocketAddr, now: Instant) {
+        if self.targets.contains(&peer) {
+            return;
+        }
+        self.recent.retain(|(addr, _)| *addr != peer);
+        if self.recent.len() >= MAX_PEERS {
+            self.recent.remove(0);
+        }
+        self.recent.push((peer, now));
+    }
+
+    /// Drop clients that went quiet and list everyone left
+    fn current(&mut self, now: Instant) -> impl Iterator<Item = &SocketAddr> {
+        self.recent
+            .retain(|(_, last_seen)| now.duration_since(*last_seen) < PEER_TIMEOUT);
+        self.targets
+            .iter()
+            .chain(self.recent.iter().map(|(addr, _)| addr))
+    }
+}
+
+/// Map an incoming message onto the action the keyboard would trigger
+fn action_for_message(message: &OscMessage) -> Option<UiAction> {
+    let path = message.address.strip_prefix(ADDRESS_PREFIX)?;
+
+    let pressed = message
+        .args
+        .first()
+        .and_then(OscArg::as_f32)
+        .is_none_or(|value| value > 0.0);
+    let value = message.args.iter().find_map(OscArg::as_f32);
+    let string_arg = message.args.iter().find_map(|arg| match arg {
+        OscArg::String(s) => Some(s.clone()),
+        _ => None,
+    });
+
+    let (command, sound_id) = match path.split_once('/') {
+        Some((command, sound_id)) => (command, Some(sound_id.to_string())),
+        None => (path, string_arg),
+    };
+
+    match (command, sound_id) {
+        ("play", Some(sound_id)) if pressed => Some(UiAction::PlaySound(sound_id)),
+        ("play", Some(sound_id)) => Some(UiAction::ReleaseSound(sound_id)),
+        ("stop", Some(sound_id)) if pressed => Some(UiAction::StopSound(sound_id)),
+        ("stop", None) if pressed => Some(UiAction::StopAll),
+        ("fade", None) if pressed => Some(UiAction::FadeOutAll),
+        ("pause", sound_id) => Some(UiAction::Pause(sound_id, pressed)),
+        ("volume", sound_id) => Some(UiAction::SetVolume(sound_id, value?.max(0.0))),
+        ("speed", None) => Some(UiAction::NudgeSpeed(value.filter(|v| *v > 0.0)?)),
+        _ => None,
+    }
+}
+
+/// Sends daemon events out as OSC state updates
+struct StateSender {
+    socket: UdpSocket,
+    peers: Arc<Mutex<Peers>>,
+    /// Number of playing voices per sound id
+    playing: HashMap<String, usize>,
+}
+
+impl StateSender {
+    fn run(mut self, events: Receiver<ServerMessage>) {
+        for message in events {
+            let ServerMessage::Event(event) = message else {
+                continue;
+            };
+
+            if let Some(message) = self.message_for_event(event) {
+                let packet = message.encode();
+                for peer in self.peers.lock().unwrap().current(Instant::now()) {
+                    self.socket.send_to(&packet, peer).ok();
+                }
+            }
+        }
+    }
+
+    fn message_for_event(&mut self, event: Event) -> Option<OscMessage> {
+        match event {
+            Event::SoundStarted { id, .. } => {
+                let voices = self.playing.entry(id.clone()).or_default();
+                *voices += 1;
+                (*voices == 1).then(|| playing_message(&id, true))
+            }
+            Event::SoundStopped { id, .. } => {
+                let voices = self.playing.get_mut(&id)?;
+                *voices -= 1;
+                if *voices > 0 {
+                    return None;
+                }
+                self.playing.remove(&id);
+                Some(playing_message(&id, false))
+            }
+            Event::VolumeChanged { id, volume } => {
+                let address = match id {
+                    Some(id) => format!("{}volume/{}", ADDRESS_PREFIX, id),
+                    None => format!("{}volume", ADDRESS_PREFIX),
+                };
+                Some(OscMessage::new(address, vec![OscArg::Float(volume)]))
+            }
+            Event::MidiLearned { .. }
+            | Event::EffectsChanged { .. }
+            | Event::TrimChanged { .. }
+            | Event::VoicePresetChanged { .. }
+            | Event::WaveformReady { .. }
+            | Event::MicLatency { .. }
+            | Event::MicGainReduction { .. }
+            | Event::SoundProgress { .. }
+            | Event::Levels { .. } => None,
+        }
+    }
+}
+
+fn playing_message(sound_id: &str, playing: bool) -> OscMessage {
+    OscMessage::new(
+        format!("{}playing/{}", ADDRESS_PREFIX, sound_id),
+        vec![OscArg::Int(playing as i32)],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    fn message(address: &str, args: Vec<OscArg>) -> OscMessage {
+        OscMessage::new(address, args)
+    }
+
+    #[test]
+    fn maps_addresses_to_actions() {
+        assert_eq!(
+            action_for_message(&message("/boomcrab/play/airhorn", vec![])),
+            Some(UiAction::PlaySound("airhorn".to_string()))
+        );
+        assert_eq!(
+            action_for_message(&message(
+                "/boomcrab/play",
+                vec![OscArg::String("airhorn".to_string())]
+            )),
+            Some(UiAction::PlaySound("airhorn".to_string()))
+        );
+        assert_eq!(
+            action_for_message(&message("/boomcrab/play/airhorn", vec![OscArg::Float(0.0)])),
+            Some(UiAction::ReleaseSound("airhorn".to_string()))
+        );
+        assert_eq!(
+            action_for_message(&message("/boomcrab/stop", vec![OscArg::Int(0)])),
+            None
+        );
+        assert_eq!(
+            action_for_message(&message("/boomcrab/volume", vec![OscArg::Float(-1.0)])),
+            Some(UiAction::SetVolume(None, 0.0))
+        );
+        assert_eq!(action_for_message(&message("/other/play/a", vec![])), None);
+    }
+
+    #[test]
+    fn targets_are_always_kept() {
+        let start = Instant::now();
+        let mut peers = Peers::new(vec![addr(9000)]);
+        peers.seen(addr(9000), start);
+        peers.seen(addr(9001), start);
+
+        let later = start + PEER_TIMEOUT;
+        let current: Vec<_> = peers.current(later).copied().collect();
+        assert_eq!(current, vec![addr(9000)]);
+    }
+
+    #[test]
+    fn active_peers_are_kept() {
+        let start = Instant::now();
+        let mut peers = Peers::new(Vec::new());
+        peers.seen(addr(9001), start);
+        peers.seen(addr(9002), start);
+        peers.seen(addr(9001), start + PEER_TIMEOUT / 2);
+
+        let later = start + PEER_TIMEOUT;
+        let current: Vec<_> = peers.current(later).copied().collect();
+        assert_eq!(current, vec![addr(9001)]);
+    }
+
+    #[test]
+    fn oldest_peer_makes_room() {
+        let now = Instant::now();
+        let mut peers = Peers::new(Vec::new());
+        for port in 0..=MAX_PEERS as u16 {
+            peers.seen(addr(10_000 + port), now);
+        }
+
+        let current: Vec<_> = peers.current(now).copied().collect();
+        assert_eq!(current.len(), MAX_PEERS);
+        assert!(!current.contains(&addr(10_000)));
+        assert!(current.contains(&addr(10_000 + MAX_PEERS as u16)));
+    }
+}
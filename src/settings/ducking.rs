@@ -0,0 +1,42 @@
+use serde::{Deserialize, Serialize};
+
+/// The `[ducking]` settings section, for turning sounds and the mic down while
+/// other sounds play
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DuckingSettings {
+    /// How far ducked sounds and the mic are turned down
+    pub depth_db: f32,
+    /// How quickly they go down once a triggering sound starts
+    pub attack_ms: u32,
+    /// How quickly they come back up once triggering sounds go quiet
+    pub release_ms: u32,
+    /// Triggering sounds quieter than this don't duck anything
+    pub threshold_db: f32,
+    /// Duck the mic passthrough along with the ducked sounds
+    pub duck_mic: bool,
+}
+
+impl Default for DuckingSettings {
+    fn default() -> Self {
+        Self {
+            depth_db: 12.0,
+            attack_ms: 20,
+            release_ms: 400,
+            threshold_db: -40.0,
+            duck_mic: true,
+        }
+    }
+}
+
+/// A sound's part in ducking
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuckRole {
+    #[default]
+    None,
+    /// Ducks the `ducked` sounds and the mic while it plays
+    Trigger,
+    /// Turned down while a `trigger` sound plays
+    Ducked,
+}
@@ -0,0 +1,319 @@
+use serde::{Deserialize, Serialize};
+
+/// One effect in a chain, from a `[[sounds.<id>.effects]]` or `[[master_effects]]` section such as
+///
+/// ```toml
+/// [[sounds.airhorn.effects]]
+/// type = "low_pass"
+/// cutoff_hz = 2000.0
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EffectSettings {
+    Eq(EqSettings),
+    HighPass(HighPassSettings),
+    LowPass(LowPassSettings),
+    Reverb(ReverbSettings),
+    Delay(DelaySettings),
+    Distortion(DistortionSettings),
+    Bitcrusher(BitcrusherSettings),
+}
+
+/// Three-band equalizer: low shelf, peak and high shelf
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EqSettings {
+    pub low_db: f32,
+    pub low_hz: f32,
+    pub mid_db: f32,
+    pub mid_hz: f32,
+    pub mid_q: f32,
+    pub high_db: f32,
+    pub high_hz: f32,
+}
+
+impl Default for EqSettings {
+    fn default() -> Self {
+        Self {
+            low_db: 0.0,
+            low_hz: 200.0,
+            mid_db: 0.0,
+            mid_hz: 1000.0,
+            mid_q: 0.7,
+            high_db: 0.0,
+            high_hz: 5000.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HighPassSettings {
+    pub cutoff_hz: f32,
+    pub q: f32,
+}
+
+impl Default for HighPassSettings {
+    fn default() -> Self {
+        Self {
+            cutoff_hz: 120.0,
+            q: 0.707,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LowPassSettings {
+    pub cutoff_hz: f32,
+    pub q: f32,
+}
+
+impl Default for LowPassSettings {
+    fn default() -> Self {
+        Self {
+            cutoff_hz: 5000.0,
+            q: 0.707,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReverbSettings {
+    /// From 0.0 for a small room to 1.0 for a hall
+    pub room_size: f32,
+    /// How quickly high frequencies die away, from 0.0 to 1.0
+    pub damping: f32,
+    /// Share of the reverberated signal in the output
+    pub mix: f32,
+}
+
+impl Default for ReverbSettings {
+    fn default() -> Self {
+        Self {
+            room_size: 0.7,
+            damping: 0.5,
+            mix: 0.3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DelaySettings {
+    pub time_ms: f32,
+    /// Share of each echo fed back into the next
+    pub feedback: f32,
+    pub mix: f32,
+}
+
+impl Default for DelaySettings {
+    fn default() -> Self {
+        Self {
+            time_ms: 350.0,
+            feedback: 0.4,
+            mix: 0.35,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DistortionSettings {
+    pub drive_db: f32,
+    pub mix: f32,
+}
+
+impl Default for DistortionSettings {
+    fn default() -> Self {
+        Self {
+            drive_db: 12.0,
+            mix: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BitcrusherSettings {
+    pub bits: f32,
+    /// Each sample is held for this many output samples
+    pub downsample: f32,
+    pub mix: f32,
+}
+
+impl Default for BitcrusherSettings {
+    fn default() -> Self {
+        Self {
+            bits: 8.0,
+            downsample: 4.0,
+            mix: 1.0,
+        }
+    }
+}
+
+/// Describes one of an effect's parameters, for editing it
+#[derive(Debug, Clone, Copy)]
+pub struct EffectParam {
+    pub name: &'static str,
+    pub unit: &'static str,
+    pub min: f32,
+    pub max: f32,
+    /// Change per step; frequencies are multiplied by `1 + step` instead
+    pub step: f32,
+    pub logarithmic: bool,
+}
+
+const fn param(
+    name: &'static str,
+    unit: &'static str,
+    min: f32,
+    max: f32,
+    step: f32,
+) -> EffectParam {
+    EffectParam {
+        name,
+        unit,
+        min,
+        max,
+        step,
+        logarithmic: false,
+    }
+}
+
+const fn frequency(name: &'static str) -> EffectParam {
+    EffectParam {
+        name,
+        unit: "Hz",
+        min: 20.0,
+        max: 20_000.0,
+        step: 0.1,
+        logarithmic: true,
+    }
+}
+
+const GAIN_DB: (f32, f32, f32) = (-24.0, 24.0, 0.5);
+const Q: EffectParam = param("q", "", 0.1, 10.0, 0.05);
+const MIX: EffectParam = param("mix", "", 0.0, 1.0, 0.05);
+
+const EQ_PARAMS: [EffectParam; 7] = [
+    param("low", "dB", GAIN_DB.0, GAIN_DB.1, GAIN_DB.2),
+    frequency("low freq"),
+    param("mid", "dB", GAIN_DB.0, GAIN_DB.1, GAIN_DB.2),
+    frequency("mid freq"),
+    Q,
+    param("high", "dB", GAIN_DB.0, GAIN_DB.1, GAIN_DB.2),
+    frequency("high freq"),
+];
+const FILTER_PARAMS: [EffectParam; 2] = [frequency("cutoff"), Q];
+const REVERB_PARAMS: [EffectParam; 3] = [
+    param("room size", "", 0.0, 1.0, 0.05),
+    param("damping", "", 0.0, 1.0, 0.05),
+    MIX,
+];
+const DELAY_PARAMS: [EffectParam; 3] = [
+    param("time", "ms", 10.0, 2000.0, 10.0),
+    param("feedback", "", 0.0, 0.95, 0.05),
+    MIX,
+];
+const DISTORTION_PARAMS: [EffectParam; 2] = [param("drive", "dB", 0.0, 48.0, 1.0), MIX];
+const BITCRUSHER_PARAMS: [EffectParam; 3] = [
+    param("bits", "", 1.0, 16.0, 1.0),
+    param("downsample", "x", 1.0, 32.0, 1.0),
+    MIX,
+];
+
+impl EffectSettings {
+    /// One of each kind of effect with default settings, in the order the editor cycles through them
+    pub fn defaults() -> [EffectSettings; 7] {
+        [
+            EffectSettings::Eq(EqSettings::default()),
+            EffectSettings::HighPass(HighPassSettings::default()),
+            EffectSettings::LowPass(LowPassSettings::default()),
+            EffectSettings::Reverb(ReverbSettings::default()),
+            EffectSettings::Delay(DelaySettings::default()),
+            EffectSettings::Distortion(DistortionSettings::default()),
+            EffectSettings::Bitcrusher(BitcrusherSettings::default()),
+        ]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EffectSettings::Eq(_) => "EQ",
+            EffectSettings::HighPass(_) => "High-pass",
+            EffectSettings::LowPass(_) => "Low-pass",
+            EffectSettings::Reverb(_) => "Reverb",
+            EffectSettings::Delay(_) => "Delay",
+            EffectSettings::Distortion(_) => "Distortion",
+            EffectSettings::Bitcrusher(_) => "Bitcrusher",
+        }
+    }
+
+    pub fn params(&self) -> &'static [EffectParam] {
+        match self {
+            EffectSettings::Eq(_) => &EQ_PARAMS,
+            EffectSettings::HighPass(_) | EffectSettings::LowPass(_) => &FILTER_PARAMS,
+            EffectSettings::Reverb(_) => &REVERB_PARAMS,
+            EffectSettings::Delay(_) => &DELAY_PARAMS,
+            EffectSettings::Distortion(_) => &DISTORTION_PARAMS,
+            EffectSettings::Bitcrusher(_) => &BITCRUSHER_PARAMS,
+        }
+    }
+
+    /// Parameter values, in the same order as [`EffectSettings::params`]
+    pub fn values(&self) -> Vec<f32> {
+        let mut effect = self.clone();
+        effect
+            .values_mut()
+            .into_iter()
+            .map(|value| *value)
+            .collect()
+    }
+
+    fn values_mut(&mut self) -> Vec<&mut f32> {
+        match self {
+            EffectSettings::Eq(eq) => vec![
+                &mut eq.low_db,
+                &mut eq.low_hz,
+                &mut eq.mid_db,
+                &mut eq.mid_hz,
+                &mut eq.mid_q,
+                &mut eq.high_db,
+                &mut eq.high_hz,
+            ],
+            EffectSettings::HighPass(filter) => vec![&mut filter.cutoff_hz, &mut filter.q],
+            EffectSettings::LowPass(filter) => vec![&mut filter.cutoff_hz, &mut filter.q],
+            EffectSettings::Reverb(reverb) => {
+                vec![&mut reverb.room_size, &mut reverb.damping, &mut reverb.mix]
+            }
+            EffectSettings::Delay(delay) => {
+                vec![&mut delay.time_ms, &mut delay.feedback, &mut delay.mix]
+            }
+            EffectSettings::Distortion(distortion) => {
+                vec![&mut distortion.drive_db, &mut distortion.mix]
+            }
+            EffectSettings::Bitcrusher(crusher) => {
+                vec![&mut crusher.bits, &mut crusher.downsample, &mut crusher.mix]
+            }
+        }
+    }
+
+    /// Move parameter `index` by `steps` steps, keeping it within its range
+    pub fn adjust(&mut self, index: usize, steps: i32) {
+        let Some(param) = self.params().get(index).copied() else {
+            return;
+        };
+        let Some(value) = self.values_mut().into_iter().nth(index) else {
+            return;
+        };
+
+        let adjusted = if param.logarithmic {
+            *value * (1.0 + param.step).powi(steps)
+        } else {
+            // Rounded to the step so repeated steps don't drift
+            ((*value + param.step * steps as f32) / param.step).round() * param.step
+        };
+        *value = adjusted.clamp(param.min, param.max);
+    }
+}
@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+
+/// The `[fade]` settings section, with the fades used by sounds that don't set their own
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FadeSettings {
+    pub fade_in_ms: u32,
+    pub fade_out_ms: u32,
+    pub curve: FadeCurve,
+    /// Length of the fade used by the "fade out all" action
+    pub fade_out_all_ms: u32,
+}
+
+impl Default for FadeSettings {
+    fn default() -> Self {
+        Self {
+            fade_in_ms: 0,
+            fade_out_ms: 0,
+            curve: FadeCurve::default(),
+            fade_out_all_ms: 1500,
+        }
+    }
+}
+
+/// How the volume moves over the course of a fade
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FadeCurve {
+    Linear,
+    /// Slow at the quiet end and fast at the loud end, which sounds even to the ear
+    Exponential,
+    /// Keeps the combined loudness steady while two sounds crossfade
+    #[default]
+    EqualPower,
+}
@@ -0,0 +1,56 @@
+use serde::{Deserialize, Serialize};
+
+/// The `[hotkeys]` settings section, for global hotkeys read from input devices
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HotkeySettings {
+    pub enabled: bool,
+    /// Only read devices whose name contains this text. When unset, every
+    /// device with one of the bound keys is read.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device: Option<String>,
+    pub bindings: Vec<HotkeyBinding>,
+}
+
+/// Maps a key to an action, written in the settings file as e.g.
+///
+/// ```toml
+/// [[hotkeys.bindings]]
+/// key = "KEY_F13"
+/// action = "play"
+/// sound = "airhorn"
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HotkeyBinding {
+    /// A Linux input key name, such as `KEY_F13` or `KEY_KP1`
+    pub key: String,
+    #[serde(flatten)]
+    pub action: HotkeyAction,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum HotkeyAction {
+    Play {
+        sound: String,
+        /// Play from this cue instead of the start
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cue: Option<String>,
+    },
+    Stop {
+        sound: String,
+    },
+    StopAll,
+    FadeOutAll,
+    /// Play every sound, including ones started meanwhile, `factor` times as fast
+    /// while the key is held, e.g. 1.5 to speed them up or 0.75 to slow them down
+    Speed {
+        factor: f32,
+    },
+    /// Switch the voice changer to `preset`, or turn it off when there is none
+    VoicePreset {
+        #[serde(default)]
+        preset: Option<String>,
+    },
+    NextVoicePreset,
+}
@@ -0,0 +1,766 @@
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{Deserialize, Serialize};
+
+/// Named actions that can be bound to keys in the `[keymap]` settings section.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum KeyAction {
+    ShowHome,
+    ShowConfig,
+    ShowEffects,
+    ShowTrim,
+    ShowSpectrum,
+    ShowPlaying,
+    ShowHelp,
+    SelectNext,
+    SelectPrevious,
+    PlaySelected,
+    StopSelected,
+    StopAll,
+    FadeOutAll,
+    VolumeUp,
+    VolumeDown,
+    SpeedUp,
+    SlowDown,
+    MidiLearn,
+    NextVoicePreset,
+    RefreshDevices,
+    ToggleEffectsTarget,
+    AddEffect,
+    RemoveEffect,
+    MoveEffectUp,
+    MoveEffectDown,
+    AddCue,
+    RemoveCue,
+    ToggleSpectrumSource,
+    TogglePause,
+    TogglePauseAll,
+    IncreaseValue,
+    DecreaseValue,
+    IncreaseValueLarge,
+    DecreaseValueLarge,
+    Quit,
+}
+
+impl KeyAction {
+    /// All actions, in the order they are shown in the footer and help screen.
+    pub const ALL: [KeyAction; 35] = [
+        KeyAction::ShowHome,
+        KeyAction::ShowConfig,
+        KeyAction::ShowEffects,
+        KeyAction::ShowTrim,
+        KeyAction::ShowSpectrum,
+        KeyAction::ShowPlaying,
+        KeyAction::ShowHelp,
+        KeyAction::SelectNext,
+        KeyAction::SelectPrevious,
+        KeyAction::PlaySelected,
+        KeyAction::StopSelected,
+        KeyAction::StopAll,
+        KeyAction::FadeOutAll,
+        KeyAction::VolumeUp,
+        KeyAction::VolumeDown,
+        KeyAction::SpeedUp,
+        KeyAction::SlowDown,
+        KeyAction::MidiLearn,
+        KeyAction::NextVoicePreset,
+        KeyAction::RefreshDevices,
+        KeyAction::ToggleEffectsTarget,
+        KeyAction::AddEffect,
+        KeyAction::RemoveEffect,
+        KeyAction::MoveEffectUp,
+        KeyAction::MoveEffectDown,
+        KeyAction::AddCue,
+        KeyAction::RemoveCue,
+        KeyAction::ToggleSpectrumSource,
+        KeyAction::TogglePause,
+        KeyAction::TogglePauseAll,
+        KeyAction::IncreaseValue,
+        KeyAction::DecreaseValue,
+        KeyAction::IncreaseValueLarge,
+        KeyAction::DecreaseValueLarge,
+        KeyAction::Quit,
+    ];
+
+    /// Name used for the action in the settings file
+    pub fn name(&self) -> &'static str {
+        match self {
+            KeyAction::ShowHome => "show_home",
+            KeyAction::ShowConfig => "show_config",
+            KeyAction::ShowEffects => "show_effects",
+            KeyAction::ShowTrim => "show_trim",
+            KeyAction::ShowSpectrum => "show_spectrum",
+            KeyAction::ShowPlaying => "show_playing",
+            KeyAction::ShowHelp => "show_help",
+            KeyAction::SelectNext => "select_next",
+            KeyAction::SelectPrevious => "select_previous",
+            KeyAction::PlaySelected => "play_selected",
+            KeyAction::StopSelected => "stop_selected",
+            KeyAction::StopAll => "stop_all",
+            KeyAction::FadeOutAll => "fade_out_all",
+            KeyAction::VolumeUp => "volume_up",
+            KeyAction::VolumeDown => "volume_down",
+            KeyAction::SpeedUp => "speed_up",
+            KeyAction::SlowDown => "slow_down",
+            KeyAction::MidiLearn => "midi_learn",
+            KeyAction::NextVoicePreset => "next_voice_preset",
+            KeyAction::RefreshDevices => "refresh_devices",
+            KeyAction::ToggleEffectsTarget => "toggle_effects_target",
+            KeyAction::AddEffect => "add_effect",
+            KeyAction::RemoveEffect => "remove_effect",
+            KeyAction::MoveEffectUp => "move_effect_up",
+            KeyAction::MoveEffectDown => "move_effect_down",
+            KeyAction::AddCue => "add_cue",
+            KeyAction::RemoveCue => "remove_cue",
+            KeyAction::ToggleSpectrumSource => "toggle_spectrum_source",
+            KeyAction::TogglePause => "toggle_pause",
+            KeyAction::TogglePauseAll => "toggle_pause_all",
+            KeyAction::IncreaseValue => "increase_value",
+            KeyAction::DecreaseValue => "decrease_value",
+            KeyAction::IncreaseValueLarge => "increase_value_large",
+            KeyAction::DecreaseValueLarge => "decrease_value_large",
+            KeyAction::Quit => "quit",
+        }
+    }
+
+    /// Short label used in the footer
+    pub fn label(&self) -> &'static str {
+        match self {
+            KeyAction::ShowHome => "Home",
+            KeyAction::ShowConfig => "Config",
+            KeyAction::ShowEffects => "Effects",
+            KeyAction::ShowTrim => "Trim",
+            KeyAction::ShowSpectrum => "Spectrum",
+            KeyAction::ShowPlaying => "Playing",
+            KeyAction::ShowHelp => "Help",
+            KeyAction::SelectNext => "Next",
+            KeyAction::SelectPrevious => "Previous",
+            KeyAction::PlaySelected => "Play",
+            KeyAction::StopSelected => "Stop",
+            KeyAction::StopAll => "Stop All",
+            KeyAction::FadeOutAll => "Fade All",
+            KeyAction::VolumeUp => "Volume Up",
+            KeyAction::VolumeDown => "Volume Down",
+            KeyAction::SpeedUp => "Faster",
+            KeyAction::SlowDown => "Slower",
+            KeyAction::MidiLearn => "MIDI Learn",
+            KeyAction::NextVoicePreset => "Voice",
+            KeyAction::RefreshDevices => "Refresh",
+            KeyAction::ToggleEffectsTarget => "Master/Sound",
+            KeyAction::AddEffect => "Add Effect",
+            KeyAction::RemoveEffect => "Remove Effect",
+            KeyAction::MoveEffectUp => "Move Up",
+            KeyAction::MoveEffectDown => "Move Down",
+            KeyAction::AddCue => "Add Cue",
+            KeyAction::RemoveCue => "Remove Cue",
+            KeyAction::ToggleSpectrumSource => "Output/Mic",
+            KeyAction::TogglePause => "Pause",
+            KeyAction::TogglePauseAll => "Pause All",
+            KeyAction::IncreaseValue => "Increase",
+            KeyAction::DecreaseValue => "Decrease",
+            KeyAction::IncreaseValueLarge => "Increase More",
+            KeyAction::DecreaseValueLarge => "Decrease More",
+            KeyAction::Quit => "Quit",
+        }
+    }
+
+    /// Longer description used on the help screen
+    pub fn description(&self) -> &'static str {
+        match self {
+            KeyAction::ShowHome => "Go to the home page",
+            KeyAction::ShowConfig => "Go to the configuration page",
+            KeyAction::ShowEffects => "Go to the effects page",
+            KeyAction::ShowTrim => "Go to the trim page for the selected sound",
+            KeyAction::ShowSpectrum => "Go to the spectrum analyzer",
+            KeyAction::ShowPlaying => "Go to the list of voices playing now",
+            KeyAction::ShowHelp => "Show this help screen",
+            KeyAction::SelectNext => "Select the next sound, or scroll this help down",
+            KeyAction::SelectPrevious => "Select the previous sound, or scroll this help up",
+            KeyAction::PlaySelected => "Play the selected sound",
+            KeyAction::StopSelected => "Stop the selected sound, or the selected voice",
+            KeyAction::StopAll => "Stop all playing sounds",
+            KeyAction::FadeOutAll => "Fade out all playing sounds",
+            KeyAction::VolumeUp => "Raise the master volume, or the selected voice's volume",
+            KeyAction::VolumeDown => "Lower the master volume, or the selected voice's volume",
+            KeyAction::SpeedUp => "Play every sound a semitone faster while held",
+            KeyAction::SlowDown => "Play every sound a semitone slower while held",
+            KeyAction::MidiLearn => "Bind the next MIDI note or controller to the selected sound",
+            KeyAction::NextVoicePreset => "Switch the mic to the next voice changer preset",
+            KeyAction::RefreshDevices => "Refresh the audio device lists",
+            KeyAction::ToggleEffectsTarget => {
+                "Switch the effects page between the master and the selected sound"
+            }
+            KeyAction::AddEffect => "Add an effect below the selected one",
+            KeyAction::RemoveEffect => "Remove the selected effect",
+            KeyAction::MoveEffectUp => "Move the selected effect earlier in the chain",
+            KeyAction::MoveEffectDown => "Move the selected effect later in the chain",
+            KeyAction::AddCue => "Add a cue at the selected point",
+            KeyAction::RemoveCue => "Remove the selected cue",
+            KeyAction::ToggleSpectrumSource => "Switch the spectrum between the output and the mic",
+            KeyAction::TogglePause => "Pause or resume the selected voice",
+            KeyAction::TogglePauseAll => "Pause every playing sound, or resume them all",
+            KeyAction::IncreaseValue => "Increase the selected value, or change the effect type",
+            KeyAction::DecreaseValue => "Decrease the selected value, or change the effect type",
+            KeyAction::IncreaseValueLarge => "Increase the selected value in larger steps",
+            KeyAction::DecreaseValueLarge => "Decrease the selected value in larger steps",
+            KeyAction::Quit => "Quit BoomCrab",
+        }
+    }
+
+    /// Whether the action is listed in the footer; all actions are on the help screen
+    pub fn in_footer(&self) -> bool {
+        !matches!(
+            self,
+            KeyAction::SelectNext
+                | KeyAction::SelectPrevious
+                | KeyAction::StopSelected
+                | KeyAction::FadeOutAll
+                | KeyAction::VolumeUp
+                | KeyAction::VolumeDown
+                | KeyAction::SpeedUp
+                | KeyAction::SlowDown
+                | KeyAction::MidiLearn
+                | KeyAction::NextVoicePreset
+                | KeyAction::ToggleEffectsTarget
+                | KeyAction::AddEffect
+                | KeyAction::RemoveEffect
+                | KeyAction::MoveEffectUp
+                | KeyAction::MoveEffectDown
+                | KeyAction::AddCue
+                | KeyAction::RemoveCue
+                | KeyAction::ToggleSpectrumSource
+                | KeyAction::TogglePause
+                | KeyAction::TogglePauseAll
+                | KeyAction::IncreaseValue
+                | KeyAction::DecreaseValue
+                | KeyAction::IncreaseValueLarge
+                | KeyAction::DecreaseValueLarge
+        )
+    }
+
+    fn default_keys(&self) -> &'static [&'static str] {
+        match self {
+            KeyAction::ShowHome => &["1"],
+            KeyAction::ShowConfig => &["2"],
+            KeyAction::ShowEffects => &["3"],
+            KeyAction::ShowTrim => &["4"],
+            KeyAction::ShowSpectrum => &["5"],
+            KeyAction::ShowPlaying => &["6"],
+            KeyAction::ShowHelp => &["?"],
+            KeyAction::SelectNext => &["down", "j"],
+            KeyAction::SelectPrevious => &["up", "k"],
+            KeyAction::PlaySelected => &["enter", "space"],
+            KeyAction::StopSelected => &["x"],
+            KeyAction::StopAll => &["s"],
+            KeyAction::FadeOutAll => &["S"],
+            KeyAction::VolumeUp => &["+", "="],
+            KeyAction::VolumeDown => &["-"],
+            KeyAction::SpeedUp => &["]"],
+            KeyAction::SlowDown => &["["],
+            KeyAction::MidiLearn => &["l"],
+            KeyAction::NextVoicePreset => &["v"],
+            KeyAction::RefreshDevices => &["r"],
+            KeyAction::ToggleEffectsTarget => &["m"],
+            KeyAction::AddEffect => &["a"],
+            KeyAction::RemoveEffect => &["d"],
+            KeyAction::MoveEffectUp => &["K"],
+            KeyAction::MoveEffectDown => &["J"],
+            KeyAction::AddCue => &["c"],
+            KeyAction::RemoveCue => &["D"],
+            KeyAction::ToggleSpectrumSource => &["i"],
+            KeyAction::TogglePause => &["p"],
+            KeyAction::TogglePauseAll => &["P"],
+            KeyAction::IncreaseValue => &["right"],
+            KeyAction::DecreaseValue => &["left"],
+            KeyAction::IncreaseValueLarge => &["shift+right"],
+            KeyAction::DecreaseValueLarge => &["shift+left"],
+            KeyAction::Quit => &["q", "esc"],
+        }
+    }
+}
+
+impl FromStr for KeyAction {
+    type Err = KeymapError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        KeyAction::ALL
+            .into_iter()
+            .find(|action| action.name() == s)
+            .ok_or_else(|| KeymapError::UnknownAction(s.to_string()))
+    }
+}
+
+#[derive(Debug)]
+pub enum KeymapError {
+    UnknownAction(String),
+    InvalidKey(String),
+    Conflict {
+        keys: String,
+        first: KeyAction,
+        second: KeyAction,
+    },
+}
+
+impl fmt::Display for KeymapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KeymapError::UnknownAction(name) => write!(f, "Unknown keymap action '{}'", name),
+            KeymapError::InvalidKey(key) => write!(f, "Invalid key '{}'", key),
+            KeymapError::Conflict {
+                keys,
+                first,
+                second,
+            } => write!(
+                f,
+                "Key binding '{}' for '{}' conflicts with a binding for '{}'",
+                keys,
+                second.name(),
+                first.name()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for KeymapError {}
+
+/// A single key press with its modifiers, e.g. `ctrl+c` or `esc`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyPress {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyPress {
+    /// Shift is folded into character keys, so `shift+r` is the same key as `R`
+    /// whether it comes from the settings file or the terminal.
+    fn normalized(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        match code {
+            KeyCode::Char(c) if modifiers.contains(KeyModifiers::SHIFT) => {
+                let mut upper = c.to_uppercase();
+                let code = match (upper.next(), upper.next()) {
+                    (Some(upper), None) => KeyCode::Char(upper),
+                    _ => code,
+                };
+                Self {
+                    code,
+                    modifiers: modifiers - KeyModifiers::SHIFT,
+                }
+            }
+            _ => Self { code, modifiers },
+        }
+    }
+}
+
+impl From<KeyEvent> for KeyPress {
+    fn from(event: KeyEvent) -> Self {
+        Self::normalized(event.code, event.modifiers)
+    }
+}
+
+impl FromStr for KeyPress {
+    type Err = KeymapError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || KeymapError::InvalidKey(s.to_string());
+
+        // A lone "+" is a key, not a separator
+        let (modifier_names, key_name) = match s.rsplit_once('+') {
+            Some(("", "")) => ("", "+"),
+            Some((mods, "")) => (mods.strip_suffix('+').ok_or_else(invalid)?, "+"),
+            Some((mods, key)) => (mods, key),
+            None => ("", s),
+        };
+
+        let mut modifiers = KeyModifiers::NONE;
+        for name in modifier_names.split('+').filter(|m| !m.is_empty()) {
+            modifiers |= match name.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return Err(invalid()),
+            };
+        }
+
+        let mut chars = key_name.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match key_name.to_ascii_lowercase().as_str() {
+                "esc" | "escape" => KeyCode::Esc,
+                "enter" | "return" => KeyCode::Enter,
+                "space" => KeyCode::Char(' '),
+                "tab" => KeyCode::Tab,
+                "backtab" => KeyCode::BackTab,
+                "backspace" => KeyCode::Backspace,
+                "delete" | "del" => KeyCode::Delete,
+                "insert" | "ins" => KeyCode::Insert,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                name => match name.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+                    Some(n @ 1..=24) => KeyCode::F(n),
+                    _ => return Err(invalid()),
+                },
+            },
+        };
+
+        Ok(Self::normalized(code, modifiers))
+    }
+}
+
+impl fmt::Display for KeyPress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "ctrl+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "alt+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            write!(f, "shift+")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::F(n) => write!(f, "f{}", n),
+            KeyCode::Esc => write!(f, "esc"),
+            KeyCode::Enter => write!(f, "enter"),
+            KeyCode::Tab => write!(f, "tab"),
+            KeyCode::BackTab => write!(f, "backtab"),
+            KeyCode::Backspace => write!(f, "backspace"),
+            KeyCode::Delete => write!(f, "delete"),
+            KeyCode::Insert => write!(f, "insert"),
+            KeyCode::Home => write!(f, "home"),
+            KeyCode::End => write!(f, "end"),
+            KeyCode::PageUp => write!(f, "pageup"),
+            KeyCode::PageDown => write!(f, "pagedown"),
+            KeyCode::Up => write!(f, "up"),
+            KeyCode::Down => write!(f, "down"),
+            KeyCode::Left => write!(f, "left"),
+            KeyCode::Right => write!(f, "right"),
+            other => write!(f, "{}", other),
+        }
+    }
+}
+
+/// One or more key presses that must be typed in order, written as e.g. `"g h"`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeySequence(pub Vec<KeyPress>);
+
+impl KeySequence {
+    fn starts_with(&self, prefix: &[KeyPress]) -> bool {
+        self.0.starts_with(prefix)
+    }
+}
+
+impl FromStr for KeySequence {
+    type Err = KeymapError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let presses = s
+            .split_whitespace()
+            .map(KeyPress::from_str)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if presses.is_empty() {
+            return Err(KeymapError::InvalidKey(s.to_string()));
+        }
+
+        Ok(Self(presses))
+    }
+}
+
+impl fmt::Display for KeySequence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, press) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}", press)?;
+        }
+        Ok(())
+    }
+}
+
+/// Result of looking up the keys typed so far
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyLookup {
+    Matched(KeyAction),
+    /// The keys are the start of a longer binding; wait for more input
+    Pending,
+    NoMatch,
+}
+
+/// Key bindings for the TUI, as stored in the `[keymap]` section of the settings file.
+///
+/// Actions missing from the file keep their default bindings. A binding that
+/// conflicts with one already taken is reported and left out, with the file's
+/// bindings taken before the defaults.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "BTreeMap<String, Vec<String>>")]
+#[serde(into = "BTreeMap<String, Vec<String>>")]
+pub struct Keymap {
+    bindings: Vec<(KeyAction, KeySequence)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let bindings = KeyAction::ALL
+            .into_iter()
+            .flat_map(|action| {
+                action
+                    .default_keys()
+                    .iter()
+                    .map(move |keys| (action, keys.parse().expect("invalid default key")))
+            })
+            .collect();
+
+        Self { bindings }
+    }
+}
+
+impl Keymap {
+    /// All key sequences bound to an action
+    pub fn keys_for(&self, action: KeyAction) -> impl Iterator<Item = &KeySequence> {
+        self.bindings
+            .iter()
+            .filter(move |(a, _)| *a == action)
+            .map(|(_, keys)| keys)
+    }
+
+    /// Look up the key presses typed so far
+    pub fn lookup(&self, pressed: &[KeyPress]) -> KeyLookup {
+        let mut pending = false;
+
+        for (action, keys) in &self.bindings {
+            if keys.0 == pressed {
+                return KeyLookup::Matched(*action);
+            }
+            if keys.starts_with(pressed) {
+                pending = true;
+            }
+        }
+
+        if pending {
+            KeyLookup::Pending
+        } else {
+            KeyLookup::NoMatch
+        }
+    }
+
+    /// Add a binding unless it is equal to, or a prefix of, a binding already
+    /// there, or the other way round
+    fn bind(&mut self, action: KeyAction, keys: KeySequence) -> Result<(), KeymapError> {
+        for (bound, bound_keys) in &self.bindings {
+            let (shorter, longer) = if bound_keys.0.len() <= keys.0.len() {
+                (bound_keys, &keys)
+            } else {
+                (&keys, bound_keys)
+            };
+
+            if longer.starts_with(&shorter.0) {
+                return Err(KeymapError::Conflict {
+                    keys: longer.to_string(),
+                    first: *bound,
+                    second: action,
+                });
+            }
+        }
+        self.bindings.push((action, keys));
+        Ok(())
+    }
+}
+
+impl TryFrom<BTreeMap<String, Vec<String>>> for Keymap {
+    type Error = KeymapError;
+
+    fn try_from(config: BTreeMap<String, Vec<String>>) -> Result<Self, Self::Error> {
+        let mut listed = Vec::new();
+        let mut overrides = Vec::new();
+        for (name, keys) in config {
+            let action: KeyAction = name.parse()?;
+            listed.push(action);
+            for keys in keys {
+                overrides.push((action, keys.parse()?));
+            }
+        }
+
+        // The file's bindings replace the defaults of the actions it lists,
+        // and an empty list leaves an action unbound
+        let defaults = Self::default()
+            .bindings
+            .into_iter()
+            .filter(|(action, _)| !listed.contains(action));
+
+        let mut keymap = Self {
+            bindings: Vec::new(),
+        };
+        for (action, keys) in overrides.into_iter().chain(defaults) {
+            if let Err(e) = keymap.bind(action, keys) {
+                eprintln!("Warning: {}, and is left out.", e);
+            }
+        }
+        keymap.bindings.sort_by_key(|(action, _)| *action);
+        Ok(keymap)
+    }
+}
+
+/// Only actions bound differently from the defaults are written, so the
+/// defaults can change without the file holding on to the old ones
+impl From<Keymap> for BTreeMap<String, Vec<String>> {
+    fn from(keymap: Keymap) -> Self {
+        let defaults = Keymap::default();
+        let mut config: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for action in KeyAction::ALL {
+            let keys: Vec<&KeySequence> = keymap.keys_for(action).collect();
+            if keys != defaults.keys_for(action).collect::<Vec<_>>() {
+                config.insert(
+                    action.name().to_string(),
+                    keys.iter().map(|keys| keys.to_string()).collect(),
+                );
+            }
+        }
+        config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(keys: &str) -> KeyPress {
+        keys.parse().unwrap()
+    }
+
+    fn keymap(config: &[(&str, &[&str])]) -> Keymap {
+        let config: BTreeMap<String, Vec<String>> = config
+            .iter()
+            .map(|(name, keys)| {
+                (
+                    name.to_string(),
+                    keys.iter().map(|k| k.to_string()).collect(),
+                )
+            })
+            .collect();
+        Keymap::try_from(config).unwrap()
+    }
+
+    #[test]
+    fn parses_modifiers_and_named_keys() {
+        assert_eq!(
+            press("ctrl+c"),
+            KeyPress {
+                code: KeyCode::Char('c'),
+                modifiers: KeyModifiers::CONTROL
+            }
+        );
+        assert_eq!(press("+").code, KeyCode::Char('+'));
+        assert_eq!(press("ctrl++").modifiers, KeyModifiers::CONTROL);
+        assert_eq!(press("F5").code, KeyCode::F(5));
+        assert_eq!(press("space").code, KeyCode::Char(' '));
+        assert!("hyper+x".parse::<KeyPress>().is_err());
+        assert!("f25".parse::<KeyPress>().is_err());
+        assert!("".parse::<KeySequence>().is_err());
+    }
+
+    #[test]
+    fn shift_folds_into_characters() {
+        assert_eq!(press("shift+r"), press("R"));
+        assert_ne!(press("shift+r"), press("r"));
+        assert_eq!(press("shift+R"), press("R"));
+        // Terminals report capitals with shift held
+        let event = KeyEvent::new(KeyCode::Char('R'), KeyModifiers::SHIFT);
+        assert_eq!(KeyPress::from(event), press("R"));
+        // Keys without a character keep shift
+        assert_eq!(press("shift+right").modifiers, KeyModifiers::SHIFT);
+        assert_eq!(press("shift+r").to_string(), "R");
+    }
+
+    #[test]
+    fn looks_up_sequences() {
+        let keymap = keymap(&[("show_help", &["g h"])]);
+        assert_eq!(keymap.lookup(&[press("g")]), KeyLookup::Pending);
+        assert_eq!(
+            keymap.lookup(&[press("g"), press("h")]),
+            KeyLookup::Matched(KeyAction::ShowHelp)
+        );
+        assert_eq!(keymap.lookup(&[press("?")]), KeyLookup::NoMatch);
+        assert_eq!(
+            keymap.lookup(&[press("1")]),
+            KeyLookup::Matched(KeyAction::ShowHome)
+        );
+    }
+
+    #[test]
+    fn shift_binding_does_not_take_lowercase_key() {
+        let keymap = keymap(&[("stop_all", &["shift+r"])]);
+        assert_eq!(
+            keymap.lookup(&[press("R")]),
+            KeyLookup::Matched(KeyAction::StopAll)
+        );
+        assert_eq!(
+            keymap.lookup(&[press("r")]),
+            KeyLookup::Matched(KeyAction::RefreshDevices)
+        );
+    }
+
+    #[test]
+    fn conflicts_leave_out_only_the_default() {
+        let keymap = keymap(&[("stop_all", &["r"])]);
+        assert_eq!(
+            keymap.lookup(&[press("r")]),
+            KeyLookup::Matched(KeyAction::StopAll)
+        );
+        assert_eq!(keymap.keys_for(KeyAction::RefreshDevices).count(), 0);
+        assert_eq!(keymap.keys_for(KeyAction::Quit).count(), 2);
+    }
+
+    #[test]
+    fn prefix_conflicts_leave_out_the_later_binding() {
+        let keymap = keymap(&[("show_help", &["g"]), ("stop_all", &["g h"])]);
+        assert_eq!(
+            keymap.lookup(&[press("g")]),
+            KeyLookup::Matched(KeyAction::ShowHelp)
+        );
+        assert_eq!(keymap.keys_for(KeyAction::StopAll).count(), 0);
+    }
+
+    #[test]
+    fn settings_with_conflicts_still_load() {
+        let settings: BTreeMap<String, Keymap> =
+            toml::from_str("[keymap]\nquit = [\"x\"]\n").unwrap();
+        let keymap = &settings["keymap"];
+        assert_eq!(
+            keymap.lookup(&[press("x")]),
+            KeyLookup::Matched(KeyAction::Quit)
+        );
+        assert_eq!(keymap.keys_for(KeyAction::StopSelected).count(), 0);
+    }
+
+    #[test]
+    fn saves_only_changed_actions() {
+        let saved: BTreeMap<String, Vec<String>> = Keymap::default().into();
+        assert!(saved.is_empty());
+
+        let keymap = keymap(&[("quit", &["ctrl+q"])]);
+        let saved: BTreeMap<String, Vec<String>> = keymap.clone().into();
+        assert_eq!(saved.len(), 1);
+        assert_eq!(saved["quit"], vec!["ctrl+q".to_string()]);
+
+        let reloaded = Keymap::try_from(saved).unwrap();
+        assert_eq!(reloaded.bindings, keymap.bindings);
+    }
+
+    #[test]
+    fn empty_list_unbinds() {
+        let keymap = keymap(&[("refresh_devices", &[])]);
+        assert_eq!(keymap.keys_for(KeyAction::RefreshDevices).count(), 0);
+        let saved: BTreeMap<String, Vec<String>> = keymap.into();
+        assert_eq!(saved["refresh_devices"], Vec::<String>::new());
+    }
+}
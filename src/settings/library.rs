@@ -0,0 +1,21 @@
+use serde::{Deserialize, Serialize};
+
+/// The `[library]` settings section, for how sounds are loaded
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LibrarySettings {
+    /// Most memory, in MB, that decoded sounds are kept in
+    pub cache_mb: u32,
+    /// Sounds longer than this, in seconds, are read from disk as they play
+    /// instead of being decoded up front
+    pub stream_after_secs: u32,
+}
+
+impl Default for LibrarySettings {
+    fn default() -> Self {
+        Self {
+            cache_mb: 256,
+            stream_after_secs: 30,
+        }
+    }
+}
@@ -0,0 +1,25 @@
+use serde::{Deserialize, Serialize};
+
+/// The `[loudness]` settings section, for playing every sound at the same loudness
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoudnessSettings {
+    pub enabled: bool,
+    /// Integrated loudness, in LUFS, that sounds are turned up or down to
+    pub target_lufs: f32,
+    /// Sounds are never turned up so far that their true peak goes over this, in dBTP
+    pub max_true_peak_db: f32,
+    /// Trust ReplayGain tags instead of analyzing the files that have them
+    pub replaygain: bool,
+}
+
+impl Default for LoudnessSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            target_lufs: -16.0,
+            max_true_peak_db: -1.0,
+            replaygain: true,
+        }
+    }
+}
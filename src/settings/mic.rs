@@ -0,0 +1,108 @@
+use serde::{Deserialize, Serialize};
+
+/// The `[mic]` settings section
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MicSettings {
+    /// Route the microphone, mixed with the sounds, into a "BoomCrab Mic" virtual source
+    pub passthrough: bool,
+    /// `node.name` of the microphone; the default source is used when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device: Option<String>,
+    pub volume: f32,
+    pub gate: GateSettings,
+    pub compressor: CompressorSettings,
+    pub limiter: LimiterSettings,
+}
+
+impl Default for MicSettings {
+    fn default() -> Self {
+        Self {
+            passthrough: false,
+            device: None,
+            volume: 1.0,
+            gate: GateSettings::default(),
+            compressor: CompressorSettings::default(),
+            limiter: LimiterSettings::default(),
+        }
+    }
+}
+
+/// The `[mic.gate]` section: mutes the mic while nobody is speaking
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GateSettings {
+    pub enabled: bool,
+    /// The gate opens when the mic gets louder than this
+    pub threshold_db: f32,
+    /// The gate closes again once the mic is this far below the threshold
+    pub hysteresis_db: f32,
+    /// Time the gate stays open after the mic gets quiet, so words aren't cut off
+    pub hold_ms: u32,
+    pub attack_ms: u32,
+    pub release_ms: u32,
+    /// Attenuation while closed
+    pub range_db: f32,
+}
+
+impl Default for GateSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            threshold_db: -45.0,
+            hysteresis_db: 6.0,
+            hold_ms: 150,
+            attack_ms: 1,
+            release_ms: 150,
+            range_db: 60.0,
+        }
+    }
+}
+
+/// The `[mic.compressor]` section: evens out loud and quiet speech
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CompressorSettings {
+    pub enabled: bool,
+    pub threshold_db: f32,
+    /// Input dB above the threshold per output dB
+    pub ratio: f32,
+    /// Width of the soft knee around the threshold
+    pub knee_db: f32,
+    pub attack_ms: u32,
+    pub release_ms: u32,
+    pub makeup_db: f32,
+}
+
+impl Default for CompressorSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            threshold_db: -20.0,
+            ratio: 3.0,
+            knee_db: 6.0,
+            attack_ms: 5,
+            release_ms: 120,
+            makeup_db: 3.0,
+        }
+    }
+}
+
+/// The `[mic.limiter]` section: keeps the mic below a ceiling it never crosses
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LimiterSettings {
+    pub enabled: bool,
+    pub ceiling_db: f32,
+    pub release_ms: u32,
+}
+
+impl Default for LimiterSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            ceiling_db: -1.0,
+            release_ms: 80,
+        }
+    }
+}
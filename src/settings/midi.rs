@@ -0,0 +1,142 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// The `[midi]` settings section
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MidiSettings {
+    pub enabled: bool,
+    /// Connect to the first input port whose name contains this text. When
+    /// unset, a virtual port named "BoomCrab MIDI In" is created instead.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port: Option<String>,
+    /// Scale the volume of sounds started by a note with the note's velocity
+    pub velocity_volume: bool,
+    pub bindings: Vec<MidiBinding>,
+    pub feedback: MidiFeedbackSettings,
+}
+
+/// The `[midi.feedback]` settings section, for lighting pads while their sound plays
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MidiFeedbackSettings {
+    pub enabled: bool,
+    /// Send to the first output port whose name contains this text. When
+    /// unset, a virtual port named "BoomCrab MIDI Out" is created instead.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port: Option<String>,
+    pub profile: FeedbackProfile,
+    /// Channel from 1 to 16 to send on; defaults to each binding's channel
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel: Option<u8>,
+    /// Overrides the profile's velocity or value for a lit pad
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_value: Option<u8>,
+    /// Overrides the profile's velocity or value for a dark pad
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub off_value: Option<u8>,
+}
+
+impl MidiFeedbackSettings {
+    pub fn on_value(&self) -> u8 {
+        self.on_value.unwrap_or(self.profile.on_value())
+    }
+
+    pub fn off_value(&self) -> u8 {
+        self.off_value.unwrap_or(self.profile.off_value())
+    }
+}
+
+/// How a controller expects its pad LEDs to be driven
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeedbackProfile {
+    /// Full velocity or value to light, zero to turn off
+    #[default]
+    Generic,
+    /// Novation Launchpad in programmer mode; lights pads green
+    Launchpad,
+    /// Akai APC mini; lights pads green
+    ApcMini,
+}
+
+impl FeedbackProfile {
+    fn on_value(&self) -> u8 {
+        match self {
+            FeedbackProfile::Generic => 127,
+            FeedbackProfile::Launchpad => 21,
+            FeedbackProfile::ApcMini => 1,
+        }
+    }
+
+    fn off_value(&self) -> u8 {
+        0
+    }
+}
+
+/// Maps a note or controller to an action, written in the settings file as e.g.
+///
+/// ```toml
+/// [[midi.bindings]]
+/// note = 36
+/// action = "play"
+/// sound = "airhorn"
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MidiBinding {
+    /// MIDI channel from 1 to 16; any channel matches when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel: Option<u8>,
+    #[serde(flatten)]
+    pub trigger: MidiTrigger,
+    #[serde(flatten)]
+    pub action: MidiAction,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MidiTrigger {
+    Note(u8),
+    Cc(u8),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum MidiAction {
+    Play {
+        sound: String,
+    },
+    Stop {
+        sound: String,
+    },
+    StopAll,
+    FadeOutAll,
+    /// Set a sound's volume from a controller value
+    Volume {
+        sound: String,
+    },
+    /// Set the master volume from a controller value
+    MasterVolume,
+}
+
+impl fmt::Display for MidiBinding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.channel {
+            Some(channel) => write!(f, "ch {:<2} ", channel)?,
+            None => write!(f, "ch *  ")?,
+        }
+        match self.trigger {
+            MidiTrigger::Note(note) => write!(f, "note {:<3}", note)?,
+            MidiTrigger::Cc(cc) => write!(f, "cc {:<5}", cc)?,
+        }
+        match &self.action {
+            MidiAction::Play { sound } => write!(f, " -> play {}", sound),
+            MidiAction::Stop { sound } => write!(f, " -> stop {}", sound),
+            MidiAction::StopAll => write!(f, " -> stop all"),
+            MidiAction::FadeOutAll => write!(f, " -> fade out all"),
+            MidiAction::Volume { sound } => write!(f, " -> volume of {}", sound),
+            MidiAction::MasterVolume => write!(f, " -> master volume"),
+        }
+    }
+}
@@ -1,7 +1,38 @@
-use std::{fmt, fs, path::PathBuf};
+pub mod ducking;
+pub mod effects;
+pub mod fade;
+pub mod hotkeys;
+pub mod keymap;
+pub mod library;
+pub mod loudness;
+pub mod mic;
+pub mod midi;
+pub mod mpris;
+pub mod obs;
+pub mod osc;
+pub mod sounds;
+pub mod voice_changer;
+pub mod web;
+
+use std::{collections::BTreeMap, fmt, fs, path::PathBuf};
 
 use serde::{Deserialize, Serialize};
-use toml;
+
+use ducking::DuckingSettings;
+use effects::EffectSettings;
+use fade::FadeSettings;
+use hotkeys::HotkeySettings;
+use keymap::Keymap;
+use library::LibrarySettings;
+use loudness::LoudnessSettings;
+use mic::MicSettings;
+use midi::MidiSettings;
+use mpris::MprisSettings;
+use obs::ObsSettings;
+use osc::OscSettings;
+use sounds::{ChokeGroupSettings, SoundMetadata};
+use voice_changer::VoiceChangerSettings;
+use web::WebSettings;
 
 #[derive(Debug)]
 pub enum SettingsError {
@@ -9,6 +40,8 @@ pub enum SettingsError {
     FileRead(std::io::Error),
     ParseError(toml::de::Error),
     SerializeError(toml::ser::Error),
+    /// The settings file is there but couldn't be loaded, so it isn't written over
+    NotLoaded(String),
 }
 
 impl fmt::Display for SettingsError {
@@ -18,6 +51,11 @@ impl fmt::Display for SettingsError {
             SettingsError::FileRead(e) => write!(f, "Failed to read settings file: {}", e),
             SettingsError::ParseError(e) => write!(f, "Failed to parse settings: {}", e),
             SettingsError::SerializeError(e) => write!(f, "Failed to serialize settings: {}", e),
+            SettingsError::NotLoaded(e) => write!(
+                f,
+                "The settings file could not be loaded ({}), so changes are not saved to it",
+                e
+            ),
         }
     }
 }
@@ -42,24 +80,59 @@ impl From<toml::ser::Error> for SettingsError {
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Default, Serialize, Deserialize)]
 pub struct BoomCrabSettings {
     pub sound_files_directory: String,
-}
-
-impl Default for BoomCrabSettings {
-    fn default() -> Self {
-        Self {
-            sound_files_directory: String::new(),
-        }
-    }
+    #[serde(default)]
+    pub keymap: Keymap,
+    /// Per-sound settings, keyed by sound id
+    #[serde(default)]
+    pub sounds: BTreeMap<String, SoundMetadata>,
+    #[serde(default)]
+    pub choke_groups: BTreeMap<String, ChokeGroupSettings>,
+    #[serde(default)]
+    pub library: LibrarySettings,
+    #[serde(default)]
+    pub fade: FadeSettings,
+    #[serde(default)]
+    pub loudness: LoudnessSettings,
+    #[serde(default)]
+    pub ducking: DuckingSettings,
+    /// Effects the whole output runs through, after the sounds' own
+    #[serde(default)]
+    pub master_effects: Vec<EffectSettings>,
+    #[serde(default)]
+    pub mic: MicSettings,
+    #[serde(default)]
+    pub voice_changer: VoiceChangerSettings,
+    #[serde(default)]
+    pub midi: MidiSettings,
+    #[serde(default)]
+    pub osc: OscSettings,
+    #[serde(default)]
+    pub web: WebSettings,
+    #[serde(default)]
+    pub mpris: MprisSettings,
+    #[serde(default)]
+    pub obs: ObsSettings,
+    #[serde(default)]
+    pub hotkeys: HotkeySettings,
+    /// Why the settings file couldn't be loaded, when it exists. Saving the
+    /// defaults that stand in for it would lose the user's settings.
+    #[serde(skip)]
+    load_error: Option<String>,
 }
 
 impl BoomCrabSettings {
     pub fn new() -> Self {
         Self::new_from_file().unwrap_or_else(|e| {
             eprintln!("Warning: Could not load settings ({}). Using defaults.", e);
-            Self::default()
+            // A missing file is created on the first save, but one that is there is left alone
+            let mut settings = Self::default();
+            if Self::settings_file_exists() {
+                settings.load_error = Some(e.to_string());
+            }
+            settings
         })
     }
 
@@ -75,10 +148,24 @@ impl BoomCrabSettings {
             .ok_or(SettingsError::ConfigDirNotFound)
     }
 
+    /// Whether changes can be saved, which they can't if the settings file
+    /// exists but couldn't be loaded
+    pub fn check_saveable(&self) -> Result<(), SettingsError> {
+        match &self.load_error {
+            Some(e) => Err(SettingsError::NotLoaded(e.clone())),
+            None => Ok(()),
+        }
+    }
+
     pub fn save_to_file(&self) -> Result<(), SettingsError> {
+        self.check_saveable()?;
         let config_file_path = Self::get_settings_file_path()?;
         let str_toml = toml::to_string_pretty(self)?;
-        fs::write(&config_file_path, str_toml)?;
+        // Written aside and moved into place, so a daemon stopped midway
+        // never leaves the file half written
+        let partial = config_file_path.with_extension("toml.partial");
+        fs::write(&partial, str_toml)?;
+        fs::rename(partial, config_file_path)?;
         Ok(())
     }
 
@@ -88,3 +175,29 @@ impl BoomCrabSettings {
             .unwrap_or(false)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_can_be_saved() {
+        assert!(BoomCrabSettings::default().check_saveable().is_ok());
+    }
+
+    #[test]
+    fn settings_that_failed_to_load_are_not_saved() {
+        let settings = BoomCrabSettings {
+            load_error: Some("invalid key".to_string()),
+            ..BoomCrabSettings::default()
+        };
+        assert!(matches!(
+            settings.check_saveable(),
+            Err(SettingsError::NotLoaded(e)) if e == "invalid key"
+        ));
+        assert!(matches!(
+            settings.save_to_file(),
+            Err(SettingsError::NotLoaded(_))
+        ));
+    }
+}
@@ -0,0 +1,9 @@
+use serde::{Deserialize, Serialize};
+
+/// The `[mpris]` settings section
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MprisSettings {
+    /// Register on the session D-Bus as an MPRIS player
+    pub enabled: bool,
+}
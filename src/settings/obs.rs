@@ -0,0 +1,96 @@
+use serde::{Deserialize, Serialize};
+
+/// The `[obs]` settings section, for reacting to OBS Studio through obs-websocket 5
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ObsSettings {
+    pub enabled: bool,
+    pub url: String,
+    /// The obs-websocket server password, when authentication is enabled in OBS
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    /// Sounds to play or stop when something happens in OBS
+    pub on_event: Vec<ObsEventBinding>,
+    /// Requests to send to OBS when a sound starts or finishes
+    pub on_sound: Vec<ObsSoundBinding>,
+}
+
+impl Default for ObsSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            url: "ws://127.0.0.1:4455".to_string(),
+            password: None,
+            on_event: Vec::new(),
+            on_sound: Vec::new(),
+        }
+    }
+}
+
+/// Runs an action when OBS reports an event, written in the settings file as e.g.
+///
+/// ```toml
+/// [[obs.on_event]]
+/// event = "scene_changed"
+/// scene = "Starting Soon"
+/// action = "play"
+/// sound = "intro"
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ObsEventBinding {
+    #[serde(flatten)]
+    pub event: ObsEvent,
+    #[serde(flatten)]
+    pub action: ObsAction,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ObsEvent {
+    /// The program scene changed, to `scene` if given or to any scene
+    SceneChanged {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        scene: Option<String>,
+    },
+    StreamStarted,
+    StreamStopped,
+    RecordingStarted,
+    RecordingStopped,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum ObsAction {
+    Play { sound: String },
+    Stop { sound: String },
+    StopAll,
+    FadeOutAll,
+}
+
+/// Sends an obs-websocket request when a sound starts or finishes, e.g.
+///
+/// ```toml
+/// [[obs.on_sound]]
+/// sound = "intro"
+/// request = "SetCurrentProgramScene"
+/// data = { sceneName = "Main" }
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ObsSoundBinding {
+    pub sound: String,
+    #[serde(default)]
+    pub when: SoundEdge,
+    /// An obs-websocket request type, such as `SetCurrentProgramScene`
+    pub request: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SoundEdge {
+    Started,
+    /// The sound's last playing voice stopped
+    #[default]
+    Finished,
+}
@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+
+/// The `[osc]` settings section
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OscSettings {
+    pub enabled: bool,
+    /// Address to listen on; use "0.0.0.0" to accept messages from the LAN
+    pub bind_address: String,
+    pub port: u16,
+    /// Port on each sender's host that state updates are sent back to. When
+    /// unset, updates go back to the port the sender's messages came from.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reply_port: Option<u16>,
+    /// Extra "host:port" addresses that always receive state updates
+    pub targets: Vec<String>,
+}
+
+impl Default for OscSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bind_address: "127.0.0.1".to_string(),
+            port: 9000,
+            reply_port: None,
+            targets: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::ducking::DuckRole;
+use super::effects::EffectSettings;
+use super::fade::FadeCurve;
+
+/// Per-sound settings, from a `[sounds.<id>]` section such as
+///
+/// ```toml
+/// [sounds.drumroll]
+/// mode = "loop"
+/// loop_count = 4
+/// fade_out_ms = 500
+/// ```
+///
+/// Fades that aren't set fall back to the `[fade]` section. Trim points and
+/// cues are in milliseconds from the start of the file, which is left as it is.
+/// Setting `gain_db` turns off loudness normalization for the sound.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SoundMetadata {
+    pub mode: TriggerMode,
+    /// Number of times a `loop` sound plays before stopping; it loops until stopped when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub loop_count: Option<u32>,
+    /// Where the sound starts, to skip silence or an intro
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_ms: Option<u32>,
+    /// Where the sound ends; it plays to the end of the file when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_ms: Option<u32>,
+    /// Named points the sound can be played from
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub cues: BTreeMap<String, u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fade_in_ms: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fade_out_ms: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fade_curve: Option<FadeCurve>,
+    /// Starting a sound fades out every other sound in its exclusive group
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exclusive_group: Option<String>,
+    /// A group from the `[choke_groups]` section
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub choke_group: Option<String>,
+    /// Gain in dB used instead of the one that brings the sound to the `[loudness]` target
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gain_db: Option<f32>,
+    /// Playback speed, which changes the pitch along with it like a tape; 1.0 when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate: Option<f32>,
+    /// Pitch shift in semitones that keeps the sound's length
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pitch_semitones: Option<f32>,
+    /// Each play changes the speed by a random amount up to this many semitones
+    /// up or down, so repeated plays don't sound identical
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub random_pitch_semitones: Option<f32>,
+    /// Whether the sound ducks others, is ducked by them, or neither
+    pub ducking: DuckRole,
+    /// Effects the sound is played through, in order
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub effects: Vec<EffectSettings>,
+}
+
+/// What playing a sound does, depending on whether it is already playing
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TriggerMode {
+    /// Plays to the end; triggering it again while it plays does nothing
+    OneShot,
+    /// Stops the sound if it is playing and starts it again from the beginning
+    Restart,
+    /// Starts another voice on top of any that are already playing
+    #[default]
+    Overlap,
+    /// Stops the sound if it is playing, starts it otherwise
+    Toggle,
+    /// Plays while the key or pad that started it is held down
+    Hold,
+    /// Repeats `loop_count` times, or until stopped
+    Loop,
+}
+
+/// A `[choke_groups.<name>]` section, such as
+///
+/// ```toml
+/// [choke_groups.reactions]
+/// choke = "fade"
+/// max_voices = 2
+/// steal = "quietest"
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChokeGroupSettings {
+    pub choke: ChokeMode,
+    /// Length of the fade for `choke = "fade"`, which stolen voices get too
+    pub fade_ms: u32,
+    /// Most voices the group plays at once; unlimited when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_voices: Option<u32>,
+    pub steal: StealPolicy,
+}
+
+impl Default for ChokeGroupSettings {
+    fn default() -> Self {
+        Self {
+            choke: ChokeMode::default(),
+            fade_ms: 50,
+            max_voices: None,
+            steal: StealPolicy::default(),
+        }
+    }
+}
+
+/// What starting a sound does to the other sounds in its choke group
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChokeMode {
+    #[default]
+    Cut,
+    Fade,
+    /// Leave them playing; the group only limits how many voices play
+    None,
+}
+
+/// Which voice makes way when a choke group is already playing `max_voices`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StealPolicy {
+    #[default]
+    Oldest,
+    Quietest,
+    /// Don't start the new voice
+    None,
+}
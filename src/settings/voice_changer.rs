@@ -0,0 +1,112 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+/// The `[voice_changer]` settings section, which transforms the passthrough mic
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VoiceChangerSettings {
+    /// Preset in use; the mic passes through unchanged when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preset: Option<String>,
+    /// Presets of your own, which replace built-in presets of the same name
+    pub presets: BTreeMap<String, VoicePreset>,
+}
+
+impl VoiceChangerSettings {
+    /// The built-in presets together with the ones from the settings file
+    pub fn all_presets(&self) -> BTreeMap<String, VoicePreset> {
+        let mut presets = builtin_presets();
+        presets.extend(self.presets.clone());
+        presets
+    }
+}
+
+/// A voice, from a `[voice_changer.presets.<name>]` section such as
+///
+/// ```toml
+/// [voice_changer.presets.giant]
+/// pitch_semitones = -5.0
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VoicePreset {
+    pub pitch_semitones: f32,
+    /// Keep the voice's timbre when changing its pitch, instead of shifting
+    /// both together like a tape played at a different speed
+    pub preserve_formants: bool,
+    /// Speak on one pitch like a robot, at this frequency
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub robot_hz: Option<f32>,
+    /// Multiply the voice by a sine at this frequency
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ring_mod_hz: Option<f32>,
+    /// Narrow and slightly overdriven, like a walkie-talkie
+    pub radio: bool,
+}
+
+impl Default for VoicePreset {
+    fn default() -> Self {
+        Self {
+            pitch_semitones: 0.0,
+            preserve_formants: true,
+            robot_hz: None,
+            ring_mod_hz: None,
+            radio: false,
+        }
+    }
+}
+
+fn builtin_presets() -> BTreeMap<String, VoicePreset> {
+    let presets = [
+        (
+            "chipmunk",
+            VoicePreset {
+                pitch_semitones: 7.0,
+                preserve_formants: false,
+                ..VoicePreset::default()
+            },
+        ),
+        (
+            "demon",
+            VoicePreset {
+                pitch_semitones: -7.0,
+                preserve_formants: false,
+                ..VoicePreset::default()
+            },
+        ),
+        (
+            "deep",
+            VoicePreset {
+                pitch_semitones: -4.0,
+                ..VoicePreset::default()
+            },
+        ),
+        (
+            "robot",
+            VoicePreset {
+                robot_hz: Some(110.0),
+                ..VoicePreset::default()
+            },
+        ),
+        (
+            "dalek",
+            VoicePreset {
+                ring_mod_hz: Some(30.0),
+                ..VoicePreset::default()
+            },
+        ),
+        (
+            "radio",
+            VoicePreset {
+                radio: true,
+                ..VoicePreset::default()
+            },
+        ),
+    ];
+
+    presets
+        .into_iter()
+        .map(|(name, preset)| (name.to_string(), preset))
+        .collect()
+}
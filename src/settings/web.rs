@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+
+/// The `[web]` settings section
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebSettings {
+    pub enabled: bool,
+    /// Address to listen on; use "0.0.0.0" to reach the soundboard from the LAN
+    pub bind_address: String,
+    pub port: u16,
+    /// When set, API requests must carry this token, either as an
+    /// `Authorization: Bearer` header or a `token` query parameter
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+impl Default for WebSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bind_address: "127.0.0.1".to_string(),
+            port: 8080,
+            token: None,
+        }
+    }
+}
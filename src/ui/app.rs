@@ -1,22 +1,182 @@
-use std::io;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+use std::{io, mem};
 
-use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
 
-use super::{Page, UiAction, config::ConfigPage, home::HomePage};
-use crate::audio::AudioDevice;
+use super::components::meter::BusMeters;
+use super::{
+    Page, UiAction, config::ConfigPage, effects::EffectsPage, help::HelpPage, home::HomePage,
+    playing::PlayingPage, spectrum::SpectrumPage, trim::TrimPage,
+};
+use crate::audio::dynamics::GainReduction;
+use crate::audio::mixer::VoiceProgress;
+use crate::audio::overview::Overview;
+use crate::audio::spectrum::{FLOOR_DB, SpectrumSource};
+use crate::audio::{AudioDevice, Sound};
+use crate::daemon::protocol::{Event as DaemonEvent, Trim};
+use crate::settings::effects::EffectSettings;
+use crate::settings::keymap::{KeyAction, KeyLookup, KeyPress, Keymap};
+use crate::settings::midi::MidiBinding;
+
+/// Change in master volume per key press
+const VOLUME_STEP: f32 = 0.05;
+const MAX_VOLUME: f32 = 2.0;
+/// Change in the speed of playing sounds while a speed key is held, a semitone
+const SPEED_STEP: f32 = 1.059_463;
+/// Steps an effect parameter moves for the larger increase and decrease
+const LARGE_EFFECT_STEPS: i32 = 10;
+/// Change in a trim point or cue per key press, in milliseconds
+const TRIM_STEP_MS: i64 = 10;
+const LARGE_TRIM_STEP_MS: i64 = 250;
+/// Auditioning the end point plays this much before it
+const AUDITION_MS: u32 = 2000;
+/// How far a voice on the now-playing page moves per key press, in milliseconds
+const SEEK_STEP_MS: i64 = 1000;
+const LARGE_SEEK_STEP_MS: i64 = 10_000;
+/// Bands the spectrum page splits 20 Hz to 20 kHz into
+pub const SPECTRUM_BANDS: usize = 48;
+/// How long a spectrum peak holds before falling, and how fast it falls
+const PEAK_HOLD: Duration = Duration::from_secs(1);
+const PEAK_FALL_DB_PER_SECOND: f32 = 30.0;
+
+/// A line of the effects page: an effect, or one of its parameters
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EffectRow {
+    Effect(usize),
+    Param(usize, usize),
+}
+
+impl EffectRow {
+    pub fn effect(&self) -> usize {
+        match *self {
+            EffectRow::Effect(effect) | EffectRow::Param(effect, _) => effect,
+        }
+    }
+}
+
+/// A line of the trim page: a trim point, or a cue by its place in name order
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrimRow {
+    Start,
+    End,
+    Cue(usize),
+}
+
+/// Highest level a spectrum band has reached lately, which holds for a while and then falls
+#[derive(Debug, Clone, Copy)]
+pub struct PeakHold {
+    held_db: f32,
+    since: Instant,
+}
+
+impl PeakHold {
+    pub fn level_db(&self) -> f32 {
+        let falling = self.since.elapsed().saturating_sub(PEAK_HOLD).as_secs_f32();
+        (self.held_db - falling * PEAK_FALL_DB_PER_SECOND).max(FLOOR_DB)
+    }
+
+    fn update(&mut self, level_db: f32) {
+        if level_db >= self.level_db() {
+            self.held_db = level_db;
+            self.since = Instant::now();
+        }
+    }
+}
 
 pub struct App {
     pub current_page: Page,
     pub audio_outputs: Vec<AudioDevice>,
     pub audio_inputs: Vec<AudioDevice>,
+    pub sounds: Vec<Sound>,
+    pub selected_sound: usize,
+    /// Voices currently playing, as (voice, sound id) pairs
+    pub playing: Vec<(u64, String)>,
+    /// Where each playing voice is and how it is playing
+    pub progress: HashMap<u64, VoiceProgress>,
+    /// Voice selected on the now-playing page, by its place in `playing`
+    pub selected_voice: usize,
+    /// Waveforms fetched so far, by sound id
+    pub overviews: HashMap<String, Overview>,
+    /// Sounds whose waveforms the daemon is still working out, or couldn't
+    /// give, which aren't asked for again until it says they are ready
+    pub overviews_pending: HashSet<String>,
+    pub master_volume: f32,
+    pub midi_bindings: Vec<MidiBinding>,
+    /// Sound waiting for a MIDI note or controller to be bound to it
+    pub midi_learning: Option<String>,
+    /// Voice changer preset in use on the mic
+    pub voice_preset: Option<String>,
+    /// Delay from the microphone to the virtual source, once the daemon has measured it
+    pub mic_latency_ms: Option<f32>,
+    /// How far the mic's gate, compressor and limiter are turning it down
+    pub mic_gain_reduction: GainReduction,
+    /// Levels of the output, mic and virtual source for the status bar
+    pub meters: BusMeters,
+    /// Effects being edited on the effects page
+    pub effects: Vec<EffectSettings>,
+    /// Sound whose effects are being edited, or `None` for the master effects
+    pub effects_sound: Option<String>,
+    pub selected_effect_row: usize,
+    /// Sound whose trim points are being edited on the trim page
+    pub trim_sound: Option<String>,
+    pub trim: Trim,
+    pub selected_trim_row: usize,
+    /// Row of the help page the list is scrolled to
+    pub selected_help_row: usize,
+    /// Signal shown on the spectrum page
+    pub spectrum_source: SpectrumSource,
+    /// Latest level of each spectrum band in dB, lowest band first
+    pub spectrum: Vec<f32>,
+    pub spectrum_peaks: Vec<PeakHold>,
+    /// Last error reported by the daemon
+    pub status_message: Option<String>,
+    pub keymap: Keymap,
+    pending_keys: Vec<KeyPress>,
+    /// Key that played a sound and hasn't been released yet, with that sound's id
+    held_key: Option<(KeyCode, String)>,
+    /// Whether the terminal reports key releases, so speed keys can be held
+    pub key_releases: bool,
+    /// Speed key that hasn't been released yet
+    held_speed_key: Option<KeyCode>,
 }
 
 impl App {
-    pub fn new() -> Self {
+    pub fn new(keymap: Keymap) -> Self {
         Self {
             current_page: Page::Home,
             audio_outputs: Vec::new(),
             audio_inputs: Vec::new(),
+            sounds: Vec::new(),
+            selected_sound: 0,
+            playing: Vec::new(),
+            progress: HashMap::new(),
+            selected_voice: 0,
+            overviews: HashMap::new(),
+            overviews_pending: HashSet::new(),
+            master_volume: 1.0,
+            midi_bindings: Vec::new(),
+            midi_learning: None,
+            voice_preset: None,
+            mic_latency_ms: None,
+            mic_gain_reduction: GainReduction::default(),
+            meters: BusMeters::default(),
+            effects: Vec::new(),
+            effects_sound: None,
+            selected_effect_row: 0,
+            trim_sound: None,
+            trim: Trim::default(),
+            selected_trim_row: 0,
+            selected_help_row: 0,
+            spectrum_source: SpectrumSource::default(),
+            spectrum: Vec::new(),
+            spectrum_peaks: Vec::new(),
+            status_message: None,
+            keymap,
+            pending_keys: Vec::new(),
+            held_key: None,
+            key_releases: false,
+            held_speed_key: None,
         }
     }
 
@@ -26,20 +186,657 @@ impl App {
         self.audio_inputs = inputs;
     }
 
+    /// Update the sound list, keeping the selection in range. Waveforms are
+    /// fetched again, since the files may have changed.
+    pub fn update_sounds(&mut self, sounds: Vec<Sound>) {
+        self.sounds = sounds;
+        self.overviews.clear();
+        self.overviews_pending.clear();
+        self.selected_sound = self.selected_sound.min(self.sounds.len().saturating_sub(1));
+    }
+
+    /// Track which sounds are playing from the daemon's events
+    pub fn handle_daemon_event(&mut self, event: DaemonEvent) {
+        match event {
+            DaemonEvent::SoundStarted { voice, id } => self.playing.push((voice, id)),
+            DaemonEvent::SoundStopped { voice, .. } => {
+                self.playing.retain(|(v, _)| *v != voice);
+                self.progress.remove(&voice);
+                self.selected_voice = self
+                    .selected_voice
+                    .min(self.playing.len().saturating_sub(1));
+            }
+            DaemonEvent::SoundProgress {
+                voice,
+                id,
+                progress,
+            } => {
+                // Voices that started before we connected show up with their progress.
+                // Voice ids count up, so ordering by them keeps the oldest first.
+                let index = self.playing.partition_point(|(v, _)| *v < voice);
+                if self.playing.get(index).is_none_or(|(v, _)| *v != voice) {
+                    self.playing.insert(index, (voice, id));
+                }
+                self.progress.insert(voice, progress);
+            }
+            DaemonEvent::MidiLearned { binding } => {
+                self.midi_bindings
+                    .retain(|b| b.channel != binding.channel || b.trigger != binding.trigger);
+                self.midi_bindings.push(binding);
+                self.midi_learning = None;
+            }
+            DaemonEvent::VolumeChanged { id: None, volume } => self.master_volume = volume,
+            DaemonEvent::EffectsChanged { id, effects } if id == self.effects_sound => {
+                self.effects = effects;
+                self.clamp_effect_row();
+            }
+            DaemonEvent::EffectsChanged { .. } => {}
+            DaemonEvent::TrimChanged { id, trim } if Some(&id) == self.trim_sound.as_ref() => {
+                self.trim = trim;
+                self.clamp_trim_row();
+            }
+            DaemonEvent::TrimChanged { .. } => {}
+            DaemonEvent::VoicePresetChanged { preset } => self.voice_preset = preset,
+            DaemonEvent::WaveformReady { id } => {
+                self.overviews.remove(&id);
+                self.overviews_pending.remove(&id);
+            }
+            DaemonEvent::MicLatency { latency_ms } => self.mic_latency_ms = Some(latency_ms),
+            DaemonEvent::MicGainReduction {
+                gate_db,
+                compressor_db,
+                limiter_db,
+            } => {
+                self.mic_gain_reduction = GainReduction {
+                    gate_db,
+                    compressor_db,
+                    limiter_db,
+                }
+            }
+            DaemonEvent::Levels {
+                master,
+                mic,
+                virtual_mic,
+            } => {
+                self.meters.master.update(Some(master));
+                self.meters.mic.update(mic);
+                self.meters.virtual_mic.update(virtual_mic);
+            }
+            DaemonEvent::VolumeChanged { id: Some(_), .. } => {}
+        }
+    }
+
+    pub fn is_playing(&self, sound_id: &str) -> bool {
+        self.playing.iter().any(|(_, id)| id == sound_id)
+    }
+
+    /// Position of the latest voice of a sound, in milliseconds from the start of its file
+    pub fn playhead(&self, sound_id: &str) -> Option<u32> {
+        self.playing
+            .iter()
+            .rev()
+            .filter(|(_, id)| id == sound_id)
+            .find_map(|(voice, _)| self.progress.get(voice))
+            .map(|progress| progress.position_ms)
+    }
+
+    /// Name of a sound, or its id if it isn't in the list
+    pub fn sound_name<'a>(&'a self, sound_id: &'a str) -> &'a str {
+        self.sounds
+            .iter()
+            .find(|sound| sound.id == sound_id)
+            .map_or(sound_id, |sound| sound.name.as_str())
+    }
+
+    fn selected_voice(&self) -> Option<u64> {
+        self.playing
+            .get(self.selected_voice)
+            .map(|(voice, _)| *voice)
+    }
+
+    /// Act on the voice selected on the now-playing page, once its progress is known
+    fn with_selected_voice(&self, act: impl FnOnce(u64, &VoiceProgress) -> UiAction) -> UiAction {
+        self.selected_voice()
+            .and_then(|voice| Some((voice, self.progress.get(&voice)?)))
+            .map_or(UiAction::None, |(voice, progress)| act(voice, progress))
+    }
+
+    /// Move the selected voice by `delta_ms`, keeping it between its trim points
+    fn seek_selected_voice(&self, delta_ms: i64) -> UiAction {
+        self.with_selected_voice(|voice, progress| {
+            let ms = (progress.position_ms as i64 + delta_ms)
+                .min(progress.end_ms as i64)
+                .max(progress.start_ms as i64);
+            UiAction::SeekVoice(voice, ms as u32)
+        })
+    }
+
+    fn set_selected_voice_volume(&self, delta: f32) -> UiAction {
+        self.with_selected_voice(|voice, progress| {
+            UiAction::SetVoiceVolume(voice, (progress.volume + delta).clamp(0.0, MAX_VOLUME))
+        })
+    }
+
+    /// The sound whose waveform is on screen, if it hasn't been fetched yet
+    pub fn wanted_overview(&self) -> Option<String> {
+        let sound_id = match self.current_page {
+            Page::Home => self.sounds.get(self.selected_sound).map(|s| &s.id),
+            Page::Trim => self.trim_sound.as_ref(),
+            Page::Config | Page::Effects | Page::Spectrum | Page::Playing | Page::Help => None,
+        }?;
+        let fetched = self.overviews.contains_key(sound_id);
+        (!fetched && !self.overviews_pending.contains(sound_id)).then(|| sound_id.clone())
+    }
+
+    /// Keep a waveform fetched for a sound, or wait for the daemon to say it is
+    /// ready when it isn't yet or couldn't be fetched
+    pub fn update_overview(&mut self, sound_id: String, overview: Option<Overview>) {
+        match overview {
+            Some(overview) => {
+                self.overviews_pending.remove(&sound_id);
+                self.overviews.insert(sound_id, overview);
+            }
+            None => {
+                self.overviews_pending.insert(sound_id);
+            }
+        }
+    }
+
+    /// The signal to fetch the spectrum of, while the spectrum page is showing
+    pub fn wanted_spectrum(&self) -> Option<SpectrumSource> {
+        (self.current_page == Page::Spectrum).then_some(self.spectrum_source)
+    }
+
+    /// Show a new spectrum, raising the peaks it goes over
+    pub fn update_spectrum(&mut self, levels: Vec<f32>) {
+        if self.spectrum_peaks.len() != levels.len() {
+            let floor = PeakHold {
+                held_db: FLOOR_DB,
+                since: Instant::now(),
+            };
+            self.spectrum_peaks = vec![floor; levels.len()];
+        }
+        for (peak, &level_db) in self.spectrum_peaks.iter_mut().zip(&levels) {
+            peak.update(level_db);
+        }
+        self.spectrum = levels;
+    }
+
+    /// Length of the sound on the trim page, once its waveform has been fetched
+    pub fn trim_duration_ms(&self) -> u32 {
+        self.trim_sound
+            .as_ref()
+            .and_then(|sound_id| self.overviews.get(sound_id))
+            .map_or(0, |overview| overview.duration_ms)
+    }
+
+    /// Lines of the effects page, each effect followed by its parameters
+    pub fn effect_rows(&self) -> Vec<EffectRow> {
+        self.effects
+            .iter()
+            .enumerate()
+            .flat_map(|(effect, settings)| {
+                std::iter::once(EffectRow::Effect(effect))
+                    .chain((0..settings.params().len()).map(move |p| EffectRow::Param(effect, p)))
+            })
+            .collect()
+    }
+
+    fn selected_effect_row(&self) -> Option<EffectRow> {
+        self.effect_rows().get(self.selected_effect_row).copied()
+    }
+
+    fn select_effect(&mut self, effect: usize) {
+        self.selected_effect_row = self
+            .effect_rows()
+            .iter()
+            .position(|row| *row == EffectRow::Effect(effect))
+            .unwrap_or(0);
+    }
+
+    fn clamp_effect_row(&mut self) {
+        self.selected_effect_row = self
+            .selected_effect_row
+            .min(self.effect_rows().len().saturating_sub(1));
+    }
+
+    /// Start editing the effects of a sound, or the master effects
+    fn load_effects(&mut self, sound_id: Option<String>) -> UiAction {
+        self.effects.clear();
+        self.selected_effect_row = 0;
+        self.effects_sound = sound_id.clone();
+        UiAction::LoadEffects(sound_id)
+    }
+
+    /// Apply an edit to the effects being shown and send them to the daemon
+    fn edit_effects(&mut self, edit: impl FnOnce(&mut Self)) -> UiAction {
+        if self.current_page != Page::Effects {
+            return UiAction::None;
+        }
+        edit(self);
+        self.clamp_effect_row();
+        UiAction::SetEffects(self.effects_sound.clone(), self.effects.clone())
+    }
+
+    /// Change the selected parameter, or the type of the selected effect, by `steps`
+    fn adjust_effect(&mut self, steps: i32) -> UiAction {
+        match self.selected_effect_row() {
+            Some(EffectRow::Param(effect, param)) => {
+                self.edit_effects(|app| app.effects[effect].adjust(param, steps))
+            }
+            Some(EffectRow::Effect(effect)) => self.edit_effects(|app| {
+                let kinds = EffectSettings::defaults();
+                let current = kinds
+                    .iter()
+                    .position(|kind| {
+                        mem::discriminant(kind) == mem::discriminant(&app.effects[effect])
+                    })
+                    .unwrap_or(0);
+                let next = (current as i32 + steps).rem_euclid(kinds.len() as i32) as usize;
+                app.effects[effect] = kinds[next].clone();
+            }),
+            None => UiAction::None,
+        }
+    }
+
+    /// Swap the selected effect with its neighbour `offset` places away
+    fn move_effect(&mut self, offset: isize) -> UiAction {
+        let Some(effect) = self.selected_effect_row().map(|row| row.effect()) else {
+            return UiAction::None;
+        };
+        let Some(other) = effect
+            .checked_add_signed(offset)
+            .filter(|&other| other < self.effects.len())
+        else {
+            return UiAction::None;
+        };
+        self.edit_effects(|app| {
+            app.effects.swap(effect, other);
+            app.select_effect(other);
+        })
+    }
+
+    /// Lines of the trim page, the start and end followed by the cues
+    pub fn trim_rows(&self) -> Vec<TrimRow> {
+        [TrimRow::Start, TrimRow::End]
+            .into_iter()
+            .chain((0..self.trim.cues.len()).map(TrimRow::Cue))
+            .collect()
+    }
+
+    /// Where a trim page row is, in milliseconds from the start of the file
+    pub fn trim_position(&self, row: TrimRow) -> u32 {
+        match row {
+            TrimRow::Start => self.trim.start_ms.unwrap_or(0),
+            TrimRow::End => self.trim.end_ms.unwrap_or(self.trim_duration_ms()),
+            TrimRow::Cue(cue) => self.trim.cues.values().nth(cue).copied().unwrap_or(0),
+        }
+    }
+
+    fn selected_trim_row(&self) -> Option<TrimRow> {
+        self.trim_rows().get(self.selected_trim_row).copied()
+    }
+
+    fn clamp_trim_row(&mut self) {
+        self.selected_trim_row = self
+            .selected_trim_row
+            .min(self.trim_rows().len().saturating_sub(1));
+    }
+
+    /// Start editing the trim points of the selected sound
+    fn load_trim(&mut self) -> UiAction {
+        self.trim = Trim::default();
+        self.selected_trim_row = 0;
+        self.trim_sound = self.sounds.get(self.selected_sound).map(|s| s.id.clone());
+        match &self.trim_sound {
+            Some(sound_id) => UiAction::LoadTrim(sound_id.clone()),
+            None => UiAction::None,
+        }
+    }
+
+    /// Apply an edit to the trim points being shown and send them to the daemon
+    fn edit_trim(&mut self, edit: impl FnOnce(&mut Self)) -> UiAction {
+        if self.current_page != Page::Trim {
+            return UiAction::None;
+        }
+        let Some(sound_id) = self.trim_sound.clone() else {
+            return UiAction::None;
+        };
+        edit(self);
+        self.clamp_trim_row();
+        UiAction::SetTrim(sound_id, self.trim.clone())
+    }
+
+    /// Move the selected trim point or cue by `delta_ms`, keeping the start before the end
+    fn nudge_trim(&mut self, delta_ms: i64) -> UiAction {
+        let Some(row) = self.selected_trim_row() else {
+            return UiAction::None;
+        };
+        let duration = self.trim_duration_ms() as i64;
+        let start = self.trim_position(TrimRow::Start) as i64;
+        let end = self.trim_position(TrimRow::End) as i64;
+
+        self.edit_trim(|app| match row {
+            TrimRow::Start => {
+                let ms = (start + delta_ms).min(end - 1).max(0);
+                app.trim.start_ms = (ms > 0).then_some(ms as u32);
+            }
+            TrimRow::End => {
+                let ms = (end + delta_ms).max(start + 1).min(duration);
+                app.trim.end_ms = (ms < duration).then_some(ms as u32);
+            }
+            TrimRow::Cue(cue) => {
+                if let Some(ms) = app.trim.cues.values_mut().nth(cue) {
+                    *ms = (*ms as i64 + delta_ms).min(duration).max(0) as u32;
+                }
+            }
+        })
+    }
+
+    /// Add a cue where the selected row is, named `cue1`, `cue2` and so on
+    fn add_cue(&mut self) -> UiAction {
+        let position = self
+            .selected_trim_row()
+            .map_or(0, |row| self.trim_position(row));
+        let name = (1..)
+            .map(|n| format!("cue{}", n))
+            .find(|name| !self.trim.cues.contains_key(name))
+            .unwrap_or_default();
+
+        self.edit_trim(|app| {
+            app.trim.cues.insert(name.clone(), position);
+            let cue = app.trim.cues.keys().position(|key| *key == name);
+            app.selected_trim_row = cue.map_or(0, |cue| cue + 2);
+        })
+    }
+
+    fn remove_cue(&mut self) -> UiAction {
+        let Some(TrimRow::Cue(cue)) = self.selected_trim_row() else {
+            return UiAction::None;
+        };
+        self.edit_trim(|app| {
+            if let Some(name) = app.trim.cues.keys().nth(cue).cloned() {
+                app.trim.cues.remove(&name);
+            }
+        })
+    }
+
+    /// Play the sound being trimmed from the selected row, or up to it for the end point
+    fn audition_trim(&self) -> UiAction {
+        match (&self.trim_sound, self.selected_trim_row()) {
+            (Some(sound_id), Some(row)) => {
+                let position = self.trim_position(row);
+                let from = match row {
+                    TrimRow::End => position.saturating_sub(AUDITION_MS),
+                    TrimRow::Start | TrimRow::Cue(_) => position,
+                };
+                UiAction::PlayFrom(sound_id.clone(), from)
+            }
+            _ => UiAction::None,
+        }
+    }
+
     /// Handle keyboard input and return an action for the main app to handle
-    pub fn handle_key_event(&mut self, key: KeyCode) -> UiAction {
-        match key {
-            KeyCode::Char('q') | KeyCode::Esc => UiAction::Quit,
-            KeyCode::Char('1') => {
+    pub fn handle_key_event(&mut self, key: KeyEvent) -> UiAction {
+        self.pending_keys.push(KeyPress::from(key));
+
+        let lookup = match self.keymap.lookup(&self.pending_keys) {
+            // A key that breaks a pending sequence may still start a new one
+            KeyLookup::NoMatch if self.pending_keys.len() > 1 => {
+                self.pending_keys.drain(..self.pending_keys.len() - 1);
+                self.keymap.lookup(&self.pending_keys)
+            }
+            lookup => lookup,
+        };
+
+        match lookup {
+            KeyLookup::Pending => UiAction::None,
+            KeyLookup::NoMatch => {
+                self.pending_keys.clear();
+                UiAction::None
+            }
+            KeyLookup::Matched(action) => {
+                self.pending_keys.clear();
+                let ui_action = self.handle_key_action(action);
+                match &ui_action {
+                    UiAction::PlaySound(sound_id) => {
+                        self.held_key = Some((key.code, sound_id.clone()));
+                    }
+                    UiAction::HoldSpeed(_) => self.held_speed_key = Some(key.code),
+                    _ => {}
+                }
+                ui_action
+            }
+        }
+    }
+
+    /// Handle a key being let go, releasing the sound it played or the speed it held
+    pub fn handle_key_release(&mut self, key: KeyEvent) -> UiAction {
+        if self.held_speed_key == Some(key.code) {
+            self.held_speed_key = None;
+            return UiAction::HoldSpeed(1.0);
+        }
+        match self.held_key.take() {
+            Some((code, sound_id)) if code == key.code => UiAction::ReleaseSound(sound_id),
+            held => {
+                self.held_key = held;
+                UiAction::None
+            }
+        }
+    }
+
+    /// Hold the speed until the key is let go, or nudge it for good when the
+    /// terminal won't say when that is
+    fn speed_action(&self, factor: f32) -> UiAction {
+        if self.key_releases {
+            UiAction::HoldSpeed(factor)
+        } else {
+            UiAction::NudgeSpeed(factor)
+        }
+    }
+
+    /// Whether a speed key is held, so quitting should let go of the speed
+    pub fn holding_speed(&self) -> bool {
+        self.held_speed_key.is_some()
+    }
+
+    fn handle_key_action(&mut self, action: KeyAction) -> UiAction {
+        match action {
+            KeyAction::Quit => UiAction::Quit,
+            KeyAction::ShowHome => {
                 self.current_page = Page::Home;
                 UiAction::None
             }
-            KeyCode::Char('2') => {
+            KeyAction::ShowConfig => {
                 self.current_page = Page::Config;
                 UiAction::None
             }
-            KeyCode::Char('r') => UiAction::RefreshAudioDevices,
-            _ => UiAction::None,
+            KeyAction::ShowEffects => {
+                self.current_page = Page::Effects;
+                self.load_effects(self.effects_sound.clone())
+            }
+            KeyAction::ShowTrim => {
+                self.current_page = Page::Trim;
+                self.load_trim()
+            }
+            KeyAction::ShowSpectrum => {
+                self.current_page = Page::Spectrum;
+                UiAction::None
+            }
+            KeyAction::ShowPlaying => {
+                self.current_page = Page::Playing;
+                UiAction::None
+            }
+            KeyAction::ShowHelp => {
+                self.current_page = Page::Help;
+                UiAction::None
+            }
+            KeyAction::SelectNext if self.current_page == Page::Playing => {
+                if self.selected_voice + 1 < self.playing.len() {
+                    self.selected_voice += 1;
+                }
+                UiAction::None
+            }
+            KeyAction::SelectPrevious if self.current_page == Page::Playing => {
+                self.selected_voice = self.selected_voice.saturating_sub(1);
+                UiAction::None
+            }
+            KeyAction::StopSelected if self.current_page == Page::Playing => self
+                .selected_voice()
+                .map_or(UiAction::None, UiAction::StopVoice),
+            KeyAction::VolumeUp if self.current_page == Page::Playing => {
+                self.set_selected_voice_volume(VOLUME_STEP)
+            }
+            KeyAction::VolumeDown if self.current_page == Page::Playing => {
+                self.set_selected_voice_volume(-VOLUME_STEP)
+            }
+            KeyAction::TogglePause if self.current_page == Page::Playing => self
+                .with_selected_voice(|voice, progress| {
+                    UiAction::PauseVoice(voice, !progress.paused)
+                }),
+            KeyAction::TogglePause => UiAction::None,
+            KeyAction::TogglePauseAll => {
+                // Resume only once everything is paused, so a second press undoes the first
+                let all_paused = self
+                    .playing
+                    .iter()
+                    .all(|(voice, _)| self.progress.get(voice).is_some_and(|p| p.paused));
+                UiAction::Pause(None, !all_paused)
+            }
+            KeyAction::IncreaseValue if self.current_page == Page::Playing => {
+                self.seek_selected_voice(SEEK_STEP_MS)
+            }
+            KeyAction::DecreaseValue if self.current_page == Page::Playing => {
+                self.seek_selected_voice(-SEEK_STEP_MS)
+            }
+            KeyAction::IncreaseValueLarge if self.current_page == Page::Playing => {
+                self.seek_selected_voice(LARGE_SEEK_STEP_MS)
+            }
+            KeyAction::DecreaseValueLarge if self.current_page == Page::Playing => {
+                self.seek_selected_voice(-LARGE_SEEK_STEP_MS)
+            }
+            KeyAction::SelectNext if self.current_page == Page::Trim => {
+                if self.selected_trim_row + 1 < self.trim_rows().len() {
+                    self.selected_trim_row += 1;
+                }
+                UiAction::None
+            }
+            KeyAction::SelectPrevious if self.current_page == Page::Trim => {
+                self.selected_trim_row = self.selected_trim_row.saturating_sub(1);
+                UiAction::None
+            }
+            KeyAction::PlaySelected if self.current_page == Page::Trim => self.audition_trim(),
+            KeyAction::SelectNext if self.current_page == Page::Help => {
+                if self.selected_help_row + 1 < KeyAction::ALL.len() {
+                    self.selected_help_row += 1;
+                }
+                UiAction::None
+            }
+            KeyAction::SelectPrevious if self.current_page == Page::Help => {
+                self.selected_help_row = self.selected_help_row.saturating_sub(1);
+                UiAction::None
+            }
+            KeyAction::SelectNext if self.current_page == Page::Effects => {
+                if self.selected_effect_row + 1 < self.effect_rows().len() {
+                    self.selected_effect_row += 1;
+                }
+                UiAction::None
+            }
+            KeyAction::SelectPrevious if self.current_page == Page::Effects => {
+                self.selected_effect_row = self.selected_effect_row.saturating_sub(1);
+                UiAction::None
+            }
+            KeyAction::SelectNext => {
+                if self.selected_sound + 1 < self.sounds.len() {
+                    self.selected_sound += 1;
+                }
+                UiAction::None
+            }
+            KeyAction::SelectPrevious => {
+                self.selected_sound = self.selected_sound.saturating_sub(1);
+                UiAction::None
+            }
+            KeyAction::PlaySelected => match self.sounds.get(self.selected_sound) {
+                Some(sound) => UiAction::PlaySound(sound.id.clone()),
+                None => UiAction::None,
+            },
+            KeyAction::StopSelected => match self.sounds.get(self.selected_sound) {
+                Some(sound) => UiAction::StopSound(sound.id.clone()),
+                None => UiAction::None,
+            },
+            KeyAction::StopAll => UiAction::StopAll,
+            KeyAction::FadeOutAll => UiAction::FadeOutAll,
+            KeyAction::VolumeUp => {
+                UiAction::SetVolume(None, (self.master_volume + VOLUME_STEP).min(MAX_VOLUME))
+            }
+            KeyAction::VolumeDown => {
+                UiAction::SetVolume(None, (self.master_volume - VOLUME_STEP).max(0.0))
+            }
+            KeyAction::SpeedUp => self.speed_action(SPEED_STEP),
+            KeyAction::SlowDown => self.speed_action(1.0 / SPEED_STEP),
+            KeyAction::MidiLearn => match self.sounds.get(self.selected_sound) {
+                Some(sound) => {
+                    self.midi_learning = Some(sound.id.clone());
+                    UiAction::MidiLearn(sound.id.clone())
+                }
+                None => UiAction::None,
+            },
+            KeyAction::NextVoicePreset => UiAction::NextVoicePreset,
+            KeyAction::RefreshDevices => UiAction::RefreshAudioDevices,
+            KeyAction::ToggleEffectsTarget if self.current_page == Page::Effects => {
+                let target = match self.effects_sound {
+                    Some(_) => None,
+                    None => self.sounds.get(self.selected_sound).map(|s| s.id.clone()),
+                };
+                self.load_effects(target)
+            }
+            KeyAction::AddEffect => {
+                let position = self
+                    .selected_effect_row()
+                    .map_or(self.effects.len(), |row| row.effect() + 1);
+                self.edit_effects(|app| {
+                    let [first, ..] = EffectSettings::defaults();
+                    app.effects.insert(position, first);
+                    app.select_effect(position);
+                })
+            }
+            KeyAction::RemoveEffect => match self.selected_effect_row() {
+                Some(row) => self.edit_effects(|app| {
+                    app.effects.remove(row.effect());
+                    app.select_effect(row.effect().saturating_sub(1));
+                }),
+                None => UiAction::None,
+            },
+            KeyAction::MoveEffectUp => self.move_effect(-1),
+            KeyAction::MoveEffectDown => self.move_effect(1),
+            KeyAction::AddCue => self.add_cue(),
+            KeyAction::RemoveCue => self.remove_cue(),
+            KeyAction::ToggleSpectrumSource => {
+                self.spectrum_source = match self.spectrum_source {
+                    SpectrumSource::Master => SpectrumSource::Mic,
+                    SpectrumSource::Mic => SpectrumSource::Master,
+                };
+                // The peaks of the other signal mean nothing for this one
+                self.spectrum_peaks.clear();
+                UiAction::None
+            }
+            KeyAction::IncreaseValue if self.current_page == Page::Trim => {
+                self.nudge_trim(TRIM_STEP_MS)
+            }
+            KeyAction::DecreaseValue if self.current_page == Page::Trim => {
+                self.nudge_trim(-TRIM_STEP_MS)
+            }
+            KeyAction::IncreaseValueLarge if self.current_page == Page::Trim => {
+                self.nudge_trim(LARGE_TRIM_STEP_MS)
+            }
+            KeyAction::DecreaseValueLarge if self.current_page == Page::Trim => {
+                self.nudge_trim(-LARGE_TRIM_STEP_MS)
+            }
+            KeyAction::IncreaseValue => self.adjust_effect(1),
+            KeyAction::DecreaseValue => self.adjust_effect(-1),
+            KeyAction::IncreaseValueLarge => self.adjust_effect(LARGE_EFFECT_STEPS),
+            KeyAction::DecreaseValueLarge => self.adjust_effect(-LARGE_EFFECT_STEPS),
+            KeyAction::ToggleEffectsTarget => UiAction::None,
         }
     }
 
@@ -47,17 +844,208 @@ impl App {
         match self.current_page {
             Page::Home => HomePage::render(frame, self),
             Page::Config => ConfigPage::render(frame, self),
+            Page::Effects => EffectsPage::render(frame, self),
+            Page::Trim => TrimPage::render(frame, self),
+            Page::Spectrum => SpectrumPage::render(frame, self),
+            Page::Playing => PlayingPage::render(frame, self),
+            Page::Help => HelpPage::render(frame, self),
         }
     }
 
     pub fn poll_events(&mut self) -> io::Result<UiAction> {
-        if event::poll(std::time::Duration::from_millis(100))? {
-            if let Event::Key(key) = event::read()? {
-                if key.kind == KeyEventKind::Press {
-                    return Ok(self.handle_key_event(key.code));
-                }
+        // Short enough to redraw the level meters about as often as they are sent
+        if event::poll(std::time::Duration::from_millis(33))?
+            && let Event::Key(key) = event::read()?
+        {
+            match key.kind {
+                KeyEventKind::Press => return Ok(self.handle_key_event(key)),
+                KeyEventKind::Release => return Ok(self.handle_key_release(key)),
+                KeyEventKind::Repeat => {}
             }
         }
         Ok(UiAction::None)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    /// An app on the trim page of a one second sound
+    fn trimming(trim: Trim, row: TrimRow) -> App {
+        let mut app = App::new(Keymap::default());
+        app.current_page = Page::Trim;
+        app.trim_sound = Some("a".to_string());
+        app.overviews.insert(
+            "a".to_string(),
+            Overview {
+                duration_ms: 1000,
+                min: Vec::new(),
+                max: Vec::new(),
+            },
+        );
+        app.trim = trim;
+        app.selected_trim_row = app.trim_rows().iter().position(|r| *r == row).unwrap();
+        app
+    }
+
+    fn trim(start_ms: Option<u32>, end_ms: Option<u32>) -> Trim {
+        Trim {
+            start_ms,
+            end_ms,
+            cues: BTreeMap::from([("drop".to_string(), 500)]),
+        }
+    }
+
+    #[test]
+    fn start_stays_before_the_end() {
+        let mut app = trimming(trim(Some(100), Some(300)), TrimRow::Start);
+        app.nudge_trim(1000);
+        assert_eq!(app.trim.start_ms, Some(299));
+
+        // Back at the start of the file the trim point goes away
+        app.nudge_trim(-1000);
+        assert_eq!(app.trim.start_ms, None);
+    }
+
+    #[test]
+    fn end_stays_after_the_start() {
+        let mut app = trimming(trim(Some(100), Some(300)), TrimRow::End);
+        app.nudge_trim(-1000);
+        assert_eq!(app.trim.end_ms, Some(101));
+
+        app.nudge_trim(5000);
+        assert_eq!(app.trim.end_ms, None);
+    }
+
+    #[test]
+    fn cues_stay_in_the_file() {
+        let mut app = trimming(trim(None, None), TrimRow::Cue(0));
+        app.nudge_trim(5000);
+        assert_eq!(app.trim.cues["drop"], 1000);
+        app.nudge_trim(-5000);
+        assert_eq!(app.trim.cues["drop"], 0);
+    }
+
+    #[test]
+    fn edits_are_sent_to_the_daemon() {
+        let mut app = trimming(trim(Some(100), None), TrimRow::Start);
+        assert_eq!(
+            app.nudge_trim(TRIM_STEP_MS),
+            UiAction::SetTrim("a".to_string(), trim(Some(110), None))
+        );
+
+        app.current_page = Page::Home;
+        assert_eq!(app.nudge_trim(TRIM_STEP_MS), UiAction::None);
+    }
+
+    fn progress(voice: u64) -> DaemonEvent {
+        DaemonEvent::SoundProgress {
+            voice,
+            id: format!("sound{}", voice),
+            progress: VoiceProgress {
+                position_ms: 0,
+                start_ms: 0,
+                end_ms: 1000,
+                play: 1,
+                plays: Some(1),
+                volume: 1.0,
+                paused: false,
+                streamed: false,
+                latency_ms: None,
+            },
+        }
+    }
+
+    #[test]
+    fn voices_playing_before_connecting_are_listed() {
+        let mut app = App::new(Keymap::default());
+        app.handle_daemon_event(DaemonEvent::SoundStarted {
+            voice: 5,
+            id: "sound5".to_string(),
+        });
+        app.handle_daemon_event(progress(5));
+        app.handle_daemon_event(progress(2));
+        app.handle_daemon_event(progress(2));
+
+        assert_eq!(
+            app.playing,
+            vec![(2, "sound2".to_string()), (5, "sound5".to_string())]
+        );
+        assert_eq!(app.progress.len(), 2);
+
+        app.handle_daemon_event(DaemonEvent::SoundStopped {
+            voice: 2,
+            id: "sound2".to_string(),
+        });
+        assert_eq!(app.playing, vec![(5, "sound5".to_string())]);
+        assert!(!app.progress.contains_key(&2));
+    }
+
+    #[test]
+    fn waveforms_not_ready_are_asked_for_once_they_are() {
+        let mut app = App::new(Keymap::default());
+        app.update_sounds(vec![Sound {
+            id: "a".to_string(),
+            name: "A".to_string(),
+            path: "a.wav".into(),
+        }]);
+        assert_eq!(app.wanted_overview().as_deref(), Some("a"));
+
+        app.update_overview("a".to_string(), None);
+        assert_eq!(app.wanted_overview(), None);
+
+        app.handle_daemon_event(DaemonEvent::WaveformReady {
+            id: "a".to_string(),
+        });
+        assert_eq!(app.wanted_overview().as_deref(), Some("a"));
+        app.update_overview("a".to_string(), Some(Overview::default()));
+        assert_eq!(app.wanted_overview(), None);
+
+        // Asked for again when the file changes
+        app.handle_daemon_event(DaemonEvent::WaveformReady {
+            id: "a".to_string(),
+        });
+        assert_eq!(app.wanted_overview().as_deref(), Some("a"));
+    }
+
+    #[test]
+    fn speed_keys_hold_the_speed_until_let_go() {
+        let press = KeyEvent::from(KeyCode::Char(']'));
+        let release = KeyEvent {
+            kind: KeyEventKind::Release,
+            ..press
+        };
+
+        let mut app = App::new(Keymap::default());
+        app.key_releases = true;
+        assert_eq!(app.handle_key_event(press), UiAction::HoldSpeed(SPEED_STEP));
+        assert!(app.holding_speed());
+        assert_eq!(app.handle_key_release(release), UiAction::HoldSpeed(1.0));
+        assert_eq!(app.handle_key_release(release), UiAction::None);
+
+        // Without releases the speed could never be let go of
+        app.key_releases = false;
+        assert_eq!(
+            app.handle_key_event(press),
+            UiAction::NudgeSpeed(SPEED_STEP)
+        );
+        assert!(!app.holding_speed());
+    }
+
+    #[test]
+    fn help_scrolls_through_every_action() {
+        let mut app = App::new(Keymap::default());
+        app.handle_key_action(KeyAction::ShowHelp);
+        app.handle_key_action(KeyAction::SelectPrevious);
+        assert_eq!(app.selected_help_row, 0);
+
+        for _ in 0..KeyAction::ALL.len() + 5 {
+            app.handle_key_action(KeyAction::SelectNext);
+        }
+        assert_eq!(app.selected_help_row, KeyAction::ALL.len() - 1);
+        assert_eq!(app.selected_sound, 0);
+    }
+}
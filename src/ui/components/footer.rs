@@ -2,13 +2,47 @@ use ratatui::{
     Frame,
     layout::{Alignment, Rect},
     style::{Color, Style},
+    text::{Line, Span},
     widgets::{Block, Borders, Paragraph},
 };
 
-pub fn render_footer(frame: &mut Frame, area: Rect) {
-    let footer = Paragraph::new("Press [1] Home | [2] Config | [r] Refresh | [q][esc] Quit")
+use super::meter::{BusMeters, level_meter};
+use crate::settings::keymap::{KeyAction, Keymap};
+
+pub fn render_footer(frame: &mut Frame, area: Rect, keymap: &Keymap) {
+    let footer = Paragraph::new(format!("Press {}", footer_text(keymap)))
         .alignment(Alignment::Center)
         .style(Style::default().fg(Color::DarkGray))
         .block(Block::default().borders(Borders::ALL));
     frame.render_widget(footer, area);
 }
+
+/// One line of level meters for the output, the mic and the virtual source,
+/// shown below the footer on every page
+pub fn render_status_bar(frame: &mut Frame, area: Rect, meters: &BusMeters) {
+    let mut spans = level_meter("Out", &meters.master);
+    spans.push(Span::raw("   "));
+    spans.extend(level_meter("Mic", &meters.mic));
+    spans.push(Span::raw("   "));
+    spans.extend(level_meter("Virtual mic", &meters.virtual_mic));
+    frame.render_widget(
+        Paragraph::new(Line::from(spans)).alignment(Alignment::Center),
+        area,
+    );
+}
+
+/// Build the footer hints, e.g. `[1] Home | [q][esc] Quit`, from the active keymap
+fn footer_text(keymap: &Keymap) -> String {
+    KeyAction::ALL
+        .into_iter()
+        .filter(KeyAction::in_footer)
+        .filter_map(|action| {
+            let keys: String = keymap
+                .keys_for(action)
+                .map(|keys| format!("[{}]", keys))
+                .collect();
+            (!keys.is_empty()).then(|| format!("{} {}", keys, action.label()))
+        })
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
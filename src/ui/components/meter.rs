@@ -0,0 +1,126 @@
+use std::time::{Duration, Instant};
+
+use ratatui::{
+    style::{Color, Style},
+    text::{Line, Span},
+};
+
+use crate::audio::levels::Level;
+
+/// Gain reduction that fills a whole meter, in dB
+const FULL_REDUCTION_DB: f32 = 24.0;
+const METER_WIDTH: usize = 24;
+
+/// A line like `Gate       ████▌·······  6.0 dB` showing how far a stage turns its
+/// input down
+pub fn reduction_meter(label: &str, reduction_db: f32) -> Line<'static> {
+    let reduction_db = reduction_db.max(0.0);
+    let filled = (reduction_db / FULL_REDUCTION_DB).min(1.0) * METER_WIDTH as f32;
+    let whole = filled as usize;
+    let half = filled.fract() >= 0.5 && whole < METER_WIDTH;
+
+    let mut bar = "█".repeat(whole);
+    if half {
+        bar.push('▌');
+    }
+    let empty = "·".repeat(METER_WIDTH - whole - half as usize);
+
+    Line::from(vec![
+        Span::raw(format!("{:<11}", label)),
+        Span::styled(bar, Style::default().fg(Color::Red)),
+        Span::styled(empty, Style::default().fg(Color::DarkGray)),
+        Span::raw(format!(" {:>5.1} dB", reduction_db)),
+    ])
+}
+
+/// Quietest level a level meter shows, in dB
+const LEVEL_FLOOR_DB: f32 = -60.0;
+const LEVEL_METER_WIDTH: usize = 16;
+/// A peak this high counts as clipping
+const CLIP_LEVEL: f32 = 1.0;
+/// How long the clip indicator stays lit after the last clip
+const CLIP_HOLD: Duration = Duration::from_secs(3);
+
+/// Meters of the output, the mic and the virtual source
+#[derive(Debug, Clone, Default)]
+pub struct BusMeters {
+    pub master: MeterReading,
+    pub mic: MeterReading,
+    pub virtual_mic: MeterReading,
+}
+
+/// The latest level of a bus, and when it last clipped
+#[derive(Debug, Clone, Default)]
+pub struct MeterReading {
+    /// Unset while the bus isn't running
+    pub level: Option<Level>,
+    clipped_at: Option<Instant>,
+}
+
+impl MeterReading {
+    pub fn update(&mut self, level: Option<Level>) {
+        if level.is_some_and(|level| level.peak >= CLIP_LEVEL) {
+            self.clipped_at = Some(Instant::now());
+        }
+        self.level = level;
+    }
+
+    /// Whether the bus has clipped in the last few seconds
+    pub fn clipping(&self) -> bool {
+        self.clipped_at
+            .is_some_and(|clipped_at| clipped_at.elapsed() < CLIP_HOLD)
+    }
+}
+
+/// Spans like `Out ████▒▒·········· -12 dB CLIP`: the RMS level in solid
+/// blocks, the peak beyond it shaded, and a clip indicator
+pub fn level_meter(label: &str, reading: &MeterReading) -> Vec<Span<'static>> {
+    let mut spans = vec![Span::raw(format!("{} ", label))];
+
+    let Some(level) = reading.level else {
+        spans.push(Span::styled(
+            "·".repeat(LEVEL_METER_WIDTH),
+            Style::default().fg(Color::DarkGray),
+        ));
+        spans.push(Span::styled(
+            "    off     ",
+            Style::default().fg(Color::DarkGray),
+        ));
+        return spans;
+    };
+
+    let (rms_db, peak_db) = (gain_to_db(level.rms), gain_to_db(level.peak));
+    spans.extend((0..LEVEL_METER_WIDTH).map(|i| {
+        let db = LEVEL_FLOOR_DB * (1.0 - (i + 1) as f32 / LEVEL_METER_WIDTH as f32);
+        let color = if db > -6.0 {
+            Color::Red
+        } else if db > -18.0 {
+            Color::Yellow
+        } else {
+            Color::Green
+        };
+        if rms_db >= db {
+            Span::styled("█", Style::default().fg(color))
+        } else if peak_db >= db {
+            Span::styled("▒", Style::default().fg(color))
+        } else {
+            Span::styled("·", Style::default().fg(Color::DarkGray))
+        }
+    }));
+
+    spans.push(Span::raw(if peak_db > LEVEL_FLOOR_DB {
+        format!(" {:>3.0} dB", peak_db)
+    } else {
+        "  -∞ dB".to_string()
+    }));
+    spans.push(if reading.clipping() {
+        Span::styled(" CLIP", Style::default().fg(Color::Red))
+    } else {
+        Span::raw("     ")
+    });
+    spans
+}
+
+fn gain_to_db(gain: f32) -> f32 {
+    20.0 * gain.max(f32::MIN_POSITIVE).log10()
+}
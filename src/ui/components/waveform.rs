@@ -0,0 +1,118 @@
+use ratatui::{buffer::Buffer, layout::Rect, style::Color, widgets::Widget};
+
+use crate::audio::overview::Overview;
+
+/// Bits of the dots in a braille cell, by column and then by row from the top
+const BRAILLE_DOTS: [[u32; 4]; 2] = [[0x01, 0x02, 0x04, 0x40], [0x08, 0x10, 0x20, 0x80]];
+const BRAILLE_BLANK: u32 = 0x2800;
+
+/// A sound's waveform in braille, two dots across and four down in each cell,
+/// with lines for markers and the playhead
+pub struct Waveform<'a> {
+    overview: &'a Overview,
+    region: (f32, f32),
+    markers: &'a [(f32, Color)],
+    playhead: Option<f32>,
+}
+
+impl<'a> Waveform<'a> {
+    pub fn new(overview: &'a Overview) -> Self {
+        Self {
+            overview,
+            region: (0.0, 1.0),
+            markers: &[],
+            playhead: None,
+        }
+    }
+
+    /// Dim the waveform outside the part between `start` and `end`, as fractions of the sound
+    pub fn region(mut self, start: f32, end: f32) -> Self {
+        self.region = (start, end);
+        self
+    }
+
+    /// Draw a line in its color at each marker's fraction of the sound. Later
+    /// markers are drawn over earlier ones.
+    pub fn markers(mut self, markers: &'a [(f32, Color)]) -> Self {
+        self.markers = markers;
+        self
+    }
+
+    /// Draw the playhead at a fraction of the sound, over everything else
+    pub fn playhead(mut self, playhead: Option<f32>) -> Self {
+        self.playhead = playhead;
+        self
+    }
+}
+
+impl Widget for Waveform<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let points = self.overview.min.len().min(self.overview.max.len());
+        if area.is_empty() || points == 0 {
+            return;
+        }
+        let width = area.width as usize;
+        let dot_columns = width * 2;
+        let dot_rows = area.height as usize * 4;
+
+        // Full scale reaches the top and bottom rows, silence is the middle one
+        let row_of = |sample: f32| {
+            ((1.0 - sample.clamp(-1.0, 1.0)) / 2.0 * (dot_rows - 1) as f32).round() as usize
+        };
+
+        let mut dots = vec![0u32; width * area.height as usize];
+        for column in 0..dot_columns {
+            let start = column * points / dot_columns;
+            let end = ((column + 1) * points / dot_columns)
+                .max(start + 1)
+                .min(points);
+            let low = self.overview.min[start..end]
+                .iter()
+                .fold(0.0f32, |low, &s| low.min(s));
+            let high = self.overview.max[start..end]
+                .iter()
+                .fold(0.0f32, |high, &s| high.max(s));
+            for row in row_of(high)..=row_of(low) {
+                dots[row / 4 * width + column / 2] |= BRAILLE_DOTS[column % 2][row % 4];
+            }
+        }
+
+        let mut colors: Vec<Color> = (0..width)
+            .map(|x| {
+                let fraction = (x as f32 + 0.5) / width as f32;
+                if fraction < self.region.0 || fraction > self.region.1 {
+                    Color::DarkGray
+                } else {
+                    Color::Cyan
+                }
+            })
+            .collect();
+
+        let lines = self
+            .markers
+            .iter()
+            .copied()
+            .chain(self.playhead.map(|fraction| (fraction, Color::White)));
+        for (fraction, color) in lines {
+            let column =
+                ((fraction.clamp(0.0, 1.0) * dot_columns as f32) as usize).min(dot_columns - 1);
+            colors[column / 2] = color;
+            for row in 0..dot_rows {
+                dots[row / 4 * width + column / 2] |= BRAILLE_DOTS[column % 2][row % 4];
+            }
+        }
+
+        for (i, &cell) in dots.iter().enumerate() {
+            let (x, y) = (i % width, i / width);
+            let symbol = char::from_u32(BRAILLE_BLANK + cell).unwrap_or(' ');
+            buf[(area.x + x as u16, area.y + y as u16)]
+                .set_char(symbol)
+                .set_fg(colors[x]);
+        }
+    }
+}
+
+/// Milliseconds as e.g. `1:02.345`
+pub fn format_ms(ms: u32) -> String {
+    format!("{}:{:02}.{:03}", ms / 60_000, ms / 1000 % 60, ms % 1000)
+}
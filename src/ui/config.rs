@@ -7,12 +7,14 @@ use ratatui::{
 };
 
 use super::app::App;
-use super::components::footer::render_footer;
+use super::components::footer::{render_footer, render_status_bar};
+use super::components::meter::reduction_meter;
+use crate::settings::keymap::KeyAction;
 
 pub struct ConfigPage;
 
 impl ConfigPage {
-    pub fn render(frame: &mut Frame, _app: &App) {
+    pub fn render(frame: &mut Frame, app: &App) {
         let area = frame.area();
 
         let chunks = Layout::default()
@@ -21,6 +23,7 @@ impl ConfigPage {
                 Constraint::Length(3),
                 Constraint::Min(10),
                 Constraint::Length(3),
+                Constraint::Length(1),
             ])
             .split(area);
 
@@ -39,22 +42,74 @@ impl ConfigPage {
             );
         frame.render_widget(title, chunks[0]);
 
-        let config_text = vec![
-            Line::from(""),
+        let heading = |text: &'static str| {
             Line::from(Span::styled(
-                "Settings",
+                text,
                 Style::default()
                     .fg(Color::Yellow)
                     .add_modifier(Modifier::BOLD),
-            )),
-            Line::from(""),
-            Line::from("Configuration options will be available here."),
-            Line::from(""),
-            Line::from(Span::styled(
-                "Coming soon!",
+            ))
+        };
+
+        let mut config_text = vec![Line::from(""), heading("MIDI Bindings"), Line::from("")];
+
+        if app.midi_bindings.is_empty() {
+            config_text.push(Line::from(Span::styled(
+                "No MIDI bindings yet.",
+                Style::default().fg(Color::DarkGray),
+            )));
+        }
+        for binding in &app.midi_bindings {
+            config_text.push(Line::from(binding.to_string()));
+        }
+
+        config_text.push(Line::from(""));
+        config_text.push(match &app.midi_learning {
+            Some(sound_id) => Line::from(Span::styled(
+                format!(
+                    "Waiting for a MIDI note or controller for '{}'...",
+                    sound_id
+                ),
                 Style::default().fg(Color::Green),
             )),
-        ];
+            None => {
+                let keys = app
+                    .keymap
+                    .keys_for(KeyAction::MidiLearn)
+                    .map(|keys| format!("[{}]", keys))
+                    .collect::<String>();
+                Line::from(Span::styled(
+                    format!(
+                        "Press {} to bind a MIDI trigger to the selected sound.",
+                        keys
+                    ),
+                    Style::default().fg(Color::DarkGray),
+                ))
+            }
+        });
+
+        config_text.extend([Line::from(""), heading("Mic"), Line::from("")]);
+        let keys = app
+            .keymap
+            .keys_for(KeyAction::NextVoicePreset)
+            .map(|keys| format!("[{}]", keys))
+            .collect::<String>();
+        config_text.push(Line::from(format!(
+            "Voice changer: {}  {}",
+            app.voice_preset.as_deref().unwrap_or("off"),
+            keys
+        )));
+        config_text.push(Line::from(match app.mic_latency_ms {
+            Some(latency_ms) => format!("Latency: {:.1} ms", latency_ms),
+            None => "Latency: -".to_string(),
+        }));
+        config_text.push(Line::from(""));
+        let reduction = &app.mic_gain_reduction;
+        config_text.extend([
+            reduction_meter("Gate", reduction.gate_db),
+            reduction_meter("Compressor", reduction.compressor_db),
+            reduction_meter("Limiter", reduction.limiter_db),
+        ]);
 
         let config = Paragraph::new(config_text)
             .alignment(Alignment::Center)
@@ -65,6 +120,7 @@ impl ConfigPage {
                     .border_style(Style::default().fg(Color::Magenta)),
             );
         frame.render_widget(config, chunks[1]);
-        render_footer(frame, chunks[2]);
+        render_footer(frame, chunks[2], &app.keymap);
+        render_status_bar(frame, chunks[3], &app.meters);
     }
 }
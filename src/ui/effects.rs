@@ -0,0 +1,125 @@
+use ratatui::{
+    Frame,
+    layout::{Alignment, Constraint, Direction, Layout},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+};
+
+use super::app::{App, EffectRow};
+use super::components::footer::{render_footer, render_status_bar};
+use crate::settings::keymap::KeyAction;
+
+pub struct EffectsPage;
+
+impl EffectsPage {
+    pub fn render(frame: &mut Frame, app: &App) {
+        let area = frame.area();
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(3),
+                Constraint::Fill(1),
+                Constraint::Length(3),
+                Constraint::Length(1),
+            ])
+            .split(area);
+
+        let target = match &app.effects_sound {
+            Some(sound_id) => app
+                .sounds
+                .iter()
+                .find(|sound| &sound.id == sound_id)
+                .map_or(sound_id.as_str(), |sound| sound.name.as_str()),
+            None => "Master",
+        };
+        let title = Paragraph::new(format!("Effects: {}", target))
+            .style(
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+            )
+            .alignment(Alignment::Center)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(Color::White)),
+            );
+        frame.render_widget(title, chunks[0]);
+
+        let items: Vec<ListItem> = app
+            .effect_rows()
+            .into_iter()
+            .map(|row| match row {
+                EffectRow::Effect(effect) => ListItem::new(Line::from(Span::styled(
+                    format!("{}. {}", effect + 1, app.effects[effect].name()),
+                    Style::default().add_modifier(Modifier::BOLD),
+                ))),
+                EffectRow::Param(effect, param) => {
+                    let settings = &app.effects[effect];
+                    let info = settings.params()[param];
+                    let value = settings.values()[param];
+                    ListItem::new(Line::from(format!(
+                        "    {:<12} {:>9.2} {}",
+                        info.name, value, info.unit
+                    )))
+                }
+            })
+            .collect();
+
+        let hint = [
+            KeyAction::AddEffect,
+            KeyAction::RemoveEffect,
+            KeyAction::ToggleEffectsTarget,
+        ]
+        .into_iter()
+        .map(|action| {
+            let keys: String = app
+                .keymap
+                .keys_for(action)
+                .map(|keys| format!("[{}]", keys))
+                .collect();
+            format!("{} {}", keys, action.label())
+        })
+        .collect::<Vec<_>>()
+        .join(" | ");
+
+        let mut block = Block::default()
+            .borders(Borders::ALL)
+            .title("Chain")
+            .title_bottom(Span::styled(
+                format!(" {} ", hint),
+                Style::default().fg(Color::DarkGray),
+            ))
+            .border_style(Style::default().fg(Color::Magenta));
+        if let Some(message) = &app.status_message {
+            block = block.title_bottom(Span::styled(
+                format!(" {} ", message),
+                Style::default().fg(Color::Red),
+            ));
+        }
+
+        if items.is_empty() {
+            let empty = Paragraph::new("No effects. Add one to start a chain.")
+                .alignment(Alignment::Center)
+                .style(Style::default().fg(Color::DarkGray))
+                .block(block);
+            frame.render_widget(empty, chunks[1]);
+        } else {
+            let list = List::new(items)
+                .block(block)
+                .highlight_style(
+                    Style::default()
+                        .fg(Color::Yellow)
+                        .add_modifier(Modifier::BOLD),
+                )
+                .highlight_symbol("> ");
+            let mut state = ListState::default().with_selected(Some(app.selected_effect_row));
+            frame.render_stateful_widget(list, chunks[1], &mut state);
+        }
+
+        render_footer(frame, chunks[2], &app.keymap);
+        render_status_bar(frame, chunks[3], &app.meters);
+    }
+}
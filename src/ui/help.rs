@@ -0,0 +1,84 @@
+use ratatui::{
+    Frame,
+    layout::{Alignment, Constraint, Direction, Layout},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+};
+
+use super::app::App;
+use super::components::footer::{render_footer, render_status_bar};
+use crate::settings::keymap::KeyAction;
+
+pub struct HelpPage;
+
+impl HelpPage {
+    pub fn render(frame: &mut Frame, app: &App) {
+        let area = frame.area();
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(3),
+                Constraint::Min(10),
+                Constraint::Length(3),
+                Constraint::Length(1),
+            ])
+            .split(area);
+
+        let title = Paragraph::new("Help")
+            .style(
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+            )
+            .alignment(Alignment::Center)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(Color::White)),
+            );
+        frame.render_widget(title, chunks[0]);
+
+        // One row per action, listing every key sequence bound to it. There are more
+        // than fit a small terminal, so the list scrolls with the selection
+        let items: Vec<ListItem> = KeyAction::ALL
+            .iter()
+            .map(|action| {
+                let keys = app
+                    .keymap
+                    .keys_for(*action)
+                    .map(|keys| keys.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+
+                ListItem::new(Line::from(vec![
+                    Span::styled(
+                        format!("{:>16}  ", keys),
+                        Style::default()
+                            .fg(Color::Yellow)
+                            .add_modifier(Modifier::BOLD),
+                    ),
+                    Span::raw(action.description()),
+                ]))
+            })
+            .collect();
+
+        let help = List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!(
+                        "Key Bindings ({}/{})",
+                        app.selected_help_row + 1,
+                        KeyAction::ALL.len()
+                    ))
+                    .border_style(Style::default().fg(Color::Magenta)),
+            )
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        let mut state = ListState::default().with_selected(Some(app.selected_help_row));
+        frame.render_stateful_widget(help, chunks[1], &mut state);
+        render_footer(frame, chunks[2], &app.keymap);
+        render_status_bar(frame, chunks[3], &app.meters);
+    }
+}
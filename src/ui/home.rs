@@ -3,12 +3,15 @@ use ratatui::{
     layout::{Alignment, Constraint, Direction, Layout},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, List, Paragraph},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
 };
 
 use super::app::App;
-use super::components::footer::render_footer;
-use crate::audio::DeviceType;
+use super::components::footer::{render_footer, render_status_bar};
+use super::components::waveform::{Waveform, format_ms};
+
+/// Rows of the selected sound's waveform, inside its border
+const WAVEFORM_HEIGHT: u16 = 4;
 
 pub struct HomePage;
 
@@ -21,7 +24,9 @@ impl HomePage {
             .constraints([
                 Constraint::Length(3),
                 Constraint::Fill(1),
+                Constraint::Length(WAVEFORM_HEIGHT + 2),
                 Constraint::Length(3),
+                Constraint::Length(1),
             ])
             .split(area);
 
@@ -39,7 +44,76 @@ impl HomePage {
             );
 
         frame.render_widget(title, chunks[0]);
-        frame.render_widget(Block::new(), chunks[1]);
-        render_footer(frame, chunks[2]);
+
+        let items: Vec<ListItem> = app
+            .sounds
+            .iter()
+            .map(|sound| {
+                let marker = if app.is_playing(&sound.id) {
+                    Span::styled("▶ ", Style::default().fg(Color::Green))
+                } else {
+                    Span::raw("  ")
+                };
+                ListItem::new(Line::from(vec![marker, Span::raw(sound.name.as_str())]))
+            })
+            .collect();
+
+        let mut block = Block::default()
+            .borders(Borders::ALL)
+            .title("Sounds")
+            .border_style(Style::default().fg(Color::Magenta));
+        if let Some(message) = &app.status_message {
+            block = block.title_bottom(Span::styled(
+                format!(" {} ", message),
+                Style::default().fg(Color::Red),
+            ));
+        }
+
+        if items.is_empty() {
+            let empty =
+                Paragraph::new("No sounds found. Set sound_files_directory in boomcrab.toml.")
+                    .alignment(Alignment::Center)
+                    .style(Style::default().fg(Color::DarkGray))
+                    .block(block);
+            frame.render_widget(empty, chunks[1]);
+        } else {
+            let list = List::new(items)
+                .block(block)
+                .highlight_style(
+                    Style::default()
+                        .fg(Color::Yellow)
+                        .add_modifier(Modifier::BOLD),
+                )
+                .highlight_symbol("> ");
+            let mut state = ListState::default().with_selected(Some(app.selected_sound));
+            frame.render_stateful_widget(list, chunks[1], &mut state);
+        }
+
+        if let Some(sound) = app.sounds.get(app.selected_sound) {
+            let overview = app.overviews.get(&sound.id);
+            let duration_ms = overview.map_or(0, |overview| overview.duration_ms);
+            let playhead = app.playhead(&sound.id);
+            let length = match playhead {
+                Some(ms) => format!("{} / {}", format_ms(ms), format_ms(duration_ms)),
+                None => format_ms(duration_ms),
+            };
+
+            let block = Block::default()
+                .borders(Borders::ALL)
+                .title(format!("{} ({})", sound.name, length))
+                .border_style(Style::default().fg(Color::Magenta));
+            let inner = block.inner(chunks[2]);
+            frame.render_widget(block, chunks[2]);
+
+            if let Some(overview) = overview
+                && duration_ms > 0
+            {
+                let playhead = playhead.map(|ms| ms as f32 / duration_ms as f32);
+                frame.render_widget(Waveform::new(overview).playhead(playhead), inner);
+            }
+        }
+
+        render_footer(frame, chunks[3], &app.keymap);
+        render_status_bar(frame, chunks[4], &app.meters);
     }
 }
@@ -1,42 +1,169 @@
 pub mod app;
 mod config;
+mod effects;
+mod help;
 mod home;
+mod playing;
+mod spectrum;
+mod trim;
 
 mod components {
     pub mod footer;
+    pub mod meter;
+    pub mod waveform;
 }
 
 use ratatui::{
     Terminal,
     crossterm::{
         ExecutableCommand,
-        terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
+        event::{
+            KeyboardEnhancementFlags, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+        },
+        terminal::{
+            EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode,
+            supports_keyboard_enhancement,
+        },
     },
 };
 use std::io::{self, stdout};
 
+use crate::daemon::protocol::{Request, Trim};
+use crate::settings::effects::EffectSettings;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Page {
     Home,
     Config,
+    Effects,
+    Trim,
+    Spectrum,
+    Playing,
+    Help,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum UiAction {
     None,
     RefreshAudioDevices,
+    PlaySound(String),
+    /// Play a sound from a position in milliseconds, to audition a trim point
+    PlayFrom(String, u32),
+    /// The key that played a sound was released
+    ReleaseSound(String),
+    StopSound(String),
+    StopAll,
+    FadeOutAll,
+    /// Pause a sound, or every sound when the id is `None`, or resume when false
+    Pause(Option<String>, bool),
+    /// Stop one voice, leaving other voices of its sound playing
+    StopVoice(u64),
+    SetVoiceVolume(u64, f32),
+    /// Pause a voice, or resume it when false
+    PauseVoice(u64, bool),
+    /// Move a voice to a position in milliseconds from the start of its file
+    SeekVoice(u64, u32),
+    /// Set the volume of a sound, or the master volume when the id is `None`
+    SetVolume(Option<String>, f32),
+    /// Multiply the speed of the sounds playing now
+    NudgeSpeed(f32),
+    /// Play every sound this many times faster until sent again with 1
+    HoldSpeed(f32),
+    MidiLearn(String),
+    NextVoicePreset,
+    /// Fetch the effects of a sound, or the master effects when the id is `None`
+    LoadEffects(Option<String>),
+    /// Replace the effects of a sound, or the master effects when the id is `None`
+    SetEffects(Option<String>, Vec<EffectSettings>),
+    /// Fetch a sound's trim points and cues
+    LoadTrim(String),
+    SetTrim(String, Trim),
     Quit,
 }
 
+impl UiAction {
+    /// The daemon request that carries out this action, if it is one the daemon handles.
+    ///
+    /// Remote controls such as OSC produce `UiAction`s too, so anything they can
+    /// do is also available from the keyboard.
+    pub fn to_request(&self) -> Option<Request> {
+        match self {
+            UiAction::PlaySound(id) => Some(Request::Play { id: id.clone() }),
+            UiAction::PlayFrom(id, position_ms) => Some(Request::PlayFrom {
+                id: id.clone(),
+                cue: None,
+                position_ms: Some(*position_ms),
+            }),
+            UiAction::ReleaseSound(id) => Some(Request::Release { id: id.clone() }),
+            UiAction::StopSound(id) => Some(Request::Stop { id: id.clone() }),
+            UiAction::StopAll => Some(Request::StopAll),
+            UiAction::FadeOutAll => Some(Request::FadeOutAll),
+            UiAction::Pause(id, paused) => Some(Request::Pause {
+                id: id.clone(),
+                paused: *paused,
+            }),
+            UiAction::StopVoice(voice) => Some(Request::StopVoice { voice: *voice }),
+            UiAction::SetVoiceVolume(voice, volume) => Some(Request::SetVoiceVolume {
+                voice: *voice,
+                volume: *volume,
+            }),
+            UiAction::PauseVoice(voice, paused) => Some(Request::PauseVoice {
+                voice: *voice,
+                paused: *paused,
+            }),
+            UiAction::SeekVoice(voice, position_ms) => Some(Request::SeekVoice {
+                voice: *voice,
+                position_ms: *position_ms,
+            }),
+            UiAction::SetVolume(id, volume) => Some(Request::SetVolume {
+                id: id.clone(),
+                volume: *volume,
+            }),
+            UiAction::NudgeSpeed(factor) => Some(Request::NudgeSpeed { factor: *factor }),
+            UiAction::HoldSpeed(factor) => Some(Request::HoldSpeed { factor: *factor }),
+            UiAction::MidiLearn(id) => Some(Request::MidiLearn { id: id.clone() }),
+            UiAction::NextVoicePreset => Some(Request::NextVoicePreset),
+            UiAction::SetEffects(id, effects) => Some(Request::SetEffects {
+                id: id.clone(),
+                effects: effects.clone(),
+            }),
+            UiAction::SetTrim(id, trim) => Some(Request::SetTrim {
+                id: id.clone(),
+                trim: trim.clone(),
+            }),
+            UiAction::None
+            | UiAction::RefreshAudioDevices
+            | UiAction::LoadEffects(_)
+            | UiAction::LoadTrim(_)
+            | UiAction::Quit => None,
+        }
+    }
+}
+
+/// Whether the terminal can report key releases, which hold mode and held
+/// speed keys need
+pub fn reports_key_releases() -> bool {
+    supports_keyboard_enhancement().unwrap_or(false)
+}
+
 pub fn setup_terminal() -> io::Result<Terminal<ratatui::backend::CrosstermBackend<std::io::Stdout>>>
 {
     enable_raw_mode()?;
     stdout().execute(EnterAlternateScreen)?;
+    if reports_key_releases() {
+        stdout().execute(PushKeyboardEnhancementFlags(
+            KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES
+                | KeyboardEnhancementFlags::REPORT_EVENT_TYPES,
+        ))?;
+    }
     let terminal = Terminal::new(ratatui::backend::CrosstermBackend::new(stdout()))?;
     Ok(terminal)
 }
 
 pub fn restore_terminal() -> io::Result<()> {
+    if reports_key_releases() {
+        stdout().execute(PopKeyboardEnhancementFlags)?;
+    }
     disable_raw_mode()?;
     stdout().execute(LeaveAlternateScreen)?;
     Ok(())
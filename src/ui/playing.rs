@@ -0,0 +1,177 @@
+use ratatui::{
+    Frame,
+    layout::{Alignment, Constraint, Direction, Layout},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+};
+
+use super::app::App;
+use super::components::footer::{render_footer, render_status_bar};
+use super::components::waveform::format_ms;
+use crate::audio::mixer::VoiceProgress;
+use crate::settings::keymap::KeyAction;
+
+/// Columns the sound names are padded or cut to, so the bars line up
+const NAME_WIDTH: usize = 24;
+const PROGRESS_WIDTH: usize = 20;
+
+/// Keys hinted at below the list, which only do something on this page
+const HINTS: [KeyAction; 4] = [
+    KeyAction::StopSelected,
+    KeyAction::TogglePause,
+    KeyAction::IncreaseValue,
+    KeyAction::VolumeUp,
+];
+
+pub struct PlayingPage;
+
+impl PlayingPage {
+    pub fn render(frame: &mut Frame, app: &App) {
+        let area = frame.area();
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(3),
+                Constraint::Fill(1),
+                Constraint::Length(3),
+                Constraint::Length(1),
+            ])
+            .split(area);
+
+        let title = Paragraph::new("Now Playing")
+            .style(
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+            )
+            .alignment(Alignment::Center)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(Color::White)),
+            );
+        frame.render_widget(title, chunks[0]);
+
+        let hints = HINTS
+            .iter()
+            .filter_map(|&action| {
+                let keys: String = app
+                    .keymap
+                    .keys_for(action)
+                    .map(|keys| format!("[{}]", keys))
+                    .collect();
+                (!keys.is_empty()).then(|| format!("{} {}", keys, action.label()))
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        let mut block = Block::default()
+            .borders(Borders::ALL)
+            .title(format!("Voices ({})", app.playing.len()))
+            .title_bottom(Span::styled(
+                format!(" {} ", hints),
+                Style::default().fg(Color::DarkGray),
+            ))
+            .border_style(Style::default().fg(Color::Magenta));
+        if let Some(message) = &app.status_message {
+            block = block.title_bottom(Span::styled(
+                format!(" {} ", message),
+                Style::default().fg(Color::Red),
+            ));
+        }
+
+        if app.playing.is_empty() {
+            let empty = Paragraph::new("Nothing is playing.")
+                .alignment(Alignment::Center)
+                .style(Style::default().fg(Color::DarkGray))
+                .block(block);
+            frame.render_widget(empty, chunks[1]);
+        } else {
+            let items: Vec<ListItem> = app
+                .playing
+                .iter()
+                .map(|(voice, sound_id)| {
+                    let name = app.sound_name(sound_id);
+                    ListItem::new(voice_line(name, app.progress.get(voice)))
+                })
+                .collect();
+            let list = List::new(items)
+                .block(block)
+                .highlight_style(
+                    Style::default()
+                        .fg(Color::Yellow)
+                        .add_modifier(Modifier::BOLD),
+                )
+                .highlight_symbol("> ");
+            let mut state = ListState::default().with_selected(Some(app.selected_voice));
+            frame.render_stateful_widget(list, chunks[1], &mut state);
+        }
+
+        render_footer(frame, chunks[2], &app.keymap);
+        render_status_bar(frame, chunks[3], &app.meters);
+    }
+}
+
+/// A voice as e.g. `▶ airhorn  ███████░░░  0:01.200 / -0:00.800  play 1/3  vol 100%  3.2 ms`,
+/// with `disk` after the latency of a sound streamed from disk
+fn voice_line<'a>(name: &str, progress: Option<&VoiceProgress>) -> Line<'a> {
+    let name: String = name.chars().take(NAME_WIDTH).collect();
+    let name = format!("{:<width$}  ", name, width = NAME_WIDTH);
+
+    // The first progress report is at most a few frames away
+    let Some(progress) = progress else {
+        return Line::from(vec![
+            Span::styled("▶ ", Style::default().fg(Color::Green)),
+            Span::raw(name),
+        ]);
+    };
+
+    let state = if progress.paused {
+        Span::styled("⏸ ", Style::default().fg(Color::Yellow))
+    } else {
+        Span::styled("▶ ", Style::default().fg(Color::Green))
+    };
+
+    let elapsed = progress.position_ms.saturating_sub(progress.start_ms);
+    let length = progress.end_ms.saturating_sub(progress.start_ms);
+    let remaining = length.saturating_sub(elapsed);
+    let filled = (elapsed as usize * PROGRESS_WIDTH)
+        .checked_div(length as usize)
+        .unwrap_or(0)
+        .min(PROGRESS_WIDTH);
+
+    let plays = match progress.plays {
+        Some(plays) => format!("play {}/{}", progress.play, plays),
+        None => format!("loop {}/∞", progress.play),
+    };
+
+    let latency = progress
+        .latency_ms
+        .map_or_else(String::new, |ms| format!("{:.1} ms", ms));
+
+    Line::from(vec![
+        state,
+        Span::raw(name),
+        Span::styled("█".repeat(filled), Style::default().fg(Color::Cyan)),
+        Span::styled(
+            "░".repeat(PROGRESS_WIDTH - filled),
+            Style::default().fg(Color::DarkGray),
+        ),
+        Span::raw(format!(
+            "  {} / -{}  {:<10}  vol {:.0}%",
+            format_ms(elapsed),
+            format_ms(remaining),
+            plays,
+            progress.volume * 100.0
+        )),
+        Span::styled(
+            format!("  {:>8}", latency),
+            Style::default().fg(Color::DarkGray),
+        ),
+        Span::styled(
+            if progress.streamed { "  disk" } else { "" },
+            Style::default().fg(Color::Blue),
+        ),
+    ])
+}
@@ -0,0 +1,150 @@
+use ratatui::{
+    Frame,
+    buffer::Buffer,
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::Span,
+    widgets::{Bar, BarChart, BarGroup, Block, Borders, Paragraph},
+};
+
+use super::app::App;
+use super::components::footer::{render_footer, render_status_bar};
+use crate::audio::spectrum::{FLOOR_DB, HIGHEST_HZ, LOWEST_HZ, SpectrumSource};
+use crate::settings::keymap::KeyAction;
+
+/// Frequencies marked below the bars
+const AXIS_HZ: [(f32, &str); 7] = [
+    (50.0, "50"),
+    (100.0, "100"),
+    (250.0, "250"),
+    (1000.0, "1k"),
+    (2500.0, "2.5k"),
+    (5000.0, "5k"),
+    (10_000.0, "10k"),
+];
+
+pub struct SpectrumPage;
+
+impl SpectrumPage {
+    pub fn render(frame: &mut Frame, app: &App) {
+        let area = frame.area();
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(3),
+                Constraint::Fill(1),
+                Constraint::Length(3),
+                Constraint::Length(1),
+            ])
+            .split(area);
+
+        let source = match app.spectrum_source {
+            SpectrumSource::Master => "Output",
+            SpectrumSource::Mic => "Mic",
+        };
+        let title = Paragraph::new(format!("Spectrum: {}", source))
+            .style(
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+            )
+            .alignment(Alignment::Center)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(Color::White)),
+            );
+        frame.render_widget(title, chunks[0]);
+
+        let keys: String = app
+            .keymap
+            .keys_for(KeyAction::ToggleSpectrumSource)
+            .map(|keys| format!("[{}]", keys))
+            .collect();
+        let mut block = Block::default()
+            .borders(Borders::ALL)
+            .title(format!("{:.0} dB to 0 dB", FLOOR_DB))
+            .title_bottom(Span::styled(
+                format!(" {} {} ", keys, KeyAction::ToggleSpectrumSource.label()),
+                Style::default().fg(Color::DarkGray),
+            ))
+            .border_style(Style::default().fg(Color::Magenta));
+        if let Some(message) = &app.status_message {
+            block = block.title_bottom(Span::styled(
+                format!(" {} ", message),
+                Style::default().fg(Color::Red),
+            ));
+        }
+        let inner = block.inner(chunks[1]);
+        frame.render_widget(block, chunks[1]);
+
+        let [bars_area, axis_area] =
+            Layout::vertical([Constraint::Fill(1), Constraint::Length(1)]).areas(inner);
+        let bands = app.spectrum.len().max(1) as u16;
+        // Each band gets the same share of the width, with a gap when there is room
+        let slot = (bars_area.width / bands).max(1);
+        let gap = u16::from(slot >= 3);
+
+        let range = -FLOOR_DB;
+        let bars: Vec<Bar> = app
+            .spectrum
+            .iter()
+            .map(|&level_db| {
+                Bar::default()
+                    .value(((level_db - FLOOR_DB) * 10.0) as u64)
+                    .text_value(String::new())
+            })
+            .collect();
+        let chart = BarChart::default()
+            .data(BarGroup::default().bars(&bars))
+            .max((range * 10.0) as u64)
+            .bar_width(slot - gap)
+            .bar_gap(gap)
+            .bar_style(Style::default().fg(Color::Cyan));
+        frame.render_widget(chart, bars_area);
+
+        draw_peaks(app, bars_area, slot, gap, frame.buffer_mut());
+
+        let mut axis = vec![' '; axis_area.width as usize];
+        for (hz, label) in AXIS_HZ {
+            let band = (hz / LOWEST_HZ).ln() / (HIGHEST_HZ / LOWEST_HZ).ln() * bands as f32;
+            let x = (band * slot as f32) as usize;
+            if x + label.len() <= axis.len() {
+                for (i, c) in label.chars().enumerate() {
+                    axis[x + i] = c;
+                }
+            }
+        }
+        frame.render_widget(
+            Paragraph::new(axis.into_iter().collect::<String>())
+                .style(Style::default().fg(Color::DarkGray)),
+            axis_area,
+        );
+
+        render_footer(frame, chunks[2], &app.keymap);
+        render_status_bar(frame, chunks[3], &app.meters);
+    }
+}
+
+/// Mark the peak of each band with a line across its bar
+fn draw_peaks(app: &App, area: Rect, slot: u16, gap: u16, buf: &mut Buffer) {
+    if area.is_empty() {
+        return;
+    }
+    for (band, peak) in app.spectrum_peaks.iter().enumerate() {
+        let x = area.x + band as u16 * slot;
+        if x + slot - gap > area.right() {
+            break;
+        }
+        let fraction = (peak.level_db() - FLOOR_DB) / -FLOOR_DB;
+        if fraction <= 0.0 {
+            continue;
+        }
+        let rows = ((fraction * area.height as f32) as u16).min(area.height - 1);
+        let y = area.bottom() - 1 - rows;
+        for column in x..x + slot - gap {
+            buf[(column, y)].set_char('▔').set_fg(Color::White);
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use ratatui::{
+    Frame,
+    layout::{Alignment, Constraint, Direction, Layout},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+};
+
+use super::app::{App, TrimRow};
+use super::components::footer::{render_footer, render_status_bar};
+use super::components::waveform::{Waveform, format_ms};
+use crate::settings::keymap::KeyAction;
+
+/// Rows of the waveform, inside its border
+const WAVEFORM_HEIGHT: u16 = 8;
+
+pub struct TrimPage;
+
+impl TrimPage {
+    pub fn render(frame: &mut Frame, app: &App) {
+        let area = frame.area();
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(3),
+                Constraint::Length(WAVEFORM_HEIGHT + 2),
+                Constraint::Fill(1),
+                Constraint::Length(3),
+                Constraint::Length(1),
+            ])
+            .split(area);
+
+        let target = app.trim_sound.as_ref().map(|sound_id| {
+            app.sounds
+                .iter()
+                .find(|sound| &sound.id == sound_id)
+                .map_or(sound_id.as_str(), |sound| sound.name.as_str())
+        });
+        let title = Paragraph::new(format!("Trim: {}", target.unwrap_or("-")))
+            .style(
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+            )
+            .alignment(Alignment::Center)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(Color::White)),
+            );
+        frame.render_widget(title, chunks[0]);
+
+        let duration_ms = app.trim_duration_ms();
+        let waveform_block = Block::default()
+            .borders(Borders::ALL)
+            .title(format!("Waveform ({})", format_ms(duration_ms)))
+            .border_style(Style::default().fg(Color::Magenta));
+        let inner = waveform_block.inner(chunks[1]);
+        frame.render_widget(waveform_block, chunks[1]);
+
+        let overview = app
+            .trim_sound
+            .as_ref()
+            .and_then(|sound_id| app.overviews.get(sound_id));
+        if let Some(overview) = overview
+            && duration_ms > 0
+        {
+            let fraction = |ms: u32| ms as f32 / duration_ms as f32;
+            let selected = app.trim_rows().get(app.selected_trim_row).copied();
+            let mut markers: Vec<(f32, Color)> = app
+                .trim_rows()
+                .into_iter()
+                .filter(|row| Some(*row) != selected)
+                .map(|row| (fraction(app.trim_position(row)), row_color(row)))
+                .collect();
+            // The selected point is drawn last so it stays visible where points overlap
+            if let Some(row) = selected {
+                markers.push((fraction(app.trim_position(row)), Color::Green));
+            }
+
+            let playhead = app
+                .trim_sound
+                .as_deref()
+                .and_then(|sound_id| app.playhead(sound_id));
+            let waveform = Waveform::new(overview)
+                .region(
+                    fraction(app.trim_position(TrimRow::Start)),
+                    fraction(app.trim_position(TrimRow::End)),
+                )
+                .markers(&markers)
+                .playhead(playhead.map(fraction));
+            frame.render_widget(waveform, inner);
+        }
+
+        let cue_names: Vec<&String> = app.trim.cues.keys().collect();
+        let items: Vec<ListItem> = app
+            .trim_rows()
+            .into_iter()
+            .map(|row| {
+                let name = match row {
+                    TrimRow::Start => "Start".to_string(),
+                    TrimRow::End => "End".to_string(),
+                    TrimRow::Cue(cue) => format!("Cue {}", cue_names[cue]),
+                };
+                ListItem::new(Line::from(vec![
+                    Span::styled(format!("{:<20}", name), Style::default().fg(row_color(row))),
+                    Span::raw(format_ms(app.trim_position(row))),
+                ]))
+            })
+            .collect();
+
+        let hint = [
+            KeyAction::PlaySelected,
+            KeyAction::AddCue,
+            KeyAction::RemoveCue,
+        ]
+        .into_iter()
+        .map(|action| {
+            let keys: String = app
+                .keymap
+                .keys_for(action)
+                .map(|keys| format!("[{}]", keys))
+                .collect();
+            let label = match action {
+                KeyAction::PlaySelected => "Audition",
+                action => action.label(),
+            };
+            format!("{} {}", keys, label)
+        })
+        .collect::<Vec<_>>()
+        .join(" | ");
+
+        let mut block = Block::default()
+            .borders(Borders::ALL)
+            .title("Points")
+            .title_bottom(Span::styled(
+                format!(" {} ", hint),
+                Style::default().fg(Color::DarkGray),
+            ))
+            .border_style(Style::default().fg(Color::Magenta));
+        if let Some(message) = &app.status_message {
+            block = block.title_bottom(Span::styled(
+                format!(" {} ", message),
+                Style::default().fg(Color::Red),
+            ));
+        }
+
+        if app.trim_sound.is_none() {
+            let empty = Paragraph::new("No sound selected.")
+                .alignment(Alignment::Center)
+                .style(Style::default().fg(Color::DarkGray))
+                .block(block);
+            frame.render_widget(empty, chunks[2]);
+        } else {
+            let list = List::new(items)
+                .block(block)
+                .highlight_style(Style::default().add_modifier(Modifier::BOLD))
+                .highlight_symbol("> ");
+            let mut state = ListState::default().with_selected(Some(app.selected_trim_row));
+            frame.render_stateful_widget(list, chunks[2], &mut state);
+        }
+
+        render_footer(frame, chunks[3], &app.keymap);
+        render_status_bar(frame, chunks[4], &app.meters);
+    }
+}
+
+fn row_color(row: TrimRow) -> Color {
+    match row {
+        TrimRow::Start | TrimRow::End => Color::Yellow,
+        TrimRow::Cue(_) => Color::Magenta,
+    }
+}
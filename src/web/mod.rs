@@ -0,0 +1,466 @@
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::mpsc::Receiver;
+use std::thread;
+
+use serde::Deserialize;
+use tiny_http::{Header, Method, Server, StatusCode};
+use tungstenite::handshake::derive_accept_key;
+use tungstenite::protocol::Role;
+use tungstenite::{Message, WebSocket};
+
+use crate::audio::AudioError;
+use crate::daemon::protocol::{Request, Response, ServerMessage};
+use crate::settings::web::WebSettings;
+
+/// The soundboard page served at `/`
+const INDEX_HTML: &str = include_str!("index.html");
+
+/// Embedded HTTP server with the web soundboard and its API.
+///
+/// Routes:
+/// - `GET /` serves the soundboard page
+/// - `GET /api/sounds` and `GET /api/devices` list sounds and audio devices
+/// - `POST /api/sounds/<id>/play` and `POST /api/sounds/<id>/stop`
+/// - `POST /api/stop` stops every sound, `POST /api/fade` fades every sound out
+/// - `POST /api/pause` and `POST /api/resume`, or `/api/sounds/<id>/pause` and
+///   `/api/sounds/<id>/resume` for a single sound
+/// - `POST /api/sounds/<id>/seek` with `{"cue": "chorus"}` or `{"position_ms": 5000}`
+/// - `POST /api/volume` with `{"volume": 0.8}`, plus `"id"` for a single sound
+/// - `GET /api/events` upgrades to a WebSocket streaming daemon events as JSON
+///
+/// API responses are the daemon protocol's JSON responses. Path segments are
+/// percent-decoded, and `POST` requests from pages on other origins are refused.
+pub struct WebServer {
+    pub local_addr: SocketAddr,
+}
+
+impl WebServer {
+    /// Start serving. `handle_request` carries out API requests and `subscribe`
+    /// opens a new event stream for each WebSocket client.
+    pub fn start<H, S>(settings: &WebSettings, handle_request: H, subscribe: S) -> io::Result<Self>
+    where
+        H: Fn(Request) -> Response + Send + Sync + 'static,
+        S: Fn() -> Receiver<ServerMessage> + Send + Sync + 'static,
+    {
+        let server = Server::http((settings.bind_address.as_str(), settings.port))
+            .map_err(io::Error::other)?;
+        let local_addr = server
+            .server_addr()
+            .to_ip()
+            .ok_or_else(|| io::Error::other("Not listening on an IP address"))?;
+
+        let token: Option<Arc<str>> = settings.token.as_deref().map(Arc::from);
+        thread::spawn(move || {
+            for request in server.incoming_requests() {
+                if let Err(e) = handle_http(request, token.as_deref(), &handle_request, &subscribe)
+                {
+                    eprintln!("Warning: Web request failed ({}).", e);
+                }
+            }
+        });
+
+        Ok(Self { local_addr })
+    }
+}
+
+#[derive(Deserialize)]
+struct VolumeBody {
+    #[serde(default)]
+    id: Option<String>,
+    volume: f32,
+}
+
+#[derive(Deserialize)]
+struct SeekBody {
+    #[serde(default)]
+    cue: Option<String>,
+    #[serde(default)]
+    position_ms: Option<u32>,
+}
+
+fn handle_http<H, S>(
+    mut request: tiny_http::Request,
+    token: Option<&str>,
+    handle_request: &H,
+    subscribe: &S,
+) -> io::Result<()>
+where
+    H: Fn(Request) -> Response,
+    S: Fn() -> Receiver<ServerMessage>,
+{
+    let url = request.url().to_string();
+    let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+    let Some(decoded) = path
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .map(|s| percent_decode(s, false))
+        .collect::<Option<Vec<String>>>()
+    else {
+        return request.respond(text_response(400, "Invalid percent-encoding in path"));
+    };
+    let segments: Vec<&str> = decoded.iter().map(String::as_str).collect();
+
+    // The page itself holds nothing secret; it passes its own `token` on to the API
+    if segments.is_empty() {
+        return match request.method() {
+            Method::Get => request.respond(
+                tiny_http::Response::from_string(INDEX_HTML)
+                    .with_header(header("Content-Type", "text/html; charset=utf-8")),
+            ),
+            _ => request.respond(text_response(405, "Method not allowed")),
+        };
+    }
+
+    if let Some(token) = token
+        && !is_authorized(&request, query, token)
+    {
+        return request.respond(text_response(401, "Missing or invalid token"));
+    }
+
+    if *request.method() != Method::Get && !is_same_origin(&request) {
+        return request.respond(text_response(403, "Cross-origin requests are not allowed"));
+    }
+
+    let api_request = match (request.method(), segments.as_slice()) {
+        (Method::Get, ["api", "events"]) => return stream_events(request, subscribe()),
+        (Method::Get, ["api", "sounds"]) => Request::ListSounds,
+        (Method::Get, ["api", "devices"]) => Request::ListDevices,
+        (Method::Post, ["api", "rescan"]) => Request::RescanSounds,
+        (Method::Post, ["api", "sounds", id, "play"]) => Request::Play { id: id.to_string() },
+        (Method::Post, ["api", "sounds", id, "stop"]) => Request::Stop { id: id.to_string() },
+        (Method::Post, ["api", "stop"]) => Request::StopAll,
+        (Method::Post, ["api", "fade"]) => Request::FadeOutAll,
+        (Method::Post, ["api", "sounds", id, "pause"]) => Request::Pause {
+            id: Some(id.to_string()),
+            paused: true,
+        },
+        (Method::Post, ["api", "sounds", id, "resume"]) => Request::Pause {
+            id: Some(id.to_string()),
+            paused: false,
+        },
+        (Method::Post, ["api", "pause"]) => Request::Pause {
+            id: None,
+            paused: true,
+        },
+        (Method::Post, ["api", "resume"]) => Request::Pause {
+            id: None,
+            paused: false,
+        },
+        (Method::Post, ["api", "sounds", id, "seek"]) => {
+            let mut body = String::new();
+            request.as_reader().read_to_string(&mut body)?;
+            match serde_json::from_str::<SeekBody>(&body) {
+                Ok(SeekBody { cue, position_ms }) => Request::Seek {
+                    id: id.to_string(),
+                    cue,
+                    position_ms,
+                },
+                Err(e) => {
+                    return request.respond(text_response(400, &format!("Invalid body: {}", e)));
+                }
+            }
+        }
+        (Method::Post, ["api", "volume"]) => {
+            let mut body = String::new();
+            request.as_reader().read_to_string(&mut body)?;
+            match serde_json::from_str::<VolumeBody>(&body) {
+                Ok(VolumeBody { id, volume }) => Request::SetVolume { id, volume },
+                Err(e) => {
+                    return request.respond(text_response(400, &format!("Invalid body: {}", e)));
+                }
+            }
+        }
+        (_, ["api", ..]) => return request.respond(text_response(404, "Unknown endpoint")),
+        _ => return request.respond(text_response(404, "Not found")),
+    };
+
+    let response = handle_request(api_request);
+    let status = match &response {
+        Response::Error { error } => match error {
+            AudioError::SoundNotFound(_) | AudioError::DeviceNotFound(_) => 404,
+            AudioError::NotSupported(_) => 400,
+            AudioError::InitializationFailed(_) | AudioError::PlaybackError(_) => 500,
+        },
+        _ => 200,
+    };
+
+    let body = serde_json::to_string(&response).map_err(io::Error::other)?;
+    request.respond(
+        tiny_http::Response::from_string(body)
+            .with_status_code(status)
+            .with_header(header("Content-Type", "application/json")),
+    )
+}
+
+/// Accepts the token from an `Authorization: Bearer` header or a `token` query
+/// parameter, since browsers cannot set headers on WebSocket connections
+fn is_authorized(request: &tiny_http::Request, query: &str, token: &str) -> bool {
+    let from_header =
+        header_value(request, "Authorization").and_then(|value| value.strip_prefix("Bearer "));
+    let from_query = query
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("token="))
+        .and_then(|value| percent_decode(value, true));
+
+    from_header
+        .map(str::to_string)
+        .into_iter()
+        .chain(from_query)
+        .any(|given| constant_time_eq(given.as_bytes(), token.as_bytes()))
+}
+
+/// Without a token anything that can reach the port may use the API, so at
+/// least keep other web pages out. Browsers send `Origin` with every cross-origin
+/// `POST`; tools like curl send none and are let through.
+fn is_same_origin(request: &tiny_http::Request) -> bool {
+    match (
+        header_value(request, "Origin"),
+        header_value(request, "Host"),
+    ) {
+        (None, _) => true,
+        (Some(origin), Some(host)) => origin
+            .strip_prefix("http://")
+            .is_some_and(|origin| origin.eq_ignore_ascii_case(host)),
+        (Some(_), None) => false,
+    }
+}
+
+fn header_value<'a>(request: &'a tiny_http::Request, name: &'static str) -> Option<&'a str> {
+    request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv(name))
+        .map(|h| h.value.as_str())
+}
+
+/// Decode `%XX` escapes, and `+` as a space in query values. `None` when the
+/// escapes are malformed or the result is not UTF-8.
+fn percent_decode(s: &str, plus_as_space: bool) -> Option<String> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut rest = s.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        rest = tail;
+        match byte {
+            b'%' => {
+                let hex = std::str::from_utf8(rest.get(..2)?).ok()?;
+                bytes.push(u8::from_str_radix(hex, 16).ok()?);
+                rest = &rest[2..];
+            }
+            b'+' if plus_as_space => bytes.push(b' '),
+            _ => bytes.push(byte),
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Compare without returning early, so response times don't reveal the token
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Upgrade the request to a WebSocket and send it every event until it disconnects
+fn stream_events(request: tiny_http::Request, events: Receiver<ServerMessage>) -> io::Result<()> {
+    let key = request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv("Sec-WebSocket-Key"))
+        .map(|h| derive_accept_key(h.value.as_bytes()));
+    let Some(accept) = key else {
+        return request.respond(text_response(400, "Expected a WebSocket upgrade"));
+    };
+
+    let response = tiny_http::Response::empty(StatusCode(101))
+        .with_header(header("Sec-WebSocket-Accept", &accept));
+    let stream = request.upgrade("websocket", response);
+
+    // The stream only goes one way; clients use the HTTP API to send commands
+    thread::spawn(move || {
+        let mut socket = WebSocket::from_raw_socket(stream, Role::Server, None);
+        for message in events {
+            let ServerMessage::Event(event) = message else {
+                continue;
+            };
+            let Ok(json) = serde_json::to_string(&event) else {
+                continue;
+            };
+            if socket.send(Message::text(json)).is_err() {
+                break;
+            }
+        }
+    });
+
+    Ok(())
+}
+
+fn header(field: &str, value: &str) -> Header {
+    Header::from_bytes(field.as_bytes(), value.as_bytes()).expect("valid header")
+}
+
+fn text_response(status: u16, text: &str) -> tiny_http::Response<io::Cursor<Vec<u8>>> {
+    tiny_http::Response::from_string(text).with_status_code(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::sync::Mutex;
+    use std::sync::mpsc;
+
+    use serde_json::{Value, json};
+
+    use super::*;
+
+    /// Start a server on an ephemeral port that answers every API request with
+    /// `Ok` and records it as JSON
+    fn start(token: Option<&str>) -> (SocketAddr, Arc<Mutex<Vec<Value>>>) {
+        let settings = WebSettings {
+            enabled: true,
+            bind_address: "127.0.0.1".to_string(),
+            port: 0,
+            token: token.map(str::to_string),
+        };
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let recorder = Arc::clone(&received);
+        let server = WebServer::start(
+            &settings,
+            move |request| {
+                recorder
+                    .lock()
+                    .unwrap()
+                    .push(serde_json::to_value(request).unwrap());
+                Response::Ok
+            },
+            || mpsc::channel().1,
+        )
+        .unwrap();
+        (server.local_addr, received)
+    }
+
+    /// Send a request and return the status code and body
+    fn send(addr: SocketAddr, method: &str, path: &str, headers: &[&str]) -> (u16, String) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let mut request = format!("{} {} HTTP/1.1\r\nHost: {}\r\n", method, path, addr);
+        for header in headers {
+            request.push_str(&format!("{}\r\n", header));
+        }
+        request.push_str("Content-Length: 0\r\nConnection: close\r\n\r\n");
+        stream.write_all(request.as_bytes()).unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let status = response[9..12].parse().unwrap();
+        let body = response
+            .split_once("\r\n\r\n")
+            .map(|(_, body)| body.to_string())
+            .unwrap_or_default();
+        (status, body)
+    }
+
+    #[test]
+    fn routes_api_requests() {
+        let (addr, received) = start(None);
+        assert_eq!(send(addr, "GET", "/api/sounds", &[]).0, 200);
+        assert_eq!(send(addr, "POST", "/api/sounds/airhorn/play", &[]).0, 200);
+        assert_eq!(send(addr, "POST", "/api/pause", &[]).0, 200);
+
+        assert_eq!(
+            *received.lock().unwrap(),
+            vec![
+                json!({"type": "list_sounds"}),
+                json!({"type": "play", "id": "airhorn"}),
+                json!({"type": "pause", "id": null, "paused": true}),
+            ]
+        );
+    }
+
+    #[test]
+    fn serves_the_page_without_a_token() {
+        let (addr, _) = start(Some("secret"));
+        let (status, body) = send(addr, "GET", "/", &[]);
+        assert_eq!(status, 200);
+        assert!(body.contains("<html"));
+    }
+
+    #[test]
+    fn answers_unknown_routes_and_methods() {
+        let (addr, received) = start(None);
+        assert_eq!(send(addr, "GET", "/api/nothing", &[]).0, 404);
+        assert_eq!(send(addr, "GET", "/favicon.ico", &[]).0, 404);
+        // Playing needs a POST
+        assert_eq!(send(addr, "GET", "/api/sounds/airhorn/play", &[]).0, 404);
+        assert_eq!(send(addr, "POST", "/", &[]).0, 405);
+        assert!(received.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn accepts_the_token_from_header_or_query() {
+        let (addr, received) = start(Some("a+b&c%d"));
+        assert_eq!(send(addr, "GET", "/api/sounds", &[]).0, 401);
+        assert_eq!(
+            send(addr, "GET", "/api/sounds", &["Authorization: Bearer wrong"]).0,
+            401
+        );
+        assert_eq!(send(addr, "GET", "/api/sounds?token=a+b", &[]).0, 401);
+        assert_eq!(
+            send(
+                addr,
+                "GET",
+                "/api/sounds",
+                &["Authorization: Bearer a+b&c%d"]
+            )
+            .0,
+            200
+        );
+        assert_eq!(
+            send(addr, "GET", "/api/sounds?x=1&token=a%2Bb%26c%25d", &[]).0,
+            200
+        );
+        assert_eq!(received.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn decodes_sound_ids() {
+        let (addr, received) = start(None);
+        assert_eq!(
+            send(addr, "POST", "/api/sounds/caf%C3%A9%20bell/play", &[]).0,
+            200
+        );
+        assert_eq!(send(addr, "POST", "/api/sounds/%zz/play", &[]).0, 400);
+        assert_eq!(
+            *received.lock().unwrap(),
+            vec![json!({"type": "play", "id": "café bell"})]
+        );
+    }
+
+    #[test]
+    fn refuses_posts_from_other_origins() {
+        let (addr, received) = start(None);
+        let own_origin = format!("Origin: http://{}", addr);
+        assert_eq!(
+            send(addr, "POST", "/api/stop", &["Origin: http://evil.example"]).0,
+            403
+        );
+        assert_eq!(send(addr, "POST", "/api/stop", &[&own_origin]).0, 200);
+        // Reading is fine, the browser keeps the answer from the other page
+        assert_eq!(
+            send(addr, "GET", "/api/sounds", &["Origin: http://evil.example"]).0,
+            200
+        );
+        assert_eq!(
+            *received.lock().unwrap(),
+            vec![json!({"type": "stop_all"}), json!({"type": "list_sounds"})]
+        );
+    }
+
+    #[test]
+    fn percent_decodes() {
+        assert_eq!(percent_decode("a%20b+c", false).as_deref(), Some("a b+c"));
+        assert_eq!(percent_decode("a%20b+c", true).as_deref(), Some("a b c"));
+        assert_eq!(percent_decode("%e2%9c%93", false).as_deref(), Some("✓"));
+        assert_eq!(percent_decode("%2", false), None);
+        assert_eq!(percent_decode("%ff", false), None);
+    }
+}